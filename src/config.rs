@@ -0,0 +1,1086 @@
+//! The configuration file.
+
+#[cfg(all(unix, feature = "containers"))]
+use crate::sensor::containers::ContainersConfig;
+#[cfg(feature = "libvirt")]
+use crate::sensor::libvirt::LibvirtConfig;
+#[cfg(feature = "lua")]
+use crate::sensor::lua::LuaSensorConfig;
+#[cfg(unix)]
+use crate::sensor::tailscale::TailscaleConfig;
+#[cfg(feature = "dbus")]
+use crate::sensor::{
+    ble::BleScanConfig,
+    dbus::DbusSensorConfig,
+    fwupd::FirmwareUpdatesConfig,
+    sessions::LoginSessionsConfig,
+    systemd::{SystemdTimerConfig, SystemdUnitConfig},
+};
+#[cfg(feature = "http-checks")]
+use crate::sensor::{http::HttpCheck, public_ip::PublicIpConfig};
+#[cfg(feature = "pattern-matching")]
+use crate::sensor::{
+    kernel_log::KernelErrorsConfig, log_match::LogMatchConfig, ssh::SshFailedLoginsConfig,
+};
+#[cfg(feature = "command-auth")]
+use crate::sink::command_auth::CommandAuthConfig;
+#[cfg(feature = "history")]
+use crate::sink::history::HistoryConfig;
+#[cfg(feature = "influxdb")]
+use crate::sink::influx::InfluxConfig;
+#[cfg(feature = "otlp")]
+use crate::sink::otlp::OtlpConfig;
+use crate::{
+    privileged::PrivilegedHelperConfig,
+    sandbox::SandboxConfig,
+    sensor::{
+        backup::BackupConfig, directory_size::DirectorySizeConfig,
+        display_power::DisplayPowerConfig, dns::DnsCheck, exec::ExecSensorConfig,
+        fail2ban::Fail2banConfig, fan::FanConfig, file_age::FileAgeConfig, ipmi::IpmiConfig,
+        mounts::NetworkMount, ping::PingTarget, port::PortCheck, remote::RemoteHostConfig,
+        screenshot::ScreenshotConfig, siren::SirenConfig, speech::SpeechConfig, units::UnitsConfig,
+        updates::OsUpdatesConfig, usb::UsbDevice, wake_on_lan::WakeOnLanTarget,
+    },
+    sink::{
+        audit::AuditLogConfig,
+        backlog::BacklogConfig,
+        command_policy::CommandPolicyConfig,
+        filter::ChangeFilterConfig,
+        home_assistant::{NamingConfig, RetainConfig},
+        homie::HomieConfig,
+        rate_limit::RateLimitConfig,
+        sparkplug::SparkplugConfig,
+    },
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::fs;
+use url::Url;
+
+// The options of features that were left out are still read, so they're kept when the config is
+// written back, but nothing is done with them.
+#[cfg(not(all(unix, feature = "containers")))]
+type ContainersConfig = LeftOut;
+#[cfg(not(feature = "libvirt"))]
+type LibvirtConfig = LeftOut;
+#[cfg(not(feature = "lua"))]
+type LuaSensorConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type BleScanConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type DbusSensorConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type FirmwareUpdatesConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type LoginSessionsConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type SystemdTimerConfig = LeftOut;
+#[cfg(not(feature = "dbus"))]
+type SystemdUnitConfig = LeftOut;
+#[cfg(not(feature = "pattern-matching"))]
+type KernelErrorsConfig = LeftOut;
+#[cfg(not(feature = "pattern-matching"))]
+type LogMatchConfig = LeftOut;
+#[cfg(not(feature = "pattern-matching"))]
+type SshFailedLoginsConfig = LeftOut;
+#[cfg(not(feature = "command-auth"))]
+type CommandAuthConfig = LeftOut;
+#[cfg(not(feature = "http-checks"))]
+type HttpCheck = LeftOut;
+#[cfg(not(feature = "http-checks"))]
+type PublicIpConfig = LeftOut;
+#[cfg(not(feature = "history"))]
+type HistoryConfig = LeftOut;
+#[cfg(not(feature = "influxdb"))]
+type InfluxConfig = LeftOut;
+#[cfg(not(feature = "otlp"))]
+type OtlpConfig = LeftOut;
+#[cfg(not(unix))]
+type TailscaleConfig = LeftOut;
+
+/// The options of something this build was made without, kept just as they were written.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+pub struct LeftOut(serde_yaml::Value);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DriveConfig {
+    /// Where the filesystem is mounted.
+    pub path: PathBuf,
+
+    /// The name its usage is reported as.
+    pub name: String,
+
+    /// The disk the filesystem is on, such as `sda`. When set, the disk's power state is
+    /// reported, and the filesystem isn't read while the disk is spun down so it isn't woken up.
+    pub device: Option<String>,
+
+    /// Reports a problem when the filesystem is mounted read-only, which is what Linux does to
+    /// filesystems it finds errors on.
+    #[serde(default)]
+    pub detect_read_only: bool,
+
+    /// Reports whether the filesystem is on a LUKS encrypted device.
+    #[serde(default)]
+    pub detect_encryption: bool,
+}
+
+/// Which filesystems to report when they are found on our own.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DriveDiscoveryConfig {
+    /// Only report filesystems of these types, such as `ext4`. If empty, every type is reported.
+    #[serde(default)]
+    pub include_filesystems: Vec<String>,
+
+    /// Never report filesystems of these types.
+    #[serde(default = "DriveDiscoveryConfig::default_exclude_filesystems")]
+    pub exclude_filesystems: Vec<String>,
+
+    /// Only report filesystems mounted at paths matching these globs. If empty, every path is reported.
+    #[serde(default)]
+    pub include_paths: Vec<String>,
+
+    /// Never report filesystems mounted at paths matching these globs.
+    #[serde(default = "DriveDiscoveryConfig::default_exclude_paths")]
+    pub exclude_paths: Vec<String>,
+}
+
+impl DriveDiscoveryConfig {
+    fn default_exclude_filesystems() -> Vec<String> {
+        [
+            "tmpfs", "devtmpfs", "squashfs", "overlay", "proc", "sysfs", "efivarfs",
+        ]
+        .iter()
+        .map(|filesystem| filesystem.to_string())
+        .collect()
+    }
+
+    fn default_exclude_paths() -> Vec<String> {
+        ["/snap/*", "/var/lib/docker/*", "/run/*", "/boot/efi"]
+            .iter()
+            .map(|path| path.to_string())
+            .collect()
+    }
+}
+
+impl Default for DriveDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            include_filesystems: Vec::new(),
+            exclude_filesystems: Self::default_exclude_filesystems(),
+            include_paths: Vec::new(),
+            exclude_paths: Self::default_exclude_paths(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PasswordSource {
+    #[serde(rename = "keyring")]
+    Keyring,
+
+    #[serde(rename = "secret_file")]
+    SecretFile(PathBuf),
+}
+
+impl Default for PasswordSource {
+    fn default() -> Self {
+        Self::Keyring
+    }
+}
+
+/// Where the MQTT server is.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub enum MqttServer {
+    Url(Url),
+
+    /// Look for a server advertising itself on the local network, written as `auto`.
+    Auto,
+}
+
+impl TryFrom<String> for MqttServer {
+    type Error = url::ParseError;
+
+    fn try_from(server: String) -> Result<Self, Self::Error> {
+        if server == "auto" {
+            Ok(Self::Auto)
+        } else {
+            Url::parse(&server).map(Self::Url)
+        }
+    }
+}
+
+impl From<MqttServer> for String {
+    fn from(server: MqttServer) -> Self {
+        server.to_string()
+    }
+}
+
+impl fmt::Display for MqttServer {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Url(url) => write!(formatter, "{}", url),
+            Self::Auto => write!(formatter, "auto"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Config {
+    /// The URL of the mqtt server, or `auto` to find one on the local network.
+    pub mqtt_server: MqttServer,
+
+    /// A SOCKS5 or HTTP proxy to reach the MQTT server through, such as `socks5://proxy:1080`.
+    pub mqtt_proxy: Option<Url>,
+
+    /// Set the username to connect to the mqtt server, if required.
+    /// The password will be fetched from the OS keyring.
+    pub username: Option<String>,
+
+    /// Where the password for the MQTT server can be found.
+    /// If a username is not specified, this field is ignored.
+    /// If not specified, this field defaults to the keyring.
+    #[serde(default)]
+    pub password_source: PasswordSource,
+
+    /// The client ID to connect to the MQTT server with. Defaults to `system-mqtt-{hostname}`.
+    pub mqtt_client_id: Option<String>,
+
+    /// If set, commands are only carried out when they're signed with a shared secret.
+    pub command_authentication: Option<CommandAuthConfig>,
+
+    /// If set, what needs root is done by `system-mqtt helper`, so the daemon doesn't have to run as root.
+    pub privileged_helper: Option<PrivilegedHelperConfig>,
+
+    /// If set, the daemon is sandboxed with Landlock and seccomp. Linux only.
+    pub sandbox: Option<SandboxConfig>,
+
+    /// Which kinds of command are carried out. When not set, none of them are.
+    pub commands: Option<CommandPolicyConfig>,
+
+    /// If set, every command we're sent is recorded here, along with what came of it.
+    pub audit_log: Option<AuditLogConfig>,
+
+    /// Tells apart instances running on the same host, such as one as root and one as a user.
+    /// It's added to the hostname in topics and the default client ID.
+    pub instance_id: Option<String>,
+
+    /// When not set, the MQTT server keeps our subscriptions and undelivered commands while we're
+    /// disconnected, so commands sent while we restart aren't lost.
+    #[serde(default = "Config::default_mqtt_clean_session")]
+    pub mqtt_clean_session: bool,
+
+    /// The interval to update at.
+    pub update_interval: Duration,
+
+    /// Update on whole multiples of `update_interval` on the clock, rather than counting from when we started.
+    #[serde(default)]
+    pub align_to_clock: bool,
+
+    /// If set, the first update is delayed by a random amount up to this long,
+    /// so machines that were started together don't all report at the same moment.
+    /// Updates aligned to the clock are offset from the set times by that amount.
+    pub splay: Option<Duration>,
+
+    /// Adds buttons to Home Assistant that shut down and restart the system.
+    #[serde(default)]
+    pub enable_power_commands: bool,
+
+    /// Adds a button to Home Assistant that suspends the system.
+    #[serde(default)]
+    pub enable_suspend_command: bool,
+
+    /// Adds a button to Home Assistant that hibernates the system.
+    #[serde(default)]
+    pub enable_hibernate_command: bool,
+
+    /// Reports when the system goes to sleep and wakes up again, and goes offline while it's asleep.
+    #[serde(default)]
+    pub enable_sleep_detection: bool,
+
+    /// Adds a button to Home Assistant that locks the screen.
+    #[serde(default)]
+    pub enable_lock_command: bool,
+
+    /// Adds a switch to Home Assistant that keeps the system from going to sleep, and a sensor
+    /// listing what's keeping it awake.
+    #[serde(default)]
+    pub enable_sleep_inhibitor: bool,
+
+    /// Shows messages sent from Home Assistant as desktop notifications.
+    #[serde(default)]
+    pub enable_notifications: bool,
+
+    /// Adds a switch to Home Assistant that follows and sets the desktop's do not disturb mode.
+    #[serde(default)]
+    pub enable_do_not_disturb: bool,
+
+    /// Reports the volume of the default audio output, and lets Home Assistant set it.
+    #[serde(default)]
+    pub enable_volume_control: bool,
+
+    /// Reports whether the default audio input is muted, and lets Home Assistant mute it.
+    #[serde(default)]
+    pub enable_microphone_mute: bool,
+
+    /// Reports how much power the CPU is drawing.
+    #[serde(default)]
+    pub enable_cpu_power: bool,
+
+    /// Reports how much of the time tasks were stalled waiting on the CPU, memory or IO.
+    #[serde(default)]
+    pub enable_pressure_stall: bool,
+
+    /// Reports how the hugepages set aside up front are being used, and how much memory is in
+    /// transparent hugepages.
+    #[serde(default)]
+    pub enable_hugepages: bool,
+
+    /// Reports how many memory errors ECC has corrected, and how many it couldn't.
+    #[serde(default)]
+    pub enable_ecc_errors: bool,
+
+    /// Reports how many processes the kernel has killed for running out of memory, and the last one.
+    #[serde(default)]
+    pub enable_oom_kills: bool,
+
+    /// Reports how much CPU and memory each logged in user is using.
+    #[serde(default)]
+    pub enable_user_usage: bool,
+
+    /// Reports how far the system clock is from the time servers.
+    #[serde(default)]
+    pub enable_clock_offset: bool,
+
+    /// Reports whether a firewall is active.
+    #[serde(default)]
+    pub enable_firewall_status: bool,
+
+    /// Reports the state of SELinux and AppArmor.
+    #[serde(default)]
+    pub enable_security_modules: bool,
+
+    /// Reports how many TCP connections are open.
+    #[serde(default)]
+    pub enable_tcp_connections: bool,
+
+    /// Reports whether the camera, microphone or speakers are being used.
+    #[serde(default)]
+    pub enable_in_use_sensors: bool,
+
+    /// Reports the brightness of the display's backlight, and lets Home Assistant set it.
+    #[serde(default)]
+    pub enable_backlight_control: bool,
+
+    /// The backlight to control, by its name under `/sys/class/backlight`.
+    /// If not set, the first one found is used.
+    pub backlight_device: Option<String>,
+
+    /// Reports the keyboard's backlight as a light, and lets Home Assistant control it.
+    #[serde(default)]
+    pub enable_keyboard_backlight: bool,
+
+    /// Adds switches to Home Assistant that turn radios, such as Wi-Fi and Bluetooth, on and off.
+    #[serde(default)]
+    pub enable_radio_switches: bool,
+
+    /// Hosts to report the round trip time and packet loss to.
+    #[serde(default)]
+    pub ping_targets: Vec<PingTarget>,
+
+    /// Host names to check can be looked up.
+    #[serde(default)]
+    pub dns_checks: Vec<DnsCheck>,
+
+    /// TCP ports to check can be connected to.
+    #[serde(default)]
+    pub port_checks: Vec<PortCheck>,
+
+    /// HTTP endpoints to check the health of.
+    #[serde(default)]
+    pub http_checks: Vec<HttpCheck>,
+
+    /// If set, our public IP addresses are reported.
+    pub public_ip: Option<PublicIpConfig>,
+
+    /// WireGuard interfaces to report the connection state of.
+    #[serde(default)]
+    pub wireguard_interfaces: Vec<String>,
+
+    /// If set, the state of our connection to Tailscale is reported.
+    pub tailscale: Option<TailscaleConfig>,
+
+    /// If set, logins and logouts are reported as they happen.
+    pub login_sessions: Option<LoginSessionsConfig>,
+
+    /// If set, whether nearby Bluetooth LE devices are around is reported.
+    pub ble_presence: Option<BleScanConfig>,
+
+    /// If set, failed SSH logins are counted.
+    pub ssh_failed_logins: Option<SshFailedLoginsConfig>,
+
+    /// Hosts that can't run system-mqtt to collect basic stats from over SSH.
+    #[serde(default)]
+    pub remote_hosts: Vec<RemoteHostConfig>,
+
+    /// Reports how many SSH sessions are open.
+    #[serde(default)]
+    pub enable_ssh_sessions: bool,
+
+    /// If set, how many addresses fail2ban has banned is reported.
+    pub fail2ban: Option<Fail2banConfig>,
+
+    /// If set, the sensors of the server's BMC are reported.
+    pub ipmi: Option<IpmiConfig>,
+
+    /// NVMe drives to report the health of, such as `nvme0`.
+    #[serde(default)]
+    pub nvme_devices: Vec<String>,
+
+    /// Linux software RAID arrays to report the progress of rebuilds and checks of, such as `md0`.
+    #[serde(default)]
+    pub md_arrays: Vec<String>,
+
+    /// ZFS pools to report the progress of resilvers and scrubs of.
+    #[serde(default)]
+    pub zfs_pools: Vec<String>,
+
+    /// Directories to report the total size of.
+    #[serde(default)]
+    pub directory_sizes: Vec<DirectorySizeConfig>,
+
+    /// Restic or borg repositories to report how long ago the last backup was made to.
+    #[serde(default)]
+    pub backups: Vec<BackupConfig>,
+
+    /// Files to report how long ago they were modified.
+    #[serde(default)]
+    pub file_ages: Vec<FileAgeConfig>,
+
+    /// Logs to count the lines matching a pattern in.
+    #[serde(default)]
+    pub log_matches: Vec<LogMatchConfig>,
+
+    /// If set, the kernel's warnings and errors are counted.
+    pub kernel_errors: Option<KernelErrorsConfig>,
+
+    /// Network filesystems to report whether they're mounted and responding.
+    #[serde(default)]
+    pub network_mounts: Vec<NetworkMount>,
+
+    /// USB devices to report whether they're plugged in.
+    #[serde(default)]
+    pub usb_devices: Vec<UsbDevice>,
+
+    /// If set, the state of Docker or Podman containers is reported.
+    pub containers: Option<ContainersConfig>,
+
+    /// If set, libvirt virtual machines are reported.
+    pub libvirt: Option<LibvirtConfig>,
+
+    /// Systemd units to report the state of, some of which Home Assistant may start and stop.
+    #[serde(default)]
+    pub systemd_units: Vec<SystemdUnitConfig>,
+
+    /// Systemd timers to report the next and last run of.
+    #[serde(default)]
+    pub systemd_timers: Vec<SystemdTimerConfig>,
+
+    /// Reports how many systemd units have failed.
+    #[serde(default)]
+    pub enable_failed_units: bool,
+
+    /// Reports the cpufreq scaling governor, and lets Home Assistant change it.
+    #[serde(default)]
+    pub enable_cpu_governor_control: bool,
+
+    /// Fans to report the speed of, and let Home Assistant control.
+    #[serde(default)]
+    pub fans: Vec<FanConfig>,
+
+    /// Reports the power profile from power-profiles-daemon, and lets Home Assistant change it.
+    #[serde(default)]
+    pub enable_power_profile: bool,
+
+    /// Reports what's playing in desktop media players, and lets Home Assistant control them.
+    #[serde(default)]
+    pub enable_media_player: bool,
+
+    /// If set, text sent from Home Assistant is spoken through the speakers.
+    pub text_to_speech: Option<SpeechConfig>,
+
+    /// If set, Home Assistant can play sounds through the speakers as a siren.
+    pub siren: Option<SirenConfig>,
+
+    /// If set, Home Assistant can ask for a screenshot, which is published as a camera.
+    pub screenshot: Option<ScreenshotConfig>,
+
+    /// If set, the displays can be turned on and off from Home Assistant.
+    pub display_power: Option<DisplayPowerConfig>,
+
+    /// If set, pending operating system updates are reported as an update entity.
+    pub os_updates: Option<OsUpdatesConfig>,
+
+    /// If set, devices with firmware updates available from fwupd are reported.
+    pub firmware_updates: Option<FirmwareUpdatesConfig>,
+
+    /// Scripts that can be run from Home Assistant, by the name of the button that runs them.
+    #[serde(default)]
+    pub scripts: BTreeMap<String, PathBuf>,
+
+    /// Machines on the local network that Home Assistant can wake up through us.
+    #[serde(default)]
+    pub wake_on_lan: Vec<WakeOnLanTarget>,
+
+    /// If set, values are only published when they change, or when the heartbeat interval passes.
+    pub publish_on_change: Option<ChangeFilterConfig>,
+
+    /// If set, limits how often values are published.
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Which messages the MQTT server keeps for clients that connect later.
+    #[serde(default)]
+    pub retain: RetainConfig,
+
+    /// How entities are named in Home Assistant, and what their ids are made from.
+    #[serde(default)]
+    pub home_assistant_naming: NamingConfig,
+
+    /// Reshapes the payloads of specific entities before they are published to the MQTT server, by entity name.
+    #[serde(default)]
+    pub payload_templates: BTreeMap<String, String>,
+
+    /// Icons shown in Home Assistant, such as `mdi:harddisk`, by entity name. Entities without one
+    /// get the icon their sensor picked.
+    #[serde(default)]
+    pub icons: BTreeMap<String, String>,
+
+    /// Publishes every value in one JSON document on a single topic at the end of each update,
+    /// instead of each value on its own topic.
+    #[serde(default)]
+    pub aggregate_state: bool,
+
+    /// Publish values and discovery messages for Home Assistant. Turn this off to only use Homie.
+    #[serde(default = "Config::default_enable_home_assistant")]
+    pub enable_home_assistant: bool,
+
+    /// Send Home Assistant the discovery messages it finds our entities through. When turned off,
+    /// values are still published on their own topics.
+    #[serde(default = "Config::default_home_assistant_discovery")]
+    pub home_assistant_discovery: bool,
+
+    /// If set, values that couldn't be published while the MQTT server was unreachable are kept,
+    /// and replayed once it's back.
+    pub offline_backlog: Option<BacklogConfig>,
+
+    /// If set, values are also published following the Homie convention.
+    pub homie: Option<HomieConfig>,
+
+    /// If set, values are also published as a Sparkplug B edge node.
+    pub sparkplug: Option<SparkplugConfig>,
+
+    /// How long a single sensor may take to collect its values before it is skipped for that update.
+    #[serde(default = "Config::default_sensor_timeout")]
+    pub sensor_timeout: Duration,
+
+    /// Reports on system-mqtt itself, such as its version and how many collections have failed.
+    #[serde(default)]
+    pub enable_diagnostics: bool,
+
+    /// Report CPU and memory usage relative to the limits of our cgroup rather than the whole host.
+    /// If not set, this is done when we're running in a container.
+    pub cgroup_aware: Option<bool>,
+
+    /// How many of the processes using the most CPU and memory to list in the attributes of the
+    /// `cpu` and `memory` entities. None are listed if not set.
+    pub top_processes: Option<usize>,
+
+    /// The names of drives, or the paths to where they are mounted.
+    pub drives: Vec<DriveConfig>,
+
+    /// If set, mounted filesystems are found on our own and reported along with `drives`.
+    pub discover_drives: Option<DriveDiscoveryConfig>,
+
+    /// Disks, such as `sda` or `nvme0n1`, to report the average time reads and writes take on.
+    #[serde(default)]
+    pub disk_latency: Vec<String>,
+
+    /// Report how full the data and metadata of LVM thin pools are.
+    #[serde(default)]
+    pub enable_lvm_thin_pools: bool,
+
+    /// The units uptime, memory, swap and filesystem usage are reported in.
+    #[serde(default)]
+    pub units: UnitsConfig,
+
+    /// Reports whether removable media, such as USB sticks and SD cards, is mounted, and how full it is.
+    #[serde(default)]
+    pub enable_removable_media: bool,
+
+    /// Reports whether we're docked, and how many external displays are connected.
+    #[serde(default)]
+    pub enable_display_sensors: bool,
+
+    /// Report how bright it is around the host, from its ambient light sensor.
+    #[serde(default)]
+    pub enable_ambient_light: bool,
+
+    /// Report the state of the battery. If not set, this is done when there is one.
+    pub enable_battery: Option<bool>,
+
+    /// The battery is reported as low when its charge drops below this percentage while discharging.
+    #[serde(default = "Config::default_battery_low_threshold")]
+    pub battery_low_threshold: f32,
+
+    /// Reports the charge the battery stops charging at, and lets Home Assistant set it.
+    #[serde(default)]
+    pub enable_battery_charge_limit: bool,
+
+    /// Network interfaces to report the total data received and transmitted through.
+    #[serde(default)]
+    pub network_interfaces: Vec<String>,
+
+    /// If set, the data used by each network interface since this day of the month is also reported.
+    pub network_monthly_reset_day: Option<u8>,
+
+    /// Where state that needs to survive a restart, such as data usage totals, is kept. Defaults
+    /// to a system-wide directory, such as `/var/lib/system-mqtt`. Set it to `~` to keep that
+    /// state in memory only, in which case it's lost when we stop.
+    #[serde(default = "Config::default_state_dir")]
+    pub state_dir: Option<PathBuf>,
+
+    /// Sensors whose values come from the output of arbitrary commands.
+    #[serde(default)]
+    pub exec_sensors: Vec<ExecSensorConfig>,
+
+    /// Sensors whose values come from Lua scripts.
+    #[serde(default)]
+    pub lua_sensors: Vec<LuaSensorConfig>,
+
+    /// Sensors whose values come from D-Bus properties or method calls.
+    #[serde(default)]
+    pub dbus_sensors: Vec<DbusSensorConfig>,
+
+    /// A directory to load WebAssembly sensor plugins from.
+    pub plugin_directory: Option<PathBuf>,
+
+    /// If set, the latest values are also served in the Prometheus format on `/metrics` at this address.
+    pub prometheus_address: Option<SocketAddr>,
+
+    /// If set, the latest values and our own health are served as JSON at this address.
+    pub status_api_address: Option<SocketAddr>,
+
+    /// If set, all values are also written to this InfluxDB server.
+    pub influxdb: Option<InfluxConfig>,
+
+    /// If set, every value is also recorded in a local SQLite database.
+    pub history: Option<HistoryConfig>,
+
+    /// If set, the latest values are also pushed to this OpenTelemetry collector.
+    pub otlp: Option<OtlpConfig>,
+
+    /// The most verbose level of log messages to emit.
+    #[serde(default = "Config::default_log_level")]
+    pub log_level: log::LevelFilter,
+}
+
+impl Config {
+    /// Loads the config file at the path. If there is no file there, a default one is written.
+    pub async fn load(path: &Path) -> Result<Self> {
+        if path.is_file() {
+            // It's a readable file we can load.
+
+            let config: Self = serde_yaml::from_str(&fs::read_to_string(path).await?)
+                .context("Failed to deserialize config file.")?;
+
+            Ok(config)
+        } else {
+            log::info!("No config file present. A default one will be written.");
+            // Doesn't exist yet. We'll create it.
+            let config = Self::default();
+
+            // Write it to a file for next time we load.
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(path, serde_yaml::to_string(&config)?).await?;
+
+            Ok(config)
+        }
+    }
+
+    fn default_mqtt_clean_session() -> bool {
+        true
+    }
+
+    fn default_enable_home_assistant() -> bool {
+        true
+    }
+
+    fn default_home_assistant_discovery() -> bool {
+        true
+    }
+
+    fn default_sensor_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_battery_low_threshold() -> f32 {
+        20.0
+    }
+
+    #[cfg(unix)]
+    fn default_state_dir() -> Option<PathBuf> {
+        Some(PathBuf::from("/var/lib/system-mqtt"))
+    }
+
+    #[cfg(windows)]
+    fn default_state_dir() -> Option<PathBuf> {
+        Some(PathBuf::from(r"C:\ProgramData\system-mqtt"))
+    }
+
+    fn default_log_level() -> log::LevelFilter {
+        log::LevelFilter::Info
+    }
+
+    /// Warns about options that are set for things this build was made without, which are ignored.
+    pub fn warn_about_left_out_options(&self) {
+        let options = [
+            (
+                "lua_sensors",
+                "lua",
+                cfg!(feature = "lua"),
+                !self.lua_sensors.is_empty(),
+            ),
+            (
+                "plugin_directory",
+                "wasm",
+                cfg!(feature = "wasm"),
+                self.plugin_directory.is_some(),
+            ),
+            (
+                "history",
+                "history",
+                cfg!(feature = "history"),
+                self.history.is_some(),
+            ),
+            (
+                "containers",
+                "containers",
+                cfg!(all(unix, feature = "containers")),
+                self.containers.is_some(),
+            ),
+            (
+                "libvirt",
+                "libvirt",
+                cfg!(feature = "libvirt"),
+                self.libvirt.is_some(),
+            ),
+            (
+                "influxdb",
+                "influxdb",
+                cfg!(feature = "influxdb"),
+                self.influxdb.is_some(),
+            ),
+            ("otlp", "otlp", cfg!(feature = "otlp"), self.otlp.is_some()),
+            (
+                "http_checks",
+                "http-checks",
+                cfg!(feature = "http-checks"),
+                !self.http_checks.is_empty(),
+            ),
+            (
+                "public_ip",
+                "http-checks",
+                cfg!(feature = "http-checks"),
+                self.public_ip.is_some(),
+            ),
+            (
+                "enable_power_commands",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_power_commands,
+            ),
+            (
+                "enable_suspend_command",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_suspend_command,
+            ),
+            (
+                "enable_hibernate_command",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_hibernate_command,
+            ),
+            (
+                "enable_lock_command",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_lock_command,
+            ),
+            (
+                "enable_sleep_inhibitor",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_sleep_inhibitor,
+            ),
+            (
+                "enable_notifications",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_notifications,
+            ),
+            (
+                "enable_do_not_disturb",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_do_not_disturb,
+            ),
+            (
+                "enable_failed_units",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_failed_units,
+            ),
+            (
+                "enable_power_profile",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_power_profile,
+            ),
+            (
+                "enable_media_player",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_media_player,
+            ),
+            (
+                "enable_ssh_sessions",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.enable_ssh_sessions,
+            ),
+            (
+                "systemd_units",
+                "dbus",
+                cfg!(feature = "dbus"),
+                !self.systemd_units.is_empty(),
+            ),
+            (
+                "systemd_timers",
+                "dbus",
+                cfg!(feature = "dbus"),
+                !self.systemd_timers.is_empty(),
+            ),
+            (
+                "firmware_updates",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.firmware_updates.is_some(),
+            ),
+            (
+                "login_sessions",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.login_sessions.is_some(),
+            ),
+            (
+                "ble_presence",
+                "dbus",
+                cfg!(feature = "dbus"),
+                self.ble_presence.is_some(),
+            ),
+            (
+                "dbus_sensors",
+                "dbus",
+                cfg!(feature = "dbus"),
+                !self.dbus_sensors.is_empty(),
+            ),
+            (
+                "enable_oom_kills",
+                "pattern-matching",
+                cfg!(feature = "pattern-matching"),
+                self.enable_oom_kills,
+            ),
+            (
+                "log_matches",
+                "pattern-matching",
+                cfg!(feature = "pattern-matching"),
+                !self.log_matches.is_empty(),
+            ),
+            (
+                "kernel_errors",
+                "pattern-matching",
+                cfg!(feature = "pattern-matching"),
+                self.kernel_errors.is_some(),
+            ),
+            (
+                "ssh_failed_logins",
+                "pattern-matching",
+                cfg!(feature = "pattern-matching"),
+                self.ssh_failed_logins.is_some(),
+            ),
+        ];
+
+        for &(option, feature, built, set) in options.iter() {
+            if set && !built {
+                log::warn!(
+                    "`{}` is set, but system-mqtt was built without the `{}` feature, so it's ignored.",
+                    option,
+                    feature
+                );
+            }
+        }
+
+        #[cfg(not(unix))]
+        if self.tailscale.is_some() {
+            log::warn!("`tailscale` is set, but is only available on Unix, so it's ignored.");
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mqtt_server: MqttServer::Url(
+                Url::parse("mqtt://localhost").expect("Failed to parse default URL."),
+            ),
+            mqtt_proxy: None,
+            username: None,
+            password_source: PasswordSource::Keyring,
+            mqtt_client_id: None,
+            instance_id: None,
+            command_authentication: None,
+            audit_log: None,
+            commands: None,
+            privileged_helper: None,
+            sandbox: None,
+            mqtt_clean_session: Self::default_mqtt_clean_session(),
+            update_interval: Duration::from_secs(30),
+            align_to_clock: false,
+            splay: None,
+            enable_power_commands: false,
+            enable_suspend_command: false,
+            enable_hibernate_command: false,
+            enable_sleep_detection: false,
+            enable_lock_command: false,
+            enable_sleep_inhibitor: false,
+            enable_notifications: false,
+            enable_do_not_disturb: false,
+            enable_volume_control: false,
+            enable_microphone_mute: false,
+            enable_cpu_power: false,
+            enable_pressure_stall: false,
+            enable_hugepages: false,
+            enable_ecc_errors: false,
+            enable_oom_kills: false,
+            enable_user_usage: false,
+            enable_clock_offset: false,
+            enable_firewall_status: false,
+            enable_security_modules: false,
+            enable_tcp_connections: false,
+            enable_in_use_sensors: false,
+            enable_backlight_control: false,
+            backlight_device: None,
+            enable_keyboard_backlight: false,
+            enable_radio_switches: false,
+            ping_targets: Vec::new(),
+            dns_checks: Vec::new(),
+            port_checks: Vec::new(),
+            http_checks: Vec::new(),
+            public_ip: None,
+            wireguard_interfaces: Vec::new(),
+            ipmi: None,
+            nvme_devices: Vec::new(),
+            md_arrays: Vec::new(),
+            zfs_pools: Vec::new(),
+            directory_sizes: Vec::new(),
+            backups: Vec::new(),
+            file_ages: Vec::new(),
+            log_matches: Vec::new(),
+            kernel_errors: None,
+            network_mounts: Vec::new(),
+            usb_devices: Vec::new(),
+            tailscale: None,
+            login_sessions: None,
+            ble_presence: None,
+            ssh_failed_logins: None,
+            remote_hosts: Vec::new(),
+            enable_ssh_sessions: false,
+            fail2ban: None,
+            containers: None,
+            libvirt: None,
+            systemd_units: Vec::new(),
+            systemd_timers: Vec::new(),
+            enable_failed_units: false,
+            enable_cpu_governor_control: false,
+            fans: Vec::new(),
+            enable_power_profile: false,
+            enable_media_player: false,
+            text_to_speech: None,
+            siren: None,
+            screenshot: None,
+            display_power: None,
+            os_updates: None,
+            firmware_updates: None,
+            scripts: BTreeMap::new(),
+            wake_on_lan: Vec::new(),
+            publish_on_change: None,
+            rate_limit: None,
+            retain: RetainConfig::default(),
+            home_assistant_naming: NamingConfig::default(),
+            payload_templates: BTreeMap::new(),
+            icons: BTreeMap::new(),
+            aggregate_state: false,
+            enable_home_assistant: Self::default_enable_home_assistant(),
+            home_assistant_discovery: Self::default_home_assistant_discovery(),
+            offline_backlog: None,
+            homie: None,
+            sparkplug: None,
+            sensor_timeout: Self::default_sensor_timeout(),
+            enable_diagnostics: false,
+            cgroup_aware: None,
+            top_processes: None,
+            drives: vec![DriveConfig {
+                path: PathBuf::from("/"),
+                name: String::from("root"),
+                device: None,
+                detect_read_only: false,
+                detect_encryption: false,
+            }],
+            discover_drives: None,
+            disk_latency: Vec::new(),
+            enable_lvm_thin_pools: false,
+            enable_removable_media: false,
+            enable_display_sensors: false,
+            enable_ambient_light: false,
+            enable_battery: None,
+            battery_low_threshold: Self::default_battery_low_threshold(),
+            enable_battery_charge_limit: false,
+            units: UnitsConfig::default(),
+            network_interfaces: Vec::new(),
+            network_monthly_reset_day: None,
+            state_dir: Self::default_state_dir(),
+            exec_sensors: Vec::new(),
+            lua_sensors: Vec::new(),
+            dbus_sensors: Vec::new(),
+            plugin_directory: None,
+            prometheus_address: None,
+            status_api_address: None,
+            influxdb: None,
+            history: None,
+            otlp: None,
+            log_level: Self::default_log_level(),
+        }
+    }
+}
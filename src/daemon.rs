@@ -0,0 +1,665 @@
+//! The main loop of the daemon.
+
+#[cfg(feature = "history")]
+use crate::sink::history::History;
+#[cfg(feature = "influxdb")]
+use crate::sink::influx;
+#[cfg(feature = "otlp")]
+use crate::sink::otlp;
+#[cfg(feature = "dbus")]
+use crate::sleep::SleepWatcher;
+#[cfg(feature = "command-auth")]
+use crate::{
+    command_secret_keyring_name, sink::command_auth::CommandAuthenticator,
+    COMMAND_SECRET_KEYRING_NAME,
+};
+use crate::{
+    config::{Config, MqttServer, PasswordSource},
+    mdns,
+    mqtt::MqttClient,
+    proxy,
+    sensor::SensorRegistry,
+    sink::{
+        audit::AuditLog, command_policy::CommandPolicy, filter::ChangeFilter,
+        home_assistant::HomeAssistant, homie::Homie, prometheus, rate_limit::RateLimiter,
+        sparkplug::Sparkplug, status_api::StatusApi, template::PayloadTemplates, Entity, Sinks,
+    },
+    state::StateStore,
+    KEYRING_SERVICE_NAME,
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use futures::future::pending;
+use rumqttc::{LastWill, MqttOptions, Transport};
+#[cfg(feature = "command-auth")]
+use std::collections::HashMap;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use sysinfo::{System, SystemExt};
+use tokio::{fs, signal, time};
+use url::Url;
+
+/// Connects to the MQTT server, registers every sensor and then reports their values until
+/// a terminate signal is received.
+///
+/// When `dry_run` is set, nothing is sent to the MQTT server and the messages are printed to stdout instead.
+pub async fn run(config: &Config, dry_run: bool) -> Result<()> {
+    log::info!("Application start.");
+    config.warn_about_left_out_options();
+
+    let hostname = System::new()
+        .host_name()
+        .context("Could not get system hostname.")?;
+
+    // Instances running on the same host are told apart by their IDs.
+    let node_name = match &config.instance_id {
+        Some(instance_id) => format!("{}-{}", hostname, instance_id),
+        None => hostname.clone(),
+    };
+
+    let client_id = config
+        .mqtt_client_id
+        .clone()
+        .unwrap_or_else(|| format!("system-mqtt-{}", node_name));
+
+    if dry_run {
+        log::info!("Dry run requested. Nothing will be sent to the MQTT server.");
+    }
+
+    let mut sinks = Sinks::default();
+    let mut connections = 0;
+
+    if config.enable_home_assistant {
+        let client = connect_sink(
+            config,
+            dry_run,
+            &client_id,
+            "home-assistant",
+            None,
+            &mut connections,
+        )
+        .await?;
+
+        let mut home_assistant =
+            HomeAssistant::new(client, hostname.clone(), config.aggregate_state);
+        home_assistant.set_payload_templates(PayloadTemplates::new(&config.payload_templates)?);
+        home_assistant.set_icons(config.icons.clone().into_iter().collect());
+        home_assistant
+            .set_naming(config.home_assistant_naming.clone())
+            .context("Invalid `home_assistant_naming`.")?;
+        home_assistant.set_retain(config.retain.clone());
+        home_assistant.set_instance_id(config.instance_id.clone());
+        home_assistant.set_discovery(config.home_assistant_discovery);
+        if let Some(backlog_config) = &config.offline_backlog {
+            home_assistant.set_backlog(backlog_config.clone());
+        }
+        sinks.add(home_assistant);
+    }
+
+    if let Some(homie_config) = &config.homie {
+        let mut homie = Homie::new(node_name.clone(), homie_config);
+        let client = connect_sink(
+            config,
+            dry_run,
+            &client_id,
+            "homie",
+            Some(homie.last_will()),
+            &mut connections,
+        )
+        .await?;
+        homie.set_client(client);
+        sinks.add(homie);
+    }
+
+    if let Some(sparkplug_config) = &config.sparkplug {
+        let mut sparkplug = Sparkplug::new(node_name.clone(), sparkplug_config);
+        let client = connect_sink(
+            config,
+            dry_run,
+            &client_id,
+            "sparkplug",
+            Some(sparkplug.last_will()),
+            &mut connections,
+        )
+        .await?;
+        sparkplug.set_client(client);
+        sinks.add(sparkplug);
+    }
+
+    // Carrying out commands nobody signed, when the config asks for them to be, would be worse
+    // than not starting at all.
+    #[cfg(not(feature = "command-auth"))]
+    ensure!(
+        config.command_authentication.is_none(),
+        "`command_authentication` is set, but system-mqtt was built without the `command-auth` feature."
+    );
+
+    #[cfg(feature = "command-auth")]
+    if let Some(command_auth_config) = &config.command_authentication {
+        let secret = read_password(
+            &command_auth_config.secret_source,
+            COMMAND_SECRET_KEYRING_NAME,
+        )
+        .await
+        .context("Failed to read the secret commands are signed with.")?;
+
+        let mut client_secrets = HashMap::new();
+        for client in &command_auth_config.clients {
+            let secret = read_password(
+                &client.secret_source,
+                &command_secret_keyring_name(&client.name),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to read the secret `{}` signs commands with.",
+                    client.name
+                )
+            })?;
+            client_secrets.insert(client.name.clone(), secret);
+        }
+
+        sinks.set_command_authenticator(CommandAuthenticator::new(
+            &secret,
+            client_secrets,
+            command_auth_config,
+        ));
+    }
+
+    // Without a policy, no commands are carried out at all.
+    let command_policy_config = config.commands.clone().unwrap_or_default();
+    // Only signed commands say which client they came from.
+    #[cfg(feature = "command-auth")]
+    let clients: Vec<&str> = config
+        .command_authentication
+        .iter()
+        .flat_map(|command_auth| command_auth.clients.iter())
+        .map(|client| client.name.as_str())
+        .collect();
+    #[cfg(not(feature = "command-auth"))]
+    let clients: Vec<&str> = Vec::new();
+    for source in &command_policy_config.sources {
+        ensure!(
+            clients.contains(&source.as_str()),
+            "Commands are allowed from `{}`, which isn't one of the clients in `command_authentication`.",
+            source
+        );
+    }
+    sinks.set_command_policy(CommandPolicy::new(command_policy_config));
+
+    if let Some(audit_log_config) = &config.audit_log {
+        sinks.set_audit_log(AuditLog::open(audit_log_config)?);
+    }
+
+    if let Some(change_filter_config) = &config.publish_on_change {
+        sinks.set_change_filter(ChangeFilter::new(change_filter_config.clone()));
+    }
+
+    if let Some(rate_limit_config) = &config.rate_limit {
+        sinks.set_rate_limiter(RateLimiter::new(rate_limit_config.clone())?);
+    }
+
+    if let Some(address) = config.prometheus_address {
+        sinks.add(prometheus::Exporter::start(address, hostname.clone()).await?);
+    }
+
+    if let Some(address) = config.status_api_address {
+        sinks.add(StatusApi::start(address, hostname.clone(), history_path(config)).await?);
+    }
+
+    #[cfg(feature = "influxdb")]
+    if let Some(influx_config) = &config.influxdb {
+        sinks.add(influx::Writer::new(influx_config, hostname.clone())?);
+    }
+
+    #[cfg(feature = "history")]
+    if let Some(history_config) = &config.history {
+        sinks.add(History::open(history_config)?);
+    }
+
+    #[cfg(feature = "otlp")]
+    if let Some(otlp_config) = &config.otlp {
+        sinks.add(otlp::Exporter::new(otlp_config, hostname.clone())?);
+    }
+
+    // Register the various sensor topics and include the details about that sensor
+
+    //    TODO - create a new register_topic to register binary_sensor so we can make availability a real binary sensor. In the
+    //    meantime, create it as a normal analog sensor with two values, and a template can be used to make it a binary.
+
+    sinks
+        .register(
+            Entity::new("sensor", "available")
+                .state_class("")
+                .icon("mdi:check-network-outline"),
+        )
+        .await
+        .context("Failed to register availability topic.")?;
+
+    let sleep_watcher = if config.enable_sleep_detection {
+        sinks
+            .register(
+                Entity::new("binary_sensor", "sleeping")
+                    .state_class("")
+                    .icon("mdi:power-sleep"),
+            )
+            .await?;
+        sinks
+            .register(
+                Entity::new("sensor", "last_resume")
+                    .device_class("timestamp")
+                    .state_class("")
+                    .icon("mdi:weather-sunset-up"),
+            )
+            .await?;
+
+        Some(SleepWatcher::new().await?)
+    } else {
+        None
+    };
+
+    let state = Arc::new(load_state(config).await);
+
+    let mut sensors = SensorRegistry::from_config(config, state.clone())?;
+    sensors.register(&mut sinks).await?;
+
+    sinks.set_available(true).await?;
+
+    let result =
+        availability_trampoline(&mut sinks, &mut sensors, &state, config, sleep_watcher).await;
+    sensors.shutdown().await;
+
+    if let Err(error) = state.save().await {
+        log::error!("Failed to save state: {:?}", error);
+    }
+
+    if let Err(error) = sinks.set_available(false).await {
+        // I don't want this error hiding whatever happened in the main loop.
+        log::error!("Error while disconnecting from home assistant: {:?}", error);
+    }
+
+    result?;
+
+    sinks.disconnect().await?;
+
+    Ok(())
+}
+
+/// Where the history is recorded, so the status API can serve it.
+#[cfg(feature = "history")]
+fn history_path(config: &Config) -> Option<PathBuf> {
+    config.history.as_ref().map(|history| history.path.clone())
+}
+
+#[cfg(not(feature = "history"))]
+fn history_path(_config: &Config) -> Option<PathBuf> {
+    None
+}
+
+/// Connects a sink to the MQTT server, unless we're doing a dry run.
+///
+/// The MQTT client can't be shared between sinks, so each one gets a connection of its own. The
+/// first keeps our client ID, and the others have what they're for added to it, like `-homie`.
+async fn connect_sink(
+    config: &Config,
+    dry_run: bool,
+    client_id: &str,
+    purpose: &str,
+    last_will: Option<LastWill>,
+    connections: &mut usize,
+) -> Result<Option<MqttClient>> {
+    if dry_run {
+        return Ok(None);
+    }
+
+    let client_id = if *connections == 0 {
+        client_id.to_string()
+    } else {
+        format!("{}-{}", client_id, purpose)
+    };
+    *connections += 1;
+
+    Ok(Some(connect_client(config, client_id, last_will).await?))
+}
+
+/// The URL to connect to the MQTT server at. When going through a proxy, that's the local end
+/// of the forwarding to it.
+async fn server_url(config: &Config) -> Result<Url> {
+    let mut url = match &config.mqtt_server {
+        MqttServer::Url(url) => url.clone(),
+        MqttServer::Auto => mdns::find_mqtt_server()
+            .await
+            .context("Failed to find the MQTT server on the local network.")?,
+    };
+
+    if let Some(proxy_url) = &config.mqtt_proxy {
+        let tls = match url.scheme() {
+            "mqtt" => false,
+            "mqtts" => true,
+            scheme => bail!("MQTT server URL has unsupported scheme `{}`.", scheme),
+        };
+        // Whoever runs the proxy, or any network past it, could read a password sent in the clear.
+        ensure!(
+            tls || config.username.is_none(),
+            "Refusing to send the MQTT password through a proxy without TLS. Use an `mqtts://` server URL."
+        );
+
+        let host = url
+            .host_str()
+            .context("MQTT server URL has no host.")?
+            .to_string();
+        let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+        log::info!("Connecting to MQTT server through proxy.");
+
+        let local_address = proxy::forward(proxy_url, &host, port, tls).await?;
+        // The TLS connection is made at the far end of the forwarding, so ours to it is plain.
+        url.set_scheme("mqtt")
+            .map_err(|_| anyhow!("Failed to point the MQTT client at the proxy."))?;
+        url.set_ip_host(local_address.ip())
+            .map_err(|_| anyhow!("Failed to point the MQTT client at the proxy."))?;
+        url.set_port(Some(local_address.port()))
+            .map_err(|_| anyhow!("Failed to point the MQTT client at the proxy."))?;
+    }
+
+    Ok(url)
+}
+
+/// Connects to the MQTT server, fetching the password from wherever the config says it is. The
+/// last will is published by the server if we lose the connection without disconnecting.
+pub async fn connect_client(
+    config: &Config,
+    client_id: String,
+    last_will: Option<LastWill>,
+) -> Result<MqttClient> {
+    let url = server_url(config).await?;
+    let tls = match url.scheme() {
+        "mqtt" => false,
+        "mqtts" => true,
+        scheme => bail!("MQTT server URL has unsupported scheme `{}`.", scheme),
+    };
+    let host = url.host_str().context("MQTT server URL has no host.")?;
+    let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+
+    log::debug!("Using MQTT client ID `{}`.", client_id);
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_clean_session(config.mqtt_clean_session);
+    if let Some(last_will) = last_will {
+        options.set_last_will(last_will);
+    }
+    if tls {
+        // Certificates are checked against the system's trusted roots.
+        options.set_transport(Transport::tls_with_default_config());
+    }
+
+    // If credentials are provided, use them.
+    if let Some(username) = &config.username {
+        // TODO make TLS mandatory when using a password.
+
+        let password = read_password(&config.password_source, username).await?;
+
+        options.set_credentials(username, password);
+    }
+
+    log::debug!("Connecting to MQTT server at `{}`.", config.mqtt_server);
+
+    let client = MqttClient::connect(options).await?;
+
+    log::debug!("Connected to MQTT server.");
+
+    Ok(client)
+}
+
+/// Rates such as CPU usage are measured between two samples, and the sensors take their first one
+/// when they're created. The first update waits this long, so there's something to compare against.
+const FIRST_UPDATE_DELAY: Duration = Duration::from_secs(1);
+
+async fn availability_trampoline(
+    sinks: &mut Sinks,
+    sensors: &mut SensorRegistry,
+    state: &StateStore,
+    config: &Config,
+    mut sleep_watcher: Option<SleepWatcher>,
+) -> Result<()> {
+    // The first update happens right away, so there are values to show before a whole interval has passed.
+    // Only the first update needs to be offset. Every update after it keeps the same offset.
+    // Updates aligned to the clock are offset from the set times by the same amount, so hosts
+    // sharing a schedule still don't all report at once.
+    let splay = random_splay(config.splay);
+
+    // Created once, so commands that come in between updates don't push the next one back.
+    let next_update = time::sleep(FIRST_UPDATE_DELAY + splay);
+    tokio::pin!(next_update);
+
+    // Created once, so a signal that comes in while we're busy updating isn't missed.
+    let terminate = terminate_signal();
+    tokio::pin!(terminate);
+
+    loop {
+        tokio::select! {
+            _ = &mut next_update => {
+                sensors.collect(sinks).await;
+                sinks.flush().await;
+                next_update
+                    .as_mut()
+                    .reset(time::Instant::now() + until_next_update(config, splay));
+
+                if let Err(error) = state.save().await {
+                    log::warn!("Failed to save state: {:?}", error);
+                }
+            }
+            (index, result) = sensors.next_event() => {
+                sensors.publish_event(sinks, index, result).await;
+                sinks.flush().await;
+            }
+            command = sinks.next_command() => {
+                sensors.command(sinks, command?).await;
+
+                // So the new state shows up right away, rather than with the next update.
+                sinks.flush().await;
+            }
+            going_to_sleep = next_sleep_event(&mut sleep_watcher) => {
+                if going_to_sleep? {
+                    log::info!("System is going to sleep.");
+
+                    sinks.publish("sleeping", String::from("ON")).await;
+                    sinks.flush().await;
+                    if let Err(error) = sinks.set_available(false).await {
+                        log::error!("Failed to report that we're going to sleep: {:?}", error);
+                    }
+
+                    if let Some(sleep_watcher) = &mut sleep_watcher {
+                        sleep_watcher.release();
+                    }
+                } else {
+                    log::info!("System has woken up.");
+
+                    if let Err(error) = sinks.set_available(true).await {
+                        log::error!("Failed to report that we're awake: {:?}", error);
+                    }
+                    sinks.publish("sleeping", String::from("OFF")).await;
+                    sinks
+                        .publish(
+                            "last_resume",
+                            humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+                        )
+                        .await;
+
+                    // Everything we published before going to sleep is out of date, so it all goes out again right away.
+                    sinks.reset_change_filter();
+                    sensors.collect(sinks).await;
+                    sinks.flush().await;
+                    next_update
+                        .as_mut()
+                        .reset(time::Instant::now() + until_next_update(config, splay));
+
+                    if let Some(sleep_watcher) = &mut sleep_watcher {
+                        sleep_watcher.inhibit().await;
+                    }
+                }
+            }
+            result = &mut terminate => {
+                result?;
+                log::info!("Terminate signal has been received.");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits for us to be asked to stop, whether by systemd stopping the service or by Ctrl-C.
+#[cfg(unix)]
+async fn terminate_signal() -> Result<()> {
+    use signal::unix::{signal, SignalKind};
+
+    let mut terminate =
+        signal(SignalKind::terminate()).context("Failed to listen for terminate signal.")?;
+    let mut interrupt =
+        signal(SignalKind::interrupt()).context("Failed to listen for interrupt signal.")?;
+
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = interrupt.recv() => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() -> Result<()> {
+    signal::ctrl_c()
+        .await
+        .context("Failed to listen for Ctrl-C.")
+}
+
+/// Without D-Bus there's no logind to hear about sleep from, so there's never a watcher.
+#[cfg(not(feature = "dbus"))]
+enum SleepWatcher {}
+
+#[cfg(not(feature = "dbus"))]
+impl SleepWatcher {
+    async fn new() -> Result<Self> {
+        bail!("Sleep detection needs a build with the `dbus` feature.")
+    }
+
+    async fn inhibit(&mut self) {
+        match *self {}
+    }
+
+    fn release(&mut self) {
+        match *self {}
+    }
+
+    async fn next(&mut self) -> Result<bool> {
+        match *self {}
+    }
+}
+
+/// Waits for the system to go to sleep or wake up. Without a watcher, that never happens.
+async fn next_sleep_event(sleep_watcher: &mut Option<SleepWatcher>) -> Result<bool> {
+    match sleep_watcher {
+        Some(sleep_watcher) => sleep_watcher.next().await,
+        None => pending().await,
+    }
+}
+
+/// Reads a password, or other secret, from where the config says it is.
+/// In the keyring, it's stored under `keyring_name`.
+async fn read_password(source: &PasswordSource, keyring_name: &str) -> Result<String> {
+    match source {
+        PasswordSource::Keyring => {
+            log::info!("Using system keyring for `{}`.", keyring_name);
+            let keyring = keyring::Entry::new(KEYRING_SERVICE_NAME, keyring_name)
+                .context("Failed to find password entry in keyring.")?;
+            keyring
+                .get_password()
+                .context("Failed to get password from keyring. If you have not yet set the password, run `system-mqtt set-password`.")
+        }
+        PasswordSource::SecretFile(file_path) => {
+            log::info!("Using hidden file for `{}`.", keyring_name);
+            let metadata = file_path
+                .metadata()
+                .context("Failed to get password file metadata.")?;
+
+            // It's not even an encrypted file, so we need to keep the permission settings pretty tight.
+            // The only time I can really enforce that is when reading the password.
+            check_secret_file_permissions(&metadata)?;
+
+            let pass: String = fs::read_to_string(file_path)
+                .await
+                .context("Failed to read password file.")?;
+            Ok(pass.as_str().trim_end().to_string())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn check_secret_file_permissions(metadata: &std::fs::Metadata) -> Result<()> {
+    use std::os::unix::prelude::MetadataExt;
+
+    if metadata.mode() & 0o777 != 0o600 {
+        bail!("Permission bits for password file must be set to 0o600 (only owner can read and write)");
+    }
+
+    if metadata.uid() != users::get_current_uid() {
+        bail!("Password file must be owned by the current user.");
+    }
+
+    if metadata.gid() != users::get_current_gid() {
+        bail!("Password file must be owned by the current group.");
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_secret_file_permissions(_metadata: &std::fs::Metadata) -> Result<()> {
+    // Windows controls who can read a file with ACLs, which the metadata doesn't tell us about.
+    log::warn!("Permissions of the password file can't be checked on this platform. Make sure only you can read it.");
+
+    Ok(())
+}
+
+/// Loads the state from the state directory. Not being able to is no reason to stop reporting,
+/// so we fall back to keeping it in memory.
+async fn load_state(config: &Config) -> StateStore {
+    if let Some(state_dir) = &config.state_dir {
+        match StateStore::load(state_dir).await {
+            Ok(state) => return state,
+            Err(error) => log::warn!(
+                "Failed to load state. It will not be kept across restarts: {:?}",
+                error
+            ),
+        }
+    }
+
+    StateStore::in_memory()
+}
+
+/// How long to wait before the next update. Updates aligned to the clock happen on whole multiples
+/// of the interval, counted from midnight UTC, so every five minutes means at :00, :05, :10 and so on,
+/// plus the splay.
+fn until_next_update(config: &Config, splay: Duration) -> Duration {
+    let interval = config.update_interval.as_nanos();
+    if !config.align_to_clock || interval == 0 {
+        return config.update_interval;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    Duration::from_nanos((interval - now % interval) as u64) + splay
+}
+
+/// A random amount of time between zero and the splay.
+fn random_splay(splay: Option<Duration>) -> Duration {
+    match splay {
+        Some(splay) => splay.mul_f64(crate::random() as f64 / u64::MAX as f64),
+        None => Duration::ZERO,
+    }
+}
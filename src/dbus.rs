@@ -0,0 +1,81 @@
+//! Helpers shared by everything that talks to D-Bus.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use zbus::{zvariant::Value, Connection};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    #[serde(rename = "system")]
+    System,
+
+    #[serde(rename = "session")]
+    Session,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::System
+    }
+}
+
+pub async fn connect(bus: Bus) -> Result<Connection> {
+    match bus {
+        Bus::System => Connection::system()
+            .await
+            .context("Failed to connect to the D-Bus system bus."),
+        Bus::Session => Connection::session()
+            .await
+            .context("Failed to connect to the D-Bus session bus."),
+    }
+}
+
+/// A connection that is only made the first time it's needed, and then reused.
+pub struct LazyConnection {
+    bus: Bus,
+    connection: Option<Connection>,
+}
+
+impl LazyConnection {
+    pub fn new(bus: Bus) -> Self {
+        Self {
+            bus,
+            connection: None,
+        }
+    }
+
+    pub async fn get(&mut self) -> Result<Connection> {
+        match &self.connection {
+            Some(connection) => Ok(connection.clone()),
+            None => {
+                let connection = connect(self.bus).await?;
+                self.connection = Some(connection.clone());
+                Ok(connection)
+            }
+        }
+    }
+}
+
+/// Turns a D-Bus value into something we can publish.
+pub fn format_value(value: &Value) -> Result<String> {
+    let value = match value {
+        Value::Bool(value) => if *value { "ON" } else { "OFF" }.to_string(),
+        Value::U8(value) => value.to_string(),
+        Value::I16(value) => value.to_string(),
+        Value::U16(value) => value.to_string(),
+        Value::I32(value) => value.to_string(),
+        Value::U32(value) => value.to_string(),
+        Value::I64(value) => value.to_string(),
+        Value::U64(value) => value.to_string(),
+        Value::F64(value) => value.to_string(),
+        Value::Str(value) => value.to_string(),
+        Value::ObjectPath(value) => value.to_string(),
+        Value::Value(value) => format_value(value)?,
+        value => bail!(
+            "Unsupported D-Bus value type `{}`.",
+            value.value_signature()
+        ),
+    };
+
+    Ok(value)
+}
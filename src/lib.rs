@@ -0,0 +1,48 @@
+//! Collects statistics about the system it's running on and reports them to an MQTT server,
+//! along with the discovery messages Home Assistant needs to pick them up.
+//!
+//! The `system-mqtt` binary is a thin command line interface over this library. To embed the
+//! same collection and discovery logic elsewhere, load a [`Config`] and hand it to [`run`].
+
+pub mod config;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod lock;
+pub mod mdns;
+pub mod mqtt;
+pub mod privileged;
+pub mod proxy;
+pub mod sandbox;
+pub mod sensor;
+pub mod setup;
+pub mod sink;
+#[cfg(feature = "dbus")]
+pub mod sleep;
+pub mod state;
+
+pub use config::Config;
+pub use daemon::run;
+
+/// The service name passwords are stored under in the OS keyring.
+pub const KEYRING_SERVICE_NAME: &str = "system-mqtt";
+
+/// The name the secret commands are signed with is stored under in the OS keyring.
+pub const COMMAND_SECRET_KEYRING_NAME: &str = "command-secret";
+
+/// The name a client's own command secret is stored under in the OS keyring, such as
+/// `command-secret-node-red`.
+pub fn command_secret_keyring_name(client: &str) -> String {
+    format!("{}-{}", COMMAND_SECRET_KEYRING_NAME, client)
+}
+
+/// A random number, good enough for spreading hosts out or telling our DNS queries apart, but not
+/// for anything secret. Every hasher the standard library hands out is seeded differently.
+pub(crate) fn random() -> u64 {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+    };
+
+    RandomState::new().build_hasher().finish()
+}
@@ -0,0 +1,113 @@
+//! Keeps a second daemon from starting with a config file that's already in use.
+//!
+//! Two daemons with the same config connect with the same client ID and publish to the same
+//! topics, so they keep kicking each other off the MQTT server. Each daemon holds a lock on a
+//! file named after its config file, which the OS releases when the daemon exits, even if it crashed.
+
+use anyhow::{bail, Context, Result};
+use fs2::FileExt;
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+/// Where the daemon keeps its lock files. systemd makes it for the service (`RuntimeDirectory=`).
+#[cfg(unix)]
+const RUN_DIRECTORY: &str = "/run/system-mqtt";
+
+/// Held for as long as the daemon runs.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock for a config file, failing if another daemon already has it.
+    pub fn acquire(config_file: &Path) -> Result<Self> {
+        let config_file = config_file
+            .canonicalize()
+            .unwrap_or_else(|_| config_file.to_path_buf());
+        let path = create_lock_directory()?.join(lock_name(&config_file));
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create lock file `{}`.", path.display()))?;
+
+        if file.try_lock_exclusive().is_err() {
+            bail!(
+                "Another system-mqtt is already running with `{}`. Stop it before starting a new one.",
+                config_file.display()
+            );
+        }
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// The directory lock files are kept in. Nobody but the user the daemon runs as (and root) can
+/// make files in it, so nobody else can take the lock first or plant a link where it goes. It
+/// may not exist yet. [`create_lock_directory`] makes it.
+#[cfg(unix)]
+pub(crate) fn lock_directory() -> Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let uid = users::get_effective_uid();
+    match std::fs::metadata(RUN_DIRECTORY) {
+        // Only root can make directories in /run, so it was made by root or by systemd for the service.
+        Ok(metadata) if metadata.is_dir() && (uid == 0 || metadata.uid() == uid) => {
+            return Ok(PathBuf::from(RUN_DIRECTORY))
+        }
+        // Root makes it when it's missing.
+        Err(_) if uid == 0 => return Ok(PathBuf::from(RUN_DIRECTORY)),
+        _ => {}
+    }
+
+    // Anyone else keeps theirs in their own runtime directory.
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .with_context(|| {
+            format!(
+                "There is nowhere to keep the lock file. Create `{}` for this user, or set `XDG_RUNTIME_DIR`.",
+                RUN_DIRECTORY
+            )
+        })
+}
+
+/// The temporary directory on Windows belongs to the user, so nobody else can get at it.
+#[cfg(not(unix))]
+pub(crate) fn lock_directory() -> Result<PathBuf> {
+    Ok(std::env::temp_dir())
+}
+
+/// Makes the lock directory if it's ours to make and it's missing, and returns it.
+pub(crate) fn create_lock_directory() -> Result<PathBuf> {
+    let directory = lock_directory()?;
+
+    #[cfg(unix)]
+    if directory == Path::new(RUN_DIRECTORY) && !directory.exists() {
+        use std::os::unix::fs::DirBuilderExt;
+
+        std::fs::DirBuilder::new()
+            .mode(0o755)
+            .create(&directory)
+            .with_context(|| format!("Failed to create `{}`.", directory.display()))?;
+    }
+
+    Ok(directory)
+}
+
+/// The lock file's name for a config file, such as `system-mqtt-etc-system-mqtt.yaml.lock`.
+fn lock_name(config_file: &Path) -> String {
+    let name: String = config_file
+        .to_string_lossy()
+        .trim_start_matches(|character| character == '/' || character == '\\')
+        .chars()
+        .map(|character| match character {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => character,
+            _ => '-',
+        })
+        .collect();
+
+    format!("system-mqtt-{}.lock", name)
+}
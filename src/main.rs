@@ -1,35 +1,40 @@
 use anyhow::{bail, Context, Result};
 use argh::FromArgs;
-use mqtt_async_client::client::{Client as MqttClient, Publish};
-use serde::{Deserialize, Serialize};
-use std::{
-    collections::{HashMap, HashSet},
-    os::unix::prelude::MetadataExt,
-    path::{Path, PathBuf},
-    time::Duration,
+use std::path::PathBuf;
+use system_mqtt::{
+    command_secret_keyring_name, lock::InstanceLock, Config, COMMAND_SECRET_KEYRING_NAME,
+    KEYRING_SERVICE_NAME,
 };
-use sysinfo::{CpuExt, DiskExt, System, SystemExt};
-use tokio::{fs, signal, time};
-use url::Url;
-
-const KEYRING_SERVICE_NAME: &str = "system-mqtt";
 
 #[derive(FromArgs)]
 /// Push system statistics to an mqtt server.
 struct Arguments {
     /// the configuration file we are to use.
-    #[argh(option, default = "PathBuf::from(\"/etc/system-mqtt.yaml\")")]
+    #[argh(option, default = "default_config_file()")]
     config_file: PathBuf,
 
     #[argh(subcommand)]
     command: SubCommand,
 }
 
+#[cfg(unix)]
+fn default_config_file() -> PathBuf {
+    PathBuf::from("/etc/system-mqtt.yaml")
+}
+
+#[cfg(windows)]
+fn default_config_file() -> PathBuf {
+    PathBuf::from(r"C:\ProgramData\system-mqtt\system-mqtt.yaml")
+}
+
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand)]
 enum SubCommand {
     Run(RunArguments),
     SetPassword(SetPasswordArguments),
+    History(HistoryArguments),
+    Helper(HelperArguments),
+    Setup(SetupArguments),
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -39,127 +44,206 @@ struct RunArguments {
     /// log to stderr instead of systemd's journal.
     #[argh(switch)]
     log_to_stderr: bool,
+
+    /// the most verbose level of log messages to emit. Overrides the config file.
+    #[argh(option)]
+    log_level: Option<log::LevelFilter>,
+
+    /// print topics and payloads to stdout instead of publishing them to the mqtt server.
+    #[argh(switch)]
+    dry_run: bool,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// Set the password used to log into the mqtt client.
 #[argh(subcommand, name = "set-password")]
-struct SetPasswordArguments {}
-
-#[derive(Serialize, Deserialize)]
-struct DriveConfig {
-    path: PathBuf,
-    name: String,
-}
-
-#[derive(Serialize, Deserialize)]
-enum PasswordSource {
-    #[serde(rename = "keyring")]
-    Keyring,
+struct SetPasswordArguments {
+    /// set the secret commands are signed with, rather than the mqtt password.
+    #[argh(switch)]
+    command_secret: bool,
 
-    #[serde(rename = "secret_file")]
-    SecretFile(PathBuf),
+    /// with --command-secret, set the secret of this client in `command_authentication`, rather
+    /// than the shared one.
+    #[argh(option)]
+    client: Option<String>,
 }
 
-impl Default for PasswordSource {
-    fn default() -> Self {
-        Self::Keyring
-    }
+#[derive(FromArgs, PartialEq, Debug)]
+/// Print the recorded history of an entity.
+#[argh(subcommand, name = "history")]
+struct HistoryArguments {
+    /// the name of the entity, such as `cpu`.
+    #[argh(positional)]
+    entity: String,
+
+    /// how far back to go, such as `1h` or `2days`.
+    #[argh(option, default = "String::from(\"1h\")")]
+    since: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Config {
-    /// The URL of the mqtt server.
-    mqtt_server: Url,
-
-    /// Set the username to connect to the mqtt server, if required.
-    /// The password will be fetched from the OS keyring.
-    username: Option<String>,
+#[derive(FromArgs, PartialEq, Debug)]
+/// Carry out what needs root for a daemon running as another user.
+#[argh(subcommand, name = "helper")]
+struct HelperArguments {
+    /// log to stderr instead of systemd's journal.
+    #[argh(switch)]
+    log_to_stderr: bool,
 
-    /// Where the password for the MQTT server can be found.
-    /// If a username is not specified, this field is ignored.
-    /// If not specified, this field defaults to the keyring.
-    #[serde(default)]
-    password_source: PasswordSource,
+    /// the most verbose level of log messages to emit. Overrides the config file.
+    #[argh(option)]
+    log_level: Option<log::LevelFilter>,
+}
 
-    /// The interval to update at.
-    update_interval: Duration,
+#[derive(FromArgs, PartialEq, Debug)]
+/// Set up the connection to the mqtt server and what's reported, by answering questions.
+#[argh(subcommand, name = "setup")]
+struct SetupArguments {}
+
+fn main() {
+    let Arguments {
+        config_file,
+        command,
+    } = argh::from_env();
+
+    // Loading the config only needs the main thread, so no other thread escapes the sandbox.
+    let config = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start async runtime.")
+        .block_on(Config::load(&config_file));
+    let config = match config {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load config file: {}", error);
+            return;
+        }
+    };
 
-    /// The names of drives, or the paths to where they are mounted.
-    drives: Vec<DriveConfig>,
-}
+    if let SubCommand::Run(arguments) = &command {
+        setup_log(
+            arguments.log_to_stderr,
+            arguments.log_level.unwrap_or(config.log_level),
+        );
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            mqtt_server: Url::parse("mqtt://localhost").expect("Failed to parse default URL."),
-            username: None,
-            password_source: PasswordSource::Keyring,
-            update_interval: Duration::from_secs(30),
-            drives: vec![DriveConfig {
-                path: PathBuf::from("/"),
-                name: String::from("root"),
-            }],
+        // Threads only take on the sandbox of the thread that started them, so it has to be in
+        // place before the runtime starts its own.
+        if config.sandbox.is_some() {
+            if let Err(error) = system_mqtt::sandbox::apply(&config, &config_file) {
+                log::error!("Failed to sandbox ourselves: {:#}", error);
+                return;
+            }
         }
     }
+
+    tokio::runtime::Runtime::new()
+        .expect("Failed to start async runtime.")
+        .block_on(run_command(config_file, config, command));
 }
 
-#[tokio::main]
-async fn main() {
-    let arguments: Arguments = argh::from_env();
-
-    match load_config(&arguments.config_file).await {
-        Ok(config) => match arguments.command {
-            SubCommand::Run(arguments) => {
-                if arguments.log_to_stderr {
-                    simple_logger::SimpleLogger::new()
-                        .env()
-                        .init()
-                        .expect("Failed to setup log.");
-                } else {
-                    systemd_journal_logger::init().expect("Failed to setup log.");
+async fn run_command(config_file: PathBuf, config: Config, command: SubCommand) {
+    match command {
+        SubCommand::Run(arguments) => {
+            // A dry run doesn't get in the way of a running daemon, so it can be used to check on one.
+            let _lock = if arguments.dry_run {
+                None
+            } else {
+                match InstanceLock::acquire(&config_file) {
+                    Ok(lock) => Some(lock),
+                    Err(error) => {
+                        log::error!("{:#}", error);
+                        return;
+                    }
                 }
+            };
 
-                log::set_max_level(log::LevelFilter::Info);
+            while let Err(error) = system_mqtt::run(&config, arguments.dry_run).await {
+                log::error!("Fatal error: {}", error);
+            }
+        }
+        SubCommand::SetPassword(arguments) => {
+            if let Err(error) = set_password(config, arguments).await {
+                eprintln!("Fatal error: {}", error);
+            }
+        }
+        SubCommand::History(arguments) => {
+            if let Err(error) = print_history(config, arguments) {
+                eprintln!("Fatal error: {}", error);
+            }
+        }
+        SubCommand::Helper(arguments) => {
+            setup_log(
+                arguments.log_to_stderr,
+                arguments.log_level.unwrap_or(config.log_level),
+            );
 
-                while let Err(error) = application_trampoline(&config).await {
-                    log::error!("Fatal error: {}", error);
-                }
+            if let Err(error) = system_mqtt::privileged::serve(&config).await {
+                log::error!("Fatal error: {:#}", error);
             }
-            SubCommand::SetPassword(_arguments) => {
-                if let Err(error) = set_password(config).await {
-                    eprintln!("Fatal error: {}", error);
-                }
+        }
+        SubCommand::Setup(_) => {
+            if let Err(error) = system_mqtt::setup::setup(&config_file, config).await {
+                eprintln!("Fatal error: {:#}", error);
             }
-        },
-        Err(error) => {
-            eprintln!("Failed to load config file: {}", error);
         }
     }
 }
 
-async fn load_config(path: &Path) -> Result<Config> {
-    if path.is_file() {
-        // It's a readable file we can load.
+fn setup_log(log_to_stderr: bool, log_level: log::LevelFilter) {
+    let connected_to_journal = connected_to_journal();
+
+    if log_to_stderr || !connected_to_journal {
+        let logger = simple_logger::SimpleLogger::new()
+            .with_level(log_level)
+            .env();
 
-        let config: Config = serde_yaml::from_str(&fs::read_to_string(path).await?)
-            .context("Failed to deserialize config file.")?;
+        // The journal timestamps everything on its own.
+        let logger = if connected_to_journal {
+            logger.without_timestamps()
+        } else {
+            logger
+        };
 
-        Ok(config)
+        logger.init().expect("Failed to setup log.");
     } else {
-        log::info!("No config file present. A default one will be written.");
-        // Doesn't exist yet. We'll create it.
-        let config = Config::default();
+        init_journal();
+    }
 
-        // Write it to a file for next time we load.
-        fs::write(path, serde_yaml::to_string(&config)?).await?;
+    log::set_max_level(log_level);
+}
 
-        Ok(config)
-    }
+#[cfg(target_os = "linux")]
+fn connected_to_journal() -> bool {
+    systemd_journal_logger::connected_to_journal()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn connected_to_journal() -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn init_journal() {
+    systemd_journal_logger::init().expect("Failed to setup log.");
+}
+
+#[cfg(not(target_os = "linux"))]
+fn init_journal() {
+    unreachable!("There is no journal to log to on this platform.");
 }
 
-async fn set_password(config: Config) -> Result<()> {
-    if let Some(username) = config.username {
+async fn set_password(config: Config, arguments: SetPasswordArguments) -> Result<()> {
+    let keyring_name = if arguments.command_secret {
+        Some(match &arguments.client {
+            Some(client) => command_secret_keyring_name(client),
+            None => COMMAND_SECRET_KEYRING_NAME.to_string(),
+        })
+    } else if arguments.client.is_some() {
+        bail!("`--client` is only for `--command-secret`.")
+    } else {
+        config.username
+    };
+
+    if let Some(username) = keyring_name {
         let password = rpassword::prompt_password("Password: ")
             .context("Failed to read password from TTY.")?;
 
@@ -173,364 +257,38 @@ async fn set_password(config: Config) -> Result<()> {
     }
 }
 
-async fn application_trampoline(config: &Config) -> Result<()> {
-    log::info!("Application start.");
-
-    let mut client_builder = MqttClient::builder();
-    client_builder.set_url_string(config.mqtt_server.as_str())?;
-
-    // If credentials are provided, use them.
-    if let Some(username) = &config.username {
-        // TODO make TLS mandatory when using a password.
-
-        let password = match &config.password_source {
-            PasswordSource::Keyring => {
-                log::info!("Using system keyring for MQTT password source.");
-                let keyring = keyring::Entry::new(KEYRING_SERVICE_NAME, username)
-                    .context("Failed to find password entry in keyring.")?;
-                keyring
-                    .get_password()
-                    .context("Failed to get password from keyring. If you have not yet set the password, run `system-mqtt set-password`.")?
-            }
-            PasswordSource::SecretFile(file_path) => {
-                log::info!("Using hidden file for MQTT password source.");
-                let metadata = file_path
-                    .metadata()
-                    .context("Failed to get password file metadata.")?;
-
-                // It's not even an encrypted file, so we need to keep the permission settings pretty tight.
-                // The only time I can really enforce that is when reading the password.
-                if metadata.mode() & 0o777 == 0o600 {
-                    if metadata.uid() == users::get_current_uid() {
-                        if metadata.gid() == users::get_current_gid() {
-                            let pass: String = fs::read_to_string(file_path)
-                                .await
-                                .context("Failed to read password file.")?;
-                            pass.as_str().trim_end().to_string()
-                        } else {
-                            bail!("Password file must be owned by the current group.");
-                        }
-                    } else {
-                        bail!("Password file must be owned by the current user.");
-                    }
-                } else {
-                    bail!("Permission bits for password file must be set to 0o600 (only owner can read and write)");
-                }
-            }
-        };
-
-        client_builder.set_username(Some(username.into()));
-        client_builder.set_password(Some(password.as_bytes().to_vec()));
-    }
-
-    let mut client = client_builder.build()?;
-    client
-        .connect()
-        .await
-        .context("Failed to connect to MQTT server.")?;
-
-    let manager = battery::Manager::new().context("Failed to initalize battery monitoring.")?;
-
-    let mut system = System::new_all();
-
-    let hostname = system
-        .host_name()
-        .context("Could not get system hostname.")?;
-
-    let mut home_assistant = HomeAssistant {
-        client,
-        hostname,
-        registered_topics: HashSet::new(),
-    };
-
-    // Register the various sensor topics and include the details about that sensor
-
-    //    TODO - create a new register_topic to register binary_sensor so we can make availability a real binary sensor. In the
-    //    meantime, create it as a normal analog sensor with two values, and a template can be used to make it a binary.
-
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some(""),
-            "available",
-            None,
-            Some("mdi:check-network-outline"),
-        )
-        .await
-        .context("Failed to register availability topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some(""),
-            "uptime",
-            Some("days"),
-            Some("mdi:timer-sand"),
-        )
-        .await
-        .context("Failed to register uptime topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some("measurement"),
-            "cpu",
-            Some("%"),
-            Some("mdi:gauge"),
-        )
-        .await
-        .context("Failed to register CPU usage topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some("measurement"),
-            "memory",
-            Some("%"),
-            Some("mdi:gauge"),
-        )
-        .await
-        .context("Failed to register memory usage topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some("measurement"),
-            "swap",
-            Some("%"),
-            Some("mdi:gauge"),
-        )
-        .await
-        .context("Failed to register swap usage topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            Some("battery"),
-            Some("measurement"),
-            "battery_level",
-            Some("%"),
-            Some("mdi:battery"),
-        )
-        .await
-        .context("Failed to register battery level topic.")?;
-    home_assistant
-        .register_topic(
-            "sensor",
-            None,
-            Some(""),
-            "battery_state",
-            None,
-            Some("mdi:battery"),
-        )
-        .await
-        .context("Failed to register battery state topic.")?;
-
-    // Register the sensors for filesystems
-    for drive in &config.drives {
-        home_assistant
-            .register_topic(
-                "sensor",
-                None,
-                Some("total"),
-                &drive.name,
-                Some("%"),
-                Some("mdi:folder"),
-            )
-            .await
-            .context("Failed to register a filesystem topic.")?;
-    }
-
-    home_assistant.set_available(true).await?;
-
-    let result = availability_trampoline(&home_assistant, &mut system, config, manager).await;
-
-    if let Err(error) = home_assistant.set_available(false).await {
-        // I don't want this error hiding whatever happened in the main loop.
-        log::error!("Error while disconnecting from home assistant: {:?}", error);
-    }
-
-    result?;
-
-    home_assistant.disconnect().await?;
-
-    Ok(())
-}
-
-async fn availability_trampoline(
-    home_assistant: &HomeAssistant,
-    system: &mut System,
-    config: &Config,
-    manager: battery::Manager,
-) -> Result<()> {
-    let drive_list: HashMap<PathBuf, String> = config
-        .drives
-        .iter()
-        .map(|drive_config| (drive_config.path.clone(), drive_config.name.clone()))
-        .collect();
-
-    system.refresh_disks();
-    system.refresh_memory();
-    system.refresh_cpu();
-
-    loop {
-        tokio::select! {
-            _ = time::sleep(config.update_interval) => {
-                system.refresh_disks();
-                system.refresh_memory();
-                system.refresh_cpu();
-
-                // Report uptime.
-                let uptime = system.uptime() as f32 / 60.0 / 60.0 / 24.0; // Convert from seconds to days.
-                home_assistant.publish("uptime", format!("{}", uptime)).await;
-
-                // Report CPU usage.
-                let cpu_usage = (system.cpus().iter().map(|cpu| cpu.cpu_usage()).sum::<f32>()) / (system.cpus().len() as f32 * 100.0);
-                home_assistant.publish("cpu", (cpu_usage * 100.0).to_string()).await;
-
-                // Report memory usage.
-                let memory_percentile = (system.total_memory() - system.available_memory()) as f64 / system.total_memory() as f64;
-                home_assistant.publish("memory", (memory_percentile.clamp(0.0, 1.0)* 100.0).to_string()).await;
-
-                // Report swap usage.
-                let swap_percentile = system.used_swap() as f64 / system.free_swap() as f64;
-                home_assistant.publish("swap", (swap_percentile.clamp(0.0, 1.0) * 100.0).to_string()).await;
-
-                // Report filesystem usage.
-                for drive in system.disks() {
-                    if let Some(drive_name) = drive_list.get(drive.mount_point()) {
-                        let drive_percentile = (drive.total_space() - drive.available_space()) as f64 / drive.total_space() as f64;
-
-                        home_assistant.publish(drive_name, (drive_percentile.clamp(0.0, 1.0) * 100.0).to_string()).await;
-                    }
-                }
-
-                // TODO we should probably combine the battery charges, but for now we're just going to use the first detected battery.
-                if let Some(battery) = manager.batteries().context("Failed to read battery info.")?.flatten().next() {
-                    use battery::State;
-
-                    let battery_state = match battery.state() {
-                        State::Charging => "charging",
-                        State::Discharging => "discharging",
-                        State::Empty => "empty",
-                        State::Full => "full",
-                        _ => "unknown",
-                    };
-
-                    home_assistant.publish("battery_state", battery_state.to_string()).await;
-
-                    let battery_full = battery.energy_full();
-                    let battery_power = battery.energy();
-                    let battery_level = battery_power / battery_full;
-
-                    home_assistant.publish("battery_level", format!("{:03}", battery_level.value)).await;
-                }
-            }
-            _ = signal::ctrl_c() => {
-                log::info!("Terminate signal has been received.");
-                break;
-            }
-        }
+#[cfg(feature = "history")]
+fn print_history(config: Config, arguments: HistoryArguments) -> Result<()> {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use system_mqtt::sink::history;
+
+    let history_config = config
+        .history
+        .context("History isn't being recorded. Set `history` in the config file.")?;
+    let since: Duration = humantime::parse_duration(&arguments.since)
+        .with_context(|| format!("Invalid duration `{}`.", arguments.since))?;
+
+    for record in history::query(
+        &history_config.path,
+        &arguments.entity,
+        SystemTime::now() - since,
+    )? {
+        let time = UNIX_EPOCH + Duration::from_secs(record.time);
+        println!(
+            "{}\t{}",
+            humantime::format_rfc3339_seconds(time),
+            record.value
+        );
     }
 
     Ok(())
 }
 
-pub struct HomeAssistant {
-    client: MqttClient,
-    hostname: String,
-    registered_topics: HashSet<String>,
-}
-
-impl HomeAssistant {
-    pub async fn set_available(&self, available: bool) -> Result<()> {
-        self.client
-            .publish(
-                Publish::new(
-                    format!("system-mqtt/{}/availability", self.hostname),
-                    if available { "online" } else { "offline" }.into(),
-                )
-                .set_retain(true),
-            )
-            .await
-            .context("Failed to publish availability topic.")
-    }
-
-    pub async fn register_topic(
-        &mut self,
-        topic_class: &str,
-        device_class: Option<&str>,
-        state_class: Option<&str>,
-        topic_name: &str,
-        unit_of_measurement: Option<&str>,
-        icon: Option<&str>,
-    ) -> Result<()> {
-        log::info!("Registering topic `{}`.", topic_name);
-
-        #[derive(Serialize)]
-        struct TopicConfig {
-            name: String,
-
-            #[serde(skip_serializing_if = "Option::is_none")]
-            device_class: Option<String>,
-            state_class: Option<String>,
-            state_topic: String,
-            unit_of_measurement: Option<String>,
-            icon: Option<String>,
-        }
-
-        let message = serde_json::ser::to_string(&TopicConfig {
-            name: format!("{}-{}", self.hostname, topic_name),
-            device_class: device_class.map(str::to_string),
-            state_class: state_class.map(str::to_string),
-            state_topic: format!("system-mqtt/{}/{}", self.hostname, topic_name),
-            unit_of_measurement: unit_of_measurement.map(str::to_string),
-            icon: icon.map(str::to_string),
-        })
-        .context("Failed to serialize topic information.")?;
-        let mut publish = Publish::new(
-            format!(
-                "homeassistant/{}/system-mqtt-{}/{}/config",
-                topic_class, self.hostname, topic_name
-            ),
-            message.into(),
-        );
-        publish.set_retain(true);
-        self.client
-            .publish(&publish)
-            .await
-            .context("Failed to publish topic to MQTT server.")?;
-
-        self.registered_topics.insert(topic_name.to_string());
-
-        Ok(())
-    }
-
-    pub async fn publish(&self, topic_name: &str, value: String) {
-        log::debug!("PUBLISH `{}` TO `{}`", value, topic_name);
-
-        if self.registered_topics.contains(topic_name) {
-            let mut publish = Publish::new(
-                format!("system-mqtt/{}/{}", self.hostname, topic_name),
-                value.into(),
-            );
-            publish.set_retain(false);
-
-            if let Err(error) = self.client.publish(&publish).await {
-                log::error!("Failed to publish topic `{}`: {:?}", topic_name, error);
-            }
-        } else {
-            log::error!(
-                "Attempt to publish topic `{}`, which was never registered with Home Assistant.",
-                topic_name
-            );
-        }
-    }
-
-    pub async fn disconnect(mut self) -> Result<()> {
-        self.set_available(false).await?;
-        self.client.disconnect().await?;
-
-        Ok(())
-    }
+#[cfg(not(feature = "history"))]
+fn print_history(_config: Config, arguments: HistoryArguments) -> Result<()> {
+    bail!(
+        "Can't show the history of `{}` over the last {}, since system-mqtt was built without the `history` feature.",
+        arguments.entity,
+        arguments.since
+    )
 }
@@ -0,0 +1,73 @@
+//! Finding the MQTT server on the local network through mDNS, also known as DNS-SD or Bonjour.
+
+use anyhow::{bail, Context, Result};
+use tokio::process::Command;
+use url::Url;
+
+/// The service type MQTT servers advertise themselves as.
+const MQTT_SERVICE: &str = "_mqtt._tcp";
+
+/// Looks for an MQTT server advertising itself on the local network, through Avahi.
+///
+/// If more than one is found, IPv4 addresses are preferred, and then whichever Avahi listed first.
+pub async fn find_mqtt_server() -> Result<Url> {
+    let output = Command::new("avahi-browse")
+        .args(["--resolve", "--terminate", "--parsable", "--no-db-lookup"])
+        .arg(MQTT_SERVICE)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run avahi-browse.")?;
+
+    if !output.status.success() {
+        bail!(
+            "avahi-browse exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    // Resolved services look like
+    // `=;eth0;IPv4;Mosquitto;_mqtt._tcp;local;broker.local;192.168.1.5;1883;"txt"`.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut servers: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split(';').collect();
+            match fields.as_slice() {
+                ["=", _, protocol, name, _, _, _, address, port, ..] => {
+                    Some((*protocol, *name, *address, port.parse::<u16>().ok()?))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    servers.sort_by_key(|(protocol, ..)| *protocol != "IPv4");
+
+    let (_, name, address, port) = servers
+        .first()
+        .context("No MQTT server is advertising itself on the local network.")?;
+    log::info!(
+        "Found MQTT server `{}` at `{}` port {}.",
+        name,
+        address,
+        port
+    );
+
+    let host = if address.contains(':') {
+        // IPv6 addresses have to be bracketed in URLs. Link-local ones come with the interface
+        // they're reachable on, which URLs have no way to hold, so those can't be used.
+        if address.contains('%') {
+            bail!(
+                "MQTT server `{}` only has a link-local IPv6 address, which can't be connected to.",
+                name
+            );
+        }
+
+        format!("[{}]", address)
+    } else {
+        address.to_string()
+    };
+
+    Url::parse(&format!("mqtt://{}:{}", host, port)).context("Invalid MQTT server address.")
+}
@@ -0,0 +1,229 @@
+//! A connection to the MQTT server.
+//!
+//! rumqttc only gets anything done, our own publishes included, while its event loop is being
+//! polled. [`MqttClient`] polls it in a task of its own, which reconnects whenever the connection
+//! drops, and hands the messages from our subscriptions back through
+//! [`MqttClient::read_subscriptions`].
+
+use anyhow::{Context, Result};
+use rumqttc::{
+    AsyncClient, ConnectionError, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS,
+    SubscribeReasonCode,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{sync::mpsc, task::JoinHandle, time};
+
+pub use rumqttc::Publish as Message;
+
+/// How many requests, such as publishes, can be waiting on the event loop before sending more has
+/// to wait too.
+const REQUEST_CAPACITY: usize = 64;
+
+/// How long connecting, publishing, subscribing or disconnecting may take before we give up on
+/// it, so a connection that has died without being closed can't hold up the update loop.
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the server is pinged when there's nothing else to send. A connection that has died
+/// without being closed, such as after a NAT forgets about it, is noticed when a ping goes
+/// unanswered, and we reconnect.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// How long to wait between attempts to reconnect.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub struct MqttClient {
+    client: AsyncClient,
+    messages: mpsc::UnboundedReceiver<Message>,
+    subscription_results: mpsc::UnboundedReceiver<Vec<SubscribeReasonCode>>,
+
+    /// Every topic we've subscribed to, so they can be subscribed to again when the server
+    /// doesn't keep our session across a reconnect.
+    topics: Arc<Mutex<Vec<String>>>,
+
+    /// Whether the event loop is connected. Publishing while it isn't only queues the message up
+    /// until we reconnect.
+    connected: Arc<AtomicBool>,
+
+    /// The task polling the event loop. It ends once we've disconnected.
+    driver: Option<JoinHandle<()>>,
+}
+
+impl MqttClient {
+    /// Connects to the MQTT server, and fails if the first attempt does.
+    pub async fn connect(mut options: MqttOptions) -> Result<Self> {
+        options.set_keep_alive(KEEP_ALIVE);
+        let (client, mut event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+
+        let connecting = async {
+            loop {
+                if let Event::Incoming(Packet::ConnAck(_)) = event_loop.poll().await? {
+                    return Ok::<_, ConnectionError>(());
+                }
+            }
+        };
+        time::timeout(OPERATION_TIMEOUT, connecting)
+            .await
+            .context("Timed out connecting to MQTT server.")?
+            .context("Failed to connect to MQTT server.")?;
+
+        let (message_sender, messages) = mpsc::unbounded_channel();
+        let (subscription_result_sender, subscription_results) = mpsc::unbounded_channel();
+        let topics = Arc::new(Mutex::new(Vec::new()));
+        let connected = Arc::new(AtomicBool::new(true));
+
+        let driver = tokio::spawn(drive(
+            event_loop,
+            client.clone(),
+            message_sender,
+            subscription_result_sender,
+            topics.clone(),
+            connected.clone(),
+        ));
+
+        Ok(Self {
+            client,
+            messages,
+            subscription_results,
+            topics,
+            connected,
+            driver: Some(driver),
+        })
+    }
+
+    pub async fn publish(
+        &self,
+        topic: String,
+        payload: impl Into<Vec<u8>>,
+        retain: bool,
+    ) -> Result<()> {
+        time::timeout(
+            OPERATION_TIMEOUT,
+            self.client.publish(topic, QoS::AtMostOnce, retain, payload),
+        )
+        .await
+        .context("Timed out publishing to MQTT server.")??;
+
+        Ok(())
+    }
+
+    /// Whether we're connected to the server right now.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Subscribes to a topic, and returns whether the server accepted the subscription.
+    pub async fn subscribe(&mut self, topic: &str) -> Result<bool> {
+        let subscribing = async {
+            self.client.subscribe(topic, QoS::AtLeastOnce).await?;
+
+            self.subscription_results
+                .recv()
+                .await
+                .context("Lost connection to MQTT server.")
+        };
+        let return_codes = time::timeout(OPERATION_TIMEOUT, subscribing)
+            .await
+            .context("Timed out subscribing on MQTT server.")??;
+        let accepted = !return_codes
+            .iter()
+            .any(|code| matches!(code, SubscribeReasonCode::Failure));
+
+        if accepted {
+            self.topics
+                .lock()
+                .expect("Topics lock was poisoned.")
+                .push(topic.to_string());
+        }
+
+        Ok(accepted)
+    }
+
+    /// Waits for a message from one of our subscriptions.
+    pub async fn read_subscriptions(&mut self) -> Result<Message> {
+        self.messages
+            .recv()
+            .await
+            .context("Lost connection to MQTT server.")
+    }
+
+    /// Disconnects once everything published before has been sent.
+    pub async fn disconnect(&mut self) -> Result<()> {
+        let disconnecting = async {
+            self.client.disconnect().await?;
+
+            // Requests are sent in order, so once the disconnect has gone out, so has everything else.
+            if let Some(driver) = self.driver.take() {
+                driver.await?;
+            }
+
+            Ok::<_, anyhow::Error>(())
+        };
+        time::timeout(OPERATION_TIMEOUT, disconnecting)
+            .await
+            .context("Timed out disconnecting from MQTT server.")??;
+
+        Ok(())
+    }
+}
+
+/// Polls the event loop until we disconnect, or the client is dropped.
+async fn drive(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    messages: mpsc::UnboundedSender<Message>,
+    subscription_results: mpsc::UnboundedSender<Vec<SubscribeReasonCode>>,
+    topics: Arc<Mutex<Vec<String>>>,
+    connected: Arc<AtomicBool>,
+) {
+    // Subscriptions restored after a reconnect, whose results nobody is waiting for.
+    let mut restored_subscriptions = 0;
+
+    while !messages.is_closed() {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                let _ = messages.send(message);
+            }
+            Ok(Event::Incoming(Packet::SubAck(ack))) => {
+                if restored_subscriptions > 0 {
+                    restored_subscriptions -= 1;
+                } else {
+                    let _ = subscription_results.send(ack.return_codes);
+                }
+            }
+            Ok(Event::Incoming(Packet::ConnAck(ack))) => {
+                log::info!("Reconnected to MQTT server.");
+                connected.store(true, Ordering::Relaxed);
+
+                if !ack.session_present {
+                    let topics = topics.lock().expect("Topics lock was poisoned.").clone();
+                    for topic in topics {
+                        // Waiting for room in the request queue would wait on this very loop.
+                        match client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                            Ok(()) => restored_subscriptions += 1,
+                            Err(error) => {
+                                log::warn!("Failed to subscribe to `{}` again: {:?}", topic, error)
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+            Ok(_) => {}
+            Err(error) => {
+                connected.store(false, Ordering::Relaxed);
+                log::warn!(
+                    "Lost connection to MQTT server, reconnecting in {:?}: {}",
+                    RECONNECT_DELAY,
+                    error
+                );
+                time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
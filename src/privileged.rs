@@ -0,0 +1,397 @@
+//! Doing what needs root on behalf of a daemon that doesn't run as root.
+//!
+//! The few things that need root, such as reading SMART logs and setting fan speeds, are
+//! described by a [`Request`]. The daemon either carries them out itself, or, when
+//! `privileged_helper` is set, hands them to `system-mqtt helper` running as root over a Unix
+//! socket. The helper only carries out requests for what its own config asks for, so a daemon
+//! that has been taken over can't use it for anything else.
+
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "privileged-helper")]
+use once_cell::sync::Lazy;
+#[cfg(feature = "privileged-helper")]
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "privileged-helper")]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+
+/// How long the helper waits for a request once a connection is made.
+#[cfg(all(unix, feature = "privileged-helper"))]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PrivilegedHelperConfig {
+    /// The Unix socket the helper listens on.
+    #[serde(default = "PrivilegedHelperConfig::default_socket")]
+    pub socket: PathBuf,
+
+    /// The user the daemon runs as. Nobody else, other than root, may make requests.
+    pub user: String,
+}
+
+impl PrivilegedHelperConfig {
+    fn default_socket() -> PathBuf {
+        PathBuf::from("/run/system-mqtt-helper/helper.sock")
+    }
+}
+
+/// Something that needs root.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// `nvme smart-log /dev/{device} --output-format=json`
+    NvmeSmartLog { device: String },
+
+    /// `ipmitool {arguments} -c sdr list`
+    IpmiSdrList { arguments: Vec<String> },
+
+    /// `hdparm -C /dev/{device}`
+    DriveStandby { device: String },
+
+    /// `wg show {interface} dump`
+    WireguardDump { interface: String },
+
+    /// `lvs --reportformat json -o vg_name,lv_name,data_percent,metadata_percent -S segtype=thin-pool`
+    LvmThinPools,
+
+    /// Writes to a control file in sysfs, such as a fan's PWM output.
+    WriteSysfs { path: PathBuf, value: String },
+}
+
+impl Request {
+    /// Carries out the request, and returns what the command printed.
+    async fn execute(&self) -> Result<String> {
+        match self {
+            Request::NvmeSmartLog { device } => {
+                run(
+                    "nvme",
+                    &[
+                        "smart-log",
+                        &format!("/dev/{}", device),
+                        "--output-format=json",
+                    ],
+                )
+                .await
+            }
+            Request::IpmiSdrList { arguments } => {
+                let mut arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+                arguments.extend(["-c", "sdr", "list"]);
+                run("ipmitool", &arguments).await
+            }
+            Request::DriveStandby { device } => {
+                run("hdparm", &["-C", &format!("/dev/{}", device)]).await
+            }
+            Request::WireguardDump { interface } => run("wg", &["show", interface, "dump"]).await,
+            Request::LvmThinPools => {
+                run(
+                    "lvs",
+                    &[
+                        "--reportformat",
+                        "json",
+                        "-o",
+                        "vg_name,lv_name,data_percent,metadata_percent",
+                        "-S",
+                        "segtype=thin-pool",
+                    ],
+                )
+                .await
+            }
+            Request::WriteSysfs { path, value } => {
+                fs::write(path, value)
+                    .await
+                    .with_context(|| format!("Failed to write to `{}`.", path.display()))?;
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// Whether the config asks for what the request is for.
+    #[cfg(feature = "privileged-helper")]
+    fn allowed_by(&self, config: &Config) -> bool {
+        match self {
+            Request::NvmeSmartLog { device } => config.nvme_devices.contains(device),
+            Request::IpmiSdrList { arguments } => config
+                .ipmi
+                .as_ref()
+                .map_or(false, |ipmi| &ipmi.arguments == arguments),
+            Request::DriveStandby { device } => config
+                .drives
+                .iter()
+                .any(|drive| drive.device.as_ref() == Some(device)),
+            Request::WireguardDump { interface } => config.wireguard_interfaces.contains(interface),
+            Request::LvmThinPools => config.enable_lvm_thin_pools,
+            Request::WriteSysfs { path, .. } => sysfs_write_allowed(path, config),
+        }
+    }
+}
+
+// The control files in sysfs that can be written to, for the features the config turns on.
+#[cfg(feature = "privileged-helper")]
+static FAN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(/sys/class/hwmon/hwmon\d+)/pwm(\d+)(_enable)?$").expect("Invalid regex.")
+});
+#[cfg(feature = "privileged-helper")]
+static GOVERNOR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^/sys/devices/system/cpu/cpu\d+/cpufreq/scaling_governor$")
+        .expect("Invalid regex.")
+});
+#[cfg(feature = "privileged-helper")]
+static BACKLIGHT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^/sys/class/backlight/([^/]+)/brightness$").expect("Invalid regex."));
+#[cfg(feature = "privileged-helper")]
+static KEYBOARD_BACKLIGHT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^/sys/class/leds/[^/]*kbd_backlight/brightness$").expect("Invalid regex.")
+});
+#[cfg(feature = "privileged-helper")]
+static RADIO: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^/sys/class/rfkill/rfkill\d+/soft$").expect("Invalid regex."));
+#[cfg(feature = "privileged-helper")]
+static CHARGE_LIMIT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^/sys/class/power_supply/[^/]+/charge_control_end_threshold$")
+        .expect("Invalid regex.")
+});
+
+/// Only the control files of the features the config turns on can be written to.
+#[cfg(feature = "privileged-helper")]
+fn sysfs_write_allowed(path: &Path, config: &Config) -> bool {
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return false;
+    }
+    let path = path.to_string_lossy();
+
+    if let Some(captures) = FAN.captures(&path) {
+        // Only the outputs of the chips the fans are on, which are known by name since their
+        // numbers can change from one boot to the next.
+        let chip =
+            std::fs::read_to_string(Path::new(&captures[1]).join("name")).unwrap_or_default();
+        return config
+            .fans
+            .iter()
+            .any(|fan| fan.chip == chip.trim() && captures[2] == fan.pwm.to_string());
+    }
+
+    if GOVERNOR.is_match(&path) {
+        return config.enable_cpu_governor_control;
+    }
+
+    if let Some(captures) = BACKLIGHT.captures(&path) {
+        return config.enable_backlight_control
+            && config
+                .backlight_device
+                .as_ref()
+                .map_or(true, |device| &captures[1] == device);
+    }
+
+    if KEYBOARD_BACKLIGHT.is_match(&path) {
+        return config.enable_keyboard_backlight;
+    }
+
+    if RADIO.is_match(&path) {
+        return config.enable_radio_switches;
+    }
+
+    if CHARGE_LIMIT.is_match(&path) {
+        return config.enable_battery_charge_limit;
+    }
+
+    false
+}
+
+async fn run(program: &str, arguments: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(arguments)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}.", program))?;
+
+    if !output.status.success() {
+        bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Carries out requests, either on its own or through the helper.
+#[derive(Clone, Default)]
+pub struct Privileged {
+    helper_socket: Option<PathBuf>,
+}
+
+impl Privileged {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            helper_socket: config
+                .privileged_helper
+                .as_ref()
+                .map(|helper| helper.socket.clone()),
+        }
+    }
+
+    /// Carries out a request, and returns what the command printed.
+    pub async fn run(&self, request: Request) -> Result<String> {
+        match &self.helper_socket {
+            Some(socket) => ask_helper(socket, &request).await,
+            None => request.execute().await,
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn ask_helper(socket: &Path, request: &Request) -> Result<String> {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+    };
+
+    let stream = UnixStream::connect(socket).await.with_context(|| {
+        format!(
+            "Failed to connect to the privileged helper at `{}`.",
+            socket.display()
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    BufReader::new(reader)
+        .read_line(&mut response)
+        .await
+        .context("Failed to read response from the privileged helper.")?;
+    let response: std::result::Result<String, String> = serde_json::from_str(&response)
+        .context("The privileged helper sent a malformed response.")?;
+
+    response.map_err(|error| anyhow!(error))
+}
+
+#[cfg(not(unix))]
+async fn ask_helper(_socket: &Path, _request: &Request) -> Result<String> {
+    bail!("The privileged helper is only available on Unix.")
+}
+
+/// Carries out requests from the daemon until we're stopped. This is what `system-mqtt helper` runs.
+#[cfg(all(unix, feature = "privileged-helper"))]
+pub async fn serve(config: &Config) -> Result<()> {
+    use futures::stream::{FuturesUnordered, StreamExt};
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixListener;
+
+    let helper_config = config
+        .privileged_helper
+        .as_ref()
+        .context("Set `privileged_helper` in the config file to run the helper.")?;
+    let user = users::get_user_by_name(&helper_config.user)
+        .with_context(|| format!("There is no user named `{}`.", helper_config.user))?;
+
+    if let Some(parent) = helper_config.socket.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    // A socket left behind by the last helper would keep us from listening.
+    if helper_config.socket.exists() {
+        fs::remove_file(&helper_config.socket).await?;
+    }
+
+    let listener = UnixListener::bind(&helper_config.socket)
+        .with_context(|| format!("Failed to listen on `{}`.", helper_config.socket.display()))?;
+    // Anyone can connect, but who they are is checked before anything is carried out.
+    std::fs::set_permissions(
+        &helper_config.socket,
+        std::fs::Permissions::from_mode(0o666),
+    )?;
+    log::info!(
+        "Carrying out privileged requests from `{}` on `{}`.",
+        helper_config.user,
+        helper_config.socket.display()
+    );
+
+    // Connections are handled side by side, so one that's slow, or never sends its request,
+    // doesn't hold up the rest.
+    let mut connections = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                // Something going wrong with one connection is no reason to stop serving the rest.
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        log::warn!("Failed to accept a connection: {:?}", error);
+                        continue;
+                    }
+                };
+
+                let peer = match stream.peer_cred() {
+                    Ok(credentials) => credentials.uid(),
+                    Err(error) => {
+                        log::warn!("Failed to find out who made a connection: {:?}", error);
+                        continue;
+                    }
+                };
+                if peer != user.uid() && peer != 0 {
+                    log::warn!("Refused a connection from user {}.", peer);
+                    continue;
+                }
+
+                connections.push(handle(stream, config));
+            }
+            Some(result) = connections.next() => {
+                if let Err(error) = result {
+                    log::warn!("Failed to handle a request: {:?}", error);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(all(unix, feature = "privileged-helper")))]
+pub async fn serve(_config: &Config) -> Result<()> {
+    bail!("The privileged helper is only available on Unix, in builds with the `privileged-helper` feature.")
+}
+
+#[cfg(all(unix, feature = "privileged-helper"))]
+async fn handle(stream: tokio::net::UnixStream, config: &Config) -> Result<()> {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        time,
+    };
+
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = String::new();
+    time::timeout(REQUEST_TIMEOUT, BufReader::new(reader).read_line(&mut line))
+        .await
+        .context("Timed out waiting for a request.")??;
+    let request: Request = serde_json::from_str(&line).context("Malformed request.")?;
+
+    let response = if request.allowed_by(config) {
+        log::debug!("Carrying out {:?}.", request);
+        request
+            .execute()
+            .await
+            .map_err(|error| format!("{:#}", error))
+    } else {
+        log::warn!("Refused {:?}, which the config doesn't ask for.", request);
+        Err(String::from(
+            "The privileged helper's config doesn't allow this.",
+        ))
+    };
+
+    let mut line = serde_json::to_string(&response)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    Ok(())
+}
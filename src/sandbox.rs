@@ -0,0 +1,236 @@
+//! Limiting what the daemon can do to what it needs, so that taking it over gets an attacker
+//! as little as possible.
+//!
+//! Landlock keeps the daemon, and the commands it runs, from reading anything outside of the
+//! system directories and the paths the config mentions, or writing anywhere but its own files.
+//! A seccomp filter keeps it from using system calls no sensor has any business with, such as
+//! loading kernel modules or tracing other processes.
+//!
+//! Both only apply to the thread that sets them up and the threads it starts afterwards, so
+//! [`apply`] has to be called before the async runtime starts any.
+
+use crate::config::Config;
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+use crate::config::PasswordSource;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SandboxConfig {
+    /// Paths, on top of the system directories, that sensors need to read, such as the
+    /// directories watched by `directory_sizes` and `file_ages`.
+    #[serde(default)]
+    pub extra_read_paths: Vec<PathBuf>,
+
+    /// Paths, on top of our own files, that sensors need to write to.
+    #[serde(default)]
+    pub extra_write_paths: Vec<PathBuf>,
+}
+
+/// Where programs, libraries and the system's own state live. Everything here can be read.
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/var", "/proc", "/sys",
+    "/dev", "/run",
+];
+
+/// System calls that nothing we do needs. They fail with `EPERM` rather than killing us, so a
+/// command that tries one anyway fails the way it would without permission.
+#[cfg(all(
+    target_os = "linux",
+    feature = "sandbox",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+const DENIED_SYSCALLS: &[i64] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+];
+
+/// The file a secret is read from, when it isn't kept in the keyring.
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+fn secret_file(source: &PasswordSource) -> Option<PathBuf> {
+    match source {
+        PasswordSource::Keyring => None,
+        PasswordSource::SecretFile(path) => Some(path.clone()),
+    }
+}
+
+/// The paths that can be read, and the paths that can be written to.
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+fn allowed_paths(config: &Config, config_file: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let sandbox = config.sandbox.clone().unwrap_or_default();
+
+    let mut read: Vec<PathBuf> = SYSTEM_PATHS.iter().map(PathBuf::from).collect();
+    read.push(config_file.to_path_buf());
+    #[cfg(feature = "wasm")]
+    read.extend(config.plugin_directory.iter().cloned());
+    read.extend(config.scripts.values().cloned());
+    #[cfg(feature = "lua")]
+    read.extend(config.lua_sensors.iter().map(|lua| lua.script.clone()));
+    // Secrets are read once the sandbox is up.
+    read.extend(secret_file(&config.password_source));
+    #[cfg(feature = "command-auth")]
+    if let Some(command_auth) = &config.command_authentication {
+        read.extend(secret_file(&command_auth.secret_source));
+        read.extend(
+            command_auth
+                .clients
+                .iter()
+                .filter_map(|client| secret_file(&client.secret_source)),
+        );
+    }
+    read.extend(sandbox.extra_read_paths);
+
+    // Commands that sensors run often need somewhere for temporary files.
+    let mut write = vec![PathBuf::from("/dev/null"), std::env::temp_dir()];
+    // The instance lock is taken after the sandbox is set up.
+    write.extend(crate::lock::lock_directory().ok());
+    write.extend(config.state_dir.iter().cloned());
+    // SQLite keeps its journal next to the database.
+    #[cfg(feature = "history")]
+    write.extend(
+        config
+            .history
+            .iter()
+            .filter_map(|history| history.path.parent().map(Path::to_path_buf)),
+    );
+    write.extend(config.audit_log.iter().map(|audit| audit.path.clone()));
+    // Without the helper, fans, the CPU governor and the backlight are set by writing to sysfs.
+    if config.privileged_helper.is_none() {
+        write.push(PathBuf::from("/sys"));
+    }
+    write.extend(sandbox.extra_write_paths);
+
+    (read, write)
+}
+
+/// Sandboxes the calling thread, and every thread and process it starts from now on.
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+pub fn apply(config: &Config, config_file: &Path) -> Result<()> {
+    use anyhow::Context;
+
+    // Landlock can only be given paths that exist, so make sure ours do.
+    if let Some(state_dir) = &config.state_dir {
+        std::fs::create_dir_all(state_dir).with_context(|| {
+            format!(
+                "Failed to create state directory `{}`.",
+                state_dir.display()
+            )
+        })?;
+    }
+
+    // The audit log is only opened once the sandbox is up, and Landlock can't be given a file
+    // that doesn't exist yet.
+    if let Some(audit_log) = &config.audit_log {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&audit_log.path)
+            .with_context(|| {
+                format!("Failed to create audit log `{}`.", audit_log.path.display())
+            })?;
+    }
+
+    // Nor can it be given the lock directory before it's made. Should that fail, taking the lock
+    // says why.
+    let _ = crate::lock::create_lock_directory();
+
+    let (read, write) = allowed_paths(config, config_file);
+    restrict_paths(read, write).context("Failed to set up Landlock.")?;
+    restrict_syscalls().context("Failed to set up the seccomp filter.")?;
+
+    Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", feature = "sandbox")))]
+pub fn apply(_config: &Config, _config_file: &Path) -> Result<()> {
+    anyhow::bail!("Sandboxing is only available on Linux, in builds with the `sandbox` feature.")
+}
+
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+fn restrict_paths(read: Vec<PathBuf>, write: Vec<PathBuf>) -> Result<()> {
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr,
+        RulesetStatus, ABI,
+    };
+
+    let abi = ABI::V2;
+    let existing = |paths: Vec<PathBuf>| -> Vec<PathBuf> {
+        paths.into_iter().filter(|path| path.exists()).collect()
+    };
+
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .create()?
+        .add_rules(path_beneath_rules(existing(read), AccessFs::from_read(abi)))?
+        .add_rules(path_beneath_rules(existing(write), AccessFs::from_all(abi)))?
+        .restrict_self()?;
+
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => log::info!("Filesystem access is sandboxed."),
+        RulesetStatus::PartiallyEnforced => log::warn!(
+            "The kernel only supports some of Landlock, so filesystem access is partly sandboxed."
+        ),
+        RulesetStatus::NotEnforced => {
+            log::warn!("The kernel doesn't support Landlock, so filesystem access isn't sandboxed.")
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    feature = "sandbox",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn restrict_syscalls() -> Result<()> {
+    use seccompiler::{BpfProgram, SeccompAction, SeccompFilter, TargetArch};
+    use std::convert::{TryFrom, TryInto};
+
+    let filter = SeccompFilter::new(
+        DENIED_SYSCALLS
+            .iter()
+            .map(|&syscall| (syscall, Vec::new()))
+            .collect(),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EPERM as u32),
+        TargetArch::try_from(std::env::consts::ARCH)?,
+    )?;
+    let program: BpfProgram = filter.try_into()?;
+
+    // The runtime that loaded the config may have left threads behind.
+    seccompiler::apply_filter_all_threads(&program)?;
+    log::info!("System calls are sandboxed.");
+
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    feature = "sandbox",
+    not(any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+fn restrict_syscalls() -> Result<()> {
+    log::warn!(
+        "There's no seccomp filter for {}, so system calls aren't sandboxed.",
+        std::env::consts::ARCH
+    );
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+/// How bright it is around the host, from an ambient light sensor on the industrial I/O bus, as
+/// most laptops and some single board computers have.
+pub struct AmbientLight {
+    /// The sensor's directory under `/sys/bus/iio/devices`.
+    device: PathBuf,
+}
+
+impl AmbientLight {
+    /// Uses the first light sensor found.
+    pub fn new() -> Result<Self> {
+        let device = std::fs::read_dir(IIO_DEVICES)
+            .context("Failed to list industrial I/O devices.")?
+            .flatten()
+            .map(|device| device.path())
+            .find(|device| {
+                device.join("in_illuminance_input").exists()
+                    || device.join("in_illuminance_raw").exists()
+            })
+            .context("There is no ambient light sensor.")?;
+
+        Ok(Self { device })
+    }
+}
+
+/// Reads a number from one of the sensor's files, if it has it.
+async fn read_value(path: &Path) -> Result<Option<f64>> {
+    match fs::read_to_string(path).await {
+        Ok(value) => Ok(Some(value.trim().parse().with_context(|| {
+            format!("`{}` does not hold a number.", path.display())
+        })?)),
+        Err(_) => Ok(None),
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for AmbientLight {
+    fn name(&self) -> &str {
+        "ambient_light"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "illuminance")
+            .device_class("illuminance")
+            .state_class("measurement")
+            .unit("lx")
+            .icon("mdi:brightness-5")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        // Some drivers give lux directly. The rest give a raw value that lux is worked out from.
+        let lux = match read_value(&self.device.join("in_illuminance_input")).await? {
+            Some(lux) => lux,
+            None => {
+                let raw = read_value(&self.device.join("in_illuminance_raw"))
+                    .await?
+                    .context("The ambient light sensor has gone away.")?;
+                let offset = read_value(&self.device.join("in_illuminance_offset"))
+                    .await?
+                    .unwrap_or(0.0);
+                let scale = read_value(&self.device.join("in_illuminance_scale"))
+                    .await?
+                    .unwrap_or(1.0);
+
+                (raw + offset) * scale
+            }
+        };
+
+        Ok(vec![Reading::new("illuminance", lux.to_string())])
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+const BACKLIGHT_CLASS: &str = "/sys/class/backlight";
+
+/// The brightness of the display's backlight, which can also be set.
+pub struct Backlight {
+    /// The device's directory under `/sys/class/backlight`.
+    device: PathBuf,
+    max_brightness: u64,
+    privileged: Privileged,
+}
+
+impl Backlight {
+    /// Uses the named backlight device, or the first one found if there's no name.
+    pub fn new(device: Option<&str>, privileged: Privileged) -> Result<Self> {
+        let device = match device {
+            Some(device) => Path::new(BACKLIGHT_CLASS).join(device),
+            None => std::fs::read_dir(BACKLIGHT_CLASS)
+                .context("Failed to list backlight devices.")?
+                .next()
+                .context("There are no backlight devices.")??
+                .path(),
+        };
+
+        // This never changes, so it's only read once.
+        let max_brightness_path = device.join("max_brightness");
+        let max_brightness = std::fs::read_to_string(&max_brightness_path)
+            .with_context(|| format!("Failed to read `{}`.", max_brightness_path.display()))?
+            .trim()
+            .parse()
+            .context("Maximum brightness is not a number.")?;
+
+        Ok(Self {
+            device,
+            max_brightness,
+            privileged,
+        })
+    }
+
+    async fn read_brightness(&self) -> Result<Reading> {
+        let path = self.device.join("brightness");
+        let brightness: u64 = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read `{}`.", path.display()))?
+            .trim()
+            .parse()
+            .context("Brightness is not a number.")?;
+        let percentage = brightness as f64 / self.max_brightness as f64 * 100.0;
+
+        Ok(Reading::new("brightness", percentage.round().to_string()))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Backlight {
+    fn name(&self) -> &str {
+        "backlight"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("number", "brightness")
+            .state_class("")
+            .unit("%")
+            .icon("mdi:brightness-6")
+            .range(0.0, 100.0, 1.0)
+            .accepts_commands(ActionClass::Desktop)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_brightness().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let percentage: f64 = payload
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` is not a brightness.", payload))?;
+        let brightness =
+            (percentage.clamp(0.0, 100.0) / 100.0 * self.max_brightness as f64).round() as u64;
+
+        self.privileged
+            .run(Request::WriteSysfs {
+                path: self.device.join("brightness"),
+                value: brightness.to_string(),
+            })
+            .await
+            .context("Failed to set brightness.")?;
+
+        Ok(vec![self.read_brightness().await?])
+    }
+}
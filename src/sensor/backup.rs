@@ -0,0 +1,234 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{fs, process::Command};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum BackupTool {
+    #[serde(rename = "restic")]
+    Restic,
+
+    #[serde(rename = "borg")]
+    Borg,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    pub tool: BackupTool,
+
+    /// Where the repository is, in whatever form the tool takes it.
+    pub repository: String,
+
+    /// A file holding the repository's password.
+    pub password_file: Option<PathBuf>,
+
+    /// How often to ask the repository for its latest snapshot. Remote repositories can take
+    /// a while to answer, so this defaults to every hour.
+    #[serde(default = "BackupConfig::default_interval")]
+    pub interval: Duration,
+}
+
+impl BackupConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+/// The snapshots as listed by `restic snapshots --json`.
+#[derive(Deserialize)]
+struct ResticSnapshot {
+    time: String,
+}
+
+/// The archives as listed by `borg list --json`.
+#[derive(Deserialize)]
+struct BorgList {
+    archives: Vec<BorgArchive>,
+}
+
+#[derive(Deserialize)]
+struct BorgArchive {
+    time: String,
+}
+
+/// Parses an RFC 3339 timestamp. Unlike `humantime`, this accepts offsets other than `Z`.
+/// Timestamps without an offset are taken to be UTC.
+fn parse_timestamp(timestamp: &str) -> Result<SystemTime> {
+    let (date_time, offset) = match timestamp.rfind(|c| c == '+' || c == '-') {
+        // The date has dashes in it too, but an offset is always the last six characters, like `+02:00`.
+        Some(split) if timestamp.len() - split == 6 => timestamp.split_at(split),
+        _ => (timestamp.trim_end_matches('Z'), ""),
+    };
+
+    let time = humantime::parse_rfc3339_weak(date_time)
+        .with_context(|| format!("Failed to parse timestamp `{}`.", timestamp))?;
+
+    if offset.is_empty() {
+        return Ok(time);
+    }
+
+    let hours: u64 = offset[1..3].parse()?;
+    let minutes: u64 = offset[4..6].parse()?;
+    let offset_duration = Duration::from_secs((hours * 60 + minutes) * 60);
+
+    // Clocks ahead of UTC read later than UTC does at the same moment.
+    Ok(if offset.starts_with('+') {
+        time - offset_duration
+    } else {
+        time + offset_duration
+    })
+}
+
+/// How long ago the last backup to a restic or borg repository was made, in hours.
+///
+/// Asking the repository is slow, so it's only done every so often. The age is worked out
+/// from the last answer every update.
+pub struct BackupSensor {
+    config: BackupConfig,
+    last_check: Option<Instant>,
+    last_backup: Option<SystemTime>,
+}
+
+impl BackupSensor {
+    pub fn new(config: BackupConfig) -> Self {
+        Self {
+            config,
+            last_check: None,
+            last_backup: None,
+        }
+    }
+
+    async fn run(&self, program: &str, args: &[&str]) -> Result<Vec<u8>> {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            // Borg prints times in local time without saying which zone that is.
+            .env("TZ", "UTC")
+            .kill_on_drop(true);
+
+        if let Some(password_file) = &self.config.password_file {
+            match self.config.tool {
+                BackupTool::Restic => {
+                    command.arg("--password-file").arg(password_file);
+                }
+                BackupTool::Borg => {
+                    let password = fs::read_to_string(password_file).await.with_context(|| {
+                        format!("Failed to read `{}`.", password_file.display())
+                    })?;
+                    command.env("BORG_PASSPHRASE", password.trim_end());
+                }
+            }
+        }
+
+        let output = command
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}.", program))?;
+
+        if !output.status.success() {
+            bail!(
+                "{} exited with {}: {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// When the latest snapshot in the repository was taken, if there is one.
+    async fn last_backup(&self) -> Result<Option<SystemTime>> {
+        let repository = self.config.repository.as_str();
+
+        let times = match self.config.tool {
+            BackupTool::Restic => {
+                let stdout = self
+                    .run(
+                        "restic",
+                        &["--repo", repository, "snapshots", "--json", "--no-lock"],
+                    )
+                    .await?;
+                let snapshots: Vec<ResticSnapshot> =
+                    serde_json::from_slice(&stdout).context("Failed to parse restic snapshots.")?;
+
+                snapshots
+                    .into_iter()
+                    .map(|snapshot| snapshot.time)
+                    .collect::<Vec<_>>()
+            }
+            BackupTool::Borg => {
+                let stdout = self
+                    .run("borg", &["list", "--json", "--last", "1", repository])
+                    .await?;
+                let list: BorgList =
+                    serde_json::from_slice(&stdout).context("Failed to parse borg archives.")?;
+
+                list.archives
+                    .into_iter()
+                    .map(|archive| archive.time)
+                    .collect()
+            }
+        };
+
+        let mut last_backup = None;
+        for time in times {
+            let time = parse_timestamp(&time)?;
+            if last_backup.map_or(true, |last_backup| time > last_backup) {
+                last_backup = Some(time);
+            }
+        }
+
+        Ok(last_backup)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for BackupSensor {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .device_class("duration")
+            .state_class("measurement")
+            .unit("h")
+            .icon("mdi:backup-restore")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let due = self.last_check.map_or(true, |last_check| {
+            last_check.elapsed() >= self.config.interval
+        });
+
+        if due {
+            self.last_check = Some(Instant::now());
+            self.last_backup = self.last_backup().await?;
+        }
+
+        // A repository without any backups in it has no age to report.
+        let last_backup = match self.last_backup {
+            Some(last_backup) => last_backup,
+            None => return Ok(Vec::new()),
+        };
+
+        let age = SystemTime::now()
+            .duration_since(last_backup)
+            .unwrap_or_default();
+
+        Ok(vec![Reading::new(
+            self.config.name.as_str(),
+            format!("{:.1}", age.as_secs_f64() / (60.0 * 60.0)),
+        )])
+    }
+}
@@ -0,0 +1,203 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::fs;
+
+const POWER_SUPPLY_CLASS: &str = "/sys/class/power_supply";
+
+/// Whether the host has a battery to report on.
+pub fn has_battery() -> bool {
+    let found = battery::Manager::new()
+        .and_then(|manager| Ok(manager.batteries()?.flatten().next().is_some()));
+
+    match found {
+        Ok(found) => found,
+        Err(error) => {
+            log::warn!("Failed to look for batteries: {:?}", error);
+            false
+        }
+    }
+}
+
+/// The charge, state and health of the battery.
+pub struct BatterySensor {
+    manager: battery::Manager,
+
+    /// The charge, in percent, below which the battery is reported as low while discharging.
+    low_threshold: f32,
+}
+
+impl BatterySensor {
+    pub fn new(low_threshold: f32) -> Result<Self> {
+        let manager = battery::Manager::new().context("Failed to initalize battery monitoring.")?;
+
+        Ok(Self {
+            manager,
+            low_threshold,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for BatterySensor {
+    fn name(&self) -> &str {
+        "battery"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "battery_level")
+                .device_class("battery")
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:battery"),
+            Entity::new("sensor", "battery_state")
+                .state_class("")
+                .icon("mdi:battery"),
+            Entity::new("binary_sensor", "battery_low")
+                .device_class("battery")
+                .state_class("")
+                .icon("mdi:battery-alert"),
+            Entity::new("sensor", "battery_cycle_count")
+                .state_class("total_increasing")
+                .icon("mdi:battery-sync")
+                .entity_category("diagnostic"),
+            Entity::new("sensor", "battery_health")
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:battery-heart-variant")
+                .entity_category("diagnostic"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        // TODO we should probably combine the battery charges, but for now we're just going to use the first detected battery.
+        if let Some(battery) = self
+            .manager
+            .batteries()
+            .context("Failed to read battery info.")?
+            .flatten()
+            .next()
+        {
+            use battery::State;
+
+            let battery_state = match battery.state() {
+                State::Charging => "charging",
+                State::Discharging => "discharging",
+                State::Empty => "empty",
+                State::Full => "full",
+                _ => "unknown",
+            };
+
+            readings.push(Reading::new("battery_state", battery_state));
+
+            let battery_full = battery.energy_full();
+            let battery_power = battery.energy();
+            let battery_level = battery_power / battery_full;
+
+            readings.push(Reading::new(
+                "battery_level",
+                format!("{:03}", battery_level.value),
+            ));
+
+            let discharging = matches!(battery.state(), State::Discharging | State::Empty);
+            let low = discharging && battery_level.value * 100.0 < self.low_threshold;
+            readings.push(Reading::new("battery_low", if low { "ON" } else { "OFF" }));
+
+            // Not every battery counts its cycles.
+            if let Some(cycle_count) = battery.cycle_count() {
+                readings.push(Reading::new("battery_cycle_count", cycle_count.to_string()));
+            }
+
+            // How much the battery holds when full, compared to what it was designed to hold.
+            readings.push(Reading::new(
+                "battery_health",
+                (battery.state_of_health().value * 100.0).to_string(),
+            ));
+        }
+
+        Ok(readings)
+    }
+}
+
+/// The charge the battery stops charging at, which can also be set. Keeping a battery that's
+/// always plugged in from charging all the way makes it last longer.
+pub struct ChargeLimit {
+    /// The battery's `charge_control_end_threshold` file.
+    threshold: PathBuf,
+    privileged: Privileged,
+}
+
+impl ChargeLimit {
+    /// Uses the first battery that has a charge limit.
+    pub fn new(privileged: Privileged) -> Result<Self> {
+        let threshold = std::fs::read_dir(POWER_SUPPLY_CLASS)
+            .context("Failed to list power supplies.")?
+            .flatten()
+            .map(|supply| supply.path().join("charge_control_end_threshold"))
+            .find(|threshold| threshold.exists())
+            .context("There are no batteries with a charge limit.")?;
+
+        Ok(Self {
+            threshold,
+            privileged,
+        })
+    }
+
+    async fn read_limit(&self) -> Result<Reading> {
+        let limit: u8 = fs::read_to_string(&self.threshold)
+            .await
+            .with_context(|| format!("Failed to read `{}`.", self.threshold.display()))?
+            .trim()
+            .parse()
+            .context("Charge limit is not a number.")?;
+
+        Ok(Reading::new("battery_charge_limit", limit.to_string()))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for ChargeLimit {
+    fn name(&self) -> &str {
+        "battery_charge_limit"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("number", "battery_charge_limit")
+            .state_class("")
+            .unit("%")
+            .icon("mdi:battery-charging-80")
+            .range(1.0, 100.0, 1.0)
+            .accepts_commands(ActionClass::Hardware)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_limit().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let limit: f64 = payload
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` is not a charge limit.", payload))?;
+
+        // Some laptops also have a charge the battery has to drop below before it starts
+        // charging again, and refuse limits that aren't above it.
+        self.privileged
+            .run(Request::WriteSysfs {
+                path: self.threshold.clone(),
+                value: (limit.clamp(1.0, 100.0).round() as u8).to_string(),
+            })
+            .await
+            .context("Failed to set the charge limit.")?;
+
+        Ok(vec![self.read_limit().await?])
+    }
+}
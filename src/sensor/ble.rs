@@ -0,0 +1,290 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::Entity,
+};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+use zbus::{dbus_proxy, fdo::ObjectManagerProxy, zvariant::Value, Connection};
+
+const BLUEZ: &str = "org.bluez";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+
+/// Apple's company identifier, which iBeacons are advertised under.
+const APPLE_COMPANY_ID: u16 = 0x004c;
+
+#[dbus_proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+trait Adapter {
+    fn set_discovery_filter(&self, properties: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    fn start_discovery(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn discovering(&self) -> zbus::Result<bool>;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BleDevice {
+    /// The name the device will be reported as.
+    pub name: String,
+
+    /// The device's MAC address, such as `AA:BB:CC:DD:EE:FF`.
+    pub address: Option<String>,
+
+    /// For iBeacons, which change their address, the UUID they advertise instead.
+    pub ibeacon_uuid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BleScanConfig {
+    /// The Bluetooth adapter to scan with.
+    #[serde(default = "BleScanConfig::default_adapter")]
+    pub adapter: String,
+
+    pub devices: Vec<BleDevice>,
+
+    /// How long a device has to go unseen before it's reported as away.
+    #[serde(default = "BleScanConfig::default_away_after")]
+    pub away_after: Duration,
+}
+
+impl BleScanConfig {
+    fn default_adapter() -> String {
+        String::from("hci0")
+    }
+
+    fn default_away_after() -> Duration {
+        Duration::from_secs(3 * 60)
+    }
+}
+
+/// What we know about a device BlueZ has seen.
+struct Sighting {
+    address: String,
+    rssi: Option<i16>,
+    ibeacon_uuid: Option<String>,
+}
+
+/// The UUID of an iBeacon, formatted like `fda50693-a4e2-4fb1-afcf-c6eb07647825`, from Apple's manufacturer data.
+fn ibeacon_uuid(data: &[u8]) -> Option<String> {
+    // Type 0x02 and length 0x15 mark an iBeacon, followed by the UUID, major and minor.
+    if data.len() < 18 || data[0] != 0x02 || data[1] != 0x15 {
+        return None;
+    }
+
+    let hex: String = data[2..18]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Whether configured Bluetooth LE devices, such as phones, watches and iBeacons, are nearby.
+///
+/// This keeps BlueZ scanning, and reports each device as a `device_tracker` entity that's
+/// `home` while the device has been seen recently.
+pub struct BleScanner {
+    config: BleScanConfig,
+    connection: LazyConnection,
+
+    /// When each device was last seen, and the signal strength it was seen with.
+    last_seen: HashMap<String, (Instant, Option<i16>)>,
+}
+
+impl BleScanner {
+    pub fn new(config: BleScanConfig) -> Result<Self> {
+        for device in config.devices.iter() {
+            ensure!(
+                device.address.is_some() != device.ibeacon_uuid.is_some(),
+                "Bluetooth device `{}` needs either an address or an iBeacon UUID.",
+                device.name
+            );
+        }
+
+        Ok(Self {
+            config,
+            connection: LazyConnection::new(Bus::System),
+            last_seen: HashMap::new(),
+        })
+    }
+
+    /// Starts scanning, if we aren't already. Scanning stops if BlueZ restarts, or the adapter is turned off and on.
+    async fn ensure_discovering(&self, connection: &Connection) -> Result<()> {
+        let adapter = AdapterProxy::builder(connection)
+            .path(format!("/org/bluez/{}", self.config.adapter))?
+            .build()
+            .await?;
+
+        if !adapter.discovering().await? {
+            log::info!("Starting Bluetooth LE scan on `{}`.", self.config.adapter);
+
+            // Reporting every advertisement, rather than just the first, is what lets us tell a device is still around.
+            let mut filter = HashMap::new();
+            filter.insert("Transport", Value::from("le"));
+            filter.insert("DuplicateData", Value::from(true));
+            adapter
+                .set_discovery_filter(filter)
+                .await
+                .context("Failed to set Bluetooth discovery filter.")?;
+            adapter
+                .start_discovery()
+                .await
+                .context("Failed to start Bluetooth discovery.")?;
+        }
+
+        Ok(())
+    }
+
+    async fn sightings(&self, connection: &Connection) -> Result<Vec<Sighting>> {
+        let objects = ObjectManagerProxy::builder(connection)
+            .destination(BLUEZ)?
+            .path("/")?
+            .build()
+            .await?
+            .get_managed_objects()
+            .await
+            .context("Failed to list Bluetooth devices.")?;
+
+        let adapter_path = format!("/org/bluez/{}/", self.config.adapter);
+        let mut sightings = Vec::new();
+
+        for (path, interfaces) in objects {
+            let properties = match interfaces.get(DEVICE_INTERFACE) {
+                Some(properties) if path.as_str().starts_with(&adapter_path) => properties,
+                _ => continue,
+            };
+
+            let address = match properties.get("Address").map(|value| &**value) {
+                Some(Value::Str(address)) => address.to_string(),
+                _ => continue,
+            };
+            let rssi = properties
+                .get("RSSI")
+                .and_then(|value| i16::try_from(&**value).ok());
+            let ibeacon_uuid = properties
+                .get("ManufacturerData")
+                .and_then(|value| match &**value {
+                    Value::Dict(data) => HashMap::<u16, Value>::try_from(data.clone()).ok(),
+                    _ => None,
+                })
+                .and_then(|mut data| data.remove(&APPLE_COMPANY_ID))
+                .and_then(|data| match data {
+                    // Each company's data is wrapped in a variant.
+                    Value::Value(data) => Vec::<u8>::try_from(*data).ok(),
+                    data => Vec::<u8>::try_from(data).ok(),
+                })
+                .and_then(|data| ibeacon_uuid(&data));
+
+            sightings.push(Sighting {
+                address,
+                rssi,
+                ibeacon_uuid,
+            });
+        }
+
+        Ok(sightings)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for BleScanner {
+    fn name(&self) -> &str {
+        "bluetooth_presence"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for device in self.config.devices.iter() {
+            entities.push(
+                Entity::new("device_tracker", &device.name)
+                    .state_class("")
+                    .source_type("bluetooth_le")
+                    .icon("mdi:bluetooth"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_rssi", device.name))
+                    .device_class("signal_strength")
+                    .state_class("measurement")
+                    .unit("dBm")
+                    .entity_category("diagnostic")
+                    .icon("mdi:signal"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        self.ensure_discovering(&connection).await?;
+        let sightings = self.sightings(&connection).await?;
+
+        let now = Instant::now();
+        let mut readings = Vec::new();
+
+        for device in self.config.devices.iter() {
+            let sighting =
+                sightings
+                    .iter()
+                    .find(|sighting| match (&device.address, &device.ibeacon_uuid) {
+                        (Some(address), _) => sighting.address.eq_ignore_ascii_case(address),
+                        (None, Some(uuid)) => sighting
+                            .ibeacon_uuid
+                            .as_ref()
+                            .map_or(false, |found| found.eq_ignore_ascii_case(uuid)),
+                        (None, None) => false,
+                    });
+
+            // BlueZ keeps reporting the last signal strength of devices it remembers, so a device
+            // only counts as seen when it's new or its signal strength changed.
+            if let Some(Sighting {
+                rssi: Some(rssi), ..
+            }) = sighting
+            {
+                let seen = match self.last_seen.get(&device.name) {
+                    Some((_, last_rssi)) => *last_rssi != Some(*rssi),
+                    None => true,
+                };
+
+                if seen {
+                    self.last_seen
+                        .insert(device.name.clone(), (now, Some(*rssi)));
+                }
+            }
+
+            let home = match self.last_seen.get(&device.name) {
+                Some((last_seen, _)) => now.duration_since(*last_seen) < self.config.away_after,
+                None => false,
+            };
+
+            readings.push(Reading::new(
+                device.name.as_str(),
+                if home { "home" } else { "not_home" },
+            ));
+
+            let rssi_name = format!("{}_rssi", device.name);
+            match self.last_seen.get(&device.name) {
+                Some((_, Some(rssi))) if home => {
+                    readings.push(Reading::new(rssi_name, rssi.to_string()))
+                }
+                _ => readings.push(Reading::unavailable(rssi_name)),
+            }
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,115 @@
+//! Resource usage of cgroups, such as the one we're running in, for when we're in a container and
+//! the whole host's numbers would be misleading. Only cgroup v2 is supported.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Checks for the files container runtimes leave behind.
+pub fn in_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || std::env::var_os("container").is_some()
+}
+
+pub struct Cgroup {
+    /// The cgroup's directory under `/sys/fs/cgroup`.
+    path: PathBuf,
+
+    /// The CPU time used by the cgroup in microseconds, and when it was read.
+    last_cpu_usage: Option<(Instant, u64)>,
+}
+
+impl Cgroup {
+    /// Finds the cgroup this process belongs to.
+    pub fn detect() -> Result<Self> {
+        // With cgroup v2 there's a single line that looks like `0::/system.slice/system-mqtt.service`.
+        // Inside a container it's usually just `0::/`.
+        let cgroup = fs::read_to_string("/proc/self/cgroup").context("Failed to read cgroup.")?;
+        let cgroup_path = cgroup
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .context("Only cgroup v2 is supported.")?;
+
+        let cgroup = Self::at(cgroup_path);
+        if !cgroup.path.join("cgroup.controllers").exists() {
+            bail!("cgroup `{}` is not mounted.", cgroup.path.display());
+        }
+
+        Ok(cgroup)
+    }
+
+    /// The cgroup with a path like `/system.slice/nginx.service`.
+    pub fn at(cgroup_path: &str) -> Self {
+        Self {
+            path: Path::new("/sys/fs/cgroup").join(cgroup_path.trim_start_matches('/')),
+            last_cpu_usage: None,
+        }
+    }
+
+    fn read(&self, file: &str) -> Result<String> {
+        let path = self.path.join(file);
+        fs::read_to_string(&path).with_context(|| format!("Failed to read `{}`.", path.display()))
+    }
+
+    /// Memory used, in bytes.
+    pub fn memory_current(&self) -> Result<u64> {
+        self.read("memory.current")?
+            .trim()
+            .parse()
+            .context("Memory usage is not a number.")
+    }
+
+    /// Memory used and the cgroup's limit, or the host's memory if there's no limit, in bytes.
+    pub fn memory_usage(&self, host_memory: u64) -> Result<(u64, u64)> {
+        let current = self.memory_current()?;
+
+        let limit = match self.read("memory.max")?.trim() {
+            "max" => host_memory,
+            limit => limit.parse().context("Memory limit is not a number.")?,
+        };
+
+        Ok((current, limit))
+    }
+
+    /// CPU used as a fraction of the cgroup's quota, or of every CPU if there's no quota.
+    /// Usage is measured between calls, so the first call has nothing to report.
+    pub fn cpu_usage(&mut self, cpu_count: usize) -> Result<Option<f64>> {
+        let now = Instant::now();
+
+        // Looks like `usage_usec 123456` followed by other statistics.
+        let usage: u64 = self
+            .read("cpu.stat")?
+            .lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .context("CPU usage is missing.")?
+            .trim()
+            .parse()
+            .context("CPU usage is not a number.")?;
+
+        // Looks like `max 100000` without a quota, or `50000 100000` for half a CPU. It's only
+        // there when the CPU controller is enabled for the cgroup, which can't set a quota otherwise.
+        let cpu_max = self.read("cpu.max").unwrap_or_default();
+        let mut cpu_max = cpu_max.split_whitespace();
+        let cpus = match (cpu_max.next(), cpu_max.next()) {
+            (Some("max"), _) | (None, _) => cpu_count as f64,
+            (Some(quota), Some(period)) => {
+                let quota: f64 = quota.parse().context("CPU quota is not a number.")?;
+                let period: f64 = period.parse().context("CPU period is not a number.")?;
+                quota / period
+            }
+            (Some(_), None) => bail!("CPU quota is missing its period."),
+        };
+
+        let result = self.last_cpu_usage.map(|(last_time, last_usage)| {
+            let elapsed = now.duration_since(last_time).as_micros() as f64;
+            usage.saturating_sub(last_usage) as f64 / (elapsed * cpus)
+        });
+        self.last_cpu_usage = Some((now, usage));
+
+        Ok(result)
+    }
+}
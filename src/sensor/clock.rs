@@ -0,0 +1,103 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use tokio::process::Command;
+
+/// How far the system clock is from the time servers it synchronizes with, in milliseconds.
+/// This is read from chrony if it's installed, or systemd-timesyncd otherwise.
+pub struct ClockOffset;
+
+/// Runs a command, or returns `None` if it isn't installed.
+async fn run(program: &str, arguments: &[&str]) -> Result<Option<String>> {
+    let output = match Command::new(program)
+        .args(arguments)
+        .kill_on_drop(true)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error).with_context(|| format!("Failed to run {}.", program)),
+    };
+
+    if !output.status.success() {
+        bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// Reads the offset from chrony's tracking report.
+fn parse_chrony(tracking: &str) -> Result<f64> {
+    // The fifth field is how far the system time is from NTP time, in seconds.
+    let offset: f64 = tracking
+        .trim()
+        .split(',')
+        .nth(4)
+        .context("chronyc did not report an offset.")?
+        .parse()
+        .context("chronyc reported an offset that is not a number.")?;
+
+    Ok(offset * 1000.0)
+}
+
+/// Reads the offset from timesyncd's status, which has a line like `Offset: -1.234ms`.
+fn parse_timesyncd(status: &str) -> Result<f64> {
+    let offset = status
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Offset:"))
+        .context("timesyncd did not report an offset. Has it synchronized yet?")?
+        .trim();
+
+    let number_length = offset
+        .find(|character: char| character.is_ascii_alphabetic())
+        .unwrap_or(offset.len());
+    let (number, unit) = offset.split_at(number_length);
+    let number: f64 = number
+        .trim_start_matches('+')
+        .parse()
+        .with_context(|| format!("`{}` is not an offset.", offset))?;
+
+    Ok(match unit {
+        "ns" => number / 1_000_000.0,
+        "us" => number / 1000.0,
+        "ms" => number,
+        "s" => number * 1000.0,
+        "min" => number * 60_000.0,
+        unit => bail!("Unknown offset unit `{}`.", unit),
+    })
+}
+
+#[async_trait(?Send)]
+impl Sensor for ClockOffset {
+    fn name(&self) -> &str {
+        "clock_offset"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "clock_offset")
+            .device_class("duration")
+            .state_class("measurement")
+            .unit("ms")
+            .icon("mdi:clock-alert-outline")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let offset = if let Some(tracking) = run("chronyc", &["-c", "tracking"]).await? {
+            parse_chrony(&tracking)?
+        } else if let Some(status) = run("timedatectl", &["timesync-status"]).await? {
+            parse_timesyncd(&status)?
+        } else {
+            bail!("Neither chrony nor systemd-timesyncd is installed.");
+        };
+
+        Ok(vec![Reading::new("clock_offset", offset.to_string())])
+    }
+}
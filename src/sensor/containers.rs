@@ -0,0 +1,186 @@
+use super::{unix_http, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerRuntime {
+    /// Use whichever runtime's socket is found first.
+    #[serde(rename = "auto")]
+    Auto,
+
+    #[serde(rename = "docker")]
+    Docker,
+
+    #[serde(rename = "podman")]
+    Podman,
+}
+
+impl Default for ContainerRuntime {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ContainersConfig {
+    #[serde(default)]
+    pub runtime: ContainerRuntime,
+
+    /// The API socket to connect to. If not set, the usual place for the runtime is used.
+    pub socket: Option<PathBuf>,
+
+    /// The containers to report on. If empty, every container that exists when we start is reported.
+    #[serde(default)]
+    pub containers: Vec<String>,
+}
+
+/// The part of a container listing we care about.
+#[derive(Deserialize)]
+struct ContainerSummary {
+    #[serde(rename = "Names")]
+    names: Vec<String>,
+
+    #[serde(rename = "State")]
+    state: String,
+}
+
+impl ContainerSummary {
+    /// Docker puts a slash in front of container names.
+    fn name(&self) -> Option<&str> {
+        self.names.first().map(|name| name.trim_start_matches('/'))
+    }
+}
+
+/// The state of Docker or Podman containers.
+///
+/// Podman serves the same API as Docker, so both are read the same way.
+pub struct ContainerSensor {
+    config: ContainersConfig,
+    socket: PathBuf,
+
+    /// Maps container names to the names of their entities.
+    containers: HashMap<String, String>,
+}
+
+impl ContainerSensor {
+    pub fn new(config: ContainersConfig) -> Result<Self> {
+        let socket = match &config.socket {
+            Some(socket) => socket.clone(),
+            None => find_socket(config.runtime)?,
+        };
+
+        log::info!("Reading containers from `{}`.", socket.display());
+
+        Ok(Self {
+            config,
+            socket,
+            containers: HashMap::new(),
+        })
+    }
+
+    async fn list_containers(&self) -> Result<Vec<ContainerSummary>> {
+        let body = unix_http::get(&self.socket, "localhost", "/containers/json?all=true").await?;
+
+        serde_json::from_str(&body).context("Failed to parse container list.")
+    }
+}
+
+fn find_socket(runtime: ContainerRuntime) -> Result<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if matches!(runtime, ContainerRuntime::Auto | ContainerRuntime::Docker) {
+        candidates.push(PathBuf::from("/var/run/docker.sock"));
+    }
+
+    if matches!(runtime, ContainerRuntime::Auto | ContainerRuntime::Podman) {
+        candidates.push(PathBuf::from("/run/podman/podman.sock"));
+
+        // Rootless Podman keeps its socket with the user's other runtime files.
+        if let Some(runtime_directory) = std::env::var_os("XDG_RUNTIME_DIR") {
+            candidates.push(Path::new(&runtime_directory).join("podman/podman.sock"));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.exists())
+        .context("No container runtime socket found. Is the Docker or Podman socket enabled?")
+}
+
+/// Entity names can't have everything a container name can.
+fn entity_name(container: &str) -> String {
+    let container: String = container
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("container_{}", container)
+}
+
+#[async_trait(?Send)]
+impl Sensor for ContainerSensor {
+    fn name(&self) -> &str {
+        "containers"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let containers = if self.config.containers.is_empty() {
+            self.list_containers()
+                .await?
+                .iter()
+                .filter_map(ContainerSummary::name)
+                .map(str::to_string)
+                .collect()
+        } else {
+            self.config.containers.clone()
+        };
+
+        self.containers = containers
+            .into_iter()
+            .map(|container| {
+                let entity_name = entity_name(&container);
+                (container, entity_name)
+            })
+            .collect();
+
+        Ok(self
+            .containers
+            .values()
+            .map(|entity_name| {
+                Entity::new("sensor", entity_name)
+                    .state_class("")
+                    .icon("mdi:docker")
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let listed = self.list_containers().await?;
+        let states: HashMap<&str, &str> = listed
+            .iter()
+            .filter_map(|container| Some((container.name()?, container.state.as_str())))
+            .collect();
+
+        Ok(self
+            .containers
+            .iter()
+            .map(|(container, entity_name)| {
+                // A container that was removed since we started is reported as missing.
+                let state = states.get(container.as_str()).copied().unwrap_or("missing");
+                Reading::new(entity_name.as_str(), state)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,133 @@
+use super::{default_state_class, Reading, Sensor};
+use crate::{
+    dbus::{format_value, Bus, LazyConnection},
+    sink::Entity,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use zbus::{fdo::PropertiesProxy, names::InterfaceName, zvariant::OwnedValue};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DbusSensorConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    /// Which bus the service lives on. Defaults to the system bus.
+    #[serde(default)]
+    pub bus: Bus,
+
+    /// The bus name of the service, such as `org.freedesktop.UPower`.
+    pub destination: String,
+
+    /// The object path, such as `/org/freedesktop/UPower/devices/DisplayDevice`.
+    pub path: String,
+
+    /// The interface the property or method belongs to, such as `org.freedesktop.UPower.Device`.
+    pub interface: String,
+
+    /// The property to read. Either this or `method` must be set.
+    pub property: Option<String>,
+
+    /// A method that takes no arguments to call. Its return value is used as the sensor's value.
+    pub method: Option<String>,
+
+    /// The unit of the value, if it has one.
+    pub unit: Option<String>,
+
+    /// The icon to show in Home Assistant.
+    pub icon: Option<String>,
+}
+
+pub struct DbusSensor {
+    config: DbusSensorConfig,
+    connection: LazyConnection,
+}
+
+impl DbusSensor {
+    pub fn new(config: DbusSensorConfig) -> Result<Self> {
+        match (&config.property, &config.method) {
+            (Some(_), None) | (None, Some(_)) => Ok(Self {
+                connection: LazyConnection::new(config.bus),
+                config,
+            }),
+            _ => bail!(
+                "D-Bus sensor `{}` must have exactly one of `property` or `method` set.",
+                config.name
+            ),
+        }
+    }
+
+    async fn read(&mut self) -> Result<String> {
+        let connection = self.connection.get().await?;
+
+        if let Some(property) = &self.config.property {
+            let proxy = PropertiesProxy::builder(&connection)
+                .destination(self.config.destination.as_str())?
+                .path(self.config.path.as_str())?
+                .build()
+                .await?;
+            let value: OwnedValue = proxy
+                .get(
+                    InterfaceName::try_from(self.config.interface.as_str())?,
+                    property,
+                )
+                .await
+                .context("Failed to read D-Bus property.")?;
+
+            format_value(&value)
+        } else if let Some(method) = &self.config.method {
+            let reply = connection
+                .call_method(
+                    Some(self.config.destination.as_str()),
+                    self.config.path.as_str(),
+                    Some(self.config.interface.as_str()),
+                    method.as_str(),
+                    &(),
+                )
+                .await
+                .context("Failed to call D-Bus method.")?;
+
+            let signature = reply.body_signature()?;
+            let value = match signature.as_str() {
+                "b" => if reply.body::<bool>()? { "ON" } else { "OFF" }.to_string(),
+                "y" => reply.body::<u8>()?.to_string(),
+                "n" => reply.body::<i16>()?.to_string(),
+                "q" => reply.body::<u16>()?.to_string(),
+                "i" => reply.body::<i32>()?.to_string(),
+                "u" => reply.body::<u32>()?.to_string(),
+                "x" => reply.body::<i64>()?.to_string(),
+                "t" => reply.body::<u64>()?.to_string(),
+                "d" => reply.body::<f64>()?.to_string(),
+                "s" | "o" => reply.body::<String>()?,
+                "v" => format_value(&reply.body::<OwnedValue>()?)?,
+                signature => bail!("Unsupported D-Bus return type `{}`.", signature),
+            };
+
+            Ok(value)
+        } else {
+            unreachable!("Checked on construction.")
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for DbusSensor {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .state_class(default_state_class(&self.config.unit))
+            .unit(self.config.unit.as_deref())
+            .icon(self.config.icon.as_deref().unwrap_or("mdi:bus"))])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let value = self.read().await?;
+
+        Ok(vec![Reading::new(self.config.name.as_str(), value)])
+    }
+}
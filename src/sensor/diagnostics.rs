@@ -0,0 +1,103 @@
+use crate::sink::{Entity, Sinks};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// Home Assistant won't take states longer than this.
+const MAX_STATE_LENGTH: usize = 255;
+
+/// Reports on system-mqtt itself: its version, how long it's been running, and how its updates
+/// are going. Unlike sensors, it's fed by the registry as it collects from them.
+pub struct Diagnostics {
+    started: Instant,
+
+    /// Collections that failed or took too long since we started, across every sensor.
+    failed_collections: u64,
+
+    last_error: Option<String>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            failed_collections: 0,
+            last_error: None,
+        }
+    }
+
+    pub async fn register(&self, sinks: &mut Sinks) -> Result<()> {
+        for entity in [
+            Entity::new("sensor", "system_mqtt_version")
+                .state_class("")
+                .icon("mdi:tag"),
+            Entity::new("sensor", "system_mqtt_uptime")
+                .device_class("duration")
+                .state_class("")
+                .unit("s")
+                .icon("mdi:timer-sand"),
+            Entity::new("sensor", "system_mqtt_failed_collections")
+                .state_class("total_increasing")
+                .icon("mdi:alert-circle-outline"),
+            Entity::new("sensor", "system_mqtt_last_error")
+                .state_class("")
+                .icon("mdi:alert"),
+            Entity::new("sensor", "system_mqtt_update_duration")
+                .device_class("duration")
+                .state_class("measurement")
+                .unit("ms")
+                .icon("mdi:timer-outline"),
+        ] {
+            sinks.register(entity.entity_category("diagnostic")).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Notes that a sensor failed to collect.
+    pub fn record_failure(&mut self, error: String) {
+        self.failed_collections += 1;
+        self.last_error = Some(error);
+    }
+
+    /// Publishes how we're doing, at the end of an update that took `update_duration`.
+    pub async fn publish(&self, sinks: &Sinks, update_duration: Duration) {
+        sinks
+            .publish("system_mqtt_version", env!("CARGO_PKG_VERSION").to_string())
+            .await;
+        sinks
+            .publish(
+                "system_mqtt_uptime",
+                self.started.elapsed().as_secs().to_string(),
+            )
+            .await;
+        sinks
+            .publish(
+                "system_mqtt_failed_collections",
+                self.failed_collections.to_string(),
+            )
+            .await;
+        sinks
+            .publish(
+                "system_mqtt_last_error",
+                self.last_error
+                    .as_deref()
+                    .unwrap_or("")
+                    .chars()
+                    .take(MAX_STATE_LENGTH)
+                    .collect(),
+            )
+            .await;
+        sinks
+            .publish(
+                "system_mqtt_update_duration",
+                update_duration.as_millis().to_string(),
+            )
+            .await;
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
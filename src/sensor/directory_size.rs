@@ -0,0 +1,123 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::task::{self, JoinHandle};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DirectorySizeConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    pub path: PathBuf,
+
+    /// How many directories deep to look. Files deeper than this aren't counted.
+    /// If not set, everything is counted.
+    pub max_depth: Option<usize>,
+
+    /// How often to add the size up. Large directories take a while, so this defaults to every hour.
+    #[serde(default = "DirectorySizeConfig::default_interval")]
+    pub interval: Duration,
+}
+
+impl DirectorySizeConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+/// The total size of the files in a directory, in bytes.
+///
+/// Adding it up can take a long time, so it's done in the background and reported once it's done.
+pub struct DirectorySize {
+    config: DirectorySizeConfig,
+    last_check: Option<Instant>,
+    running: Option<JoinHandle<Result<u64>>>,
+}
+
+impl DirectorySize {
+    pub fn new(config: DirectorySizeConfig) -> Self {
+        Self {
+            config,
+            last_check: None,
+            running: None,
+        }
+    }
+}
+
+/// Adds up the size of every file under `path`. Symbolic links aren't followed.
+fn directory_size(path: &Path, depth_left: Option<usize>) -> Result<u64> {
+    let mut size = 0;
+
+    for entry in
+        fs::read_dir(path).with_context(|| format!("Failed to list `{}`.", path.display()))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            match depth_left {
+                Some(0) => {}
+                Some(depth_left) => size += directory_size(&entry.path(), Some(depth_left - 1))?,
+                None => size += directory_size(&entry.path(), None)?,
+            }
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[async_trait(?Send)]
+impl Sensor for DirectorySize {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .device_class("data_size")
+            .state_class("measurement")
+            .unit("B")
+            .icon("mdi:folder-information")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        if let Some(running) = &mut self.running {
+            if let Some(result) = running.now_or_never() {
+                self.running = None;
+
+                let size =
+                    result.map_err(|error| anyhow!("Adding up size failed: {}", error))??;
+                readings.push(Reading::new(self.config.name.as_str(), size.to_string()));
+            }
+        }
+
+        let due = match self.last_check {
+            Some(last_check) => last_check.elapsed() >= self.config.interval,
+            None => true,
+        };
+
+        if due && self.running.is_none() {
+            self.last_check = Some(Instant::now());
+
+            let path = self.config.path.clone();
+            let max_depth = self.config.max_depth;
+            self.running = Some(task::spawn_blocking(move || {
+                directory_size(&path, max_depth)
+            }));
+        }
+
+        Ok(readings)
+    }
+}
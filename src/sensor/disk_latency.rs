@@ -0,0 +1,142 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::fs;
+
+/// What `/proc/diskstats` says about a disk.
+#[derive(Clone, Copy)]
+struct DiskStats {
+    reads: u64,
+    time_reading_ms: u64,
+    writes: u64,
+    time_writing_ms: u64,
+}
+
+impl DiskStats {
+    /// Parses a line like `8 0 sda 1234 56 78900 4321 567 89 12340 9876 0 5678 14197 ...`.
+    /// After the name come the reads completed, reads merged, sectors read and milliseconds
+    /// spent reading, and then the same four for writes.
+    fn parse(line: &str) -> Option<(&str, Self)> {
+        let mut fields = line.split_whitespace().skip(2);
+        let name = fields.next()?;
+        let mut fields = fields.map(|field| field.parse::<u64>().ok());
+
+        let reads = fields.next()??;
+        let time_reading_ms = fields.nth(2)??;
+        let writes = fields.next()??;
+        let time_writing_ms = fields.nth(2)??;
+
+        Some((
+            name,
+            Self {
+                reads,
+                time_reading_ms,
+                writes,
+                time_writing_ms,
+            },
+        ))
+    }
+}
+
+/// The average time reads and writes took on configured disks, between updates. A disk can be
+/// the reason a machine feels slow without being anywhere near full.
+pub struct DiskLatency {
+    disks: Vec<String>,
+
+    /// What each disk's stats were last time.
+    last_stats: HashMap<String, DiskStats>,
+}
+
+impl DiskLatency {
+    pub fn new(disks: Vec<String>) -> Self {
+        Self {
+            disks,
+            last_stats: HashMap::new(),
+        }
+    }
+}
+
+/// How long each operation took on average, in milliseconds. Without any operations, they took
+/// no time at all.
+fn average(time_ms: u64, operations: u64) -> f64 {
+    if operations > 0 {
+        time_ms as f64 / operations as f64
+    } else {
+        0.0
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for DiskLatency {
+    fn name(&self) -> &str {
+        "disk_latency"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for disk in self.disks.iter() {
+            for direction in ["read", "write"] {
+                entities.push(
+                    Entity::new("sensor", &format!("{}_{}_latency", disk, direction))
+                        .device_class("duration")
+                        .state_class("measurement")
+                        .unit("ms")
+                        .icon("mdi:harddisk"),
+                );
+            }
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let diskstats = fs::read_to_string("/proc/diskstats")
+            .await
+            .context("Failed to read /proc/diskstats.")?;
+        let stats: HashMap<&str, DiskStats> =
+            diskstats.lines().filter_map(DiskStats::parse).collect();
+
+        let mut readings = Vec::new();
+        for disk in self.disks.iter() {
+            let read_latency = format!("{}_read_latency", disk);
+            let write_latency = format!("{}_write_latency", disk);
+
+            let stats = match stats.get(disk.as_str()) {
+                Some(stats) => *stats,
+                // Unplugged, most likely.
+                None => {
+                    self.last_stats.remove(disk);
+                    readings.push(Reading::unavailable(read_latency));
+                    readings.push(Reading::unavailable(write_latency));
+                    continue;
+                }
+            };
+
+            // Latency is measured between updates, so there's nothing to report the first time.
+            // The counters start over when a disk is plugged back in.
+            if let Some(last) = self.last_stats.insert(disk.clone(), stats) {
+                readings.push(Reading::new(
+                    read_latency,
+                    average(
+                        stats.time_reading_ms.saturating_sub(last.time_reading_ms),
+                        stats.reads.saturating_sub(last.reads),
+                    )
+                    .to_string(),
+                ));
+                readings.push(Reading::new(
+                    write_latency,
+                    average(
+                        stats.time_writing_ms.saturating_sub(last.time_writing_ms),
+                        stats.writes.saturating_sub(last.writes),
+                    )
+                    .to_string(),
+                ));
+            }
+        }
+
+        Ok(readings)
+    }
+}
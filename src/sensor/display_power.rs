@@ -0,0 +1,97 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::process::ExitStatus;
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DisplayPowerConfig {
+    /// Turns the displays on. Commands are run with `sh -c`.
+    #[serde(default = "DisplayPowerConfig::default_on_command")]
+    pub on_command: String,
+
+    /// Turns the displays off.
+    #[serde(default = "DisplayPowerConfig::default_off_command")]
+    pub off_command: String,
+
+    /// Succeeds while the displays are on, and fails while they're off.
+    #[serde(default = "DisplayPowerConfig::default_state_command")]
+    pub state_command: String,
+}
+
+impl DisplayPowerConfig {
+    fn default_on_command() -> String {
+        String::from("wlopm --on '*'")
+    }
+
+    fn default_off_command() -> String {
+        String::from("wlopm --off '*'")
+    }
+
+    fn default_state_command() -> String {
+        String::from("wlopm | grep -q ' on$'")
+    }
+}
+
+/// A switch that turns the displays on and off, through DPMS or the compositor's output power
+/// control, and follows them when something else does.
+pub struct DisplayPower {
+    config: DisplayPowerConfig,
+}
+
+impl DisplayPower {
+    pub fn new(config: DisplayPowerConfig) -> Self {
+        Self { config }
+    }
+
+    async fn read_state(&self) -> Result<Reading> {
+        let on = run(&self.config.state_command).await?.success();
+
+        Ok(Reading::new("display_power", if on { "ON" } else { "OFF" }))
+    }
+}
+
+async fn run(command: &str) -> Result<ExitStatus> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .kill_on_drop(true)
+        .status()
+        .await
+        .with_context(|| format!("Failed to run `{}`.", command))
+}
+
+#[async_trait(?Send)]
+impl Sensor for DisplayPower {
+    fn name(&self) -> &str {
+        "display_power"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("switch", "display_power")
+            .state_class("")
+            .icon("mdi:monitor")
+            .accepts_commands(ActionClass::Desktop)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_state().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let command = match payload {
+            "ON" => &self.config.on_command,
+            "OFF" => &self.config.off_command,
+            payload => bail!("Unknown display power command `{}`.", payload),
+        };
+
+        let status = run(command).await?;
+        if !status.success() {
+            bail!("`{}` exited with {}.", command, status);
+        }
+
+        Ok(vec![self.read_state().await?])
+    }
+}
@@ -0,0 +1,91 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::fs;
+
+const DRM_CLASS: &str = "/sys/class/drm";
+
+/// Connectors for panels built into the machine, which don't count as external displays.
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+/// Whether we're docked, going by the displays plugged in.
+///
+/// Displays are read from the state of the DRM connectors. A USB-C dock shows up as a
+/// DisplayPort connector, so having any display other than the built-in panel connected
+/// counts as being docked.
+pub struct Displays;
+
+impl Displays {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The names of the connectors of every external display that's connected, such as `DP-1`.
+async fn external_displays() -> Result<Vec<String>> {
+    let mut displays = Vec::new();
+
+    let mut entries = fs::read_dir(DRM_CLASS)
+        .await
+        .context("Failed to list DRM devices.")?;
+    while let Some(entry) = entries.next_entry().await? {
+        // Connectors are named after their card, like `card0-DP-1`. The cards themselves have no status.
+        let file_name = entry.file_name();
+        let connector = match file_name.to_string_lossy().split_once('-') {
+            Some((card, connector)) if card.starts_with("card") => connector.to_string(),
+            _ => continue,
+        };
+
+        if INTERNAL_CONNECTORS
+            .iter()
+            .any(|internal| connector.starts_with(internal))
+        {
+            continue;
+        }
+
+        let status = match fs::read_to_string(entry.path().join("status")).await {
+            Ok(status) => status,
+            Err(_) => continue,
+        };
+
+        if status.trim() == "connected" {
+            displays.push(connector);
+        }
+    }
+
+    displays.sort();
+    Ok(displays)
+}
+
+#[async_trait(?Send)]
+impl Sensor for Displays {
+    fn name(&self) -> &str {
+        "displays"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("binary_sensor", "docked")
+                .device_class("connectivity")
+                .state_class("")
+                .icon("mdi:laptop"),
+            Entity::new("sensor", "external_displays")
+                .icon("mdi:monitor-multiple")
+                .json_attributes(),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let displays = external_displays().await?;
+
+        Ok(vec![
+            Reading::new("docked", if displays.is_empty() { "OFF" } else { "ON" }),
+            Reading::new("external_displays", displays.len().to_string()).attributes(
+                serde_json::to_string(&json!({ "connectors": displays }))
+                    .context("Failed to serialize display connectors.")?,
+            ),
+        ])
+    }
+}
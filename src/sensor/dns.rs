@@ -0,0 +1,165 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    time,
+};
+
+/// How long to wait for an answer before calling the lookup a failure.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DnsCheck {
+    /// The name the sensors will be reported as.
+    pub name: String,
+
+    /// The host name to look up.
+    pub host: String,
+
+    /// The DNS server to ask. If not set, the system's resolver is used.
+    pub resolver: Option<IpAddr>,
+}
+
+/// Whether host names can be looked up, and how long it takes.
+pub struct DnsSensor {
+    checks: Vec<DnsCheck>,
+}
+
+impl DnsSensor {
+    pub fn new(checks: Vec<DnsCheck>) -> Self {
+        Self { checks }
+    }
+}
+
+/// Asks a specific DNS server for the A record of a host.
+async fn query(resolver: IpAddr, host: &str) -> Result<()> {
+    let id = crate::random() as u16;
+
+    // A header asking for recursion, with one question.
+    let mut request = Vec::new();
+    request.extend_from_slice(&id.to_be_bytes());
+    request.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in host.trim_end_matches('.').split('.') {
+        ensure!(
+            !label.is_empty() && label.len() < 64,
+            "`{}` is not a valid host name.",
+            host
+        );
+        request.push(label.len() as u8);
+        request.extend_from_slice(label.as_bytes());
+    }
+    // The end of the name, then type A and class IN.
+    request.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x01]);
+
+    let bind_address: IpAddr = if resolver.is_ipv4() {
+        [0, 0, 0, 0].into()
+    } else {
+        [0u16; 8].into()
+    };
+    let socket = UdpSocket::bind((bind_address, 0)).await?;
+    socket.connect(SocketAddr::new(resolver, 53)).await?;
+    socket.send(&request).await?;
+
+    let mut response = [0; 512];
+    loop {
+        let length = socket.recv(&mut response).await?;
+        ensure!(length >= 12, "Response from DNS server is too short.");
+
+        // Anything else is a stray answer to some other question.
+        if response[0..2] == id.to_be_bytes() {
+            break;
+        }
+    }
+
+    let response_code = response[3] & 0x0F;
+    let answers = u16::from_be_bytes([response[6], response[7]]);
+    match response_code {
+        0 if answers > 0 => Ok(()),
+        0 => bail!("DNS server has no address for `{}`.", host),
+        3 => bail!("`{}` does not exist.", host),
+        code => bail!("DNS server failed with response code {}.", code),
+    }
+}
+
+async fn check(dns_check: &DnsCheck) -> Vec<Reading> {
+    let start = Instant::now();
+
+    let lookup = async {
+        match dns_check.resolver {
+            Some(resolver) => query(resolver, &dns_check.host).await,
+            None => {
+                let mut addresses = lookup_host((dns_check.host.as_str(), 0)).await?;
+                ensure!(
+                    addresses.next().is_some(),
+                    "No address for `{}`.",
+                    dns_check.host
+                );
+                Ok(())
+            }
+        }
+    };
+
+    let result = match time::timeout(LOOKUP_TIMEOUT, lookup).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("Timed out.")),
+    };
+
+    match result {
+        Ok(()) => vec![
+            Reading::new(dns_check.name.as_str(), "ON"),
+            Reading::new(
+                format!("{}_lookup_time", dns_check.name),
+                (start.elapsed().as_secs_f64() * 1000.0).to_string(),
+            ),
+        ],
+        Err(error) => {
+            log::info!("Looking up `{}` failed: {:?}", dns_check.host, error);
+            vec![Reading::new(dns_check.name.as_str(), "OFF")]
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for DnsSensor {
+    fn name(&self) -> &str {
+        "dns"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for dns_check in self.checks.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &dns_check.name)
+                    .device_class("connectivity")
+                    .state_class("")
+                    .icon("mdi:dns"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_lookup_time", dns_check.name))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("ms")
+                    .icon("mdi:timer-outline"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(join_all(self.checks.iter().map(check))
+            .await
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
@@ -0,0 +1,425 @@
+use super::{
+    mounts::{filesystem_stats, mount_table},
+    units::SizeUnit,
+    Reading, Sensor,
+};
+use crate::{
+    config::{DriveConfig, DriveDiscoveryConfig},
+    privileged::{Privileged, Request},
+    sink::Entity,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use sysinfo::{DiskExt, System, SystemExt};
+use tokio::fs;
+
+/// How full the configured filesystems are.
+pub struct DriveSensor {
+    /// Finds what's mounted. Reading a hung network filesystem can block forever, so this is read
+    /// from a blocking task. Linux tells us without asking the filesystems.
+    #[cfg(not(target_os = "linux"))]
+    system: Arc<Mutex<System>>,
+
+    /// Maps mount points to the names they are reported as.
+    drives: HashMap<PathBuf, String>,
+
+    /// Held while a filesystem is being asked how full it is, by mount point.
+    locks: HashMap<PathBuf, Arc<Mutex<()>>>,
+
+    /// Filesystems on disks that may spin down, along with the names they are reported as and the disk.
+    spinning: Vec<(PathBuf, String, String)>,
+
+    /// Filesystems to report a problem for when they're mounted read-only, along with the names
+    /// they are reported as.
+    read_only_checks: Vec<(PathBuf, String)>,
+
+    /// Filesystems to report whether they're encrypted, along with the names they are reported as.
+    encryption_checks: Vec<(PathBuf, String)>,
+
+    unit: SizeUnit,
+    privileged: Privileged,
+}
+
+impl DriveSensor {
+    pub fn new(
+        drives: &[DriveConfig],
+        discovery: Option<&DriveDiscoveryConfig>,
+        unit: SizeUnit,
+        privileged: Privileged,
+    ) -> Result<Self> {
+        let mut system = System::new();
+        system.refresh_disks_list();
+
+        let spinning = drives
+            .iter()
+            .filter_map(|drive_config| {
+                let device = drive_config.device.clone()?;
+                Some((drive_config.path.clone(), drive_config.name.clone(), device))
+            })
+            .collect();
+
+        let read_only_checks = drives
+            .iter()
+            .filter(|drive_config| drive_config.detect_read_only)
+            .map(|drive_config| (drive_config.path.clone(), drive_config.name.clone()))
+            .collect();
+
+        let encryption_checks = drives
+            .iter()
+            .filter(|drive_config| drive_config.detect_encryption)
+            .map(|drive_config| (drive_config.path.clone(), drive_config.name.clone()))
+            .collect();
+
+        let mut drives: HashMap<PathBuf, String> = drives
+            .iter()
+            .map(|drive_config| (drive_config.path.clone(), drive_config.name.clone()))
+            .collect();
+
+        if let Some(discovery) = discovery {
+            let filter = DiscoveryFilter::new(discovery);
+
+            for drive in system.disks() {
+                let file_system = String::from_utf8_lossy(drive.file_system());
+                let mount_point = drive.mount_point();
+
+                if !drives.contains_key(mount_point) && filter.accepts(&file_system, mount_point) {
+                    log::info!(
+                        "Found {} filesystem at `{}`.",
+                        file_system,
+                        mount_point.display()
+                    );
+                    drives.insert(mount_point.to_path_buf(), entity_name("drive", mount_point));
+                }
+            }
+        }
+
+        let locks = drives
+            .keys()
+            .map(|mount_point| (mount_point.clone(), Arc::new(Mutex::new(()))))
+            .collect();
+
+        Ok(Self {
+            #[cfg(not(target_os = "linux"))]
+            system: Arc::new(Mutex::new(system)),
+            drives,
+            locks,
+            spinning,
+            read_only_checks,
+            encryption_checks,
+            unit,
+            privileged,
+        })
+    }
+}
+
+impl DriveSensor {
+    /// Where filesystems are mounted right now.
+    #[cfg(target_os = "linux")]
+    async fn mount_points(&self) -> Result<Vec<PathBuf>> {
+        super::mounts::mount_points().await
+    }
+
+    /// Where filesystems are mounted right now.
+    #[cfg(not(target_os = "linux"))]
+    async fn mount_points(&self) -> Result<Vec<PathBuf>> {
+        let system = self.system.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<PathBuf>> {
+            // If the last collection is still stuck we'd just get stuck behind it.
+            let mut system = system.try_lock().map_err(|_| {
+                anyhow::anyhow!(
+                    "The previous collection has not finished. A filesystem may be hung."
+                )
+            })?;
+
+            // Filesystems come and go, so we look for them again every time.
+            system.refresh_disks_list();
+
+            Ok(system
+                .disks()
+                .iter()
+                .map(|drive| drive.mount_point().to_path_buf())
+                .collect())
+        })
+        .await?
+    }
+}
+
+/// Whether a disk is spun down. Asking doesn't wake it up.
+async fn in_standby(privileged: &Privileged, device: &str) -> Result<bool> {
+    // Prints something like `drive state is:  standby`, or `active/idle` when it's spinning.
+    let stdout = privileged
+        .run(Request::DriveStandby {
+            device: device.to_string(),
+        })
+        .await?;
+    let state = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("drive state is:"))
+        .with_context(|| format!("hdparm did not report the state of `{}`.", device))?;
+
+    Ok(matches!(state.trim(), "standby" | "sleeping"))
+}
+
+/// The name of the LUKS device a block device is on, such as `luks-1234abcd`, if it's on one.
+/// Encrypted devices are found by following the devices under each device mapper device, so
+/// filesystems on LVM on LUKS are found too.
+async fn luks_device(device: &str) -> Option<String> {
+    // Filesystems are mounted from links like `/dev/mapper/root`, which lead to the real device.
+    let device = fs::canonicalize(device).await.ok()?;
+    let mut devices = vec![device.file_name()?.to_string_lossy().into_owned()];
+
+    while let Some(device) = devices.pop() {
+        let sysfs = Path::new("/sys/class/block").join(&device);
+
+        // Devices set up by cryptsetup have UUIDs like `CRYPT-LUKS2-<uuid>-<name>`.
+        if let Ok(uuid) = fs::read_to_string(sysfs.join("dm/uuid")).await {
+            if uuid.starts_with("CRYPT-LUKS") {
+                return fs::read_to_string(sysfs.join("dm/name"))
+                    .await
+                    .ok()
+                    .map(|name| name.trim().to_string());
+            }
+        }
+
+        if let Ok(mut slaves) = fs::read_dir(sysfs.join("slaves")).await {
+            while let Ok(Some(slave)) = slaves.next_entry().await {
+                devices.push(slave.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    None
+}
+
+/// Decides which of the filesystems we found are worth reporting.
+pub(crate) struct DiscoveryFilter<'a> {
+    config: &'a DriveDiscoveryConfig,
+}
+
+impl<'a> DiscoveryFilter<'a> {
+    pub(crate) fn new(config: &'a DriveDiscoveryConfig) -> Self {
+        Self { config }
+    }
+
+    pub(crate) fn accepts(&self, file_system: &str, mount_point: &Path) -> bool {
+        let mount_point = mount_point.to_string_lossy();
+
+        (self.config.include_filesystems.is_empty()
+            || self
+                .config
+                .include_filesystems
+                .iter()
+                .any(|included| included == file_system))
+            && !self
+                .config
+                .exclude_filesystems
+                .iter()
+                .any(|excluded| excluded == file_system)
+            && (self.config.include_paths.is_empty()
+                || matches_any(&self.config.include_paths, &mount_point))
+            && !matches_any(&self.config.exclude_paths, &mount_point)
+    }
+}
+
+/// Whether the path matches any of the globs, where `*` matches anything and `?` matches any one character.
+fn matches_any(globs: &[String], path: &str) -> bool {
+    let path: Vec<char> = path.chars().collect();
+
+    globs.iter().any(|glob| {
+        let glob: Vec<char> = glob.chars().collect();
+        glob_matches(&glob, &path)
+    })
+}
+
+fn glob_matches(glob: &[char], path: &[char]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((&'*', rest)) => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+        Some((&expected, rest)) => match path.split_first() {
+            Some((&found, path)) => {
+                (expected == '?' || expected == found) && glob_matches(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+/// The name a filesystem we found is reported as, based on where it's mounted, such as `drive_mnt_data`.
+pub(crate) fn entity_name(prefix: &str, mount_point: &Path) -> String {
+    let mount_point: String = mount_point
+        .to_string_lossy()
+        .trim_matches('/')
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if mount_point.is_empty() {
+        format!("{}_root", prefix)
+    } else {
+        format!("{}_{}", prefix, mount_point)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for DriveSensor {
+    fn name(&self) -> &str {
+        "drives"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities: Vec<Entity> = self
+            .drives
+            .values()
+            .map(|name| {
+                Entity::new("sensor", name)
+                    .device_class(self.unit.device_class())
+                    .state_class("total")
+                    .unit(self.unit.symbol())
+                    .icon("mdi:folder")
+            })
+            .collect();
+
+        for (_, name, _) in self.spinning.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_power_state", name))
+                    .state_class("")
+                    .icon("mdi:harddisk"),
+            );
+        }
+
+        for (_, name) in self.read_only_checks.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &format!("{}_read_only", name))
+                    .device_class("problem")
+                    .state_class("")
+                    .icon("mdi:lock-alert"),
+            );
+        }
+
+        for (_, name) in self.encryption_checks.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &format!("{}_encrypted", name))
+                    .state_class("")
+                    .icon("mdi:shield-lock")
+                    .entity_category("diagnostic")
+                    .json_attributes(),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        let mounts = if self.read_only_checks.is_empty() && self.encryption_checks.is_empty() {
+            Vec::new()
+        } else {
+            mount_table().await?
+        };
+
+        // When something is mounted over another mount, the last one is what's seen.
+        let find_mount = |mount_point: &PathBuf| {
+            mounts
+                .iter()
+                .rev()
+                .find(|mount| &mount.mount_point == mount_point)
+        };
+
+        for (mount_point, name) in self.read_only_checks.iter() {
+            let entity = format!("{}_read_only", name);
+
+            readings.push(match find_mount(mount_point) {
+                Some(mount) => Reading::new(entity, if mount.read_only { "ON" } else { "OFF" }),
+                None => Reading::unavailable(entity),
+            });
+        }
+
+        for (mount_point, name) in self.encryption_checks.iter() {
+            let entity = format!("{}_encrypted", name);
+
+            readings.push(match find_mount(mount_point) {
+                Some(mount) => {
+                    let luks_device = luks_device(&mount.device).await;
+                    Reading::new(entity, if luks_device.is_some() { "ON" } else { "OFF" })
+                        .attributes(
+                            serde_json::json!({
+                                "device": mount.device,
+                                "luks_device": luks_device,
+                            })
+                            .to_string(),
+                        )
+                }
+                None => Reading::unavailable(entity),
+            });
+        }
+
+        // Filesystems on disks that are spun down are left alone until the disk wakes up for some other reason.
+        let mut sleeping = Vec::new();
+        for (mount_point, name, device) in self.spinning.iter() {
+            let standby = in_standby(&self.privileged, device).await?;
+            readings.push(Reading::new(
+                format!("{}_power_state", name),
+                if standby { "standby" } else { "active" },
+            ));
+
+            if standby {
+                sleeping.push(mount_point.clone());
+            }
+        }
+
+        let mount_points = self.mount_points().await?;
+        let unit = self.unit;
+
+        // Each filesystem is asked on its own, so a hung one only holds up itself.
+        readings.extend(
+            join_all(self.drives.iter().map(|(mount_point, drive_name)| {
+                let mounted = mount_points.contains(mount_point);
+                let asleep = sleeping.contains(mount_point);
+                let lock = self.locks[mount_point].clone();
+
+                async move {
+                    // Anything that isn't mounted right now is unavailable until it comes back.
+                    if !mounted {
+                        return Some(Reading::unavailable(drive_name.as_str()));
+                    }
+                    if asleep {
+                        return None;
+                    }
+
+                    Some(match filesystem_stats(mount_point, lock).await {
+                        Some(stats) => Reading::new(
+                            drive_name.as_str(),
+                            unit.format_bytes(
+                                stats.total_space() - stats.available_space(),
+                                stats.total_space(),
+                            ),
+                        ),
+                        None => {
+                            log::warn!("`{}` is not responding.", mount_point.display());
+                            Reading::unavailable(drive_name.as_str())
+                        }
+                    })
+                }
+            }))
+            .await
+            .into_iter()
+            .flatten(),
+        );
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,108 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Where the kernel lists memory controllers, as `mc0`, `mc1` and so on.
+const MEMORY_CONTROLLERS: &str = "/sys/devices/system/edac/mc";
+
+/// How many memory errors ECC has caught, from the kernel's EDAC drivers. Corrected errors
+/// did no harm, but a growing number of them is a sign memory is failing. Uncorrected errors
+/// mean data was lost.
+pub struct EccErrors {
+    /// The memory controllers' directories.
+    controllers: Vec<PathBuf>,
+}
+
+impl EccErrors {
+    pub fn new() -> Self {
+        Self {
+            controllers: Vec::new(),
+        }
+    }
+
+    async fn read_count(controller: &Path, file: &str) -> Result<u64> {
+        let path = controller.join(file);
+        fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read `{}`.", path.display()))?
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` is not a number.", path.display()))
+    }
+}
+
+impl Default for EccErrors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for EccErrors {
+    fn name(&self) -> &str {
+        "ecc_errors"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entries = fs::read_dir(MEMORY_CONTROLLERS).await.with_context(|| {
+            format!(
+                "Failed to read `{}`. Is there an EDAC driver for this memory controller?",
+                MEMORY_CONTROLLERS
+            )
+        })?;
+
+        self.controllers.clear();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name().to_string_lossy().starts_with("mc") {
+                self.controllers.push(entry.path());
+            }
+        }
+        self.controllers.sort();
+
+        if self.controllers.is_empty() {
+            bail!("No memory controllers with ECC were found.");
+        }
+
+        Ok(vec![
+            Entity::new("sensor", "ecc_corrected_errors")
+                .state_class("total_increasing")
+                .icon("mdi:memory")
+                .json_attributes(),
+            Entity::new("sensor", "ecc_uncorrected_errors")
+                .state_class("total_increasing")
+                .icon("mdi:memory")
+                .json_attributes(),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let (mut corrected, mut uncorrected) = (0, 0);
+        let (mut corrected_by_controller, mut uncorrected_by_controller) = (Map::new(), Map::new());
+
+        for controller in self.controllers.iter() {
+            let name = controller
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let count = Self::read_count(controller, "ce_count").await?;
+            corrected += count;
+            corrected_by_controller.insert(name.clone(), Value::from(count));
+
+            let count = Self::read_count(controller, "ue_count").await?;
+            uncorrected += count;
+            uncorrected_by_controller.insert(name, Value::from(count));
+        }
+
+        Ok(vec![
+            Reading::new("ecc_corrected_errors", corrected.to_string())
+                .attributes(json!({ "controllers": corrected_by_controller }).to_string()),
+            Reading::new("ecc_uncorrected_errors", uncorrected.to_string())
+                .attributes(json!({ "controllers": uncorrected_by_controller }).to_string()),
+        ])
+    }
+}
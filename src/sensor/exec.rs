@@ -0,0 +1,171 @@
+use super::{default_state_class, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+#[cfg(feature = "pattern-matching")]
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How the output of an exec sensor's command is turned into a value.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ParseMode {
+    /// Use the whole output, with surrounding whitespace trimmed.
+    #[serde(rename = "plain")]
+    Plain,
+
+    /// Parse the output as JSON and use the value found at this JSON pointer (e.g. `/sensors/0/temp`).
+    #[serde(rename = "json")]
+    Json(String),
+
+    /// Use the first capture group of this regular expression, or the whole match if it has no groups.
+    #[serde(rename = "regex")]
+    Regex(String),
+}
+
+impl Default for ParseMode {
+    fn default() -> Self {
+        Self::Plain
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExecSensorConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    /// The command to run. It is passed to `sh -c`.
+    pub command: String,
+
+    /// How to get the value out of the command's output.
+    #[serde(default)]
+    pub parse: ParseMode,
+
+    /// The unit of the value, if it has one.
+    pub unit: Option<String>,
+
+    /// The icon to show in Home Assistant.
+    pub icon: Option<String>,
+
+    /// How often to run the command. If not specified, it runs every update.
+    pub interval: Option<Duration>,
+}
+
+pub struct ExecSensor {
+    config: ExecSensorConfig,
+    #[cfg(feature = "pattern-matching")]
+    regex: Option<Regex>,
+    last_run: Option<Instant>,
+}
+
+impl ExecSensor {
+    pub fn new(config: ExecSensorConfig) -> Result<Self> {
+        #[cfg(not(feature = "pattern-matching"))]
+        if let ParseMode::Regex(_) = &config.parse {
+            bail!(
+                "Exec sensor `{}` parses its output with a regular expression, which this build was made without. Rebuild with the `pattern-matching` feature.",
+                config.name
+            );
+        }
+
+        #[cfg(feature = "pattern-matching")]
+        let regex = if let ParseMode::Regex(pattern) = &config.parse {
+            Some(Regex::new(pattern).with_context(|| {
+                format!(
+                    "Invalid regular expression for exec sensor `{}`.",
+                    config.name
+                )
+            })?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            config,
+            #[cfg(feature = "pattern-matching")]
+            regex,
+            last_run: None,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for ExecSensor {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .state_class(default_state_class(&self.config.unit))
+            .unit(self.config.unit.as_deref())
+            .icon(
+                self.config.icon.as_deref().unwrap_or("mdi:console"),
+            )])
+    }
+
+    /// Runs the command if it is due and returns the parsed value.
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let now = Instant::now();
+        if let (Some(last_run), Some(interval)) = (self.last_run, self.config.interval) {
+            if now.duration_since(last_run) < interval {
+                return Ok(Vec::new());
+            }
+        }
+        self.last_run = Some(now);
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run command.")?;
+
+        if !output.status.success() {
+            bail!(
+                "Command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stdout = stdout.trim();
+
+        let value = match &self.config.parse {
+            ParseMode::Plain => stdout.to_string(),
+            ParseMode::Json(pointer) => {
+                let document: serde_json::Value =
+                    serde_json::from_str(stdout).context("Command output is not valid JSON.")?;
+                match document
+                    .pointer(pointer)
+                    .with_context(|| format!("Nothing found at JSON pointer `{}`.", pointer))?
+                {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                }
+            }
+            #[cfg(feature = "pattern-matching")]
+            ParseMode::Regex(_) => {
+                let regex = self
+                    .regex
+                    .as_ref()
+                    .expect("Regex should have been compiled on construction.");
+                let captures = regex
+                    .captures(stdout)
+                    .context("Regular expression did not match command output.")?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|value| value.as_str().to_string())
+                    .unwrap_or_default()
+            }
+            #[cfg(not(feature = "pattern-matching"))]
+            ParseMode::Regex(_) => unreachable!("Exec sensors that parse with a regex aren't made without the `pattern-matching` feature."),
+        };
+
+        Ok(vec![Reading::new(self.config.name.as_str(), value)])
+    }
+}
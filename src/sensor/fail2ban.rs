@@ -0,0 +1,129 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Fail2banConfig {
+    /// The jails to report. If empty, every jail fail2ban has when we start is reported.
+    #[serde(default)]
+    pub jails: Vec<String>,
+}
+
+/// Runs `fail2ban-client` and returns what it printed.
+async fn fail2ban_client(args: &[&str]) -> Result<String> {
+    let output = Command::new("fail2ban-client")
+        .args(args)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run fail2ban-client.")?;
+
+    if !output.status.success() {
+        bail!(
+            "fail2ban-client exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Finds a field in the tree `fail2ban-client status` prints, such as `` `- Jail list:	sshd, nginx ``.
+fn status_field<'a>(status: &'a str, field: &str) -> Option<&'a str> {
+    status.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let name = name.trim_start_matches(|character: char| {
+            character.is_whitespace() || "|`-".contains(character)
+        });
+
+        (name == field).then(|| value.trim())
+    })
+}
+
+/// How many addresses fail2ban has banned in each jail.
+///
+/// This asks the fail2ban server through `fail2ban-client`, which needs access to its socket,
+/// usually meaning root.
+pub struct Fail2ban {
+    jails: Vec<String>,
+}
+
+impl Fail2ban {
+    pub fn new(config: Fail2banConfig) -> Self {
+        Self {
+            jails: config.jails,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Fail2ban {
+    fn name(&self) -> &str {
+        "fail2ban"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        if self.jails.is_empty() {
+            let status = fail2ban_client(&["status"]).await?;
+            let jails = status_field(&status, "Jail list")
+                .context("fail2ban-client did not list any jails.")?;
+
+            self.jails = jails
+                .split(',')
+                .map(str::trim)
+                .filter(|jail| !jail.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        let mut entities = Vec::new();
+
+        for jail in self.jails.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("fail2ban_{}_banned", jail))
+                    .state_class("measurement")
+                    .icon("mdi:shield-lock")
+                    .json_attributes(),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("fail2ban_{}_total_bans", jail))
+                    .state_class("total_increasing")
+                    .icon("mdi:shield-lock-outline"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        for jail in self.jails.iter() {
+            let status = fail2ban_client(&["status", jail]).await?;
+
+            let banned = status_field(&status, "Currently banned")
+                .with_context(|| format!("fail2ban did not report bans of jail `{}`.", jail))?;
+            let total_bans = status_field(&status, "Total banned")
+                .with_context(|| format!("fail2ban did not report bans of jail `{}`.", jail))?;
+            let addresses: Vec<_> = status_field(&status, "Banned IP list")
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect();
+
+            readings.push(
+                Reading::new(format!("fail2ban_{}_banned", jail), banned)
+                    .attributes(serde_json::json!({ "addresses": addresses }).to_string()),
+            );
+            readings.push(Reading::new(
+                format!("fail2ban_{}_total_bans", jail),
+                total_bans,
+            ));
+        }
+
+        Ok(readings)
+    }
+}
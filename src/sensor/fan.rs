@@ -0,0 +1,205 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::fs;
+
+const HWMON_CLASS: &str = "/sys/class/hwmon";
+
+/// The value of `pwmN_enable` that hands the fan to us.
+const MANUAL_CONTROL: &str = "1";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FanConfig {
+    /// The name the fan will be reported as.
+    pub name: String,
+
+    /// The name of the hwmon chip the fan is on, as found in `/sys/class/hwmon/*/name`, such as `nct6798`.
+    pub chip: String,
+
+    /// Which of the chip's PWM outputs drives the fan. `2` means `pwm2`.
+    pub pwm: u32,
+
+    /// The slowest the fan can be set to, in percent. Keeps a fan from being stopped by accident.
+    #[serde(default = "FanConfig::default_min_percent")]
+    pub min_percent: f64,
+
+    /// The fastest the fan can be set to, in percent.
+    #[serde(default = "FanConfig::default_max_percent")]
+    pub max_percent: f64,
+}
+
+impl FanConfig {
+    fn default_min_percent() -> f64 {
+        20.0
+    }
+
+    fn default_max_percent() -> f64 {
+        100.0
+    }
+}
+
+/// The speed a fan is driven at, in percent, which can also be set.
+///
+/// The fan is left to the chip until it's set for the first time. When we stop, it's
+/// handed back to whatever was controlling it before.
+pub struct Fan {
+    config: FanConfig,
+
+    /// The `pwmN` file of the fan.
+    pwm: Option<PathBuf>,
+
+    /// What `pwmN_enable` was before we took control of the fan, if we have.
+    original_mode: Option<String>,
+
+    privileged: Privileged,
+}
+
+impl Fan {
+    pub fn new(config: FanConfig, privileged: Privileged) -> Result<Self> {
+        ensure!(
+            0.0 <= config.min_percent
+                && config.min_percent <= config.max_percent
+                && config.max_percent <= 100.0,
+            "The speed range of fan `{}` must be within 0 to 100 percent.",
+            config.name
+        );
+
+        Ok(Self {
+            config,
+            pwm: None,
+            original_mode: None,
+            privileged,
+        })
+    }
+
+    fn pwm(&self) -> Result<&PathBuf> {
+        self.pwm.as_ref().context("Fan was never found.")
+    }
+
+    fn enable_path(&self) -> Result<PathBuf> {
+        let pwm = self.pwm()?;
+        Ok(pwm.with_file_name(format!("pwm{}_enable", self.config.pwm)))
+    }
+
+    /// Finds the hwmon chip with the configured name.
+    async fn find_chip(&self) -> Result<PathBuf> {
+        let mut entries = fs::read_dir(HWMON_CLASS)
+            .await
+            .context("Failed to list hwmon chips.")?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            if let Ok(name) = fs::read_to_string(entry.path().join("name")).await {
+                if name.trim() == self.config.chip {
+                    return Ok(entry.path());
+                }
+            }
+        }
+
+        bail!("There is no hwmon chip named `{}`.", self.config.chip)
+    }
+
+    async fn read_speed(&self) -> Result<Reading> {
+        let pwm = self.pwm()?;
+        let value: f64 = fs::read_to_string(pwm)
+            .await
+            .with_context(|| format!("Failed to read `{}`.", pwm.display()))?
+            .trim()
+            .parse()
+            .context("Fan PWM value is not a number.")?;
+
+        Ok(Reading::new(
+            self.config.name.as_str(),
+            (value / 255.0 * 100.0).round().to_string(),
+        ))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Fan {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let pwm = self
+            .find_chip()
+            .await?
+            .join(format!("pwm{}", self.config.pwm));
+        ensure!(
+            pwm.exists(),
+            "hwmon chip `{}` has no `pwm{}`.",
+            self.config.chip,
+            self.config.pwm
+        );
+        self.pwm = Some(pwm);
+
+        Ok(vec![Entity::new("number", &self.config.name)
+            .state_class("")
+            .unit("%")
+            .icon("mdi:fan")
+            .range(self.config.min_percent, self.config.max_percent, 1.0)
+            .accepts_commands(ActionClass::Hardware)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_speed().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let percentage: f64 = payload
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` is not a fan speed.", payload))?;
+        let percentage = percentage.clamp(self.config.min_percent, self.config.max_percent);
+        let value = (percentage / 100.0 * 255.0).round() as u8;
+
+        // The chip ignores what we write to the PWM output unless it's in manual mode.
+        if self.original_mode.is_none() {
+            let enable_path = self.enable_path()?;
+            let original_mode = fs::read_to_string(&enable_path)
+                .await
+                .with_context(|| format!("Failed to read `{}`.", enable_path.display()))?;
+
+            self.privileged
+                .run(Request::WriteSysfs {
+                    path: enable_path,
+                    value: MANUAL_CONTROL.to_string(),
+                })
+                .await
+                .context("Failed to take control of fan.")?;
+            self.original_mode = Some(original_mode.trim().to_string());
+        }
+
+        self.privileged
+            .run(Request::WriteSysfs {
+                path: self.pwm()?.clone(),
+                value: value.to_string(),
+            })
+            .await
+            .context("Failed to set fan speed.")?;
+
+        Ok(vec![self.read_speed().await?])
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        if let Some(original_mode) = self.original_mode.take() {
+            log::info!("Handing fan `{}` back to its chip.", self.config.name);
+
+            self.privileged
+                .run(Request::WriteSysfs {
+                    path: self.enable_path()?,
+                    value: original_mode,
+                })
+                .await
+                .context("Failed to hand fan back to its chip.")?;
+        }
+
+        Ok(())
+    }
+}
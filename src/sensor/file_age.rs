@@ -0,0 +1,65 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::SystemTime};
+use tokio::fs;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FileAgeConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    pub path: PathBuf,
+}
+
+/// How long ago a file was last modified, in seconds.
+/// If the file doesn't exist, the sensor is unavailable.
+pub struct FileAge {
+    config: FileAgeConfig,
+}
+
+impl FileAge {
+    pub fn new(config: FileAgeConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for FileAge {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .device_class("duration")
+            .state_class("measurement")
+            .unit("s")
+            .icon("mdi:file-clock")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let modified = fs::metadata(&self.config.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| {
+                format!(
+                    "Failed to read modification time of `{}`.",
+                    self.config.path.display()
+                )
+            })?;
+
+        // A file modified in the future is treated as brand new.
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default()
+            .as_secs();
+
+        Ok(vec![Reading::new(
+            self.config.name.as_str(),
+            age.to_string(),
+        )])
+    }
+}
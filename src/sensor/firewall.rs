@@ -0,0 +1,98 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::{io::ErrorKind, process::Output};
+use tokio::process::Command;
+
+/// Whether a firewall is active. firewalld, ufw and plain nftables are all checked, and any one
+/// of them being active counts.
+///
+/// Asking ufw and nftables needs root.
+pub struct Firewall;
+
+/// Runs a command, or returns `None` if it isn't installed.
+async fn run(program: &str, arguments: &[&str]) -> Result<Option<Output>> {
+    match Command::new(program)
+        .args(arguments)
+        .kill_on_drop(true)
+        .output()
+        .await
+    {
+        Ok(output) => Ok(Some(output)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("Failed to run {}.", program)),
+    }
+}
+
+async fn firewalld_running() -> Result<Option<bool>> {
+    // Exits with an error and prints `not running` when it isn't.
+    Ok(run("firewall-cmd", &["--state"]).await?.map(|output| {
+        output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "running"
+    }))
+}
+
+async fn ufw_active() -> Result<Option<bool>> {
+    match run("ufw", &["status"]).await? {
+        Some(output) if output.status.success() => Ok(Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.trim() == "Status: active"),
+        )),
+        Some(output) => bail!(
+            "ufw exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ),
+        None => Ok(None),
+    }
+}
+
+async fn nftables_active() -> Result<Option<bool>> {
+    match run("nft", &["list", "ruleset"]).await? {
+        // An empty ruleset lets everything through.
+        Some(output) if output.status.success() => Ok(Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.trim_start().starts_with("chain ")),
+        )),
+        Some(output) => bail!(
+            "nft exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ),
+        None => Ok(None),
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Firewall {
+    fn name(&self) -> &str {
+        "firewall"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("binary_sensor", "firewall")
+            .state_class("")
+            .icon("mdi:wall-fire")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let states = [
+            firewalld_running().await?,
+            ufw_active().await?,
+            nftables_active().await?,
+        ];
+
+        if states.iter().all(Option::is_none) {
+            bail!("None of firewalld, ufw or nftables are installed.");
+        }
+
+        let active = states.iter().any(|state| *state == Some(true));
+
+        Ok(vec![Reading::new(
+            "firewall",
+            if active { "ON" } else { "OFF" },
+        )])
+    }
+}
@@ -0,0 +1,181 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::Entity,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use zbus::{
+    dbus_proxy,
+    zvariant::{OwnedValue, Value},
+    Connection,
+};
+
+/// The flag fwupd sets on devices it can update.
+const UPDATABLE: u64 = 1 << 1;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.fwupd",
+    default_service = "org.freedesktop.fwupd",
+    default_path = "/"
+)]
+trait Fwupd {
+    fn get_devices(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+
+    /// The releases newer than what the device has, newest first.
+    fn get_upgrades(&self, device_id: &str) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FirmwareUpdatesConfig {
+    /// Also adds an update entity to Home Assistant, which shows the devices that have updates.
+    #[serde(default)]
+    pub update_entity: bool,
+}
+
+/// A device with newer firmware available.
+struct PendingUpdate {
+    name: String,
+    version: String,
+    latest_version: String,
+}
+
+fn string(properties: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match properties.get(key).map(|value| &**value) {
+        Some(Value::Str(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Devices with firmware updates available from fwupd, such as the UEFI firmware or an SSD.
+/// Only metadata fwupd already has is looked at, so it has to be refreshed by something else,
+/// such as `fwupd-refresh.timer`.
+pub struct FirmwareUpdates {
+    config: FirmwareUpdatesConfig,
+    connection: LazyConnection,
+}
+
+impl FirmwareUpdates {
+    pub fn new(config: FirmwareUpdatesConfig) -> Self {
+        Self {
+            config,
+            connection: LazyConnection::new(Bus::System),
+        }
+    }
+
+    async fn pending_updates(connection: &Connection) -> Result<Vec<PendingUpdate>> {
+        let proxy = FwupdProxy::new(connection).await?;
+        let devices = proxy
+            .get_devices()
+            .await
+            .context("Failed to list firmware devices.")?;
+
+        let mut updates = Vec::new();
+        for device in devices {
+            let updatable = matches!(
+                device.get("Flags").map(|value| &**value),
+                Some(Value::U64(flags)) if flags & UPDATABLE != 0
+            );
+            let id = match string(&device, "DeviceId") {
+                Some(id) if updatable => id,
+                _ => continue,
+            };
+
+            let releases = match proxy.get_upgrades(&id).await {
+                Ok(releases) => releases,
+                // This is how fwupd says the firmware is up to date, or that there's no
+                // metadata for the device.
+                Err(zbus::Error::MethodError(name, _, _))
+                    if name.as_str().ends_with(".NothingToDo")
+                        || name.as_str().ends_with(".NotSupported") =>
+                {
+                    continue
+                }
+                Err(error) => {
+                    return Err(error).context("Failed to check device for firmware updates.")
+                }
+            };
+
+            if let Some(latest) = releases.first() {
+                updates.push(PendingUpdate {
+                    name: string(&device, "Name").unwrap_or(id),
+                    version: string(&device, "Version").unwrap_or_default(),
+                    latest_version: string(latest, "Version").unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(updates)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for FirmwareUpdates {
+    fn name(&self) -> &str {
+        "firmware_updates"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = vec![Entity::new("sensor", "firmware_pending_updates")
+            .state_class("measurement")
+            .icon("mdi:chip")
+            .json_attributes()];
+
+        if self.config.update_entity {
+            entities.push(
+                Entity::new("update", "firmware_update")
+                    .state_class("")
+                    .icon("mdi:chip"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        let updates = Self::pending_updates(&connection).await?;
+
+        let names: Vec<&str> = updates.iter().map(|update| update.name.as_str()).collect();
+        let mut readings =
+            vec![
+                Reading::new("firmware_pending_updates", updates.len().to_string())
+                    .attributes(json!({ "devices": names }).to_string()),
+            ];
+
+        if self.config.update_entity {
+            // Each device has its own version, so the entity lists the devices instead. Home
+            // Assistant shows an update whenever the two versions differ.
+            let installed_version = "up to date";
+            let latest_version = if updates.is_empty() {
+                installed_version.to_string()
+            } else {
+                updates
+                    .iter()
+                    .map(|update| {
+                        format!(
+                            "{} ({} to {})",
+                            update.name, update.version, update.latest_version
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            readings.push(Reading::new(
+                "firmware_update",
+                json!({
+                    "installed_version": installed_version,
+                    "latest_version": latest_version,
+                    "in_progress": false,
+                })
+                .to_string(),
+            ));
+        }
+
+        Ok(readings)
+    }
+}
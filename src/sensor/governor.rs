@@ -0,0 +1,107 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+const SYS_CPU: &str = "/sys/devices/system/cpu";
+
+/// The cpufreq scaling governor, such as `performance` or `powersave`, which can also be set.
+///
+/// The governor of the first CPU is reported, and setting it sets it for every CPU.
+/// Setting it needs root.
+pub struct CpuGovernor {
+    /// The governors the kernel offers.
+    governors: Vec<String>,
+
+    privileged: Privileged,
+}
+
+impl CpuGovernor {
+    pub fn new(privileged: Privileged) -> Self {
+        Self {
+            governors: Vec::new(),
+            privileged,
+        }
+    }
+
+    fn cpufreq_path(file: &str) -> PathBuf {
+        Path::new(SYS_CPU).join("cpu0/cpufreq").join(file)
+    }
+
+    async fn read_governor() -> Result<Reading> {
+        let governor = fs::read_to_string(Self::cpufreq_path("scaling_governor"))
+            .await
+            .context("Failed to read CPU governor.")?;
+
+        Ok(Reading::new("cpu_governor", governor.trim()))
+    }
+}
+
+impl Default for CpuGovernor {
+    fn default() -> Self {
+        Self::new(Privileged::default())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for CpuGovernor {
+    fn name(&self) -> &str {
+        "cpu_governor"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        self.governors = fs::read_to_string(Self::cpufreq_path("scaling_available_governors"))
+            .await
+            .context(
+                "Failed to list CPU governors. The CPU frequency driver may not support them.",
+            )?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+
+        Ok(vec![Entity::new("select", "cpu_governor")
+            .state_class("")
+            .icon("mdi:speedometer")
+            .options(self.governors.clone())
+            .accepts_commands(ActionClass::Hardware)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![Self::read_governor().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        if !self.governors.iter().any(|governor| governor == payload) {
+            bail!("`{}` is not a CPU governor.", payload);
+        }
+
+        let mut entries = fs::read_dir(SYS_CPU).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            // Only the `cpuN` directories are CPUs.
+            let is_cpu = name
+                .strip_prefix("cpu")
+                .map_or(false, |number| number.parse::<u32>().is_ok());
+
+            let governor_path = entry.path().join("cpufreq/scaling_governor");
+            if is_cpu && governor_path.exists() {
+                self.privileged
+                    .run(Request::WriteSysfs {
+                        path: governor_path,
+                        value: payload.to_string(),
+                    })
+                    .await
+                    .with_context(|| format!("Failed to set CPU governor of `{}`.", name))?;
+            }
+        }
+
+        Ok(vec![Self::read_governor().await?])
+    }
+}
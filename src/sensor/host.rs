@@ -0,0 +1,103 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::{Duration, UNIX_EPOCH};
+use sysinfo::{CpuExt, System, SystemExt};
+
+/// Details about the host that don't change while we're running, such as the kernel version.
+///
+/// They're read once on startup and then republished every update, so they're there for
+/// Home Assistant after it restarts.
+pub struct HostInfo {
+    readings: Vec<(&'static str, String)>,
+}
+
+impl HostInfo {
+    pub fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu();
+        system.refresh_memory();
+
+        let mut readings = Vec::new();
+
+        if let Some(kernel_version) = system.kernel_version() {
+            readings.push(("kernel_version", kernel_version));
+        }
+
+        if let Some(os_name) = system.name() {
+            readings.push(("os_name", os_name));
+        }
+
+        if let Some(os_version) = system.os_version() {
+            readings.push(("os_version", os_version));
+        }
+
+        if let Some(cpu) = system.cpus().first() {
+            readings.push(("cpu_model", cpu.brand().trim().to_string()));
+        }
+
+        readings.push((
+            "cpu_cores",
+            system
+                .physical_core_count()
+                .unwrap_or_else(|| system.cpus().len())
+                .to_string(),
+        ));
+
+        readings.push(("total_memory", system.total_memory().to_string()));
+
+        let boot_time = UNIX_EPOCH + Duration::from_secs(system.boot_time());
+        readings.push((
+            "last_boot",
+            humantime::format_rfc3339_seconds(boot_time).to_string(),
+        ));
+
+        Self { readings }
+    }
+}
+
+impl Default for HostInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for HostInfo {
+    fn name(&self) -> &str {
+        "host"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .readings
+            .iter()
+            .map(|(name, _)| {
+                let entity = Entity::new("sensor", name).entity_category("diagnostic");
+
+                match *name {
+                    "cpu_cores" => entity.state_class("").icon("mdi:cpu-64-bit"),
+                    "total_memory" => entity
+                        .device_class("data_size")
+                        .state_class("")
+                        .unit("B")
+                        .icon("mdi:memory"),
+                    "last_boot" => entity
+                        .device_class("timestamp")
+                        .state_class("")
+                        .icon("mdi:restart"),
+                    _ => entity.state_class("").icon("mdi:information-outline"),
+                }
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(self
+            .readings
+            .iter()
+            .map(|(name, value)| Reading::new(*name, value.as_str()))
+            .collect())
+    }
+}
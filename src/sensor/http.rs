@@ -0,0 +1,133 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How long to wait for a response before calling the endpoint down.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HttpCheck {
+    /// The name the sensors will be reported as.
+    pub name: String,
+
+    pub url: Url,
+
+    /// The status code a healthy endpoint responds with.
+    #[serde(default = "HttpCheck::default_expected_status")]
+    pub expected_status: u16,
+
+    /// If set, the response body must contain this to be healthy.
+    pub contains: Option<String>,
+}
+
+impl HttpCheck {
+    fn default_expected_status() -> u16 {
+        200
+    }
+}
+
+/// Whether HTTP endpoints are healthy, and how long they take to respond.
+pub struct HttpSensor {
+    checks: Vec<HttpCheck>,
+    client: reqwest::Client,
+}
+
+impl HttpSensor {
+    pub fn new(checks: Vec<HttpCheck>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client.")?;
+
+        Ok(Self { checks, client })
+    }
+}
+
+async fn request(client: &reqwest::Client, http_check: &HttpCheck) -> Result<()> {
+    let response = client.get(http_check.url.clone()).send().await?;
+
+    let status = response.status().as_u16();
+    ensure!(
+        status == http_check.expected_status,
+        "Expected status {}, but got {}.",
+        http_check.expected_status,
+        status
+    );
+
+    if let Some(contains) = &http_check.contains {
+        let body = response.text().await?;
+        ensure!(
+            body.contains(contains.as_str()),
+            "Response does not contain `{}`.",
+            contains
+        );
+    }
+
+    Ok(())
+}
+
+async fn check(client: &reqwest::Client, http_check: &HttpCheck) -> Vec<Reading> {
+    let start = Instant::now();
+
+    match request(client, http_check).await {
+        Ok(()) => vec![
+            Reading::new(http_check.name.as_str(), "ON"),
+            Reading::new(
+                format!("{}_response_time", http_check.name),
+                (start.elapsed().as_secs_f64() * 1000.0).to_string(),
+            ),
+        ],
+        Err(error) => {
+            log::info!("Health check of `{}` failed: {:?}", http_check.url, error);
+            vec![Reading::new(http_check.name.as_str(), "OFF")]
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for HttpSensor {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for http_check in self.checks.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &http_check.name)
+                    .device_class("connectivity")
+                    .state_class("")
+                    .icon("mdi:web-check"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_response_time", http_check.name))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("ms")
+                    .icon("mdi:timer-outline"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let client = &self.client;
+
+        Ok(join_all(
+            self.checks
+                .iter()
+                .map(|http_check| check(client, http_check)),
+        )
+        .await
+        .into_iter()
+        .flatten()
+        .collect())
+    }
+}
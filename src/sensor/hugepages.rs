@@ -0,0 +1,95 @@
+use super::{parse_meminfo, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::fs;
+
+/// Where the mode transparent hugepages are in is shown, like `always [madvise] never`.
+const THP_MODE_PATH: &str = "/sys/kernel/mm/transparent_hugepage/enabled";
+
+/// How the hugepages set aside up front are being used, and how much memory has been backed by
+/// transparent hugepages.
+pub struct HugepagesSensor;
+
+#[async_trait(?Send)]
+impl Sensor for HugepagesSensor {
+    fn name(&self) -> &str {
+        "hugepages"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "hugepages_total")
+                .state_class("measurement")
+                .icon("mdi:memory"),
+            Entity::new("sensor", "hugepages_free")
+                .state_class("measurement")
+                .icon("mdi:memory"),
+            Entity::new("sensor", "hugepages_used")
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:memory")
+                .json_attributes(),
+            Entity::new("sensor", "transparent_hugepages")
+                .device_class("data_size")
+                .state_class("measurement")
+                .unit("MiB")
+                .icon("mdi:memory")
+                .json_attributes(),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let meminfo = fs::read_to_string("/proc/meminfo")
+            .await
+            .context("Failed to read /proc/meminfo.")?;
+        let fields = parse_meminfo(&meminfo);
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .copied()
+                .with_context(|| format!("/proc/meminfo does not report {}.", name))
+        };
+
+        let total = field("HugePages_Total")?;
+        let free = field("HugePages_Free")?;
+        let used = if total > 0 {
+            (total - free) as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        // Only shown when transparent hugepages are built into the kernel.
+        let thp_mode = fs::read_to_string(THP_MODE_PATH)
+            .await
+            .ok()
+            .and_then(|modes| {
+                modes.split_whitespace().find_map(|mode| {
+                    mode.strip_prefix('[')?
+                        .strip_suffix(']')
+                        .map(str::to_string)
+                })
+            });
+
+        Ok(vec![
+            Reading::new("hugepages_total", total.to_string()),
+            Reading::new("hugepages_free", free.to_string()),
+            Reading::new("hugepages_used", used.to_string()).attributes(
+                json!({
+                    "page_size_kib": field("Hugepagesize")?,
+                    // Promised to a mapping, but not faulted in yet.
+                    "reserved": field("HugePages_Rsvd")?,
+                    // Allocated beyond the total, when overcommitting is allowed.
+                    "surplus": field("HugePages_Surp")?,
+                })
+                .to_string(),
+            ),
+            Reading::new(
+                "transparent_hugepages",
+                (field("AnonHugePages")? as f64 / 1024.0).to_string(),
+            )
+            .attributes(json!({ "mode": thp_mode }).to_string()),
+        ])
+    }
+}
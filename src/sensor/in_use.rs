@@ -0,0 +1,92 @@
+use super::{volume::pactl, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio::fs;
+
+/// Whether the camera, microphone or speakers are being used by anything.
+///
+/// Only the processes system-mqtt is allowed to look into are checked for the camera, so it
+/// needs to run as root or as the user that's logged into the desktop.
+pub struct InUseSensor;
+
+/// Whether any process has a video device open.
+async fn camera_in_use() -> Result<bool> {
+    let mut processes = fs::read_dir("/proc")
+        .await
+        .context("Failed to list processes.")?;
+
+    while let Some(process) = processes.next_entry().await? {
+        // Processes come and go, and we can't look at the ones we don't own. Either way, skip them.
+        let mut descriptors = match fs::read_dir(process.path().join("fd")).await {
+            Ok(descriptors) => descriptors,
+            Err(_) => continue,
+        };
+
+        while let Ok(Some(descriptor)) = descriptors.next_entry().await {
+            if let Ok(target) = fs::read_link(descriptor.path()).await {
+                if target.to_string_lossy().starts_with("/dev/video") {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Whether any audio source, other than the monitors of outputs, is being recorded from.
+async fn microphone_in_use() -> Result<bool> {
+    let output = pactl(&["list", "short", "sources"]).await?;
+
+    Ok(any_running(&output, |name| !name.ends_with(".monitor")))
+}
+
+/// Whether any audio output is playing something.
+async fn audio_playing() -> Result<bool> {
+    let output = pactl(&["list", "short", "sinks"]).await?;
+
+    Ok(any_running(&output, |_| true))
+}
+
+/// Whether any of the devices listed by `pactl list short` that `filter` accepts the name of are running.
+fn any_running(output: &str, filter: impl Fn(&str) -> bool) -> bool {
+    // Each line looks like `55	alsa_input.pci-0000_00_1f.3.analog-stereo	PipeWire	s32le 2ch 48000Hz	RUNNING`.
+    output.lines().any(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        matches!(fields.as_slice(), [_, name, .., state] if filter(name) && *state == "RUNNING")
+    })
+}
+
+#[async_trait(?Send)]
+impl Sensor for InUseSensor {
+    fn name(&self) -> &str {
+        "in_use"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("binary_sensor", "camera_in_use")
+                .state_class("")
+                .icon("mdi:webcam"),
+            Entity::new("binary_sensor", "microphone_in_use")
+                .state_class("")
+                .icon("mdi:microphone"),
+            Entity::new("binary_sensor", "audio_playing")
+                .device_class("sound")
+                .state_class("")
+                .icon("mdi:speaker"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let (camera, microphone, playing) =
+            futures::join!(camera_in_use(), microphone_in_use(), audio_playing());
+
+        Ok(vec![
+            Reading::new("camera_in_use", if camera? { "ON" } else { "OFF" }),
+            Reading::new("microphone_in_use", if microphone? { "ON" } else { "OFF" }),
+            Reading::new("audio_playing", if playing? { "ON" } else { "OFF" }),
+        ])
+    }
+}
@@ -0,0 +1,136 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use zbus::{dbus_proxy, zvariant::OwnedFd};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    /// What each inhibitor holds off, who took it, why, its mode, and the user ID and process ID
+    /// that took it.
+    fn list_inhibitors(&self) -> zbus::Result<Vec<(String, String, String, String, u32, u32)>>;
+}
+
+/// A switch that keeps the system from going to sleep, on its own or when idle, while it's on,
+/// and a sensor counting what else is keeping it awake.
+pub struct SleepInhibitor {
+    connection: LazyConnection,
+
+    /// logind keeps the system awake for as long as this is open.
+    inhibitor: Option<OwnedFd>,
+}
+
+impl SleepInhibitor {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::System),
+            inhibitor: None,
+        }
+    }
+
+    fn read_switch(&self) -> Reading {
+        Reading::new(
+            "inhibit_sleep",
+            if self.inhibitor.is_some() {
+                "ON"
+            } else {
+                "OFF"
+            },
+        )
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SleepInhibitor {
+    fn name(&self) -> &str {
+        "sleep_inhibitor"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("switch", "inhibit_sleep")
+                .state_class("")
+                .icon("mdi:sleep-off")
+                .accepts_commands(ActionClass::Desktop),
+            Entity::new("sensor", "sleep_inhibitors")
+                .state_class("measurement")
+                .icon("mdi:sleep-off")
+                .json_attributes(),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+        let inhibitors = manager
+            .list_inhibitors()
+            .await
+            .context("Failed to list inhibitors.")?;
+
+        // Delay inhibitors, like the one sleep detection takes, only hold off sleep for a moment.
+        let blocking = inhibitors
+            .iter()
+            .filter(|(_, _, _, mode, _, _)| mode == "block")
+            .count();
+        let attributes = json!({
+            "inhibitors": inhibitors
+                .iter()
+                .map(|(what, who, why, mode, _, pid)| json!({
+                    "what": what,
+                    "who": who,
+                    "why": why,
+                    "mode": mode,
+                    "pid": pid,
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        Ok(vec![
+            self.read_switch(),
+            Reading::new("sleep_inhibitors", blocking.to_string())
+                .attributes(attributes.to_string()),
+        ])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        match payload {
+            "ON" => {
+                if self.inhibitor.is_none() {
+                    let connection = self.connection.get().await?;
+                    let manager = ManagerProxy::new(&connection).await?;
+
+                    log::info!("Keeping the system awake.");
+                    self.inhibitor = Some(
+                        manager
+                            .inhibit(
+                                "sleep:idle",
+                                "system-mqtt",
+                                "Asked to by Home Assistant",
+                                "block",
+                            )
+                            .await
+                            .context("Failed to keep the system awake.")?,
+                    );
+                }
+            }
+            "OFF" => {
+                if self.inhibitor.take().is_some() {
+                    log::info!("No longer keeping the system awake.");
+                }
+            }
+            payload => bail!("Unknown sleep inhibitor command `{}`.", payload),
+        }
+
+        Ok(vec![self.read_switch()])
+    }
+}
@@ -0,0 +1,156 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::Entity,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct IpmiConfig {
+    /// Extra arguments for ipmitool, such as the ones to reach a remote BMC.
+    #[serde(default)]
+    pub arguments: Vec<String>,
+
+    /// The names of the sensors to report, as ipmitool shows them. If empty, every sensor is reported.
+    #[serde(default)]
+    pub sensors: Vec<String>,
+}
+
+/// A sensor as listed by `ipmitool -c sdr list`.
+struct SdrReading {
+    name: String,
+    value: String,
+    unit: String,
+}
+
+impl SdrReading {
+    /// The unit Home Assistant knows it by, and its device class.
+    fn unit(&self) -> (Option<&'static str>, Option<&'static str>) {
+        match self.unit.as_str() {
+            "degrees C" => (Some("°C"), Some("temperature")),
+            "Volts" => (Some("V"), Some("voltage")),
+            "Amps" => (Some("A"), Some("current")),
+            "Watts" => (Some("W"), Some("power")),
+            "RPM" => (Some("RPM"), None),
+            "percent" => (Some("%"), None),
+            _ => (None, None),
+        }
+    }
+}
+
+/// Temperatures, fans, power supplies and the like, as read from a server's BMC through ipmitool.
+///
+/// Talking to the local BMC needs root.
+pub struct IpmiSensor {
+    config: IpmiConfig,
+
+    /// Maps the names of IPMI sensors to the names of their entities.
+    sensors: HashMap<String, String>,
+
+    privileged: Privileged,
+}
+
+impl IpmiSensor {
+    pub fn new(config: IpmiConfig, privileged: Privileged) -> Self {
+        Self {
+            config,
+            sensors: HashMap::new(),
+            privileged,
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<SdrReading>> {
+        let output = self
+            .privileged
+            .run(Request::IpmiSdrList {
+                arguments: self.config.arguments.clone(),
+            })
+            .await?;
+
+        // Each line looks like `CPU1 Temp,45,degrees C,ok`. Sensors that only have a state,
+        // such as power supplies, look like `PS1 Status,0x01,discrete,ok`, and we report their state instead.
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                match fields.as_slice() {
+                    [name, value, unit, status, ..] => Some(SdrReading {
+                        name: name.trim().to_string(),
+                        value: if *unit == "discrete" {
+                            status.trim().to_string()
+                        } else {
+                            value.trim().to_string()
+                        },
+                        unit: unit.trim().to_string(),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Entity names can't have everything a sensor name can.
+fn entity_name(sensor: &str) -> String {
+    let sensor: String = sensor
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("ipmi_{}", sensor)
+}
+
+#[async_trait(?Send)]
+impl Sensor for IpmiSensor {
+    fn name(&self) -> &str {
+        "ipmi"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for reading in self.list().await? {
+            if !self.config.sensors.is_empty() && !self.config.sensors.contains(&reading.name) {
+                continue;
+            }
+
+            let entity_name = entity_name(&reading.name);
+            let (unit, device_class) = reading.unit();
+
+            entities.push(
+                Entity::new("sensor", &entity_name)
+                    .device_class(device_class)
+                    .state_class(if unit.is_some() { "measurement" } else { "" })
+                    .unit(unit)
+                    .icon("mdi:server"),
+            );
+
+            self.sensors.insert(reading.name, entity_name);
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            // Sensors with no reading, such as an empty CPU socket, say `na`.
+            .filter(|reading| reading.value != "na")
+            .filter_map(|reading| {
+                let entity_name = self.sensors.get(&reading.name)?;
+                Some(Reading::new(entity_name.as_str(), reading.value))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,115 @@
+use super::{log_match::read_journal, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Home Assistant won't take states longer than this.
+const MAX_STATE_LENGTH: usize = 255;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KernelErrorsConfig {
+    /// Regular expressions for the messages to count, such as `I/O error` or `USB disconnect`.
+    /// If there are none, every message at warning level or above is counted.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+/// How many messages at warning level or above the kernel has logged since we started, and the
+/// last of them. Failing hardware almost always shows up here first.
+///
+/// This reads the kernel's messages from the journal, so system-mqtt needs to be allowed to read
+/// the system journal (root, or members of the `systemd-journal` group).
+pub struct KernelErrors {
+    patterns: Vec<Regex>,
+
+    /// Where we got to in the journal. Until we know, we have nothing to count from.
+    cursor: Option<String>,
+
+    errors: u64,
+}
+
+impl KernelErrors {
+    pub fn new(config: KernelErrorsConfig) -> Result<Self> {
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).with_context(|| {
+                    format!(
+                        "Invalid regular expression `{}` for kernel errors.",
+                        pattern
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            patterns,
+            cursor: None,
+            errors: 0,
+        })
+    }
+
+    fn is_match(&self, message: &str) -> bool {
+        self.patterns.is_empty()
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(message))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for KernelErrors {
+    fn name(&self) -> &str {
+        "kernel_errors"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "kernel_errors")
+                .state_class("total_increasing")
+                .icon("mdi:alert-octagon"),
+            Entity::new("sensor", "kernel_last_error")
+                .state_class("")
+                .icon("mdi:alert-octagon"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let first = self.cursor.is_none();
+        let (messages, cursor) =
+            read_journal(&["--dmesg", "--priority=warning"], self.cursor.as_deref()).await?;
+
+        // With nothing new, there's no new cursor either, so we keep the old one.
+        if let Some(cursor) = cursor {
+            self.cursor = Some(cursor);
+        }
+
+        if first {
+            return Ok(vec![Reading::new("kernel_errors", "0")]);
+        }
+
+        let mut readings = Vec::new();
+        let mut last_error = None;
+        for message in messages.lines().filter(|message| self.is_match(message)) {
+            self.errors += 1;
+            last_error = Some(message);
+        }
+
+        readings.push(Reading::new("kernel_errors", self.errors.to_string()));
+        if let Some(last_error) = last_error {
+            readings.push(Reading::new(
+                "kernel_last_error",
+                last_error
+                    .chars()
+                    .take(MAX_STATE_LENGTH)
+                    .collect::<String>(),
+            ));
+        }
+
+        Ok(readings)
+    }
+}
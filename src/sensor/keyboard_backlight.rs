@@ -0,0 +1,130 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::fs;
+
+const LEDS_CLASS: &str = "/sys/class/leds";
+
+/// What Home Assistant sends, and what we report back, such as `{"state": "ON", "brightness": 2}`.
+#[derive(Serialize, Deserialize)]
+struct LightState {
+    state: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    brightness: Option<u64>,
+}
+
+/// The keyboard's backlight, as a light that can be turned on, off and dimmed.
+pub struct KeyboardBacklight {
+    /// The device's directory under `/sys/class/leds`.
+    device: PathBuf,
+    max_brightness: u64,
+
+    /// What the brightness was the last time the backlight was on, so turning it back on
+    /// returns to it.
+    last_brightness: u64,
+
+    privileged: Privileged,
+}
+
+impl KeyboardBacklight {
+    /// Uses the first keyboard backlight found, such as `tpacpi::kbd_backlight`.
+    pub fn new(privileged: Privileged) -> Result<Self> {
+        let device = std::fs::read_dir(LEDS_CLASS)
+            .context("Failed to list LEDs.")?
+            .flatten()
+            .map(|led| led.path())
+            .find(|led| {
+                led.file_name().map_or(false, |name| {
+                    name.to_string_lossy().ends_with("kbd_backlight")
+                })
+            })
+            .context("There is no keyboard backlight.")?;
+
+        // This never changes, so it's only read once.
+        let max_brightness_path = device.join("max_brightness");
+        let max_brightness = std::fs::read_to_string(&max_brightness_path)
+            .with_context(|| format!("Failed to read `{}`.", max_brightness_path.display()))?
+            .trim()
+            .parse()
+            .context("Maximum brightness is not a number.")?;
+
+        Ok(Self {
+            device,
+            max_brightness,
+            last_brightness: max_brightness,
+            privileged,
+        })
+    }
+
+    async fn read_state(&mut self) -> Result<Reading> {
+        let path = self.device.join("brightness");
+        let brightness: u64 = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read `{}`.", path.display()))?
+            .trim()
+            .parse()
+            .context("Brightness is not a number.")?;
+        if brightness > 0 {
+            self.last_brightness = brightness;
+        }
+
+        let state = LightState {
+            state: String::from(if brightness > 0 { "ON" } else { "OFF" }),
+            brightness: Some(brightness),
+        };
+
+        Ok(Reading::new(
+            "keyboard_backlight",
+            serde_json::to_string(&state)?,
+        ))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for KeyboardBacklight {
+    fn name(&self) -> &str {
+        "keyboard_backlight"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("light", "keyboard_backlight")
+            .state_class("")
+            .icon("mdi:keyboard")
+            .brightness_scale(self.max_brightness)
+            .accepts_commands(ActionClass::Desktop)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_state().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let command: LightState = serde_json::from_str(payload)
+            .with_context(|| format!("`{}` is not a light command.", payload))?;
+
+        let brightness = match command.state.as_str() {
+            "OFF" => 0,
+            _ => command
+                .brightness
+                .unwrap_or(self.last_brightness)
+                .min(self.max_brightness),
+        };
+
+        self.privileged
+            .run(Request::WriteSysfs {
+                path: self.device.join("brightness"),
+                value: brightness.to_string(),
+            })
+            .await
+            .context("Failed to set the keyboard backlight.")?;
+
+        Ok(vec![self.read_state().await?])
+    }
+}
@@ -0,0 +1,226 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LibvirtConfig {
+    /// Which libvirt daemon to connect to.
+    #[serde(default = "LibvirtConfig::default_uri")]
+    pub uri: String,
+
+    /// The virtual machines to report on. If empty, every virtual machine defined when we start is reported.
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+impl LibvirtConfig {
+    fn default_uri() -> String {
+        String::from("qemu:///system")
+    }
+}
+
+/// The state, CPU and memory usage of libvirt virtual machines.
+///
+/// This goes through `virsh`, which talks to libvirt over its socket.
+pub struct LibvirtSensor {
+    config: LibvirtConfig,
+
+    /// Maps virtual machine names to the names of their entities.
+    domains: HashMap<String, String>,
+
+    /// The CPU time each virtual machine had used in nanoseconds, and when it was read.
+    last_cpu_times: HashMap<String, (Instant, u64)>,
+}
+
+impl LibvirtSensor {
+    pub fn new(config: LibvirtConfig) -> Self {
+        Self {
+            config,
+            domains: HashMap::new(),
+            last_cpu_times: HashMap::new(),
+        }
+    }
+
+    async fn virsh(&self, arguments: &[&str]) -> Result<String> {
+        let output = Command::new("virsh")
+            .arg("--connect")
+            .arg(&self.config.uri)
+            .args(arguments)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run virsh.")?;
+
+        if !output.status.success() {
+            bail!(
+                "virsh exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Entity names can't have everything a virtual machine name can.
+fn entity_name(domain: &str) -> String {
+    let domain: String = domain
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("vm_{}", domain)
+}
+
+fn state_name(state: &str) -> &'static str {
+    match state {
+        "1" => "running",
+        "2" => "blocked",
+        "3" => "paused",
+        "4" => "shutting down",
+        "5" => "shut off",
+        "6" => "crashed",
+        "7" => "suspended",
+        _ => "unknown",
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for LibvirtSensor {
+    fn name(&self) -> &str {
+        "libvirt"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let domains = if self.config.domains.is_empty() {
+            self.virsh(&["list", "--all", "--name"])
+                .await?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()
+        } else {
+            self.config.domains.clone()
+        };
+
+        self.domains = domains
+            .into_iter()
+            .map(|domain| {
+                let entity_name = entity_name(&domain);
+                (domain, entity_name)
+            })
+            .collect();
+
+        let mut entities = Vec::new();
+        for entity_name in self.domains.values() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_state", entity_name))
+                    .state_class("")
+                    .icon("mdi:server"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_cpu", entity_name))
+                    .state_class("measurement")
+                    .unit("%")
+                    .icon("mdi:gauge"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_memory", entity_name))
+                    .device_class("data_size")
+                    .state_class("measurement")
+                    .unit("MiB")
+                    .icon("mdi:memory"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let now = Instant::now();
+
+        // Looks like a `Domain: 'name'` line for each virtual machine, followed by `key=value` lines.
+        let output = self
+            .virsh(&["domstats", "--state", "--cpu-total", "--vcpu", "--balloon"])
+            .await?;
+
+        let mut statistics: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+        let mut current = None;
+        for line in output.lines().map(str::trim) {
+            if let Some(domain) = line.strip_prefix("Domain: ") {
+                current = Some(statistics.entry(domain.trim_matches('\'')).or_default());
+            } else if let (Some(current), Some((key, value))) = (&mut current, line.split_once('='))
+            {
+                current.insert(key, value);
+            }
+        }
+
+        let mut readings = Vec::new();
+        for (domain, entity_name) in self.domains.iter() {
+            let statistics = match statistics.get(domain.as_str()) {
+                Some(statistics) => statistics,
+                None => {
+                    // It was removed since we started.
+                    readings.push(Reading::new(format!("{}_state", entity_name), "missing"));
+                    continue;
+                }
+            };
+
+            let state = statistics.get("state.state").copied().unwrap_or_default();
+            readings.push(Reading::new(
+                format!("{}_state", entity_name),
+                state_name(state),
+            ));
+
+            if let Some(memory) = statistics.get("balloon.current") {
+                let memory: u64 = memory.parse().context("Memory usage is not a number.")?;
+                readings.push(Reading::new(
+                    format!("{}_memory", entity_name),
+                    (memory / 1024).to_string(),
+                ));
+            }
+
+            // CPU usage is measured between updates, and is relative to the virtual machine's own CPUs.
+            if let (Some(cpu_time), Some(vcpus)) =
+                (statistics.get("cpu.time"), statistics.get("vcpu.current"))
+            {
+                let cpu_time: u64 = cpu_time.parse().context("CPU time is not a number.")?;
+                let vcpus: u64 = vcpus.parse().context("CPU count is not a number.")?;
+
+                if let Some((last_time, last_cpu_time)) =
+                    self.last_cpu_times.insert(domain.clone(), (now, cpu_time))
+                {
+                    let elapsed = now.duration_since(last_time);
+                    if elapsed > Duration::ZERO && vcpus > 0 {
+                        let usage = cpu_time.saturating_sub(last_cpu_time) as f64
+                            / (elapsed.as_nanos() as f64 * vcpus as f64);
+                        readings.push(Reading::new(
+                            format!("{}_cpu", entity_name),
+                            (usage.clamp(0.0, 1.0) * 100.0).to_string(),
+                        ));
+                    }
+                }
+            } else {
+                // A virtual machine that isn't running has no CPU statistics.
+                self.last_cpu_times.remove(domain);
+            }
+        }
+
+        Ok(readings)
+    }
+}
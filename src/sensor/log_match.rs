@@ -0,0 +1,211 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{io::SeekFrom, path::PathBuf};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    process::Command,
+};
+
+/// Where log lines come from.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum LogSource {
+    /// A log file. If it's rotated or truncated, we start over from its beginning.
+    #[serde(rename = "file")]
+    File(PathBuf),
+
+    /// The journal of a systemd unit, such as `nginx.service`.
+    #[serde(rename = "journal")]
+    Journal(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LogMatchConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    pub source: LogSource,
+
+    /// A regular expression. Each line it matches is counted.
+    pub pattern: String,
+}
+
+/// Where we got to in the log last time.
+enum Position {
+    /// How far into the file we've read, in bytes.
+    File(u64),
+
+    /// The journal's cursor.
+    Journal(String),
+}
+
+/// How many new lines in a log match a pattern, counted each update.
+///
+/// Lines logged before we started aren't counted.
+pub struct LogMatch {
+    config: LogMatchConfig,
+    pattern: Regex,
+    position: Option<Position>,
+}
+
+impl LogMatch {
+    pub fn new(config: LogMatchConfig) -> Result<Self> {
+        let pattern = Regex::new(&config.pattern).with_context(|| {
+            format!(
+                "Invalid regular expression for log sensor `{}`.",
+                config.name
+            )
+        })?;
+
+        Ok(Self {
+            config,
+            pattern,
+            position: None,
+        })
+    }
+
+    /// Reads the lines added to a file since `offset`, and returns them along with where they end.
+    /// A line that's still being written is left for next time.
+    async fn read_file(path: &PathBuf, offset: Option<u64>) -> Result<(String, u64)> {
+        let mut file = File::open(path)
+            .await
+            .with_context(|| format!("Failed to open `{}`.", path.display()))?;
+        let length = file.metadata().await?.len();
+
+        let offset = match offset {
+            // The first time, we only want to know where the end is.
+            None => return Ok((String::new(), length)),
+            // The file got smaller, so it must have been rotated or truncated.
+            Some(offset) if offset > length => 0,
+            Some(offset) => offset,
+        };
+
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).await?;
+
+        let complete = content
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        content.truncate(complete);
+
+        Ok((
+            String::from_utf8_lossy(&content).into_owned(),
+            offset + complete as u64,
+        ))
+    }
+}
+
+/// Reads the journal entries `filter` matches logged since `cursor`, and returns them along with
+/// the new cursor.
+pub(crate) async fn read_journal(
+    filter: &[&str],
+    cursor: Option<&str>,
+) -> Result<(String, Option<String>)> {
+    let mut command = Command::new("journalctl");
+    command
+        .args(filter)
+        .args(["--output=cat", "--no-pager", "--quiet", "--show-cursor"])
+        .kill_on_drop(true);
+
+    match cursor {
+        Some(cursor) => command.arg(format!("--after-cursor={}", cursor)),
+        // The first time, we only want to know where the end is.
+        None => command.arg("--lines=1"),
+    };
+
+    let output = command
+        .output()
+        .await
+        .context("Failed to run journalctl.")?;
+    if !output.status.success() {
+        bail!(
+            "journalctl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    // The cursor is printed after the entries, on a line like `-- cursor: s=...`.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = Vec::new();
+    let mut new_cursor = None;
+    for line in stdout.lines() {
+        match line.strip_prefix("-- cursor: ") {
+            Some(found) => new_cursor = Some(found.to_string()),
+            None => lines.push(line),
+        }
+    }
+
+    if cursor.is_none() {
+        lines.clear();
+    }
+
+    Ok((lines.join("\n"), new_cursor))
+}
+
+#[async_trait(?Send)]
+impl Sensor for LogMatch {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .state_class("measurement")
+            .icon("mdi:text-search")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let first = self.position.is_none();
+
+        let lines = match &self.config.source {
+            LogSource::File(path) => {
+                let offset = match self.position {
+                    Some(Position::File(offset)) => Some(offset),
+                    _ => None,
+                };
+
+                let (lines, offset) = Self::read_file(path, offset).await?;
+                self.position = Some(Position::File(offset));
+
+                lines
+            }
+            LogSource::Journal(unit) => {
+                let cursor = match &self.position {
+                    Some(Position::Journal(cursor)) => Some(cursor.as_str()),
+                    _ => None,
+                };
+
+                let (lines, cursor) = read_journal(&["--unit", unit], cursor).await?;
+
+                // With nothing new, there's no new cursor either, so we keep the old one.
+                if let Some(cursor) = cursor {
+                    self.position = Some(Position::Journal(cursor));
+                }
+
+                lines
+            }
+        };
+
+        // Until we know where the log ends, we have nothing to count from.
+        if first {
+            return Ok(Vec::new());
+        }
+
+        let matches = lines
+            .lines()
+            .filter(|line| self.pattern.is_match(line))
+            .count();
+
+        Ok(vec![Reading::new(
+            self.config.name.as_str(),
+            matches.to_string(),
+        )])
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+
+/// Something logind can do to the system, such as shutting it down.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogindAction {
+    Shutdown,
+    Reboot,
+    Suspend,
+    Hibernate,
+    LockScreen,
+}
+
+impl LogindAction {
+    const ALL: [LogindAction; 5] = [
+        LogindAction::Shutdown,
+        LogindAction::Reboot,
+        LogindAction::Suspend,
+        LogindAction::Hibernate,
+        LogindAction::LockScreen,
+    ];
+
+    /// The name of the button entity.
+    fn entity_name(self) -> &'static str {
+        match self {
+            LogindAction::Shutdown => "shutdown",
+            LogindAction::Reboot => "reboot",
+            LogindAction::Suspend => "suspend",
+            LogindAction::Hibernate => "hibernate",
+            LogindAction::LockScreen => "lock_screen",
+        }
+    }
+
+    /// The logind method that carries out the action.
+    fn method(self) -> &'static str {
+        match self {
+            LogindAction::Shutdown => "PowerOff",
+            LogindAction::Reboot => "Reboot",
+            LogindAction::Suspend => "Suspend",
+            LogindAction::Hibernate => "Hibernate",
+            LogindAction::LockScreen => "LockSessions",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            LogindAction::Shutdown => "mdi:power",
+            LogindAction::Reboot => "mdi:restart",
+            LogindAction::Suspend => "mdi:power-sleep",
+            LogindAction::Hibernate => "mdi:snowflake",
+            LogindAction::LockScreen => "mdi:lock",
+        }
+    }
+
+    fn action_class(self) -> ActionClass {
+        match self {
+            LogindAction::LockScreen => ActionClass::Desktop,
+            _ => ActionClass::Power,
+        }
+    }
+}
+
+/// Buttons that have logind do something to the system, such as shutting it down or locking the screen.
+pub struct LogindButtons {
+    actions: Vec<LogindAction>,
+    connection: LazyConnection,
+}
+
+impl LogindButtons {
+    pub fn new(actions: Vec<LogindAction>) -> Self {
+        Self {
+            actions,
+            connection: LazyConnection::new(Bus::System),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for LogindButtons {
+    fn name(&self) -> &str {
+        "logind"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .actions
+            .iter()
+            .map(|action| {
+                Entity::new("button", action.entity_name())
+                    .state_class("")
+                    .icon(action.icon())
+                    .accepts_commands(action.action_class())
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        // Buttons don't have a state.
+        Ok(Vec::new())
+    }
+
+    async fn command(&mut self, entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        let action = match LogindAction::ALL
+            .iter()
+            .find(|action| action.entity_name() == entity_name)
+        {
+            Some(action) if self.actions.contains(action) => *action,
+            _ => bail!("Unknown logind command `{}`.", entity_name),
+        };
+        let method = action.method();
+
+        log::info!("Calling logind `{}`.", method);
+
+        let connection = self.connection.get().await?;
+        let result = if action == LogindAction::LockScreen {
+            // Locks every session, which includes whichever one is on screen.
+            connection
+                .call_method(
+                    Some("org.freedesktop.login1"),
+                    "/org/freedesktop/login1",
+                    Some("org.freedesktop.login1.Manager"),
+                    method,
+                    &(),
+                )
+                .await
+        } else {
+            // The argument says whether polkit may ask the user for permission interactively, which there's nobody to do.
+            connection
+                .call_method(
+                    Some("org.freedesktop.login1"),
+                    "/org/freedesktop/login1",
+                    Some("org.freedesktop.login1.Manager"),
+                    method,
+                    &(false,),
+                )
+                .await
+        };
+        result.with_context(|| format!("Failed to call logind `{}`.", method))?;
+
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,152 @@
+use super::{default_state_class, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use mlua::{HookTriggers, Lua, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::task;
+
+/// How many instructions a script gets to run each time it is collected.
+/// Keeps a script that's stuck in a loop from hanging the whole daemon.
+const INSTRUCTIONS_PER_CALL: u64 = 100_000_000;
+
+/// How often the instruction budget is checked.
+const INSTRUCTIONS_PER_CHECK: u32 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LuaSensorConfig {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    /// The Lua script to run. Whatever the script returns is the value of the sensor.
+    pub script: PathBuf,
+
+    /// The unit of the value, if it has one.
+    pub unit: Option<String>,
+
+    /// The icon to show in Home Assistant.
+    pub icon: Option<String>,
+}
+
+pub struct LuaSensor {
+    config: LuaSensorConfig,
+
+    /// Scripts run on a blocking thread, so the interpreter has to be shared with it.
+    lua: Arc<Mutex<Lua>>,
+    source: Arc<str>,
+
+    /// What's left of the instruction budget for the current run.
+    fuel: Arc<AtomicU64>,
+}
+
+impl LuaSensor {
+    pub fn new(config: LuaSensorConfig) -> Result<Self> {
+        let source = std::fs::read_to_string(&config.script).with_context(|| {
+            format!(
+                "Failed to read Lua script `{}` for sensor `{}`.",
+                config.script.display(),
+                config.name
+            )
+        })?;
+
+        let lua = Lua::new();
+
+        // Scripts spend most of their time reading files out of /proc and /sys, so give them a shortcut for it.
+        let read_file = lua.create_function(|_, path: String| {
+            std::fs::read_to_string(path).map_err(mlua::Error::external)
+        })?;
+        lua.globals().set("read_file", read_file)?;
+
+        // Running a script on a blocking thread keeps it from holding everything else up, but
+        // nothing can stop one that never returns.
+        let fuel = Arc::new(AtomicU64::new(0));
+        let hook_fuel = fuel.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INSTRUCTIONS_PER_CHECK),
+            move |_, _| {
+                hook_fuel
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |fuel| {
+                        fuel.checked_sub(INSTRUCTIONS_PER_CHECK as u64)
+                    })
+                    .map(|_| ())
+                    .map_err(|_| {
+                        mlua::Error::RuntimeError(String::from(
+                            "Script ran out of instructions. Is it stuck in a loop?",
+                        ))
+                    })
+            },
+        );
+
+        Ok(Self {
+            config,
+            lua: Arc::new(Mutex::new(lua)),
+            source: source.into(),
+            fuel,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for LuaSensor {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", &self.config.name)
+            .state_class(default_state_class(&self.config.unit))
+            .unit(self.config.unit.as_deref())
+            .icon(
+                self.config.icon.as_deref().unwrap_or("mdi:language-lua"),
+            )])
+    }
+
+    /// Runs the script and returns its result.
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let lua = self.lua.clone();
+        let source = self.source.clone();
+        let name = self.config.name.clone();
+        let fuel = self.fuel.clone();
+
+        // Scripts never yield, so they're run where they can't hold up the other sensors and the
+        // MQTT connection while they go.
+        let value = task::spawn_blocking(move || {
+            let lua = lua.lock().expect("Lua lock was poisoned.");
+            fuel.store(INSTRUCTIONS_PER_CALL, Ordering::Relaxed);
+            let value: Value = lua
+                .load(&*source)
+                .set_name(&name)
+                .eval()
+                .context("Lua script failed.")?;
+
+            script_value(value)
+        })
+        .await
+        .context("Lua script panicked.")??;
+
+        Ok(vec![Reading::new(self.config.name.as_str(), value)])
+    }
+}
+
+/// What a script returned, as the value of its sensor.
+fn script_value(value: Value) -> Result<String> {
+    Ok(match value {
+        Value::Boolean(value) => if value { "ON" } else { "OFF" }.to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => value
+            .to_str()
+            .context("Lua script returned a string that isn't valid UTF-8.")?
+            .trim()
+            .to_string(),
+        Value::Nil => bail!("Lua script did not return a value."),
+        _ => bail!("Lua script must return a number, string or boolean."),
+    })
+}
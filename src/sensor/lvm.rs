@@ -0,0 +1,129 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::Entity,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// What `lvs --reportformat json` prints.
+#[derive(Deserialize)]
+struct Report {
+    report: Vec<ReportSection>,
+}
+
+#[derive(Deserialize)]
+struct ReportSection {
+    lv: Vec<ThinPool>,
+}
+
+#[derive(Deserialize)]
+struct ThinPool {
+    vg_name: String,
+    lv_name: String,
+
+    /// Percentages, like `12.34`. They're empty while the pool isn't active.
+    data_percent: String,
+    metadata_percent: String,
+}
+
+impl ThinPool {
+    fn entity_name(&self) -> String {
+        let name = format!("lvm_{}_{}", self.vg_name, self.lv_name);
+        name.chars()
+            .map(|character| {
+                if character.is_ascii_alphanumeric() {
+                    character.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+/// How full the data and metadata of LVM thin pools are. When either fills up, every volume in the
+/// pool stops taking writes, which the usage of the filesystems on them won't warn about.
+pub struct LvmThinPools {
+    privileged: Privileged,
+
+    /// The pools found when we started, by the names they're reported as.
+    pools: Vec<String>,
+}
+
+impl LvmThinPools {
+    pub fn new(privileged: Privileged) -> Self {
+        Self {
+            privileged,
+            pools: Vec::new(),
+        }
+    }
+
+    async fn read_pools(&self) -> Result<Vec<ThinPool>> {
+        let output = self.privileged.run(Request::LvmThinPools).await?;
+        let report: Report =
+            serde_json::from_str(&output).context("Failed to parse LVM thin pools.")?;
+
+        Ok(report
+            .report
+            .into_iter()
+            .flat_map(|section| section.lv)
+            .collect())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for LvmThinPools {
+    fn name(&self) -> &str {
+        "lvm_thin_pools"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        self.pools = self
+            .read_pools()
+            .await?
+            .iter()
+            .map(ThinPool::entity_name)
+            .collect();
+
+        let mut entities = Vec::new();
+        for pool in self.pools.iter() {
+            for part in ["data", "metadata"] {
+                entities.push(
+                    Entity::new("sensor", &format!("{}_{}", pool, part))
+                        .state_class("measurement")
+                        .unit("%")
+                        .icon("mdi:database"),
+                );
+            }
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let pools = self.read_pools().await?;
+
+        let mut readings = Vec::new();
+        for name in self.pools.iter() {
+            let pool = pools.iter().find(|pool| &pool.entity_name() == name);
+
+            for part in ["data", "metadata"] {
+                let entity = format!("{}_{}", name, part);
+                let percent = pool.and_then(|pool| match part {
+                    "data" => pool.data_percent.parse::<f64>().ok(),
+                    _ => pool.metadata_percent.parse::<f64>().ok(),
+                });
+
+                // Pools that are gone, or inactive, are unavailable until they're back.
+                readings.push(match percent {
+                    Some(percent) => Reading::new(entity, percent.to_string()),
+                    None => Reading::unavailable(entity),
+                });
+            }
+        }
+
+        Ok(readings)
+    }
+}
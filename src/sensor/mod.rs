@@ -0,0 +1,935 @@
+//! Things that collect values to be published.
+//!
+//! Each sensor is self-contained: it says which entities it publishes when registered,
+//! and then produces readings for them each update.
+
+use crate::{
+    config::Config,
+    privileged::Privileged,
+    sink::{audit::Decision, Command, Entity, Sinks},
+    state::StateStore,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::{join_all, pending, select_all};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time;
+
+pub mod ambient_light;
+pub mod backlight;
+pub mod backup;
+pub mod battery;
+#[cfg(feature = "dbus")]
+pub mod ble;
+pub mod cgroup;
+pub mod clock;
+#[cfg(all(unix, feature = "containers"))]
+pub mod containers;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod diagnostics;
+pub mod directory_size;
+pub mod disk_latency;
+pub mod display_power;
+pub mod displays;
+pub mod dns;
+pub mod drives;
+pub mod edac;
+pub mod exec;
+pub mod fail2ban;
+pub mod fan;
+pub mod file_age;
+pub mod firewall;
+#[cfg(feature = "dbus")]
+pub mod fwupd;
+pub mod governor;
+pub mod host;
+#[cfg(feature = "http-checks")]
+pub mod http;
+pub mod hugepages;
+pub mod in_use;
+#[cfg(feature = "dbus")]
+pub mod inhibit;
+pub mod ipmi;
+#[cfg(feature = "pattern-matching")]
+pub mod kernel_log;
+pub mod keyboard_backlight;
+#[cfg(feature = "libvirt")]
+pub mod libvirt;
+#[cfg(feature = "pattern-matching")]
+pub mod log_match;
+#[cfg(feature = "dbus")]
+pub mod logind;
+#[cfg(feature = "lua")]
+pub mod lua;
+pub mod lvm;
+pub mod mounts;
+#[cfg(feature = "dbus")]
+pub mod mpris;
+pub mod network;
+#[cfg(feature = "dbus")]
+pub mod notify;
+pub mod nvme;
+#[cfg(feature = "pattern-matching")]
+pub mod oom;
+pub mod ping;
+pub mod port;
+#[cfg(feature = "dbus")]
+pub mod power_profile;
+pub mod pressure;
+#[cfg(feature = "http-checks")]
+pub mod public_ip;
+pub mod raid;
+pub mod rapl;
+pub mod remote;
+pub mod removable;
+pub mod rfkill;
+pub mod screenshot;
+pub mod scripts;
+pub mod security;
+#[cfg(feature = "dbus")]
+pub mod sessions;
+pub mod siren;
+pub mod speech;
+#[cfg(feature = "pattern-matching")]
+pub mod ssh;
+pub mod steal;
+pub mod system;
+#[cfg(feature = "dbus")]
+pub mod systemd;
+#[cfg(unix)]
+pub mod tailscale;
+pub mod tcp;
+pub mod units;
+#[cfg(unix)]
+mod unix_http;
+pub mod updates;
+pub mod usb;
+#[cfg(unix)]
+pub mod user_usage;
+pub mod volume;
+pub mod wake_on_lan;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wireguard;
+
+/// The value of an entity at the time it was collected.
+pub struct Reading {
+    /// The name of the entity this is a value for.
+    pub entity: String,
+    pub value: String,
+
+    /// Extra details about the value, as a JSON object. Only entities registered with
+    /// `json_attributes` have somewhere to put them.
+    pub attributes: Option<String>,
+
+    /// When not set, the entity is marked unavailable instead of having a value published,
+    /// such as when the thing it measures has gone away.
+    pub available: bool,
+}
+
+impl Reading {
+    pub fn new(entity: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            entity: entity.into(),
+            value: value.into(),
+            attributes: None,
+            available: true,
+        }
+    }
+
+    /// Marks an entity unavailable until it has a value again.
+    pub fn unavailable(entity: impl Into<String>) -> Self {
+        Self {
+            entity: entity.into(),
+            value: String::new(),
+            attributes: None,
+            available: false,
+        }
+    }
+
+    pub fn attributes(mut self, attributes: String) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+}
+
+/// Entities that appeared or went away while a sensor was running.
+#[derive(Default)]
+pub struct EntityChanges {
+    pub added: Vec<Entity>,
+
+    /// The names of the entities that went away.
+    pub removed: Vec<String>,
+}
+
+#[async_trait(?Send)]
+pub trait Sensor {
+    /// What to call this sensor in log messages.
+    fn name(&self) -> &str;
+
+    /// The entities this sensor publishes values for.
+    async fn register(&mut self) -> Result<Vec<Entity>>;
+
+    /// Reads the current values. A sensor may return no readings if it has nothing new to say.
+    async fn collect(&mut self) -> Result<Vec<Reading>>;
+
+    /// For sensors driven by events, such as D-Bus signals. Waits for the next event and returns
+    /// the readings it brings, which are published right away instead of with the next update.
+    /// Sensors that aren't driven by events never return.
+    ///
+    /// Waiting is given up whenever something else happens first, so this should only await
+    /// things that can be picked up again later, such as the next item of a stream.
+    async fn next_event(&mut self) -> Result<Vec<Reading>> {
+        pending().await
+    }
+
+    /// Called once when we stop, for sensors that need to put things back the way they were.
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// For sensors whose entities change while running, such as when a drive is plugged in.
+    /// Called after every collection, before the readings are published, and returns what changed since the last call.
+    fn entity_changes(&mut self) -> EntityChanges {
+        EntityChanges::default()
+    }
+
+    /// Acts on a command for one of this sensor's entities, such as a button being pressed.
+    /// Any readings returned are published right away, so the new state shows up without waiting for the next update.
+    async fn command(&mut self, entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        bail!("`{}` does not accept commands.", entity_name)
+    }
+}
+
+/// A sensor along with what we know about it after it was registered.
+struct RegisteredSensor {
+    sensor: Box<dyn Sensor>,
+
+    /// The names of the entities this sensor publishes.
+    entities: Vec<String>,
+
+    /// Set while the sensor is failing, so its entities are marked unavailable only once.
+    failing: bool,
+    /// Entities the sensor said are unavailable, even though it's working.
+    unavailable: HashSet<String>,
+}
+
+/// All of the sensors we are collecting from.
+pub struct SensorRegistry {
+    /// Sensors waiting to be registered.
+    sensors: Vec<Box<dyn Sensor>>,
+
+    /// Sensors that have been registered and can be collected from.
+    registered: Vec<RegisteredSensor>,
+
+    /// How long a single sensor may take to collect before we give up on it for this update.
+    timeout: Duration,
+
+    /// When set, how our updates are going is reported along with the sensors.
+    diagnostics: Option<diagnostics::Diagnostics>,
+}
+
+impl SensorRegistry {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            sensors: Vec::new(),
+            registered: Vec::new(),
+            timeout,
+            diagnostics: None,
+        }
+    }
+
+    /// Creates every sensor the config asks for.
+    pub fn from_config(config: &Config, state: Arc<StateStore>) -> Result<Self> {
+        let mut registry = Self::new(config.sensor_timeout);
+        let privileged = Privileged::new(config);
+        if config.enable_diagnostics {
+            registry.diagnostics = Some(diagnostics::Diagnostics::new());
+        }
+
+        let cgroup = if config.cgroup_aware.unwrap_or_else(cgroup::in_container) {
+            log::info!("Reporting CPU and memory usage relative to our cgroup.");
+            Some(cgroup::Cgroup::detect()?)
+        } else {
+            None
+        };
+        registry.add(system::SystemSensor::new(
+            cgroup,
+            config.units,
+            config.top_processes,
+        ));
+        registry.add(host::HostInfo::new());
+
+        // Only Linux reports steal time, and it's only worth reporting when it's there.
+        if let Some(steal_time_sensor) = steal::StealTimeSensor::new() {
+            registry.add(steal_time_sensor);
+        }
+        if config.enable_cpu_power {
+            registry.add(rapl::RaplSensor::new()?);
+        }
+
+        if config.enable_pressure_stall {
+            registry.add(pressure::PressureSensor);
+        }
+
+        if config.enable_hugepages {
+            registry.add(hugepages::HugepagesSensor);
+        }
+
+        if config.enable_ecc_errors {
+            registry.add(edac::EccErrors::new());
+        }
+
+        #[cfg(feature = "pattern-matching")]
+        if config.enable_oom_kills {
+            registry.add(oom::OomKills::new());
+        }
+
+        #[cfg(unix)]
+        if config.enable_user_usage {
+            registry.add(user_usage::UserUsage::new());
+        }
+
+        registry.add(drives::DriveSensor::new(
+            &config.drives,
+            config.discover_drives.as_ref(),
+            config.units.drives,
+            privileged.clone(),
+        )?);
+
+        if !config.disk_latency.is_empty() {
+            registry.add(disk_latency::DiskLatency::new(config.disk_latency.clone()));
+        }
+
+        if config.enable_lvm_thin_pools {
+            registry.add(lvm::LvmThinPools::new(privileged.clone()));
+        }
+
+        if config.enable_display_sensors {
+            registry.add(displays::Displays::new());
+        }
+
+        if config.enable_ambient_light {
+            registry.add(ambient_light::AmbientLight::new()?);
+        }
+
+        if config.enable_removable_media {
+            registry.add(removable::RemovableMedia::new(config.units.drives));
+        }
+
+        // Hosts without a battery would only ever show battery entities as unknown.
+        let enable_battery = config.enable_battery.unwrap_or_else(|| {
+            let found = battery::has_battery();
+            if !found {
+                log::info!("No battery found. Battery state will not be reported.");
+            }
+            found
+        });
+        if enable_battery {
+            registry.add(battery::BatterySensor::new(config.battery_low_threshold)?);
+        }
+
+        if config.enable_battery_charge_limit {
+            registry.add(battery::ChargeLimit::new(privileged.clone())?);
+        }
+
+        #[cfg(feature = "dbus")]
+        {
+            let mut logind_actions = Vec::new();
+            if config.enable_power_commands {
+                logind_actions.push(logind::LogindAction::Shutdown);
+                logind_actions.push(logind::LogindAction::Reboot);
+            }
+            if config.enable_suspend_command {
+                logind_actions.push(logind::LogindAction::Suspend);
+            }
+            if config.enable_hibernate_command {
+                logind_actions.push(logind::LogindAction::Hibernate);
+            }
+            if config.enable_lock_command {
+                logind_actions.push(logind::LogindAction::LockScreen);
+            }
+            if !logind_actions.is_empty() {
+                registry.add(logind::LogindButtons::new(logind_actions));
+            }
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_sleep_inhibitor {
+            registry.add(inhibit::SleepInhibitor::new());
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_notifications {
+            registry.add(notify::Notifier::new());
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_do_not_disturb {
+            registry.add(notify::DoNotDisturb::new());
+        }
+
+        if config.enable_volume_control {
+            registry.add(volume::VolumeControl);
+        }
+
+        if config.enable_microphone_mute {
+            registry.add(volume::MicrophoneMute);
+        }
+
+        if config.enable_clock_offset {
+            registry.add(clock::ClockOffset);
+        }
+
+        if config.enable_firewall_status {
+            registry.add(firewall::Firewall);
+        }
+
+        if config.enable_security_modules {
+            registry.add(security::SecurityModules);
+        }
+
+        if config.enable_tcp_connections {
+            registry.add(tcp::TcpConnections);
+        }
+
+        if config.enable_in_use_sensors {
+            registry.add(in_use::InUseSensor);
+        }
+
+        if config.enable_backlight_control {
+            registry.add(backlight::Backlight::new(
+                config.backlight_device.as_deref(),
+                privileged.clone(),
+            )?);
+        }
+
+        if config.enable_keyboard_backlight {
+            registry.add(keyboard_backlight::KeyboardBacklight::new(
+                privileged.clone(),
+            )?);
+        }
+
+        if config.enable_radio_switches {
+            registry.add(rfkill::RadioSwitches::new(privileged.clone()));
+        }
+
+        #[cfg(all(unix, feature = "containers"))]
+        if let Some(containers_config) = &config.containers {
+            registry.add(containers::ContainerSensor::new(containers_config.clone())?);
+        }
+
+        #[cfg(feature = "libvirt")]
+        if let Some(libvirt_config) = &config.libvirt {
+            registry.add(libvirt::LibvirtSensor::new(libvirt_config.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if !config.systemd_units.is_empty() {
+            registry.add(systemd::SystemdUnits::new(config.systemd_units.clone())?);
+        }
+
+        #[cfg(feature = "dbus")]
+        if !config.systemd_timers.is_empty() {
+            registry.add(systemd::SystemdTimers::new(config.systemd_timers.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_failed_units {
+            registry.add(systemd::FailedUnits::new());
+        }
+
+        if config.enable_cpu_governor_control {
+            registry.add(governor::CpuGovernor::new(privileged.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_power_profile {
+            registry.add(power_profile::PowerProfile::new());
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_media_player {
+            registry.add(mpris::MediaPlayer::new());
+        }
+
+        if let Some(speech_config) = &config.text_to_speech {
+            registry.add(speech::Speech::new(speech_config.clone()));
+        }
+
+        if let Some(siren_config) = &config.siren {
+            registry.add(siren::Siren::new(siren_config.clone())?);
+        }
+
+        if let Some(screenshot_config) = &config.screenshot {
+            registry.add(screenshot::Screenshot::new(screenshot_config.clone()));
+        }
+
+        if let Some(display_power_config) = &config.display_power {
+            registry.add(display_power::DisplayPower::new(
+                display_power_config.clone(),
+            ));
+        }
+
+        if let Some(os_updates_config) = &config.os_updates {
+            registry.add(updates::OsUpdates::new(os_updates_config.clone())?);
+        }
+
+        #[cfg(feature = "dbus")]
+        if let Some(firmware_updates_config) = &config.firmware_updates {
+            registry.add(fwupd::FirmwareUpdates::new(firmware_updates_config.clone()));
+        }
+
+        if !config.scripts.is_empty() {
+            registry.add(scripts::ScriptButtons::new(config.scripts.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if let Some(login_sessions_config) = &config.login_sessions {
+            registry.add(sessions::LoginSessions::new(login_sessions_config.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if let Some(ble_presence_config) = &config.ble_presence {
+            registry.add(ble::BleScanner::new(ble_presence_config.clone())?);
+        }
+
+        #[cfg(feature = "pattern-matching")]
+        if let Some(ssh_config) = &config.ssh_failed_logins {
+            registry.add(ssh::SshFailedLogins::new(ssh_config.clone()));
+        }
+
+        for remote_host_config in config.remote_hosts.iter() {
+            registry.add(remote::RemoteHost::new(remote_host_config.clone()));
+        }
+
+        #[cfg(feature = "dbus")]
+        if config.enable_ssh_sessions {
+            registry.add(sessions::SshSessions::new());
+        }
+
+        if let Some(fail2ban_config) = &config.fail2ban {
+            registry.add(fail2ban::Fail2ban::new(fail2ban_config.clone()));
+        }
+
+        if !config.ping_targets.is_empty() {
+            registry.add(ping::PingSensor::new(config.ping_targets.clone()));
+        }
+
+        if !config.dns_checks.is_empty() {
+            registry.add(dns::DnsSensor::new(config.dns_checks.clone()));
+        }
+
+        if !config.port_checks.is_empty() {
+            registry.add(port::PortSensor::new(config.port_checks.clone()));
+        }
+
+        #[cfg(feature = "http-checks")]
+        if !config.http_checks.is_empty() {
+            registry.add(http::HttpSensor::new(config.http_checks.clone())?);
+        }
+
+        #[cfg(feature = "http-checks")]
+        if let Some(public_ip_config) = &config.public_ip {
+            registry.add(public_ip::PublicIpSensor::new(public_ip_config.clone())?);
+        }
+
+        #[cfg(unix)]
+        if let Some(tailscale_config) = &config.tailscale {
+            registry.add(tailscale::TailscaleSensor::new(tailscale_config.clone()));
+        }
+
+        if !config.wireguard_interfaces.is_empty() {
+            registry.add(wireguard::WireGuardSensor::new(
+                config.wireguard_interfaces.clone(),
+                privileged.clone(),
+            ));
+        }
+
+        if let Some(ipmi_config) = &config.ipmi {
+            registry.add(ipmi::IpmiSensor::new(
+                ipmi_config.clone(),
+                privileged.clone(),
+            ));
+        }
+
+        if !config.nvme_devices.is_empty() {
+            registry.add(nvme::NvmeSensor::new(
+                config.nvme_devices.clone(),
+                privileged.clone(),
+            ));
+        }
+
+        if !config.md_arrays.is_empty() || !config.zfs_pools.is_empty() {
+            registry.add(raid::ResyncSensor::new(
+                config.md_arrays.clone(),
+                config.zfs_pools.clone(),
+            ));
+        }
+
+        if !config.network_mounts.is_empty() {
+            registry.add(mounts::MountSensor::new(config.network_mounts.clone()));
+        }
+
+        if !config.usb_devices.is_empty() {
+            registry.add(usb::UsbSensor::new(config.usb_devices.clone()));
+        }
+
+        if !config.wake_on_lan.is_empty() {
+            registry.add(wake_on_lan::WakeOnLan::new(&config.wake_on_lan)?);
+        }
+
+        if !config.network_interfaces.is_empty() {
+            registry.add(network::NetworkSensor::new(
+                &config.network_interfaces,
+                state,
+                config.network_monthly_reset_day,
+            )?);
+        }
+
+        for backup_config in &config.backups {
+            registry.add(backup::BackupSensor::new(backup_config.clone()));
+        }
+
+        for directory_size_config in &config.directory_sizes {
+            registry.add(directory_size::DirectorySize::new(
+                directory_size_config.clone(),
+            ));
+        }
+
+        for file_age_config in &config.file_ages {
+            registry.add(file_age::FileAge::new(file_age_config.clone()));
+        }
+
+        for fan_config in &config.fans {
+            registry.add(fan::Fan::new(fan_config.clone(), privileged.clone())?);
+        }
+
+        #[cfg(feature = "pattern-matching")]
+        for log_match_config in &config.log_matches {
+            registry.add(log_match::LogMatch::new(log_match_config.clone())?);
+        }
+
+        #[cfg(feature = "pattern-matching")]
+        if let Some(kernel_errors_config) = &config.kernel_errors {
+            registry.add(kernel_log::KernelErrors::new(kernel_errors_config.clone())?);
+        }
+
+        for exec_config in &config.exec_sensors {
+            registry.add(exec::ExecSensor::new(exec_config.clone())?);
+        }
+
+        #[cfg(feature = "lua")]
+        for lua_config in &config.lua_sensors {
+            registry.add(lua::LuaSensor::new(lua_config.clone())?);
+        }
+
+        #[cfg(feature = "dbus")]
+        for dbus_config in &config.dbus_sensors {
+            registry.add(dbus::DbusSensor::new(dbus_config.clone())?);
+        }
+
+        #[cfg(feature = "wasm")]
+        if let Some(plugin_directory) = &config.plugin_directory {
+            for plugin in wasm::load_plugins(plugin_directory)? {
+                registry.add(plugin);
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn add(&mut self, sensor: impl Sensor + 'static) {
+        self.sensors.push(Box::new(sensor));
+    }
+
+    /// Registers the entities of every sensor with the sinks.
+    /// A sensor that fails to register is left out, but the others carry on.
+    pub async fn register(&mut self, sinks: &mut Sinks) -> Result<()> {
+        for mut sensor in self.sensors.drain(..) {
+            let entities = match sensor.register().await {
+                Ok(entities) => entities,
+                Err(error) => {
+                    log::warn!(
+                        "Sensor `{}` failed to register and will not be collected: {:?}",
+                        sensor.name(),
+                        error
+                    );
+                    continue;
+                }
+            };
+
+            let mut entity_names = Vec::new();
+            for entity in entities {
+                entity_names.push(entity.name.clone());
+
+                sinks
+                    .register(entity)
+                    .await
+                    .with_context(|| format!("Failed to register sensor `{}`.", sensor.name()))?;
+            }
+
+            self.registered.push(RegisteredSensor {
+                sensor,
+                entities: entity_names,
+                failing: false,
+                unavailable: HashSet::new(),
+            });
+        }
+
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics
+                .register(sinks)
+                .await
+                .context("Failed to register diagnostics.")?;
+        }
+
+        Ok(())
+    }
+
+    /// Collects from every sensor at once and publishes the readings.
+    /// A sensor that is slow to respond or fails won't hold up the others. Its entities are
+    /// marked unavailable until it recovers.
+    pub async fn collect(&mut self, sinks: &mut Sinks) {
+        let started = Instant::now();
+        let timeout = self.timeout;
+        let results = join_all(self.registered.iter_mut().map(|registered| async move {
+            let result = time::timeout(timeout, registered.sensor.collect()).await;
+            (registered, result)
+        }))
+        .await;
+
+        for (registered, result) in results {
+            let sensor_name = registered.sensor.name();
+
+            let readings = match result {
+                Ok(Ok(readings)) => readings,
+                Ok(Err(error)) => {
+                    log::warn!("Sensor `{}` failed: {:?}", sensor_name, error);
+                    if let Some(diagnostics) = &mut self.diagnostics {
+                        diagnostics.record_failure(format!("{}: {:#}", sensor_name, error));
+                    }
+                    registered.set_failing(true, sinks).await;
+                    continue;
+                }
+                Err(_) => {
+                    log::warn!(
+                        "Sensor `{}` took longer than {:?} to collect.",
+                        sensor_name,
+                        timeout
+                    );
+                    if let Some(diagnostics) = &mut self.diagnostics {
+                        diagnostics.record_failure(format!(
+                            "{}: took longer than {:?} to collect",
+                            sensor_name, timeout
+                        ));
+                    }
+                    registered.set_failing(true, sinks).await;
+                    continue;
+                }
+            };
+
+            registered.set_failing(false, sinks).await;
+            registered.apply_entity_changes(sinks).await;
+
+            for reading in readings {
+                registered.publish(reading, sinks).await;
+            }
+        }
+
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.publish(sinks, started.elapsed()).await;
+        }
+    }
+
+    /// Waits for any sensor to have an event. Returns the sensor, to hand to `publish_event`, and what the event brought.
+    pub async fn next_event(&mut self) -> (usize, Result<Vec<Reading>>) {
+        if self.registered.is_empty() {
+            return pending().await;
+        }
+
+        let (result, index, _) = select_all(
+            self.registered
+                .iter_mut()
+                .map(|registered| registered.sensor.next_event()),
+        )
+        .await;
+
+        (index, result)
+    }
+
+    /// Publishes the readings an event brought.
+    pub async fn publish_event(
+        &mut self,
+        sinks: &mut Sinks,
+        index: usize,
+        result: Result<Vec<Reading>>,
+    ) {
+        let registered = match self.registered.get_mut(index) {
+            Some(registered) => registered,
+            None => return,
+        };
+
+        match result {
+            Ok(readings) => {
+                registered.apply_entity_changes(sinks).await;
+
+                for reading in readings {
+                    registered.publish(reading, sinks).await;
+                }
+            }
+            Err(error) => log::warn!(
+                "Sensor `{}` failed to handle an event: {:?}",
+                registered.sensor.name(),
+                error
+            ),
+        }
+    }
+
+    /// Lets every sensor clean up before we stop.
+    pub async fn shutdown(&mut self) {
+        for registered in self.registered.iter_mut() {
+            if let Err(error) = registered.sensor.shutdown().await {
+                log::error!(
+                    "Sensor `{}` failed to shut down: {:?}",
+                    registered.sensor.name(),
+                    error
+                );
+            }
+        }
+    }
+
+    /// Hands a command to the sensor the entity belongs to. Commands get as long as collecting
+    /// does, so one that hangs can't hold up everything else.
+    pub async fn command(&mut self, sinks: &Sinks, command: Command) {
+        let timeout = self.timeout;
+        let registered = self
+            .registered
+            .iter_mut()
+            .find(|registered| registered.entities.contains(&command.entity));
+
+        match registered {
+            Some(registered) => {
+                log::info!("Received command for `{}`.", command.entity);
+
+                let result = time::timeout(
+                    timeout,
+                    registered.sensor.command(&command.entity, &command.payload),
+                )
+                .await;
+                match result {
+                    Ok(Ok(readings)) => {
+                        sinks.audit(&command, Decision::Allowed, "ok");
+
+                        for reading in readings {
+                            registered.publish(reading, sinks).await;
+                        }
+                    }
+                    Ok(Err(error)) => {
+                        log::warn!("Command for `{}` failed: {:?}", command.entity, error);
+                        sinks.audit(&command, Decision::Allowed, &format!("{:#}", error));
+                    }
+                    Err(_) => {
+                        log::warn!(
+                            "Command for `{}` took longer than {:?}.",
+                            command.entity,
+                            timeout
+                        );
+                        sinks.audit(&command, Decision::Allowed, "timed out");
+                    }
+                }
+            }
+            None => {
+                log::warn!(
+                    "Received command for `{}`, which no sensor provides.",
+                    command.entity
+                );
+                sinks.audit(&command, Decision::Denied, "no sensor provides the entity");
+            }
+        }
+    }
+}
+
+impl RegisteredSensor {
+    async fn set_failing(&mut self, failing: bool, sinks: &Sinks) {
+        if self.failing != failing {
+            self.failing = failing;
+
+            if !failing {
+                log::info!("Sensor `{}` has recovered.", self.sensor.name());
+            }
+
+            // Entities that were unavailable before the sensor failed stay that way until they have a value.
+            for entity_name in self.entities.iter() {
+                if !self.unavailable.contains(entity_name) {
+                    sinks.set_entity_available(entity_name, !failing).await;
+                }
+            }
+        }
+    }
+
+    /// Registers the entities the sensor gained and unregisters the ones it lost.
+    async fn apply_entity_changes(&mut self, sinks: &mut Sinks) {
+        let changes = self.sensor.entity_changes();
+
+        for entity_name in changes.removed {
+            if let Err(error) = sinks.unregister(&entity_name).await {
+                log::warn!("Failed to unregister `{}`: {:?}", entity_name, error);
+            }
+
+            self.entities.retain(|name| *name != entity_name);
+            self.unavailable.remove(&entity_name);
+        }
+
+        for entity in changes.added {
+            let entity_name = entity.name.clone();
+
+            match sinks.register(entity).await {
+                Ok(()) => self.entities.push(entity_name),
+                Err(error) => log::warn!("Failed to register `{}`: {:?}", entity_name, error),
+            }
+        }
+    }
+
+    /// Publishes a reading, marking its entity available or unavailable as the reading says.
+    async fn publish(&mut self, reading: Reading, sinks: &Sinks) {
+        if !reading.available {
+            if self.unavailable.insert(reading.entity.clone()) {
+                sinks.set_entity_available(&reading.entity, false).await;
+            }
+            return;
+        }
+
+        if self.unavailable.remove(&reading.entity) {
+            sinks.set_entity_available(&reading.entity, true).await;
+        }
+
+        sinks.publish_reading(reading).await;
+    }
+}
+
+/// Sensors configured by the user don't have a state class unless they have a unit.
+pub(crate) fn default_state_class(unit: &Option<String>) -> &'static str {
+    if unit.is_some() {
+        "measurement"
+    } else {
+        ""
+    }
+}
+
+/// Reads the fields of `/proc/meminfo`, like `HugePages_Free:       12`, leaving out units.
+pub(crate) fn parse_meminfo(meminfo: &str) -> HashMap<&str, u64> {
+    meminfo
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let value = value.split_whitespace().next()?.parse().ok()?;
+            Some((name, value))
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use fs2::FsStats;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{fs, task, time};
+
+/// How long a mount has to respond before we call it hung.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NetworkMount {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    /// Where the filesystem is mounted.
+    pub path: PathBuf,
+}
+
+/// Whether network filesystems are mounted and responding, and whether they're stale: mounted,
+/// but not responding.
+pub struct MountSensor {
+    mounts: Vec<(NetworkMount, Arc<Mutex<()>>)>,
+}
+
+impl MountSensor {
+    pub fn new(mounts: Vec<NetworkMount>) -> Self {
+        Self {
+            mounts: mounts
+                .into_iter()
+                .map(|mount| (mount, Arc::new(Mutex::new(()))))
+                .collect(),
+        }
+    }
+}
+
+/// Asks a filesystem how big it is and how much space is left, without getting stuck if it
+/// doesn't answer. Nothing means it didn't.
+///
+/// The lock is held for as long as the request runs. A request to a hung server can block
+/// forever, so while the last one is still going we don't start another.
+pub(crate) async fn filesystem_stats(path: &Path, lock: Arc<Mutex<()>>) -> Option<FsStats> {
+    let path = path.to_path_buf();
+    let request = task::spawn_blocking(move || match lock.try_lock() {
+        Ok(_guard) => fs2::statvfs(&path).ok(),
+        Err(_) => None,
+    });
+
+    match time::timeout(RESPONSE_TIMEOUT, request).await {
+        Ok(Ok(stats)) => stats,
+        _ => None,
+    }
+}
+
+/// A mounted filesystem, according to the kernel.
+pub(crate) struct MountEntry {
+    /// What's mounted, such as `/dev/mapper/root` or `server:/export`.
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub read_only: bool,
+}
+
+/// The filesystems that are mounted, in the order they were mounted.
+pub(crate) async fn mount_table() -> Result<Vec<MountEntry>> {
+    // Reading this doesn't touch the filesystems themselves, so it can't hang.
+    // Each line looks like `server:/export /mnt/media nfs4 rw,relatime 0 0`.
+    let mounts = fs::read_to_string("/proc/mounts")
+        .await
+        .context("Failed to read mounts.")?;
+
+    Ok(mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount_point = fields.next()?;
+            let options = fields.nth(1)?;
+
+            // Spaces and other special characters are escaped as octal, such as `\040`.
+            Some(MountEntry {
+                device: device.replace("\\040", " "),
+                mount_point: PathBuf::from(mount_point.replace("\\040", " ")),
+                read_only: options.split(',').any(|option| option == "ro"),
+            })
+        })
+        .collect())
+}
+
+/// Where filesystems are mounted, according to the kernel.
+pub(crate) async fn mount_points() -> Result<Vec<PathBuf>> {
+    Ok(mount_table()
+        .await?
+        .into_iter()
+        .map(|mount| mount.mount_point)
+        .collect())
+}
+
+#[async_trait(?Send)]
+impl Sensor for MountSensor {
+    fn name(&self) -> &str {
+        "mounts"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for (mount, _) in self.mounts.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &mount.name)
+                    .device_class("connectivity")
+                    .state_class("")
+                    .icon("mdi:folder-network"),
+            );
+            entities.push(
+                Entity::new("binary_sensor", &format!("{}_stale", mount.name))
+                    .device_class("problem")
+                    .state_class("")
+                    .icon("mdi:folder-alert"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mount_points = mount_points().await?;
+
+        Ok(join_all(self.mounts.iter().map(|(mount, lock)| {
+            let mounted = mount_points.contains(&mount.path);
+
+            async move {
+                let available =
+                    mounted && filesystem_stats(&mount.path, lock.clone()).await.is_some();
+                let stale = mounted && !available;
+                if stale {
+                    log::info!("`{}` is not responding.", mount.path.display());
+                }
+
+                vec![
+                    Reading::new(mount.name.as_str(), if available { "ON" } else { "OFF" }),
+                    Reading::new(
+                        format!("{}_stale", mount.name),
+                        if stale { "ON" } else { "OFF" },
+                    ),
+                ]
+            }
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .collect())
+    }
+}
@@ -0,0 +1,171 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{format_value, Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use zbus::{
+    dbus_proxy,
+    fdo::DBusProxy,
+    zvariant::{OwnedValue, Value},
+    CacheProperties, Connection,
+};
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[dbus_proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+}
+
+/// What's playing in desktop media players, along with buttons to control them.
+pub struct MediaPlayer {
+    connection: LazyConnection,
+}
+
+impl MediaPlayer {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::Session),
+        }
+    }
+
+    /// Finds the player to report on. A player that's playing is preferred over one that isn't.
+    async fn find_player(connection: &Connection) -> Result<Option<PlayerProxy<'static>>> {
+        let names = DBusProxy::new(connection)
+            .await?
+            .list_names()
+            .await
+            .context("Failed to list D-Bus names.")?;
+
+        let mut first_player = None;
+        for name in names {
+            if name.starts_with(MPRIS_PREFIX) {
+                let player = PlayerProxy::builder(connection)
+                    .destination(name.to_string())?
+                    .cache_properties(CacheProperties::No)
+                    .build()
+                    .await?;
+
+                if player.playback_status().await? == "Playing" {
+                    return Ok(Some(player));
+                }
+
+                if first_player.is_none() {
+                    first_player = Some(player);
+                }
+            }
+        }
+
+        Ok(first_player)
+    }
+}
+
+impl Default for MediaPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for MediaPlayer {
+    fn name(&self) -> &str {
+        "mpris"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "media_state")
+                .state_class("")
+                .icon("mdi:play-pause"),
+            Entity::new("sensor", "media_title")
+                .state_class("")
+                .icon("mdi:music"),
+            Entity::new("sensor", "media_artist")
+                .state_class("")
+                .icon("mdi:account-music"),
+            Entity::new("button", "media_play_pause")
+                .state_class("")
+                .icon("mdi:play-pause")
+                .accepts_commands(ActionClass::Media),
+            Entity::new("button", "media_next")
+                .state_class("")
+                .icon("mdi:skip-next")
+                .accepts_commands(ActionClass::Media),
+            Entity::new("button", "media_previous")
+                .state_class("")
+                .icon("mdi:skip-previous")
+                .accepts_commands(ActionClass::Media),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+
+        let (state, title, artist) = match Self::find_player(&connection).await? {
+            Some(player) => {
+                let metadata = player
+                    .metadata()
+                    .await
+                    .context("Failed to read media metadata.")?;
+
+                let title = match metadata.get("xesam:title") {
+                    Some(title) => format_value(title)?,
+                    None => String::new(),
+                };
+
+                // There can be more than one artist.
+                let artist = match metadata.get("xesam:artist").map(|artist| &**artist) {
+                    Some(Value::Array(artists)) => artists
+                        .get()
+                        .iter()
+                        .map(format_value)
+                        .collect::<Result<Vec<_>>>()?
+                        .join(", "),
+                    Some(artist) => format_value(artist)?,
+                    None => String::new(),
+                };
+
+                (player.playback_status().await?, title, artist)
+            }
+            None => (String::from("Stopped"), String::new(), String::new()),
+        };
+
+        Ok(vec![
+            Reading::new("media_state", state),
+            Reading::new("media_title", title),
+            Reading::new("media_artist", artist),
+        ])
+    }
+
+    async fn command(&mut self, entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        let player = Self::find_player(&connection)
+            .await?
+            .context("There is no media player to control.")?;
+
+        match entity_name {
+            "media_play_pause" => player.play_pause().await,
+            "media_next" => player.next().await,
+            "media_previous" => player.previous().await,
+            entity_name => bail!("Unknown media command `{}`.", entity_name),
+        }
+        .context("Failed to control media player.")?;
+
+        // The player may not have caught up yet, so the new state is left for the next update.
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,374 @@
+use super::{Reading, Sensor};
+use crate::{sink::Entity, state::StateStore};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use sysinfo::{NetworkExt, NetworksExt, System, SystemExt};
+use tokio::{fs, process::Command};
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// The counters of packets that didn't make it, from each interface's `statistics` directory.
+/// They're reported as how many there were per minute.
+const PROBLEM_COUNTERS: [&str; 4] = ["rx_errors", "tx_errors", "rx_dropped", "tx_dropped"];
+
+/// An interface as listed by `ip -json address`.
+#[derive(Deserialize)]
+struct IpInterface {
+    ifname: String,
+
+    #[serde(default)]
+    addr_info: Vec<IpAddress>,
+}
+
+#[derive(Deserialize)]
+struct IpAddress {
+    /// `inet` or `inet6`.
+    family: String,
+    local: String,
+
+    /// `global` for addresses other hosts can reach us at, and `link` or `host` for the rest.
+    scope: String,
+}
+
+/// The addresses of every interface, by the name of the interface.
+async fn addresses() -> Result<HashMap<String, Vec<IpAddress>>> {
+    let output = Command::new("ip")
+        .args(["-json", "address"])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run ip.")?;
+
+    if !output.status.success() {
+        bail!(
+            "ip exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    let interfaces: Vec<IpInterface> =
+        serde_json::from_slice(&output.stdout).context("Failed to parse addresses.")?;
+
+    Ok(interfaces
+        .into_iter()
+        .map(|interface| (interface.ifname, interface.addr_info))
+        .collect())
+}
+
+/// The day, counted from the epoch, that the month we're in started on if months start on `reset_day`.
+/// Dates are in UTC.
+fn month_start(now: SystemTime, reset_day: u8) -> Result<u64> {
+    let days = now.duration_since(UNIX_EPOCH)?.as_secs() / (60 * 60 * 24);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    let (year, month) = if day >= u32::from(reset_day) {
+        (year, month)
+    } else if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    };
+
+    Ok(days_from_civil(year, month, u32::from(reset_day)) as u64)
+}
+
+/// Turns days since the epoch into a year, month and day.
+/// This is Howard Hinnant's algorithm, from <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// The opposite of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// How much data has gone through the configured network interfaces, whether their links are up,
+/// and what addresses they have.
+/// The totals are kept in the state store, so they keep counting across restarts and reboots.
+pub struct NetworkSensor {
+    system: System,
+    interfaces: Vec<String>,
+    state: Arc<StateStore>,
+
+    /// The state of links is read from sysfs, so it's only reported where there is one.
+    sysfs: bool,
+
+    /// If set, usage since this day of the month is also reported.
+    monthly_reset_day: Option<u8>,
+
+    /// The problem counters of each interface, and when they were read.
+    last_problem_counters: HashMap<String, (Instant, [u64; PROBLEM_COUNTERS.len()])>,
+}
+
+impl NetworkSensor {
+    pub fn new(
+        interfaces: &[String],
+        state: Arc<StateStore>,
+        monthly_reset_day: Option<u8>,
+    ) -> Result<Self> {
+        if let Some(reset_day) = monthly_reset_day {
+            // Every month has these days.
+            ensure!(
+                (1..=28).contains(&reset_day),
+                "The day monthly network usage resets on must be from 1 to 28."
+            );
+        }
+
+        let mut system = System::new();
+        system.refresh_networks_list();
+
+        Ok(Self {
+            system,
+            interfaces: interfaces.to_vec(),
+            state,
+            sysfs: Path::new(SYS_CLASS_NET).exists(),
+            monthly_reset_day,
+            last_problem_counters: HashMap::new(),
+        })
+    }
+
+    fn sysfs_path(interface: &str) -> PathBuf {
+        Path::new(SYS_CLASS_NET).join(interface)
+    }
+
+    /// Whether the interface is up and has a carrier. An interface that doesn't exist isn't up.
+    async fn link_up(interface: &str) -> bool {
+        match fs::read_to_string(Self::sysfs_path(interface).join("operstate")).await {
+            Ok(state) => state.trim() == "up",
+            Err(_) => false,
+        }
+    }
+
+    /// The speed the link was negotiated at, in Mbit/s. Wireless interfaces, and links that
+    /// are down, don't have one.
+    async fn link_speed(interface: &str) -> Option<u64> {
+        let speed = fs::read_to_string(Self::sysfs_path(interface).join("speed"))
+            .await
+            .ok()?;
+
+        // Unknown speeds are reported as -1, which this won't parse.
+        speed.trim().parse().ok()
+    }
+
+    /// The interface's problem counters. An interface that doesn't exist doesn't have any.
+    async fn problem_counters(interface: &str) -> Option<[u64; PROBLEM_COUNTERS.len()]> {
+        let statistics = Self::sysfs_path(interface).join("statistics");
+
+        let mut counters = [0; PROBLEM_COUNTERS.len()];
+        for (counter, name) in counters.iter_mut().zip(PROBLEM_COUNTERS) {
+            *counter = fs::read_to_string(statistics.join(name))
+                .await
+                .ok()?
+                .trim()
+                .parse()
+                .ok()?;
+        }
+
+        Some(counters)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for NetworkSensor {
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for interface in self.interfaces.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_received", interface))
+                    .device_class("data_size")
+                    .state_class("total_increasing")
+                    .unit("B")
+                    .icon("mdi:download-network"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_transmitted", interface))
+                    .device_class("data_size")
+                    .state_class("total_increasing")
+                    .unit("B")
+                    .icon("mdi:upload-network"),
+            );
+
+            if self.monthly_reset_day.is_some() {
+                entities.push(
+                    Entity::new("sensor", &format!("{}_received_this_month", interface))
+                        .device_class("data_size")
+                        .state_class("total_increasing")
+                        .unit("B")
+                        .icon("mdi:download-network"),
+                );
+                entities.push(
+                    Entity::new("sensor", &format!("{}_transmitted_this_month", interface))
+                        .device_class("data_size")
+                        .state_class("total_increasing")
+                        .unit("B")
+                        .icon("mdi:upload-network"),
+                );
+            }
+
+            if self.sysfs {
+                entities.push(
+                    Entity::new("binary_sensor", &format!("{}_link", interface))
+                        .device_class("connectivity")
+                        .state_class("")
+                        .icon("mdi:ethernet"),
+                );
+                entities.push(
+                    Entity::new("sensor", &format!("{}_link_speed", interface))
+                        .device_class("data_rate")
+                        .state_class("measurement")
+                        .unit("Mbit/s")
+                        .icon("mdi:speedometer"),
+                );
+
+                for family in ["ipv4", "ipv6"] {
+                    entities.push(
+                        Entity::new("sensor", &format!("{}_{}", interface, family))
+                            .state_class("")
+                            .icon("mdi:ip-network"),
+                    );
+                }
+
+                for counter in PROBLEM_COUNTERS {
+                    entities.push(
+                        Entity::new("sensor", &format!("{}_{}", interface, counter))
+                            .state_class("measurement")
+                            .unit("packets/min")
+                            .icon("mdi:alert-network"),
+                    );
+                }
+            }
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let system = &mut self.system;
+        system.refresh_networks();
+        let boot_time = system.boot_time();
+        let month_start = self
+            .monthly_reset_day
+            .map(|reset_day| month_start(SystemTime::now(), reset_day))
+            .transpose()?;
+
+        let mut readings = Vec::new();
+        for (interface, data) in system.networks().iter() {
+            if self.interfaces.contains(interface) {
+                for (direction, raw) in [
+                    ("received", data.total_received()),
+                    ("transmitted", data.total_transmitted()),
+                ] {
+                    let entity_name = format!("{}_{}", interface, direction);
+                    let total = self.state.accumulate(&entity_name, raw, boot_time);
+
+                    if let Some(month_start) = month_start {
+                        let monthly_name = format!("{}_this_month", entity_name);
+                        let monthly =
+                            self.state
+                                .since_period_start(&monthly_name, total, month_start);
+
+                        readings.push(Reading::new(monthly_name, monthly.to_string()));
+                    }
+
+                    readings.push(Reading::new(entity_name, total.to_string()));
+                }
+            }
+        }
+
+        if self.sysfs {
+            let mut addresses = addresses().await?;
+
+            for interface in self.interfaces.iter() {
+                let up = Self::link_up(interface).await;
+                readings.push(Reading::new(
+                    format!("{}_link", interface),
+                    if up { "ON" } else { "OFF" },
+                ));
+
+                if let Some(speed) = Self::link_speed(interface).await {
+                    readings.push(Reading::new(
+                        format!("{}_link_speed", interface),
+                        speed.to_string(),
+                    ));
+                }
+
+                // Addresses only reachable from the link itself aren't interesting, so the first global one is reported.
+                let interface_addresses = addresses.remove(interface).unwrap_or_default();
+                for (family, ip_family) in [("ipv4", "inet"), ("ipv6", "inet6")] {
+                    let address = interface_addresses
+                        .iter()
+                        .find(|address| address.family == ip_family && address.scope == "global")
+                        .map_or("", |address| address.local.as_str());
+
+                    readings.push(Reading::new(format!("{}_{}", interface, family), address));
+                }
+
+                // Rates are measured between updates, so there's nothing to report the first time.
+                let now = Instant::now();
+                match Self::problem_counters(interface).await {
+                    Some(counters) => {
+                        if let Some((last_time, last_counters)) = self
+                            .last_problem_counters
+                            .insert(interface.clone(), (now, counters))
+                        {
+                            let minutes = now.duration_since(last_time).as_secs_f64() / 60.0;
+
+                            for ((name, count), last_count) in
+                                PROBLEM_COUNTERS.iter().zip(counters).zip(last_counters)
+                            {
+                                // The counters start over when the interface is recreated.
+                                let rate = count.saturating_sub(last_count) as f64 / minutes;
+                                readings.push(Reading::new(
+                                    format!("{}_{}", interface, name),
+                                    rate.to_string(),
+                                ));
+                            }
+                        }
+                    }
+                    None => {
+                        self.last_problem_counters.remove(interface);
+                    }
+                }
+            }
+        }
+
+        Ok(readings)
+    }
+}
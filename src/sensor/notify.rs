@@ -0,0 +1,283 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::{collections::HashMap, env};
+use tokio::process::Command;
+use zbus::{dbus_proxy, zvariant::Value};
+
+/// The parts of the notification server used to hold notifications back, which KDE and some other
+/// desktops support.
+#[dbus_proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    /// Holds notifications back until the returned cookie is given to `un_inhibit`.
+    fn inhibit(
+        &self,
+        desktop_entry: &str,
+        reason: &str,
+        hints: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<u32>;
+
+    fn un_inhibit(&self, cookie: u32) -> zbus::Result<()>;
+
+    /// Whether notifications are being held back, by us or anyone else.
+    #[dbus_proxy(property)]
+    fn inhibited(&self) -> zbus::Result<bool>;
+}
+
+/// What a notification looks like when it's sent as JSON. A payload that isn't JSON is used as the body.
+#[derive(Deserialize)]
+struct Notification {
+    #[serde(default = "Notification::default_title")]
+    title: String,
+
+    #[serde(alias = "message")]
+    body: String,
+
+    #[serde(default)]
+    urgency: Urgency,
+}
+
+impl Notification {
+    fn default_title() -> String {
+        String::from("Home Assistant")
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum Urgency {
+    #[serde(rename = "low")]
+    Low,
+
+    #[serde(rename = "normal")]
+    Normal,
+
+    #[serde(rename = "critical")]
+    Critical,
+}
+
+impl Default for Urgency {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// Shows messages from Home Assistant as desktop notifications.
+pub struct Notifier {
+    connection: LazyConnection,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::Session),
+        }
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Notifier {
+    fn name(&self) -> &str {
+        "notify"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("notify", "notify")
+            .state_class("")
+            .icon("mdi:message-badge-outline")
+            .accepts_commands(ActionClass::Desktop)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(Vec::new())
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let notification = match serde_json::from_str(payload) {
+            Ok(notification) => notification,
+            Err(_) => Notification {
+                title: Notification::default_title(),
+                body: payload.to_string(),
+                urgency: Urgency::default(),
+            },
+        };
+
+        let mut hints = HashMap::new();
+        hints.insert("urgency", Value::U8(notification.urgency as u8));
+
+        self.connection
+            .get()
+            .await?
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "Notify",
+                &(
+                    "system-mqtt",
+                    0u32, // Don't replace an existing notification.
+                    "",   // No icon.
+                    notification.title.as_str(),
+                    notification.body.as_str(),
+                    Vec::<&str>::new(), // No actions.
+                    hints,
+                    -1i32, // Let the notification server decide when it goes away.
+                ),
+            )
+            .await
+            .context("Failed to show notification.")?;
+
+        Ok(Vec::new())
+    }
+}
+
+/// A switch that's on while the desktop isn't showing notifications, which can also be flipped.
+///
+/// GNOME keeps this in its settings, which we read and write with `gsettings`. Other desktops,
+/// such as KDE, are asked through the notification server.
+pub struct DoNotDisturb {
+    gnome: bool,
+    connection: LazyConnection,
+
+    /// Given to us by the notification server when we held notifications back.
+    cookie: Option<u32>,
+}
+
+impl DoNotDisturb {
+    pub fn new() -> Self {
+        // Looks like `ubuntu:GNOME`.
+        let gnome = env::var("XDG_CURRENT_DESKTOP").map_or(false, |desktop| {
+            desktop.split(':').any(|name| name == "GNOME")
+        });
+
+        Self {
+            gnome,
+            connection: LazyConnection::new(Bus::Session),
+            cookie: None,
+        }
+    }
+
+    async fn read_state(&self) -> Result<Reading> {
+        let enabled = if self.gnome {
+            !gsettings(&["get", "org.gnome.desktop.notifications", "show-banners"])
+                .await?
+                .trim()
+                .parse::<bool>()
+                .context("gsettings did not report whether notifications are shown.")?
+        } else {
+            let connection = self.connection.get().await?;
+            NotificationsProxy::new(&connection)
+                .await?
+                .inhibited()
+                .await
+                .context("Failed to read whether notifications are held back.")?
+        };
+
+        Ok(Reading::new(
+            "do_not_disturb",
+            if enabled { "ON" } else { "OFF" },
+        ))
+    }
+}
+
+impl Default for DoNotDisturb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `gsettings` and returns what it printed.
+async fn gsettings(arguments: &[&str]) -> Result<String> {
+    let output = Command::new("gsettings")
+        .args(arguments)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run gsettings.")?;
+
+    if !output.status.success() {
+        bail!(
+            "gsettings exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[async_trait(?Send)]
+impl Sensor for DoNotDisturb {
+    fn name(&self) -> &str {
+        "do_not_disturb"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("switch", "do_not_disturb")
+            .state_class("")
+            .icon("mdi:bell-off")
+            .accepts_commands(ActionClass::Desktop)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        // Polled, so changes made on the desktop show up too.
+        Ok(vec![self.read_state().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let enable = match payload {
+            "ON" => true,
+            "OFF" => false,
+            payload => bail!("Unknown do not disturb command `{}`.", payload),
+        };
+
+        if self.gnome {
+            gsettings(&[
+                "set",
+                "org.gnome.desktop.notifications",
+                "show-banners",
+                if enable { "false" } else { "true" },
+            ])
+            .await?;
+        } else {
+            let connection = self.connection.get().await?;
+            let notifications = NotificationsProxy::new(&connection).await?;
+
+            // Notifications held back by someone else stay that way until they let them go.
+            match (enable, self.cookie) {
+                (true, None) => {
+                    self.cookie = Some(
+                        notifications
+                            .inhibit("system-mqtt", "Asked to by Home Assistant", HashMap::new())
+                            .await
+                            .context("Failed to hold notifications back.")?,
+                    );
+                }
+                (false, Some(cookie)) => {
+                    notifications
+                        .un_inhibit(cookie)
+                        .await
+                        .context("Failed to let notifications through.")?;
+                    self.cookie = None;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(vec![self.read_state().await?])
+    }
+}
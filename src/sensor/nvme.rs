@@ -0,0 +1,109 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::Entity,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// The parts of an NVMe drive's SMART log we care about, as printed by `nvme smart-log -o json`.
+#[derive(Deserialize)]
+struct SmartLog {
+    /// In Kelvin.
+    temperature: f64,
+
+    /// Percent of the spare capacity left.
+    avail_spare: f64,
+
+    /// The estimated percent of the drive's life used. It can go past 100.
+    /// Older versions of nvme-cli spell it differently.
+    #[serde(alias = "percentage_used")]
+    percent_used: f64,
+}
+
+/// Wear, spare capacity and temperature of NVMe drives.
+///
+/// This goes through the `nvme` command from nvme-cli, which needs root.
+pub struct NvmeSensor {
+    devices: Vec<String>,
+    privileged: Privileged,
+}
+
+impl NvmeSensor {
+    pub fn new(devices: Vec<String>, privileged: Privileged) -> Self {
+        Self {
+            devices,
+            privileged,
+        }
+    }
+
+    async fn read_device(&self, device: &str) -> Result<Vec<Reading>> {
+        let output = self
+            .privileged
+            .run(Request::NvmeSmartLog {
+                device: device.to_string(),
+            })
+            .await?;
+
+        let log: SmartLog = serde_json::from_str(&output)
+            .with_context(|| format!("Failed to parse SMART log of `{}`.", device))?;
+
+        Ok(vec![
+            Reading::new(format!("{}_wear", device), log.percent_used.to_string()),
+            Reading::new(
+                format!("{}_available_spare", device),
+                log.avail_spare.to_string(),
+            ),
+            Reading::new(
+                format!("{}_temperature", device),
+                (log.temperature - 273.15).round().to_string(),
+            ),
+        ])
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for NvmeSensor {
+    fn name(&self) -> &str {
+        "nvme"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for device in self.devices.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_wear", device))
+                    .state_class("measurement")
+                    .unit("%")
+                    .icon("mdi:harddisk"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_available_spare", device))
+                    .state_class("measurement")
+                    .unit("%")
+                    .icon("mdi:harddisk-plus"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_temperature", device))
+                    .device_class("temperature")
+                    .state_class("measurement")
+                    .unit("°C")
+                    .icon("mdi:thermometer"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        for device in self.devices.iter() {
+            readings.extend(self.read_device(device).await?);
+        }
+
+        Ok(readings)
+    }
+}
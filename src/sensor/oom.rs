@@ -0,0 +1,133 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::json;
+use tokio::{fs, process::Command};
+
+/// A process the kernel killed for running out of memory.
+struct Victim {
+    name: String,
+    pid: u32,
+}
+
+/// How many processes the kernel's OOM killer has killed since boot, and which one it killed last.
+///
+/// The count comes from `/proc/vmstat`, and includes kills inside cgroups that ran out of memory.
+/// The name of the last process killed comes from the kernel's messages in the journal, so
+/// system-mqtt needs to be allowed to read the system journal (root, or members of the
+/// `systemd-journal` group) to know it.
+pub struct OomKills {
+    /// Matches the message the kernel logs for each kill, capturing the PID and name of the process.
+    kill: Regex,
+
+    /// The count the last time we looked, so we only search the kernel's messages after a kill.
+    last_count: Option<u64>,
+
+    last_victim: Option<Victim>,
+}
+
+impl OomKills {
+    pub fn new() -> Self {
+        Self {
+            // Such as `Out of memory: Killed process 1234 (java) total-vm:...`, or the same
+            // starting with `Memory cgroup out of memory:` when a cgroup ran out.
+            kill: Regex::new(r"out of memory: Killed process (\d+) \(([^)]*)\)")
+                .expect("Failed to compile OOM kill pattern."),
+            last_count: None,
+            last_victim: None,
+        }
+    }
+
+    async fn read_count() -> Result<u64> {
+        fs::read_to_string("/proc/vmstat")
+            .await
+            .context("Failed to read /proc/vmstat.")?
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .context("The kernel doesn't count OOM kills. It needs to be at least 4.13.")?
+            .trim()
+            .parse()
+            .context("OOM kill count is not a number.")
+    }
+
+    /// The last process killed since boot, from the kernel's messages.
+    async fn find_last_victim(&self) -> Result<Option<Victim>> {
+        let output = Command::new("journalctl")
+            .args(["--dmesg", "--output=cat", "--no-pager", "--quiet"])
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run journalctl.")?;
+
+        if !output.status.success() {
+            bail!(
+                "journalctl exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .lines()
+            .rev()
+            .find_map(|line| self.kill.captures(line))
+            .and_then(|captures| {
+                Some(Victim {
+                    pid: captures.get(1)?.as_str().parse().ok()?,
+                    name: captures.get(2)?.as_str().to_string(),
+                })
+            }))
+    }
+}
+
+impl Default for OomKills {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for OomKills {
+    fn name(&self) -> &str {
+        "oom_kills"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "oom_kills")
+                .state_class("total_increasing")
+                .icon("mdi:skull-crossbones"),
+            Entity::new("sensor", "oom_last_killed")
+                .state_class("")
+                .icon("mdi:skull-crossbones")
+                .json_attributes(),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let count = Self::read_count().await?;
+
+        if count > 0 && self.last_count != Some(count) {
+            // Knowing how many kills there were matters more than knowing who was killed.
+            match self.find_last_victim().await {
+                Ok(Some(victim)) => self.last_victim = Some(victim),
+                Ok(None) => {}
+                Err(error) => log::warn!("Failed to find the last OOM kill: {:?}", error),
+            }
+        }
+        self.last_count = Some(count);
+
+        let mut readings = vec![Reading::new("oom_kills", count.to_string())];
+        if let Some(victim) = &self.last_victim {
+            readings.push(
+                Reading::new("oom_last_killed", victim.name.as_str())
+                    .attributes(json!({ "pid": victim.pid }).to_string()),
+            );
+        }
+
+        Ok(readings)
+    }
+}
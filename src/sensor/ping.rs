@@ -0,0 +1,133 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PingTarget {
+    /// The name the sensors will be reported as.
+    pub name: String,
+
+    /// The host name or address to ping.
+    pub host: String,
+
+    /// How many pings to send each update.
+    #[serde(default = "PingTarget::default_count")]
+    pub count: u32,
+}
+
+impl PingTarget {
+    fn default_count() -> u32 {
+        3
+    }
+}
+
+/// Round trip time and packet loss to the configured hosts.
+///
+/// This goes through the `ping` command, since sending ICMP ourselves would need privileges.
+pub struct PingSensor {
+    targets: Vec<PingTarget>,
+}
+
+impl PingSensor {
+    pub fn new(targets: Vec<PingTarget>) -> Self {
+        Self { targets }
+    }
+}
+
+async fn ping(target: &PingTarget) -> Result<Vec<Reading>> {
+    let output = Command::new("ping")
+        .arg("-q")
+        .arg("-n")
+        .args(["-c", &target.count.to_string()])
+        .args(["-W", "1"])
+        .arg(&target.host)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run ping.")?;
+
+    // ping exits with an error when nothing comes back, but still prints its statistics.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Looks like `3 packets transmitted, 3 received, 0% packet loss, time 2003ms`.
+    let packet_loss = stdout
+        .lines()
+        .find(|line| line.contains("packet loss"))
+        .and_then(|line| {
+            line.split(", ")
+                .find_map(|part| part.strip_suffix("% packet loss"))
+        })
+        .with_context(|| {
+            format!(
+                "Failed to ping `{}`: {}",
+                target.host,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )
+        })?;
+
+    let mut readings = vec![Reading::new(
+        format!("{}_packet_loss", target.name),
+        packet_loss,
+    )];
+
+    // Looks like `rtt min/avg/max/mdev = 0.039/0.046/0.054/0.006 ms`.
+    // There isn't one if every ping was lost.
+    if let Some(average) = stdout
+        .lines()
+        .find_map(|line| line.split_once(" = "))
+        .and_then(|(_, times)| times.split('/').nth(1))
+    {
+        readings.push(Reading::new(format!("{}_latency", target.name), average));
+    }
+
+    Ok(readings)
+}
+
+#[async_trait(?Send)]
+impl Sensor for PingSensor {
+    fn name(&self) -> &str {
+        "ping"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for target in self.targets.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_latency", target.name))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("ms")
+                    .icon("mdi:timer-outline"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_packet_loss", target.name))
+                    .state_class("measurement")
+                    .unit("%")
+                    .icon("mdi:lan-disconnect"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        // Pinging takes a while, so every target is pinged at once.
+        let results = join_all(self.targets.iter().map(ping)).await;
+
+        let mut readings = Vec::new();
+        for result in results {
+            match result {
+                Ok(target_readings) => readings.extend(target_readings),
+                // One unreachable resolver or a typo shouldn't hide the other targets.
+                Err(error) => log::warn!("{:?}", error),
+            }
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,106 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::{net::TcpStream, time};
+
+/// How long to wait for a connection before calling the port unreachable.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PortCheck {
+    /// The name the sensors will be reported as.
+    pub name: String,
+
+    /// The host name or address to connect to.
+    pub host: String,
+
+    pub port: u16,
+}
+
+/// Whether TCP ports can be connected to, and how long it takes.
+pub struct PortSensor {
+    checks: Vec<PortCheck>,
+}
+
+impl PortSensor {
+    pub fn new(checks: Vec<PortCheck>) -> Self {
+        Self { checks }
+    }
+}
+
+async fn check(port_check: &PortCheck) -> Vec<Reading> {
+    let start = Instant::now();
+    let connection = time::timeout(
+        CONNECT_TIMEOUT,
+        TcpStream::connect((port_check.host.as_str(), port_check.port)),
+    )
+    .await;
+
+    match connection {
+        Ok(Ok(_)) => vec![
+            Reading::new(port_check.name.as_str(), "ON"),
+            Reading::new(
+                format!("{}_connect_time", port_check.name),
+                (start.elapsed().as_secs_f64() * 1000.0).to_string(),
+            ),
+        ],
+        Ok(Err(error)) => {
+            log::info!(
+                "Connecting to {}:{} failed: {}",
+                port_check.host,
+                port_check.port,
+                error
+            );
+            vec![Reading::new(port_check.name.as_str(), "OFF")]
+        }
+        Err(_) => {
+            log::info!(
+                "Connecting to {}:{} timed out.",
+                port_check.host,
+                port_check.port
+            );
+            vec![Reading::new(port_check.name.as_str(), "OFF")]
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for PortSensor {
+    fn name(&self) -> &str {
+        "port"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for port_check in self.checks.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &port_check.name)
+                    .device_class("connectivity")
+                    .state_class("")
+                    .icon("mdi:lan-connect"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_connect_time", port_check.name))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("ms")
+                    .icon("mdi:timer-outline"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(join_all(self.checks.iter().map(check))
+            .await
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
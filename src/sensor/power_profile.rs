@@ -0,0 +1,114 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{format_value, Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use zbus::{dbus_proxy, zvariant::OwnedValue, CacheProperties, Connection};
+
+#[dbus_proxy(
+    interface = "net.hadess.PowerProfiles",
+    default_service = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles"
+)]
+trait PowerProfiles {
+    #[dbus_proxy(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn set_active_profile(&self, profile: &str) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn profiles(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+/// The power profile from power-profiles-daemon, such as `balanced` or `power-saver`, which can also be set.
+pub struct PowerProfile {
+    connection: LazyConnection,
+
+    /// The profiles this system has.
+    profiles: Vec<String>,
+}
+
+impl PowerProfile {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::System),
+            profiles: Vec::new(),
+        }
+    }
+
+    async fn proxy(connection: &Connection) -> Result<PowerProfilesProxy<'static>> {
+        Ok(PowerProfilesProxy::builder(connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?)
+    }
+
+    async fn read_profile(connection: &Connection) -> Result<Reading> {
+        let profile = Self::proxy(connection)
+            .await?
+            .active_profile()
+            .await
+            .context("Failed to read power profile.")?;
+
+        Ok(Reading::new("power_profile", profile))
+    }
+}
+
+impl Default for PowerProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for PowerProfile {
+    fn name(&self) -> &str {
+        "power_profile"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let connection = self.connection.get().await?;
+
+        // Not every system has every profile. Performance in particular needs hardware support.
+        self.profiles = Self::proxy(&connection)
+            .await?
+            .profiles()
+            .await
+            .context("Failed to list power profiles.")?
+            .iter()
+            .filter_map(|profile| profile.get("Profile"))
+            .map(|profile| format_value(profile))
+            .collect::<Result<_>>()?;
+
+        Ok(vec![Entity::new("select", "power_profile")
+            .state_class("")
+            .icon("mdi:leaf")
+            .options(self.profiles.clone())
+            .accepts_commands(ActionClass::Hardware)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+
+        Ok(vec![Self::read_profile(&connection).await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        if !self.profiles.iter().any(|profile| profile == payload) {
+            bail!("`{}` is not a power profile.", payload);
+        }
+
+        let connection = self.connection.get().await?;
+        Self::proxy(&connection)
+            .await?
+            .set_active_profile(payload)
+            .await
+            .context("Failed to set power profile.")?;
+
+        Ok(vec![Self::read_profile(&connection).await?])
+    }
+}
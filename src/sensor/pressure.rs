@@ -0,0 +1,119 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::fs;
+
+/// The resources the kernel reports pressure for, in `/proc/pressure`.
+const RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+/// One line of a pressure file, like `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+/// The averages are the percentage of time tasks were stalled waiting for the resource.
+struct Pressure {
+    /// `some` when at least one task was stalled, and `full` when all of them were.
+    kind: String,
+    avg10: f64,
+    avg60: f64,
+    avg300: f64,
+}
+
+impl Pressure {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next()?.to_string();
+
+        let mut average = |name: &str| -> Option<f64> {
+            fields
+                .next()?
+                .strip_prefix(name)?
+                .strip_prefix('=')?
+                .parse()
+                .ok()
+        };
+
+        Some(Self {
+            kind,
+            avg10: average("avg10")?,
+            avg60: average("avg60")?,
+            avg300: average("avg300")?,
+        })
+    }
+}
+
+/// How much of the time tasks were stalled waiting on the CPU, memory or IO, from the kernel's
+/// Pressure Stall Information. A machine can be busy without anything having to wait, which
+/// utilization doesn't tell apart.
+pub struct PressureSensor;
+
+impl PressureSensor {
+    /// The entities of a resource. The CPU only has a `full` line on newer kernels.
+    async fn entity_names(resource: &str) -> Result<Vec<String>> {
+        let path = format!("/proc/pressure/{}", resource);
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {}. Is PSI enabled in the kernel?", path))?;
+
+        Ok(contents
+            .lines()
+            .filter_map(Pressure::parse)
+            .map(|pressure| format!("pressure_{}_{}", resource, pressure.kind))
+            .collect())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for PressureSensor {
+    fn name(&self) -> &str {
+        "pressure"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for resource in RESOURCES {
+            for name in Self::entity_names(resource).await? {
+                entities.push(
+                    Entity::new("sensor", &name)
+                        .state_class("measurement")
+                        .unit("%")
+                        .icon("mdi:gauge-full")
+                        .json_attributes(),
+                );
+            }
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        for resource in RESOURCES {
+            let path = format!("/proc/pressure/{}", resource);
+            let contents = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("Failed to read {}.", path))?;
+
+            for pressure in contents.lines().filter_map(Pressure::parse) {
+                // The state is the average over the last minute, which is about how often we
+                // update. The others are there for a closer or longer look.
+                readings.push(
+                    Reading::new(
+                        format!("pressure_{}_{}", resource, pressure.kind),
+                        pressure.avg60.to_string(),
+                    )
+                    .attributes(
+                        json!({
+                            "avg10": pressure.avg10,
+                            "avg300": pressure.avg300,
+                        })
+                        .to_string(),
+                    ),
+                );
+            }
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,127 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PublicIpConfig {
+    /// An endpoint that responds with our public IPv4 address as plain text.
+    #[serde(default = "PublicIpConfig::default_ipv4_url")]
+    pub ipv4_url: Url,
+
+    /// An endpoint that responds with our public IPv6 address as plain text.
+    /// If not set, only the IPv4 address is reported.
+    #[serde(default = "PublicIpConfig::default_ipv6_url")]
+    pub ipv6_url: Option<Url>,
+
+    /// How often to check. Public addresses rarely change, so this defaults to every 15 minutes.
+    #[serde(default = "PublicIpConfig::default_interval")]
+    pub interval: Duration,
+}
+
+impl PublicIpConfig {
+    fn default_ipv4_url() -> Url {
+        Url::parse("https://api.ipify.org").expect("Failed to parse default URL.")
+    }
+
+    fn default_ipv6_url() -> Option<Url> {
+        Some(Url::parse("https://api6.ipify.org").expect("Failed to parse default URL."))
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(15 * 60)
+    }
+}
+
+/// The addresses the rest of the internet sees us at.
+pub struct PublicIpSensor {
+    config: PublicIpConfig,
+    client: reqwest::Client,
+    last_check: Option<Instant>,
+}
+
+impl PublicIpSensor {
+    pub fn new(config: PublicIpConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client.")?;
+
+        Ok(Self {
+            config,
+            client,
+            last_check: None,
+        })
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<IpAddr> {
+        let address = self
+            .client
+            .get(url.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        address
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` did not respond with an IP address.", url))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for PublicIpSensor {
+    fn name(&self) -> &str {
+        "public_ip"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = vec![Entity::new("sensor", "public_ipv4")
+            .state_class("")
+            .icon("mdi:ip-network")];
+
+        if self.config.ipv6_url.is_some() {
+            entities.push(
+                Entity::new("sensor", "public_ipv6")
+                    .state_class("")
+                    .icon("mdi:ip-network"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let now = Instant::now();
+        if let Some(last_check) = self.last_check {
+            if now.duration_since(last_check) < self.config.interval {
+                return Ok(Vec::new());
+            }
+        }
+        self.last_check = Some(now);
+
+        let ipv4 = self
+            .fetch(&self.config.ipv4_url)
+            .await
+            .context("Failed to find public IPv4 address.")?;
+        let mut readings = vec![Reading::new("public_ipv4", ipv4.to_string())];
+
+        if let Some(ipv6_url) = &self.config.ipv6_url {
+            // Plenty of networks don't have IPv6, which shouldn't stop the IPv4 address from being reported.
+            match self.fetch(ipv6_url).await {
+                Ok(ipv6) => readings.push(Reading::new("public_ipv6", ipv6.to_string())),
+                Err(error) => log::debug!("Failed to find public IPv6 address: {:?}", error),
+            }
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,233 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+use tokio::{fs, process::Command};
+
+const BLOCK_DEVICES: &str = "/sys/block";
+
+/// What an array or pool is busy with, if anything.
+struct SyncState {
+    /// What's being done, such as `resync`, `check`, `resilver` or `scrub`.
+    action: String,
+
+    /// How far along it is, in percent.
+    progress: Option<f64>,
+
+    /// How many minutes are estimated to be left.
+    remaining: Option<f64>,
+}
+
+impl SyncState {
+    fn idle() -> Self {
+        Self {
+            action: String::from("idle"),
+            progress: None,
+            remaining: None,
+        }
+    }
+}
+
+/// Progress of rebuilds, resyncs and checks of Linux software RAID arrays, and resilvers and scrubs of ZFS pools.
+///
+/// Progress and the time left are unavailable while an array or pool isn't doing anything.
+pub struct ResyncSensor {
+    md_arrays: Vec<String>,
+    zfs_pools: Vec<String>,
+}
+
+impl ResyncSensor {
+    pub fn new(md_arrays: Vec<String>, zfs_pools: Vec<String>) -> Self {
+        Self {
+            md_arrays,
+            zfs_pools,
+        }
+    }
+}
+
+async fn read_md_attribute(array: &str, attribute: &str) -> Result<String> {
+    let path = Path::new(BLOCK_DEVICES)
+        .join(array)
+        .join("md")
+        .join(attribute);
+    let value = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read `{}`.", path.display()))?;
+
+    Ok(value.trim().to_string())
+}
+
+/// Reads the state of an md array from sysfs.
+async fn md_state(array: &str) -> Result<SyncState> {
+    let action = read_md_attribute(array, "sync_action").await?;
+    if action == "idle" || action == "frozen" {
+        return Ok(SyncState::idle());
+    }
+
+    // This is `<done> / <total>` in sectors, or `none` if it hasn't started yet.
+    let completed = read_md_attribute(array, "sync_completed").await?;
+    let (done, total) = match completed.split_once('/') {
+        Some((done, total)) => (
+            done.trim()
+                .parse::<f64>()
+                .context("Sync progress is not a number.")?,
+            total
+                .trim()
+                .parse::<f64>()
+                .context("Sync size is not a number.")?,
+        ),
+        None => {
+            return Ok(SyncState {
+                action,
+                progress: None,
+                remaining: None,
+            })
+        }
+    };
+
+    // In KiB per second, or `none`.
+    let speed = read_md_attribute(array, "sync_speed")
+        .await?
+        .parse::<f64>()
+        .ok()
+        .filter(|speed| *speed > 0.0);
+
+    Ok(SyncState {
+        action,
+        progress: (total > 0.0).then(|| done / total * 100.0),
+        // Sectors are 512 bytes, regardless of the drives underneath.
+        remaining: speed.map(|speed| (total - done) / 2.0 / speed / 60.0),
+    })
+}
+
+/// Parses how long `zpool status` says is left, like `00:34:55` or `1 days 02:00:00`.
+fn parse_zfs_remaining(remaining: &str) -> Option<f64> {
+    let (days, time) = match remaining.split_once(" days ") {
+        Some((days, time)) => (days.trim().parse::<f64>().ok()?, time),
+        None => (0.0, remaining),
+    };
+
+    let mut minutes = days * 24.0 * 60.0;
+    let mut parts = time.trim().split(':');
+    minutes += parts.next()?.parse::<f64>().ok()? * 60.0;
+    minutes += parts.next()?.parse::<f64>().ok()?;
+    minutes += parts.next()?.parse::<f64>().ok()? / 60.0;
+
+    Some(minutes)
+}
+
+/// Reads the state of a ZFS pool from `zpool status`.
+async fn zfs_state(pool: &str) -> Result<SyncState> {
+    let output = Command::new("zpool")
+        .args(["status", pool])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run zpool.")?;
+
+    if !output.status.success() {
+        bail!(
+            "zpool exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    let status = String::from_utf8_lossy(&output.stdout);
+    let mut lines = status.lines().map(str::trim);
+
+    // Looks like `scan: scrub in progress since Sun Jul 25 16:07:49 2021`.
+    let action = match lines.find_map(|line| line.strip_prefix("scan:")) {
+        Some(scan) if scan.contains("in progress") => scan
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        _ => return Ok(SyncState::idle()),
+    };
+
+    // Followed by a line like `0B repaired, 29.30% done, 00:34:55 to go`.
+    let mut state = SyncState {
+        action,
+        progress: None,
+        remaining: None,
+    };
+    if let Some(line) = lines.find(|line| line.contains("% done")) {
+        for part in line.split(", ") {
+            if let Some(progress) = part.strip_suffix("% done") {
+                state.progress = progress.trim().parse().ok();
+            } else if let Some(remaining) = part.strip_suffix(" to go") {
+                state.remaining = parse_zfs_remaining(remaining);
+            }
+        }
+    }
+
+    Ok(state)
+}
+
+#[async_trait(?Send)]
+impl Sensor for ResyncSensor {
+    fn name(&self) -> &str {
+        "resync"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for name in self.md_arrays.iter().chain(self.zfs_pools.iter()) {
+            entities.push(
+                Entity::new("sensor", &format!("{}_sync_action", name))
+                    .state_class("")
+                    .icon("mdi:harddisk"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_sync_progress", name))
+                    .state_class("measurement")
+                    .unit("%")
+                    .icon("mdi:progress-clock"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_sync_remaining", name))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("min")
+                    .icon("mdi:timer-sand"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut states = Vec::new();
+
+        for array in self.md_arrays.iter() {
+            states.push((array, md_state(array).await?));
+        }
+
+        for pool in self.zfs_pools.iter() {
+            states.push((pool, zfs_state(pool).await?));
+        }
+
+        let mut readings = Vec::new();
+
+        for (name, state) in states {
+            readings.push(Reading::new(format!("{}_sync_action", name), state.action));
+
+            let progress_name = format!("{}_sync_progress", name);
+            readings.push(match state.progress {
+                Some(progress) => Reading::new(progress_name, format!("{:.1}", progress)),
+                None => Reading::unavailable(progress_name),
+            });
+
+            let remaining_name = format!("{}_sync_remaining", name);
+            readings.push(match state.remaining {
+                Some(remaining) => Reading::new(remaining_name, remaining.round().to_string()),
+                None => Reading::unavailable(remaining_name),
+            });
+        }
+
+        Ok(readings)
+    }
+}
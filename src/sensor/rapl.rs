@@ -0,0 +1,134 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::{path::PathBuf, time::Instant};
+use tokio::fs;
+
+const POWERCAP: &str = "/sys/class/powercap";
+
+/// A RAPL power zone, such as a CPU package.
+struct Zone {
+    path: PathBuf,
+
+    /// The name of the entity its power draw is reported as.
+    entity_name: String,
+
+    /// The energy counter wraps around to zero after this many microjoules.
+    max_energy: u64,
+
+    /// The energy counter the last time it was read, and when.
+    last: Option<(u64, Instant)>,
+}
+
+impl Zone {
+    async fn energy(&self) -> Result<u64> {
+        fs::read_to_string(self.path.join("energy_uj"))
+            .await
+            .with_context(|| format!("Failed to read energy of `{}`.", self.path.display()))?
+            .trim()
+            .parse()
+            .context("Energy is not a number.")
+    }
+}
+
+/// How much power the CPU packages are drawing, from the Intel and AMD RAPL interfaces.
+///
+/// The energy counters can only be read by root.
+pub struct RaplSensor {
+    zones: Vec<Zone>,
+}
+
+impl RaplSensor {
+    pub fn new() -> Result<Self> {
+        let mut zones = Vec::new();
+
+        for entry in std::fs::read_dir(POWERCAP).context("Failed to list power zones.")? {
+            let path = entry?.path();
+            let file_name = path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            // Only the top level zones, such as `intel-rapl:0`, are packages. The ones
+            // below them, such as `intel-rapl:0:0`, are parts of a package.
+            if !file_name.starts_with("intel-rapl:") || file_name.matches(':').count() != 1 {
+                continue;
+            }
+
+            // Named something like `package-0`.
+            let name = std::fs::read_to_string(path.join("name"))
+                .with_context(|| format!("Failed to read name of `{}`.", path.display()))?;
+            let max_energy = std::fs::read_to_string(path.join("max_energy_range_uj"))
+                .with_context(|| format!("Failed to read range of `{}`.", path.display()))?
+                .trim()
+                .parse()
+                .context("Energy range is not a number.")?;
+
+            zones.push(Zone {
+                entity_name: format!("cpu_{}_power", name.trim().replace('-', "_")),
+                path,
+                max_energy,
+                last: None,
+            });
+        }
+
+        if zones.is_empty() {
+            bail!("No RAPL power zones found. Is the intel_rapl module loaded?");
+        }
+
+        Ok(Self { zones })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for RaplSensor {
+    fn name(&self) -> &str {
+        "rapl"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .zones
+            .iter()
+            .map(|zone| {
+                Entity::new("sensor", &zone.entity_name)
+                    .device_class("power")
+                    .state_class("measurement")
+                    .unit("W")
+                    .icon("mdi:flash")
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        for zone in self.zones.iter_mut() {
+            let energy = zone.energy().await?;
+            let now = Instant::now();
+
+            // Power is measured between updates, so the first one has nothing to report.
+            if let Some((last_energy, last_time)) = zone.last {
+                let used = if energy >= last_energy {
+                    energy - last_energy
+                } else {
+                    zone.max_energy - last_energy + energy
+                };
+                let seconds = now.duration_since(last_time).as_secs_f64();
+
+                if seconds > 0.0 {
+                    let watts = used as f64 / 1_000_000.0 / seconds;
+                    readings.push(Reading::new(
+                        zone.entity_name.as_str(),
+                        format!("{:.1}", watts),
+                    ));
+                }
+            }
+
+            zone.last = Some((energy, now));
+        }
+
+        Ok(readings)
+    }
+}
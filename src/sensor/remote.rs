@@ -0,0 +1,230 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Run on the remote host. Everything we need comes back in one go, split up by the markers.
+const SCRIPT: &str = "cat /proc/stat; echo '--- meminfo'; cat /proc/meminfo; \
+    echo '--- uptime'; cat /proc/uptime; echo '--- df'; df -Pk /";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RemoteHostConfig {
+    /// The name the host will be reported as, in Home Assistant and at the start of its entities' names.
+    pub name: String,
+
+    /// Where to connect to, as it would be given to `ssh`, such as `admin@nas.local`.
+    pub address: String,
+
+    /// The port sshd listens on, if it isn't 22.
+    pub port: Option<u16>,
+
+    /// The private key to log in with. Defaults to whatever ssh would use.
+    pub identity_file: Option<PathBuf>,
+}
+
+/// Busy and total CPU time, from the first line of `/proc/stat`.
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// CPU, memory and root filesystem usage, along with uptime, of a host that can't run
+/// system-mqtt itself, collected by reading `/proc` over SSH.
+///
+/// The host is reported as a device of its own. Logging in has to work without a password
+/// prompt, since there's nobody around to type one in.
+pub struct RemoteHost {
+    config: RemoteHostConfig,
+
+    /// CPU usage is worked out from the difference between two readings.
+    last_cpu_times: Option<CpuTimes>,
+}
+
+impl RemoteHost {
+    pub fn new(config: RemoteHostConfig) -> Self {
+        Self {
+            config,
+            last_cpu_times: None,
+        }
+    }
+
+    fn entity_name(&self, metric: &str) -> String {
+        format!("{}_{}", self.config.name, metric)
+    }
+
+    async fn run_script(&self) -> Result<String> {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+        if let Some(port) = self.config.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity_file) = &self.config.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+
+        let output = command
+            .arg(&self.config.address)
+            .arg(SCRIPT)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run ssh.")?;
+
+        if !output.status.success() {
+            bail!(
+                "ssh exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+fn parse_cpu_times(stat: &str) -> Result<CpuTimes> {
+    let times = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("cpu "))
+        .context("Remote host did not report CPU times.")?
+        .split_whitespace()
+        .map(|time| time.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Remote CPU time is not a number.")?;
+
+    // Idle and waiting for IO are the fourth and fifth.
+    let idle = times.iter().skip(3).take(2).sum::<u64>();
+    // Guest time is already counted in user time.
+    let total = times.iter().take(8).sum::<u64>();
+
+    Ok(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+fn parse_memory_usage(meminfo: &str) -> Result<f64> {
+    let field = |name: &str| -> Result<f64> {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+            .with_context(|| format!("Remote host did not report `{}`.", name))
+    };
+
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+
+    Ok((total - available) / total * 100.0)
+}
+
+fn parse_disk_usage(df: &str) -> Result<f64> {
+    // The line after the header, like `/dev/sda1 30830568 9462860 19778348 33% /`.
+    let columns: Vec<_> = df
+        .lines()
+        .nth(1)
+        .context("Remote host did not report disk usage.")?
+        .split_whitespace()
+        .collect();
+    let (used, available): (f64, f64) = match columns.as_slice() {
+        [_, _, used, available, ..] => (
+            used.parse().context("Remote disk usage is not a number.")?,
+            available
+                .parse()
+                .context("Remote disk space is not a number.")?,
+        ),
+        _ => bail!("Remote host reported disk usage in an unknown format."),
+    };
+
+    Ok(used / (used + available) * 100.0)
+}
+
+#[async_trait(?Send)]
+impl Sensor for RemoteHost {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let host = &self.config.name;
+
+        Ok(vec![
+            Entity::new("sensor", &self.entity_name("uptime"))
+                .device_class("duration")
+                .state_class("")
+                .unit("s")
+                .icon("mdi:timer-sand")
+                .remote_host(host),
+            Entity::new("sensor", &self.entity_name("cpu"))
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:gauge")
+                .remote_host(host),
+            Entity::new("sensor", &self.entity_name("memory"))
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:gauge")
+                .remote_host(host),
+            Entity::new("sensor", &self.entity_name("disk"))
+                .state_class("measurement")
+                .unit("%")
+                .icon("mdi:harddisk")
+                .remote_host(host),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let output = self
+            .run_script()
+            .await
+            .with_context(|| format!("Failed to collect from `{}`.", self.config.address))?;
+
+        let mut sections = output.split("--- ");
+        let stat = sections.next().unwrap_or_default();
+        let mut section = |name: &str| {
+            sections
+                .next()
+                .and_then(|section| section.strip_prefix(name))
+                .map(str::trim_start)
+                .with_context(|| format!("Remote host did not report its {}.", name))
+        };
+        let meminfo = section("meminfo")?;
+        let uptime = section("uptime")?;
+        let df = section("df")?;
+
+        let uptime: f64 = uptime
+            .split_whitespace()
+            .next()
+            .and_then(|uptime| uptime.parse().ok())
+            .context("Remote uptime is not a number.")?;
+
+        let cpu_times = parse_cpu_times(stat)?;
+        let cpu_name = self.entity_name("cpu");
+        let cpu = match self.last_cpu_times.replace(cpu_times) {
+            Some(last) if cpu_times.total > last.total => {
+                let busy = cpu_times.busy.saturating_sub(last.busy) as f64;
+                let total = (cpu_times.total - last.total) as f64;
+                Reading::new(cpu_name, format!("{:.1}", busy / total * 100.0))
+            }
+            // There's nothing to compare the first reading to.
+            _ => Reading::unavailable(cpu_name),
+        };
+
+        Ok(vec![
+            Reading::new(self.entity_name("uptime"), uptime.round().to_string()),
+            cpu,
+            Reading::new(
+                self.entity_name("memory"),
+                format!("{:.1}", parse_memory_usage(meminfo)?),
+            ),
+            Reading::new(
+                self.entity_name("disk"),
+                format!("{:.1}", parse_disk_usage(df)?),
+            ),
+        ])
+    }
+}
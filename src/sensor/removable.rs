@@ -0,0 +1,112 @@
+use super::{drives, units::SizeUnit, EntityChanges, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::{collections::BTreeMap, path::PathBuf};
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// Whether any removable media, such as USB sticks and SD cards, is mounted, and how full each is.
+///
+/// A usage entity is registered for each piece of media when it's mounted, and removed again once
+/// it's unmounted.
+pub struct RemovableMedia {
+    system: System,
+    unit: SizeUnit,
+
+    /// Maps the mount points of the media we know about to the names they're reported as.
+    mounted: BTreeMap<PathBuf, String>,
+
+    changes: EntityChanges,
+}
+
+impl RemovableMedia {
+    pub fn new(unit: SizeUnit) -> Self {
+        Self {
+            system: System::new(),
+            unit,
+            mounted: BTreeMap::new(),
+            changes: EntityChanges::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for RemovableMedia {
+    fn name(&self) -> &str {
+        "removable_media"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new(
+            "binary_sensor",
+            "removable_media_present",
+        )
+        .state_class("")
+        .icon("mdi:sd")
+        .json_attributes()])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        self.system.refresh_disks_list();
+
+        let mut readings = Vec::new();
+        let mut mounted = BTreeMap::new();
+        for disk in self
+            .system
+            .disks()
+            .iter()
+            .filter(|disk| disk.is_removable())
+        {
+            let mount_point = disk.mount_point().to_path_buf();
+            let name = drives::entity_name("removable", &mount_point);
+
+            if !self.mounted.contains_key(&mount_point) {
+                log::info!("Removable media mounted at `{}`.", mount_point.display());
+
+                self.changes.added.push(
+                    Entity::new("sensor", &name)
+                        .device_class(self.unit.device_class())
+                        .state_class("total")
+                        .unit(self.unit.symbol())
+                        .icon("mdi:sd"),
+                );
+            }
+
+            let used = disk.total_space() - disk.available_space();
+            readings.push(Reading::new(
+                name.as_str(),
+                self.unit.format_bytes(used, disk.total_space()),
+            ));
+
+            mounted.insert(mount_point, name);
+        }
+
+        for (mount_point, name) in self.mounted.iter() {
+            if !mounted.contains_key(mount_point) {
+                log::info!("Removable media at `{}` went away.", mount_point.display());
+                self.changes.removed.push(name.clone());
+            }
+        }
+
+        let mount_points: Vec<_> = mounted.keys().collect();
+        readings.push(
+            Reading::new(
+                "removable_media_present",
+                if mounted.is_empty() { "OFF" } else { "ON" },
+            )
+            .attributes(
+                serde_json::to_string(&json!({ "mount_points": mount_points }))
+                    .context("Failed to serialize mount points.")?,
+            ),
+        );
+
+        self.mounted = mounted;
+
+        Ok(readings)
+    }
+
+    fn entity_changes(&mut self) -> EntityChanges {
+        std::mem::take(&mut self.changes)
+    }
+}
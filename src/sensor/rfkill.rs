@@ -0,0 +1,180 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::path::PathBuf;
+use tokio::fs;
+
+const RFKILL_CLASS: &str = "/sys/class/rfkill";
+
+/// A radio that can be turned off, such as `phy0` or `hci0`.
+struct Radio {
+    /// The device's directory under `/sys/class/rfkill`.
+    path: PathBuf,
+    name: String,
+
+    /// Such as `wlan` or `bluetooth`.
+    kind: String,
+
+    /// Turned off by software, which we can undo.
+    soft_blocked: bool,
+
+    /// Turned off by a hardware switch, which we can't.
+    hard_blocked: bool,
+}
+
+/// Every radio there is right now. They come and go, such as when a Bluetooth adapter is reset.
+async fn radios() -> Result<Vec<Radio>> {
+    let mut radios = Vec::new();
+
+    let mut entries = fs::read_dir(RFKILL_CLASS)
+        .await
+        .context("Failed to list radios.")?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let read = |file: &'static str| {
+            let path = path.join(file);
+            async move {
+                fs::read_to_string(&path)
+                    .await
+                    .with_context(|| format!("Failed to read `{}`.", path.display()))
+                    .map(|value| value.trim().to_string())
+            }
+        };
+
+        radios.push(Radio {
+            name: read("name").await?,
+            kind: read("type").await?,
+            soft_blocked: read("soft").await? == "1",
+            hard_blocked: read("hard").await? == "1",
+            path,
+        });
+    }
+
+    Ok(radios)
+}
+
+/// The switch a kind of radio is controlled by, such as `wifi_radio`.
+fn entity_name(kind: &str) -> String {
+    match kind {
+        "wlan" => String::from("wifi_radio"),
+        kind => format!("{}_radio", kind),
+    }
+}
+
+/// A switch for each kind of radio, such as Wi-Fi and Bluetooth, that turns every radio of that
+/// kind on or off. It's on while all of them are on.
+pub struct RadioSwitches {
+    /// The kinds of radio found when we started.
+    kinds: Vec<String>,
+    privileged: Privileged,
+}
+
+impl RadioSwitches {
+    pub fn new(privileged: Privileged) -> Self {
+        Self {
+            kinds: Vec::new(),
+            privileged,
+        }
+    }
+
+    fn read_states(&self, radios: &[Radio]) -> Vec<Reading> {
+        self.kinds
+            .iter()
+            .map(|kind| {
+                let radios: Vec<&Radio> =
+                    radios.iter().filter(|radio| &radio.kind == kind).collect();
+                let entity = entity_name(kind);
+
+                // The adapter may be gone for now, such as a USB dongle that was unplugged.
+                if radios.is_empty() {
+                    return Reading::unavailable(entity);
+                }
+
+                let on = radios
+                    .iter()
+                    .all(|radio| !radio.soft_blocked && !radio.hard_blocked);
+                let attributes = json!({
+                    "radios": radios.iter().map(|radio| &radio.name).collect::<Vec<_>>(),
+                    "hard_blocked": radios.iter().any(|radio| radio.hard_blocked),
+                });
+
+                Reading::new(entity, if on { "ON" } else { "OFF" })
+                    .attributes(attributes.to_string())
+            })
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for RadioSwitches {
+    fn name(&self) -> &str {
+        "rfkill"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        for radio in radios().await? {
+            if !self.kinds.contains(&radio.kind) {
+                self.kinds.push(radio.kind);
+            }
+        }
+        self.kinds.sort();
+
+        Ok(self
+            .kinds
+            .iter()
+            .map(|kind| {
+                Entity::new("switch", &entity_name(kind))
+                    .state_class("")
+                    .icon(match kind.as_str() {
+                        "wlan" => "mdi:wifi",
+                        "bluetooth" => "mdi:bluetooth",
+                        _ => "mdi:radio-tower",
+                    })
+                    .json_attributes()
+                    .accepts_commands(ActionClass::Network)
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(self.read_states(&radios().await?))
+    }
+
+    async fn command(&mut self, entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let kind = match self
+            .kinds
+            .iter()
+            .find(|kind| self::entity_name(kind) == entity_name)
+        {
+            Some(kind) => kind,
+            None => bail!("`{}` is not a radio.", entity_name),
+        };
+        let blocked = match payload {
+            "ON" => "0",
+            "OFF" => "1",
+            payload => bail!("Unknown radio command `{}`.", payload),
+        };
+
+        for radio in radios().await? {
+            if &radio.kind == kind {
+                log::info!("Turning radio `{}` {}.", radio.name, payload.to_lowercase());
+                self.privileged
+                    .run(Request::WriteSysfs {
+                        path: radio.path.join("soft"),
+                        value: blocked.to_string(),
+                    })
+                    .await
+                    .with_context(|| {
+                        format!("Failed to turn radio `{}` {}.", radio.name, payload)
+                    })?;
+            }
+        }
+
+        Ok(self.read_states(&radios().await?))
+    }
+}
@@ -0,0 +1,77 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScreenshotConfig {
+    /// The command that takes a screenshot. It's run with `sh -c` and must write the image to stdout.
+    #[serde(default = "ScreenshotConfig::default_command")]
+    pub command: String,
+}
+
+impl ScreenshotConfig {
+    fn default_command() -> String {
+        String::from("grim -")
+    }
+}
+
+/// A button that takes a screenshot, and a camera that shows the last one taken.
+pub struct Screenshot {
+    config: ScreenshotConfig,
+}
+
+impl Screenshot {
+    pub fn new(config: ScreenshotConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Screenshot {
+    fn name(&self) -> &str {
+        "screenshot"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("camera", "screenshot")
+                .state_class("")
+                .icon("mdi:monitor-screenshot"),
+            Entity::new("button", "take_screenshot")
+                .state_class("")
+                .icon("mdi:camera")
+                .accepts_commands(ActionClass::Screenshots),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        // Screenshots are only taken when asked for.
+        Ok(Vec::new())
+    }
+
+    async fn command(&mut self, _entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run screenshot command.")?;
+
+        if !output.status.success() {
+            bail!(
+                "Screenshot command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        let image = base64::engine::general_purpose::STANDARD.encode(&output.stdout);
+
+        Ok(vec![Reading::new("screenshot", image)])
+    }
+}
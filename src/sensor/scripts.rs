@@ -0,0 +1,118 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    process::Stdio,
+    time::SystemTime,
+};
+use tokio::process::{Child, Command};
+
+/// Buttons that run the scripts listed in the config.
+/// Only those scripts can ever be run, and nothing from the command is passed to them.
+pub struct ScriptButtons {
+    scripts: BTreeMap<String, PathBuf>,
+
+    /// Scripts that have been started but haven't finished yet.
+    running: HashMap<String, Child>,
+}
+
+impl ScriptButtons {
+    pub fn new(scripts: BTreeMap<String, PathBuf>) -> Self {
+        Self {
+            scripts,
+            running: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for ScriptButtons {
+    fn name(&self) -> &str {
+        "scripts"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for name in self.scripts.keys() {
+            entities.push(
+                Entity::new("button", name)
+                    .state_class("")
+                    .icon("mdi:script-text-play-outline")
+                    .accepts_commands(ActionClass::Scripts),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_exit_status", name))
+                    .state_class("")
+                    .icon("mdi:script-text-outline"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_last_run", name))
+                    .device_class("timestamp")
+                    .state_class("")
+                    .icon("mdi:clock-outline"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+        let mut finished = Vec::new();
+
+        for (name, child) in self.running.iter_mut() {
+            if let Some(status) = child
+                .try_wait()
+                .with_context(|| format!("Failed to check on script `{}`.", name))?
+            {
+                log::info!("Script `{}` finished with {}.", name, status);
+
+                // A script that was killed by a signal has no exit code.
+                let exit_status = match status.code() {
+                    Some(code) => code.to_string(),
+                    None => String::from("killed"),
+                };
+                readings.push(Reading::new(format!("{}_exit_status", name), exit_status));
+                finished.push(name.clone());
+            }
+        }
+
+        for name in finished {
+            self.running.remove(&name);
+        }
+
+        Ok(readings)
+    }
+
+    async fn command(&mut self, entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        let path = match self.scripts.get(entity_name) {
+            Some(path) => path,
+            None => bail!("`{}` is not a configured script.", entity_name),
+        };
+
+        if self.running.contains_key(entity_name) {
+            bail!("Script `{}` is still running.", entity_name);
+        }
+
+        log::info!("Running script `{}`.", entity_name);
+
+        // Scripts can run for a long time, so we don't wait for them here. Collection picks up the exit status.
+        let child = Command::new(path)
+            .stdin(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run script `{}`.", path.display()))?;
+        self.running.insert(entity_name.to_string(), child);
+
+        Ok(vec![
+            Reading::new(format!("{}_exit_status", entity_name), "running"),
+            Reading::new(
+                format!("{}_last_run", entity_name),
+                humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            ),
+        ])
+    }
+}
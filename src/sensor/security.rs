@@ -0,0 +1,82 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::{io::ErrorKind, path::Path};
+use tokio::fs;
+
+const SELINUX: &str = "/sys/fs/selinux";
+const APPARMOR_ENABLED: &str = "/sys/module/apparmor/parameters/enabled";
+const APPARMOR_PROFILES: &str = "/sys/kernel/security/apparmor/profiles";
+
+/// The state of the mandatory access control modules, SELinux and AppArmor.
+///
+/// Listing AppArmor's profiles needs root.
+pub struct SecurityModules;
+
+/// `enforcing`, `permissive` or `disabled`.
+async fn selinux_mode() -> Result<&'static str> {
+    if !Path::new(SELINUX).exists() {
+        return Ok("disabled");
+    }
+
+    let enforce = fs::read_to_string(Path::new(SELINUX).join("enforce"))
+        .await
+        .context("Failed to read SELinux mode.")?;
+
+    Ok(if enforce.trim() == "1" {
+        "enforcing"
+    } else {
+        "permissive"
+    })
+}
+
+/// How many AppArmor profiles are being enforced. Zero if AppArmor is disabled.
+async fn apparmor_enforced_profiles() -> Result<usize> {
+    match fs::read_to_string(APPARMOR_ENABLED).await {
+        Ok(enabled) if enabled.trim() == "Y" => {}
+        Ok(_) => return Ok(0),
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error).context("Failed to read whether AppArmor is enabled."),
+    }
+
+    // Each line looks like `/usr/sbin/cupsd (enforce)`.
+    let profiles = fs::read_to_string(APPARMOR_PROFILES)
+        .await
+        .context("Failed to read AppArmor profiles.")?;
+
+    Ok(profiles
+        .lines()
+        .filter(|line| line.trim_end().ends_with("(enforce)"))
+        .count())
+}
+
+#[async_trait(?Send)]
+impl Sensor for SecurityModules {
+    fn name(&self) -> &str {
+        "security_modules"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "selinux")
+                .state_class("")
+                .icon("mdi:shield-lock")
+                .entity_category("diagnostic"),
+            Entity::new("sensor", "apparmor_enforced_profiles")
+                .state_class("measurement")
+                .icon("mdi:shield-lock")
+                .entity_category("diagnostic"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![
+            Reading::new("selinux", selinux_mode().await?),
+            Reading::new(
+                "apparmor_enforced_profiles",
+                apparmor_enforced_profiles().await?.to_string(),
+            ),
+        ])
+    }
+}
@@ -0,0 +1,329 @@
+use super::{Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::Entity,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::{
+    future::pending,
+    stream::{self, LocalBoxStream},
+    StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use zbus::{
+    dbus_proxy,
+    zvariant::{ObjectPath, OwnedObjectPath},
+    Connection,
+};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    /// The ID, user ID, user name, seat and object path of every session.
+    fn list_sessions(&self) -> zbus::Result<Vec<(String, u32, String, String, OwnedObjectPath)>>;
+
+    #[dbus_proxy(signal)]
+    fn session_new(&self, session_id: &str, object_path: ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[dbus_proxy(signal)]
+    fn session_removed(&self, session_id: &str, object_path: ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Session {
+    /// The name of the user the session belongs to.
+    #[dbus_proxy(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    /// Where a remote session came from. Empty for local ones.
+    #[dbus_proxy(property)]
+    fn remote_host(&self) -> zbus::Result<String>;
+
+    /// The PAM service that opened the session, such as `sshd` or `gdm-password`.
+    #[dbus_proxy(property)]
+    fn service(&self) -> zbus::Result<String>;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoginSessionsConfig {
+    /// Users to report whether they're logged in.
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+enum SessionEvent {
+    Opened {
+        id: String,
+        user: String,
+        remote_host: String,
+        service: String,
+    },
+    Closed {
+        id: String,
+    },
+}
+
+/// Looks up who a new session belongs to and where it came from.
+async fn session_opened(
+    connection: Connection,
+    id: String,
+    path: OwnedObjectPath,
+) -> Result<SessionEvent> {
+    let session = SessionProxy::builder(&connection)
+        .path(path)?
+        .build()
+        .await?;
+    let user = session
+        .name()
+        .await
+        .context("Failed to read the user of a new session.")?;
+
+    Ok(SessionEvent::Opened {
+        id,
+        user,
+        remote_host: session.remote_host().await.unwrap_or_default(),
+        service: session.service().await.unwrap_or_default(),
+    })
+}
+
+/// Logins and logouts, as they happen, from logind. Also reports whether the configured users are logged in.
+pub struct LoginSessions {
+    config: LoginSessionsConfig,
+    connection: LazyConnection,
+
+    /// Set once we're listening for sessions.
+    events: Option<LocalBoxStream<'static, Result<SessionEvent>>>,
+
+    /// Maps the IDs of open sessions to the users they belong to.
+    sessions: HashMap<String, String>,
+}
+
+impl LoginSessions {
+    pub fn new(config: LoginSessionsConfig) -> Self {
+        Self {
+            config,
+            connection: LazyConnection::new(Bus::System),
+            events: None,
+            sessions: HashMap::new(),
+        }
+    }
+
+    fn entity_name(user: &str) -> String {
+        format!("user_{}_logged_in", user)
+    }
+
+    fn logged_in(&self, user: &str) -> Reading {
+        let logged_in = self
+            .sessions
+            .values()
+            .any(|session_user| session_user == user);
+        Reading::new(
+            Self::entity_name(user),
+            if logged_in { "ON" } else { "OFF" },
+        )
+    }
+
+    fn handle(&mut self, event: SessionEvent) -> Vec<Reading> {
+        let (user, event) = match event {
+            SessionEvent::Opened {
+                id,
+                user,
+                remote_host,
+                service,
+            } => {
+                log::info!("User `{}` logged in, in session {}.", user, id);
+
+                let event = json!({
+                    "event_type": "login",
+                    "user": user,
+                    "session": id,
+                    "remote_host": remote_host,
+                    "service": service,
+                });
+                self.sessions.insert(id, user.clone());
+
+                (user, event)
+            }
+            SessionEvent::Closed { id } => {
+                // Sessions we never heard of can't be reported, since there's no telling whose they were.
+                let user = match self.sessions.remove(&id) {
+                    Some(user) => user,
+                    None => return Vec::new(),
+                };
+
+                log::info!("User `{}` logged out of session {}.", user, id);
+
+                let event = json!({
+                    "event_type": "logout",
+                    "user": user,
+                    "session": id,
+                });
+
+                (user, event)
+            }
+        };
+
+        let mut readings = vec![Reading::new("login_event", event.to_string())];
+        if self.config.users.contains(&user) {
+            readings.push(self.logged_in(&user));
+        }
+
+        readings
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for LoginSessions {
+    fn name(&self) -> &str {
+        "login_sessions"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let connection = self.connection.get().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+
+        // Start listening before listing the sessions, so none can slip in between.
+        let opened = manager
+            .receive_session_new()
+            .await
+            .context("Failed to listen for new sessions.")?
+            .filter_map(|signal| async move {
+                let args = signal.args().ok()?;
+                Some((args.session_id.to_string(), args.object_path.into()))
+            })
+            .then(move |(id, path)| session_opened(connection.clone(), id, path));
+        let closed = manager
+            .receive_session_removed()
+            .await
+            .context("Failed to listen for closed sessions.")?
+            .filter_map(|signal| async move {
+                let args = signal.args().ok()?;
+                Some(Ok(SessionEvent::Closed {
+                    id: args.session_id.to_string(),
+                }))
+            });
+        self.events = Some(stream::select(opened, closed).boxed_local());
+
+        self.sessions = manager
+            .list_sessions()
+            .await
+            .context("Failed to list sessions.")?
+            .into_iter()
+            .map(|(id, _, user, _, _)| (id, user))
+            .collect();
+
+        let mut entities = vec![Entity::new("event", "login_event")
+            .state_class("")
+            .icon("mdi:login")
+            .options(vec![String::from("login"), String::from("logout")])];
+
+        for user in self.config.users.iter() {
+            entities.push(
+                Entity::new("binary_sensor", &Self::entity_name(user))
+                    .device_class("occupancy")
+                    .state_class("")
+                    .icon("mdi:account"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(self
+            .config
+            .users
+            .iter()
+            .map(|user| self.logged_in(user))
+            .collect())
+    }
+
+    async fn next_event(&mut self) -> Result<Vec<Reading>> {
+        let event = match &mut self.events {
+            Some(events) => events
+                .next()
+                .await
+                .context("Lost connection to logind.")??,
+            None => return pending().await,
+        };
+
+        Ok(self.handle(event))
+    }
+}
+
+/// How many SSH sessions are open, and where they came from.
+///
+/// Sessions are listed by logind, so this only sees SSH sessions when sshd is using PAM with
+/// `pam_systemd`, which is the default on most distributions.
+pub struct SshSessions {
+    connection: LazyConnection,
+}
+
+impl SshSessions {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::System),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SshSessions {
+    fn name(&self) -> &str {
+        "ssh_sessions"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "ssh_sessions")
+            .state_class("measurement")
+            .icon("mdi:console-network")
+            .json_attributes()])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+
+        let mut sessions = 0u64;
+        let mut sources = BTreeMap::<String, u64>::new();
+        let mut users = BTreeSet::new();
+
+        for (_, _, user, _, path) in manager
+            .list_sessions()
+            .await
+            .context("Failed to list sessions.")?
+        {
+            let session = SessionProxy::builder(&connection)
+                .path(path)?
+                .build()
+                .await?;
+
+            // Sessions can close while we're looking at them.
+            match session.service().await {
+                Ok(service) if service == "sshd" => {}
+                _ => continue,
+            }
+
+            sessions += 1;
+            *sources
+                .entry(session.remote_host().await.unwrap_or_default())
+                .or_default() += 1;
+            users.insert(user);
+        }
+
+        let attributes = json!({ "sources": sources, "users": users });
+
+        Ok(vec![
+            Reading::new("ssh_sessions", sessions.to_string()).attributes(attributes.to_string())
+        ])
+    }
+}
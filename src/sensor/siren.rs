@@ -0,0 +1,188 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{process::Command, task::JoinHandle, time};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SirenConfig {
+    /// The sounds the siren can play. The first one is played when none is asked for.
+    pub sounds: Vec<SirenSound>,
+
+    /// The command that plays a sound. It's run with `sh -c`, with the sound's path in `$SOUND`
+    /// and the volume, from 0 to 100, in `$VOLUME`.
+    #[serde(default = "SirenConfig::default_command")]
+    pub command: String,
+}
+
+impl SirenConfig {
+    fn default_command() -> String {
+        String::from(r#"paplay --volume=$((VOLUME * 65536 / 100)) "$SOUND""#)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SirenSound {
+    /// The name Home Assistant asks for the sound by, its tone.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// What Home Assistant sends to turn the siren on or off, such as
+/// `{"state": "ON", "tone": "doorbell", "volume_level": 0.5, "duration": 10}`.
+#[derive(Deserialize)]
+struct SirenCommand {
+    state: String,
+    tone: Option<String>,
+
+    /// From 0 to 1.
+    volume_level: Option<f64>,
+
+    /// In seconds.
+    duration: Option<u64>,
+}
+
+/// Plays sounds through the speakers when Home Assistant turns it on, such as a doorbell chime.
+pub struct Siren {
+    config: SirenConfig,
+
+    /// Whatever is playing right now.
+    playing: Option<JoinHandle<()>>,
+}
+
+impl Siren {
+    pub fn new(config: SirenConfig) -> Result<Self> {
+        ensure!(
+            !config.sounds.is_empty(),
+            "The siren has no sounds to play."
+        );
+
+        Ok(Self {
+            config,
+            playing: None,
+        })
+    }
+
+    fn read_state(&self) -> Reading {
+        let playing = self
+            .playing
+            .as_ref()
+            .map_or(false, |playing| !playing.is_finished());
+
+        Reading::new("siren", if playing { "ON" } else { "OFF" })
+    }
+
+    fn stop(&mut self) {
+        // The player is killed along with the task.
+        if let Some(playing) = self.playing.take() {
+            playing.abort();
+        }
+    }
+}
+
+/// Plays a sound once.
+async fn play(command: &str, sound: &Path, volume: u8) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SOUND", sound)
+        .env("VOLUME", volume.to_string())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .context("Failed to run siren command.")?;
+
+    ensure!(status.success(), "Siren command exited with {}.", status);
+    Ok(())
+}
+
+/// Plays a sound over and over, until it's stopped or fails.
+async fn play_repeatedly(command: &str, sound: &Path, volume: u8) -> Result<()> {
+    loop {
+        play(command, sound, volume).await?;
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Siren {
+    fn name(&self) -> &str {
+        "siren"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("siren", "siren")
+            .state_class("")
+            .icon("mdi:bullhorn")
+            .options(
+                self.config
+                    .sounds
+                    .iter()
+                    .map(|sound| sound.name.clone())
+                    .collect(),
+            )
+            .accepts_commands(ActionClass::Media)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![self.read_state()])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        // Without any parameters, Home Assistant may send just the state.
+        let command = match serde_json::from_str(payload) {
+            Ok(command) => command,
+            Err(_) => SirenCommand {
+                state: payload.trim().to_string(),
+                tone: None,
+                volume_level: None,
+                duration: None,
+            },
+        };
+
+        match command.state.as_str() {
+            "ON" => {
+                let sound = match &command.tone {
+                    Some(tone) => self
+                        .config
+                        .sounds
+                        .iter()
+                        .find(|sound| &sound.name == tone)
+                        .with_context(|| format!("There is no sound called `{}`.", tone))?,
+                    None => &self.config.sounds[0],
+                }
+                .path
+                .clone();
+                let volume = (command.volume_level.unwrap_or(1.0).clamp(0.0, 1.0) * 100.0) as u8;
+                let duration = command.duration.map(Duration::from_secs);
+                let player = self.config.command.clone();
+
+                // A new sound cuts off whatever was playing.
+                self.stop();
+                self.playing = Some(tokio::spawn(async move {
+                    let result = match duration {
+                        // The sound is played over and over until the time is up.
+                        Some(duration) => {
+                            time::timeout(duration, play_repeatedly(&player, &sound, volume))
+                                .await
+                                .unwrap_or(Ok(()))
+                        }
+                        None => play(&player, &sound, volume).await,
+                    };
+
+                    if let Err(error) = result {
+                        log::warn!("Failed to play siren: {:?}", error);
+                    }
+                }));
+            }
+            "OFF" => self.stop(),
+            state => bail!("Unknown siren command `{}`.", state),
+        }
+
+        Ok(vec![self.read_state()])
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use tokio::{io::AsyncWriteExt, process::Command, task};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpeechConfig {
+    /// The command that speaks text. It's run with `sh -c` and given the text on stdin.
+    #[serde(default = "SpeechConfig::default_command")]
+    pub command: String,
+}
+
+impl SpeechConfig {
+    fn default_command() -> String {
+        String::from("espeak-ng --stdin")
+    }
+}
+
+/// Speaks text sent from Home Assistant through the speakers.
+pub struct Speech {
+    config: SpeechConfig,
+}
+
+impl Speech {
+    pub fn new(config: SpeechConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for Speech {
+    fn name(&self) -> &str {
+        "speech"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("text", "speak")
+            .state_class("")
+            .icon("mdi:account-voice")
+            .accepts_commands(ActionClass::Media)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(Vec::new())
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        // The text goes in through stdin so nothing in it can be mistaken for part of the command.
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Failed to run speech command.")?;
+        let mut stdin = child.stdin.take().context("Speech command has no stdin.")?;
+        let text = payload.to_string();
+
+        // Speaking takes a while, so it happens in the background.
+        task::spawn(async move {
+            let result = async {
+                stdin.write_all(text.as_bytes()).await?;
+                drop(stdin);
+
+                let status = child.wait().await?;
+                anyhow::ensure!(status.success(), "Speech command exited with {}.", status);
+
+                Ok(())
+            }
+            .await;
+
+            if let Err(error) = result {
+                log::warn!("Failed to speak: {:?}", error);
+            }
+        });
+
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,95 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::process::Command;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SshFailedLoginsConfig {
+    /// How far back to count failed logins. Defaults to an hour.
+    #[serde(default = "SshFailedLoginsConfig::default_window")]
+    pub window: Duration,
+}
+
+impl SshFailedLoginsConfig {
+    fn default_window() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+/// How many times logging in over SSH has failed recently, and where those attempts came from.
+///
+/// This reads sshd's messages from the journal, so system-mqtt needs to be allowed to read the
+/// system journal (root, or members of the `systemd-journal` group).
+pub struct SshFailedLogins {
+    config: SshFailedLoginsConfig,
+
+    /// Matches the messages sshd logs for failed logins, capturing the address they came from.
+    failure: Regex,
+}
+
+impl SshFailedLogins {
+    pub fn new(config: SshFailedLoginsConfig) -> Self {
+        Self {
+            config,
+            // Such as `Failed password for invalid user admin from 192.0.2.7 port 52914 ssh2`.
+            failure: Regex::new(r"^Failed \S+ for (?:invalid user )?.*? from (\S+) port \d+")
+                .expect("Failed to compile SSH failure pattern."),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SshFailedLogins {
+    fn name(&self) -> &str {
+        "ssh_failed_logins"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "ssh_failed_logins")
+            .state_class("measurement")
+            .icon("mdi:account-lock")
+            .json_attributes()])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let output = Command::new("journalctl")
+            .args(["_COMM=sshd", "--output=cat", "--no-pager", "--quiet"])
+            .arg(format!("--since=-{}s", self.config.window.as_secs()))
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run journalctl.")?;
+
+        if !output.status.success() {
+            bail!(
+                "journalctl exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut sources = BTreeMap::<&str, u64>::new();
+        for captures in stdout
+            .lines()
+            .filter_map(|line| self.failure.captures(line))
+        {
+            if let Some(address) = captures.get(1) {
+                *sources.entry(address.as_str()).or_default() += 1;
+            }
+        }
+
+        let attempts: u64 = sources.values().sum();
+        let attributes = serde_json::json!({ "sources": sources });
+
+        Ok(vec![Reading::new(
+            "ssh_failed_logins",
+            attempts.to_string(),
+        )
+        .attributes(attributes.to_string())])
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio::fs;
+
+/// CPU time counters from the first line of `/proc/stat`, in ticks.
+struct CpuTimes {
+    steal: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    fn parse(stat: &str) -> Option<Self> {
+        // Looks like `cpu  user nice system idle iowait irq softirq steal guest guest_nice`.
+        let times = stat
+            .lines()
+            .next()?
+            .strip_prefix("cpu ")?
+            .split_whitespace()
+            .map(|time| time.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        // Guest time is already counted in user time, so only the first eight are added up.
+        Some(Self {
+            steal: *times.get(7)?,
+            total: times.iter().take(8).sum(),
+        })
+    }
+
+    async fn read() -> Result<Self> {
+        let stat = fs::read_to_string("/proc/stat")
+            .await
+            .context("Failed to read /proc/stat.")?;
+
+        Self::parse(&stat).context("/proc/stat does not report steal time.")
+    }
+}
+
+/// How much of the time our virtual CPUs wanted to run, the hypervisor was running something else instead.
+pub struct StealTimeSensor {
+    last_times: Option<CpuTimes>,
+}
+
+impl StealTimeSensor {
+    /// Returns `None` if the kernel doesn't report steal time.
+    pub fn new() -> Option<Self> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        let times = CpuTimes::parse(&stat)?;
+
+        Some(Self {
+            last_times: Some(times),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for StealTimeSensor {
+    fn name(&self) -> &str {
+        "steal"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "cpu_steal")
+            .state_class("measurement")
+            .unit("%")
+            .icon("mdi:gauge")])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let times = CpuTimes::read().await?;
+
+        let mut readings = Vec::new();
+        if let Some(last_times) = &self.last_times {
+            let total = times.total.saturating_sub(last_times.total);
+            if total > 0 {
+                let steal = times.steal.saturating_sub(last_times.steal) as f64 / total as f64;
+                readings.push(Reading::new(
+                    "cpu_steal",
+                    (steal.clamp(0.0, 1.0) * 100.0).to_string(),
+                ));
+            }
+        }
+        self.last_times = Some(times);
+
+        Ok(readings)
+    }
+}
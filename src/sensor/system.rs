@@ -0,0 +1,254 @@
+use super::{cgroup::Cgroup, parse_meminfo, units::UnitsConfig, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use sysinfo::{CpuExt, PidExt, ProcessExt, System, SystemExt};
+
+/// Uptime, CPU, memory and swap usage.
+pub struct SystemSensor {
+    system: System,
+
+    /// When set, CPU and memory usage are reported relative to this cgroup's limits instead of the whole host.
+    cgroup: Option<Cgroup>,
+
+    units: UnitsConfig,
+
+    /// How many of the processes using the most CPU and memory to list as attributes.
+    top_processes: Option<usize>,
+
+    /// Swap usage is only reported if there was any swap when we started.
+    has_swap: bool,
+}
+
+impl SystemSensor {
+    pub fn new(cgroup: Option<Cgroup>, units: UnitsConfig, top_processes: Option<usize>) -> Self {
+        let mut system = System::new();
+
+        // CPU usage is measured between refreshes, so we need a first one to compare against.
+        system.refresh_memory();
+        system.refresh_cpu();
+        if top_processes.is_some() {
+            system.refresh_processes();
+        }
+
+        let has_swap = system.total_swap() > 0;
+        if !has_swap {
+            log::info!("No swap found. Swap usage will not be reported.");
+        }
+
+        let mut sensor = Self {
+            system,
+            cgroup,
+            units,
+            top_processes,
+            has_swap,
+        };
+        if let Some(cgroup) = &mut sensor.cgroup {
+            if let Err(error) = cgroup.cpu_usage(sensor.system.cpus().len()) {
+                log::warn!("Failed to read cgroup CPU usage: {:?}", error);
+            }
+        }
+
+        sensor
+    }
+}
+
+impl Default for SystemSensor {
+    fn default() -> Self {
+        Self::new(None, UnitsConfig::default(), None)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SystemSensor {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut cpu = Entity::new("sensor", "cpu")
+            .state_class("measurement")
+            .unit("%")
+            .icon("mdi:gauge");
+        if self.top_processes.is_some() {
+            cpu = cpu.json_attributes();
+        }
+
+        let mut entities = vec![
+            Entity::new("sensor", "uptime")
+                .device_class("duration")
+                .state_class("")
+                .unit(self.units.uptime.symbol())
+                .icon("mdi:timer-sand"),
+            cpu,
+            Entity::new("sensor", "memory")
+                .device_class(self.units.memory.device_class())
+                .state_class("measurement")
+                .unit(self.units.memory.symbol())
+                .icon("mdi:gauge")
+                .json_attributes(),
+        ];
+
+        if self.has_swap {
+            entities.push(
+                Entity::new("sensor", "swap")
+                    .device_class(self.units.swap.device_class())
+                    .state_class("measurement")
+                    .unit(self.units.swap.symbol())
+                    .icon("mdi:gauge"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let system = &mut self.system;
+        system.refresh_memory();
+        system.refresh_cpu();
+
+        let (mut cpu_attributes, mut memory_attributes) = (Map::new(), Map::new());
+        if let Some(count) = self.top_processes {
+            system.refresh_processes();
+
+            let (cpu, memory) = top_processes(system, count);
+            cpu_attributes.insert("top_processes".into(), cpu);
+            memory_attributes.insert("top_processes".into(), memory);
+        }
+
+        let mut readings = Vec::new();
+
+        // Report uptime.
+        readings.push(Reading::new(
+            "uptime",
+            self.units.uptime.format_seconds(system.uptime()),
+        ));
+
+        match &mut self.cgroup {
+            Some(cgroup) => {
+                // Report CPU usage.
+                if let Some(cpu_usage) = cgroup.cpu_usage(system.cpus().len())? {
+                    readings.push(with_attributes(
+                        Reading::new("cpu", (cpu_usage.clamp(0.0, 1.0) * 100.0).to_string()),
+                        cpu_attributes,
+                    ));
+                }
+
+                // Report memory usage.
+                let (used, limit) = cgroup.memory_usage(system.total_memory())?;
+                readings.push(with_attributes(
+                    Reading::new("memory", self.units.memory.format_bytes(used, limit)),
+                    memory_attributes,
+                ));
+            }
+            None => {
+                // Report CPU usage.
+                let cpu_usage = (system.cpus().iter().map(|cpu| cpu.cpu_usage()).sum::<f32>())
+                    / (system.cpus().len() as f32 * 100.0);
+                readings.push(with_attributes(
+                    Reading::new("cpu", (cpu_usage * 100.0).to_string()),
+                    cpu_attributes,
+                ));
+
+                // Report memory usage.
+                let used = system.total_memory() - system.available_memory();
+                if let Some(breakdown) = memory_breakdown().await {
+                    memory_attributes.extend(breakdown);
+                }
+                readings.push(with_attributes(
+                    Reading::new(
+                        "memory",
+                        self.units.memory.format_bytes(used, system.total_memory()),
+                    ),
+                    memory_attributes,
+                ));
+            }
+        }
+
+        // Report swap usage. If it was all turned off since we started, none of it is in use.
+        if self.has_swap {
+            readings.push(Reading::new(
+                "swap",
+                self.units
+                    .swap
+                    .format_bytes(system.used_swap(), system.total_swap()),
+            ));
+        }
+
+        Ok(readings)
+    }
+}
+
+/// Attaches attributes to a reading, unless there are none.
+fn with_attributes(reading: Reading, attributes: Map<String, Value>) -> Reading {
+    if attributes.is_empty() {
+        reading
+    } else {
+        reading.attributes(Value::Object(attributes).to_string())
+    }
+}
+
+/// The processes using the most CPU, as a percentage of the whole machine, and the processes
+/// using the most memory, in MiB.
+fn top_processes(system: &System, count: usize) -> (Value, Value) {
+    let cpu_count = system.cpus().len().max(1) as f32;
+    let mut processes: Vec<_> = system.processes().values().collect();
+
+    // A process's CPU usage is a percentage of a single CPU.
+    processes.sort_by(|a, b| b.cpu_usage().total_cmp(&a.cpu_usage()));
+    let cpu = processes
+        .iter()
+        .take(count)
+        .map(|process| {
+            json!({
+                "name": process.name(),
+                "pid": process.pid().as_u32(),
+                "value": process.cpu_usage() / cpu_count,
+            })
+        })
+        .collect();
+
+    processes.sort_by_key(|process| std::cmp::Reverse(process.memory()));
+    let memory = processes
+        .iter()
+        .take(count)
+        .map(|process| {
+            json!({
+                "name": process.name(),
+                "pid": process.pid().as_u32(),
+                "value": process.memory() / 1024 / 1024,
+            })
+        })
+        .collect();
+
+    (Value::Array(cpu), Value::Array(memory))
+}
+
+/// Where the memory is going, in MiB. Used memory alone doesn't say much on Linux, where memory
+/// that would otherwise be free is used as a cache for files. Only Linux reports this, so
+/// elsewhere there's nothing.
+async fn memory_breakdown() -> Option<Map<String, Value>> {
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo").await.ok()?;
+    let fields = parse_meminfo(&meminfo);
+    let mib = |name: &str| fields.get(name).map(|kib| kib / 1024);
+
+    let total = mib("MemTotal")?;
+    let available = mib("MemAvailable")?;
+
+    let breakdown = json!({
+        "total": total,
+        // The same as the state: what can't be given back to programs when they ask for it.
+        "used": total.saturating_sub(available),
+        "free": mib("MemFree")?,
+        "available": available,
+        "cached": mib("Cached")?,
+        "buffers": mib("Buffers")?,
+        "shared": mib("Shmem")?,
+    });
+
+    match breakdown {
+        Value::Object(breakdown) => Some(breakdown),
+        _ => None,
+    }
+}
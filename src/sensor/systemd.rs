@@ -0,0 +1,486 @@
+use super::{cgroup::Cgroup, Reading, Sensor};
+use crate::{
+    dbus::{Bus, LazyConnection},
+    sink::{ActionClass, Entity},
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath, CacheProperties, Connection};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn stop_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Each unit is its name, description, load state, active state, sub state, the unit it
+    /// follows, its object path, and the ID, type and object path of its queued job.
+    #[allow(clippy::type_complexity)]
+    fn list_units_filtered(
+        &self,
+        states: &[&str],
+    ) -> zbus::Result<
+        Vec<(
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+            OwnedObjectPath,
+            u32,
+            String,
+            OwnedObjectPath,
+        )>,
+    >;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Unit {
+    #[dbus_proxy(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Service",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Service {
+    /// Like `/system.slice/nginx.service`, or empty while the service isn't running.
+    #[dbus_proxy(property)]
+    fn control_group(&self) -> zbus::Result<String>;
+
+    /// How the service last ended, such as `success` or `exit-code`.
+    #[dbus_proxy(property)]
+    fn result(&self) -> zbus::Result<String>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Timer",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Timer {
+    /// The unit the timer starts.
+    #[dbus_proxy(property)]
+    fn unit(&self) -> zbus::Result<String>;
+
+    /// Microseconds since the Unix epoch, or 0 if the timer won't elapse on the calendar.
+    #[dbus_proxy(property, name = "NextElapseUSecRealtime")]
+    fn next_elapse_usec_realtime(&self) -> zbus::Result<u64>;
+
+    /// Microseconds since the Unix epoch, or 0 if the timer has never elapsed.
+    #[dbus_proxy(property, name = "LastTriggerUSec")]
+    fn last_trigger_usec(&self) -> zbus::Result<u64>;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SystemdUnitConfig {
+    /// The name the unit will be reported as.
+    pub name: String,
+
+    /// The systemd unit, such as `nginx.service`.
+    pub unit: String,
+
+    /// If set, the unit is a switch Home Assistant can start and stop it with.
+    /// Otherwise it's a sensor reporting its state, such as `active` or `failed`.
+    #[serde(default)]
+    pub controllable: bool,
+
+    /// If set, the CPU and memory the unit is using are reported too, as `<name>_cpu` and
+    /// `<name>_memory`. Only services can be asked for this.
+    #[serde(default)]
+    pub resource_usage: bool,
+}
+
+/// The state of configured systemd units, some of which can be started and stopped.
+pub struct SystemdUnits {
+    units: Vec<SystemdUnitConfig>,
+    connection: LazyConnection,
+
+    /// The cgroups of the running units we report the resource usage of, by unit.
+    cgroups: HashMap<String, Cgroup>,
+}
+
+impl SystemdUnits {
+    pub fn new(units: Vec<SystemdUnitConfig>) -> Result<Self> {
+        for unit in units.iter().filter(|unit| unit.resource_usage) {
+            ensure!(
+                unit.unit.ends_with(".service"),
+                "Resource usage can only be reported for services, which `{}` is not.",
+                unit.unit
+            );
+        }
+
+        Ok(Self {
+            units,
+            connection: LazyConnection::new(Bus::System),
+            cgroups: HashMap::new(),
+        })
+    }
+
+    /// How much CPU, as a percentage of what the unit may use, and memory, in MiB, a unit is
+    /// using. Both are unavailable while the unit isn't running.
+    async fn read_usage(
+        connection: &Connection,
+        unit: &SystemdUnitConfig,
+        cgroups: &mut HashMap<String, Cgroup>,
+    ) -> Result<Vec<Reading>> {
+        let cpu_entity = format!("{}_cpu", unit.name);
+        let memory_entity = format!("{}_memory", unit.name);
+
+        let path = ManagerProxy::new(connection)
+            .await?
+            .load_unit(&unit.unit)
+            .await
+            .with_context(|| format!("Failed to load unit `{}`.", unit.unit))?;
+        let control_group = ServiceProxy::builder(connection)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?
+            .control_group()
+            .await
+            .with_context(|| format!("Failed to read cgroup of unit `{}`.", unit.unit))?;
+
+        if control_group.is_empty() {
+            cgroups.remove(&unit.unit);
+            return Ok(vec![
+                Reading::unavailable(cpu_entity),
+                Reading::unavailable(memory_entity),
+            ]);
+        }
+
+        // Kept between updates, since CPU usage is measured between them.
+        let cgroup = cgroups
+            .entry(unit.unit.clone())
+            .or_insert_with(|| Cgroup::at(&control_group));
+
+        let cpu_count = std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1);
+        let mut readings = Vec::new();
+
+        // CPU usage is measured between updates, so there's nothing to report the first time.
+        if let Some(cpu_usage) = cgroup.cpu_usage(cpu_count)? {
+            readings.push(Reading::new(
+                cpu_entity,
+                (cpu_usage.clamp(0.0, 1.0) * 100.0).to_string(),
+            ));
+        }
+        readings.push(Reading::new(
+            memory_entity,
+            (cgroup.memory_current()? as f64 / 1024.0 / 1024.0).to_string(),
+        ));
+
+        Ok(readings)
+    }
+
+    async fn read_state(connection: &Connection, unit: &SystemdUnitConfig) -> Result<Reading> {
+        let manager = ManagerProxy::new(connection).await?;
+        let path = manager
+            .load_unit(&unit.unit)
+            .await
+            .with_context(|| format!("Failed to load unit `{}`.", unit.unit))?;
+
+        let active_state = UnitProxy::builder(connection)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?
+            .active_state()
+            .await
+            .with_context(|| format!("Failed to read state of unit `{}`.", unit.unit))?;
+
+        let value = if unit.controllable {
+            if active_state == "active" {
+                "ON"
+            } else {
+                "OFF"
+            }
+            .to_string()
+        } else {
+            active_state
+        };
+
+        Ok(Reading::new(unit.name.as_str(), value))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SystemdUnits {
+    fn name(&self) -> &str {
+        "systemd"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for unit in self.units.iter() {
+            entities.push(if unit.controllable {
+                Entity::new("switch", &unit.name)
+                    .state_class("")
+                    .icon("mdi:cog-play")
+                    .accepts_commands(ActionClass::Services)
+            } else {
+                Entity::new("sensor", &unit.name)
+                    .state_class("")
+                    .icon("mdi:cog")
+            });
+
+            if unit.resource_usage {
+                entities.push(
+                    Entity::new("sensor", &format!("{}_cpu", unit.name))
+                        .state_class("measurement")
+                        .unit("%")
+                        .icon("mdi:gauge"),
+                );
+                entities.push(
+                    Entity::new("sensor", &format!("{}_memory", unit.name))
+                        .device_class("data_size")
+                        .state_class("measurement")
+                        .unit("MiB")
+                        .icon("mdi:gauge"),
+                );
+            }
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+
+        let mut readings = Vec::new();
+        for unit in self.units.iter() {
+            readings.push(Self::read_state(&connection, unit).await?);
+
+            if unit.resource_usage {
+                readings.extend(Self::read_usage(&connection, unit, &mut self.cgroups).await?);
+            }
+        }
+
+        Ok(readings)
+    }
+
+    async fn command(&mut self, entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let unit = match self
+            .units
+            .iter()
+            .find(|unit| unit.name == entity_name && unit.controllable)
+        {
+            Some(unit) => unit,
+            None => bail!("`{}` is not a controllable unit.", entity_name),
+        };
+
+        let connection = self.connection.get().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+
+        match payload {
+            "ON" => {
+                log::info!("Starting unit `{}`.", unit.unit);
+                manager.start_unit(&unit.unit, "replace").await
+            }
+            "OFF" => {
+                log::info!("Stopping unit `{}`.", unit.unit);
+                manager.stop_unit(&unit.unit, "replace").await
+            }
+            payload => bail!("Unknown unit command `{}`.", payload),
+        }
+        .with_context(|| format!("Failed to change state of unit `{}`.", unit.unit))?;
+
+        Ok(vec![Self::read_state(&connection, unit).await?])
+    }
+}
+
+/// How many systemd units have failed, with their names as an attribute.
+pub struct FailedUnits {
+    connection: LazyConnection,
+}
+
+impl FailedUnits {
+    pub fn new() -> Self {
+        Self {
+            connection: LazyConnection::new(Bus::System),
+        }
+    }
+}
+
+impl Default for FailedUnits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for FailedUnits {
+    fn name(&self) -> &str {
+        "systemd_failed_units"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("sensor", "systemd_failed_units")
+            .state_class("measurement")
+            .icon("mdi:alert-circle")
+            .json_attributes()])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+        let units: Vec<String> = ManagerProxy::new(&connection)
+            .await?
+            .list_units_filtered(&["failed"])
+            .await
+            .context("Failed to list failed units.")?
+            .into_iter()
+            .map(|unit| unit.0)
+            .collect();
+
+        let attributes = serde_json::json!({ "units": units });
+
+        Ok(vec![Reading::new(
+            "systemd_failed_units",
+            units.len().to_string(),
+        )
+        .attributes(attributes.to_string())])
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SystemdTimerConfig {
+    /// The name the timer will be reported as.
+    pub name: String,
+
+    /// The systemd timer, such as `backup.timer`.
+    pub timer: String,
+}
+
+/// When configured systemd timers will next go off, and how the service they started last ended.
+pub struct SystemdTimers {
+    timers: Vec<SystemdTimerConfig>,
+    connection: LazyConnection,
+}
+
+impl SystemdTimers {
+    pub fn new(timers: Vec<SystemdTimerConfig>) -> Self {
+        Self {
+            timers,
+            connection: LazyConnection::new(Bus::System),
+        }
+    }
+
+    /// A systemd timestamp, or nothing if it's 0, which systemd uses for never.
+    fn format_timestamp(usec: u64) -> Option<String> {
+        (usec != 0).then(|| {
+            humantime::format_rfc3339_seconds(SystemTime::UNIX_EPOCH + Duration::from_micros(usec))
+                .to_string()
+        })
+    }
+
+    async fn read_timer(
+        connection: &Connection,
+        timer: &SystemdTimerConfig,
+    ) -> Result<Vec<Reading>> {
+        let manager = ManagerProxy::new(connection).await?;
+        let path = manager
+            .load_unit(&timer.timer)
+            .await
+            .with_context(|| format!("Failed to load unit `{}`.", timer.timer))?;
+        let proxy = TimerProxy::builder(connection)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+
+        let read_failed = || format!("Failed to read timer `{}`.", timer.timer);
+        let next_run = proxy
+            .next_elapse_usec_realtime()
+            .await
+            .with_context(read_failed)?;
+        let last_run = proxy.last_trigger_usec().await.with_context(read_failed)?;
+        let service = proxy.unit().await.with_context(read_failed)?;
+
+        let path = manager
+            .load_unit(&service)
+            .await
+            .with_context(|| format!("Failed to load unit `{}`.", service))?;
+        let last_result = ServiceProxy::builder(connection)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?
+            .result()
+            .await
+            .with_context(|| format!("Failed to read result of unit `{}`.", service))?;
+
+        let next_run_entity = format!("{}_next_run", timer.name);
+        let next_run = match Self::format_timestamp(next_run) {
+            Some(next_run) => Reading::new(next_run_entity, next_run),
+            // Timers that only go off relative to boot or other units don't have a time on the
+            // calendar, and stopped timers don't go off at all.
+            None => Reading::unavailable(next_run_entity),
+        };
+
+        Ok(vec![
+            next_run,
+            Reading::new(format!("{}_last_result", timer.name), last_result).attributes(
+                json!({
+                    "last_run": Self::format_timestamp(last_run),
+                    "unit": service,
+                })
+                .to_string(),
+            ),
+        ])
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for SystemdTimers {
+    fn name(&self) -> &str {
+        "systemd_timers"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for timer in self.timers.iter() {
+            entities.push(
+                Entity::new("sensor", &format!("{}_next_run", timer.name))
+                    .device_class("timestamp")
+                    .state_class("")
+                    .icon("mdi:timer-outline"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_last_result", timer.name))
+                    .state_class("")
+                    .icon("mdi:timer-check-outline")
+                    .json_attributes(),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let connection = self.connection.get().await?;
+
+        let mut readings = Vec::new();
+        for timer in self.timers.iter() {
+            readings.extend(Self::read_timer(&connection, timer).await?);
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,112 @@
+use super::{unix_http, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TailscaleConfig {
+    /// The socket tailscaled serves its local API on.
+    #[serde(default = "TailscaleConfig::default_socket")]
+    pub socket: PathBuf,
+}
+
+impl TailscaleConfig {
+    fn default_socket() -> PathBuf {
+        PathBuf::from("/var/run/tailscale/tailscaled.sock")
+    }
+}
+
+/// The parts of tailscaled's status we care about.
+#[derive(Deserialize)]
+struct Status {
+    #[serde(rename = "BackendState")]
+    backend_state: String,
+
+    #[serde(rename = "Self")]
+    this_node: Option<NodeStatus>,
+}
+
+#[derive(Deserialize)]
+struct NodeStatus {
+    #[serde(rename = "Online", default)]
+    online: bool,
+
+    #[serde(rename = "TailscaleIPs", default)]
+    addresses: Vec<IpAddr>,
+
+    /// Set when this node offers to be an exit node, and that has been approved.
+    #[serde(rename = "ExitNodeOption", default)]
+    exit_node: bool,
+}
+
+/// Whether we're connected to our tailnet, and how the rest of it sees us.
+pub struct TailscaleSensor {
+    config: TailscaleConfig,
+}
+
+impl TailscaleSensor {
+    pub fn new(config: TailscaleConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for TailscaleSensor {
+    fn name(&self) -> &str {
+        "tailscale"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("binary_sensor", "tailscale_connected")
+                .device_class("connectivity")
+                .state_class("")
+                .icon("mdi:vpn"),
+            Entity::new("sensor", "tailscale_ip")
+                .state_class("")
+                .icon("mdi:ip-network"),
+            Entity::new("binary_sensor", "tailscale_exit_node")
+                .state_class("")
+                .icon("mdi:exit-run"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let body = unix_http::get(
+            &self.config.socket,
+            "local-tailscaled.sock",
+            "/localapi/v0/status",
+        )
+        .await?;
+        let status: Status =
+            serde_json::from_str(&body).context("Failed to parse Tailscale status.")?;
+
+        let connected = status.backend_state == "Running"
+            && status.this_node.as_ref().map_or(false, |node| node.online);
+        let mut readings = vec![Reading::new(
+            "tailscale_connected",
+            if connected { "ON" } else { "OFF" },
+        )];
+
+        if let Some(node) = &status.this_node {
+            // Prefer the IPv4 address, since it's the one people type.
+            let address = node
+                .addresses
+                .iter()
+                .find(|address| address.is_ipv4())
+                .or_else(|| node.addresses.first());
+            if let Some(address) = address {
+                readings.push(Reading::new("tailscale_ip", address.to_string()));
+            }
+
+            readings.push(Reading::new(
+                "tailscale_exit_node",
+                if node.exit_node { "ON" } else { "OFF" },
+            ));
+        }
+
+        Ok(readings)
+    }
+}
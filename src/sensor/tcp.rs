@@ -0,0 +1,65 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio::fs;
+
+/// How sockets in each state are written in `/proc/net/tcp`.
+const ESTABLISHED: &str = "01";
+const TIME_WAIT: &str = "06";
+
+/// How many TCP connections are established, and how many are waiting to be cleaned up.
+pub struct TcpConnections;
+
+/// Counts sockets in the established and time wait states from a table like `/proc/net/tcp`.
+async fn count(table: &str) -> Result<(u64, u64)> {
+    let content = match fs::read_to_string(table).await {
+        Ok(content) => content,
+        // IPv6 may be disabled, in which case there's no table for it.
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read `{}`.", table)),
+    };
+
+    let mut established = 0;
+    let mut time_wait = 0;
+
+    // After the header, each line looks like `0: 0100007F:0CEA 00000000:0000 0A ...`,
+    // where the fourth column is the state.
+    for line in content.lines().skip(1) {
+        match line.split_whitespace().nth(3) {
+            Some(ESTABLISHED) => established += 1,
+            Some(TIME_WAIT) => time_wait += 1,
+            _ => {}
+        }
+    }
+
+    Ok((established, time_wait))
+}
+
+#[async_trait(?Send)]
+impl Sensor for TcpConnections {
+    fn name(&self) -> &str {
+        "tcp"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![
+            Entity::new("sensor", "tcp_established")
+                .state_class("measurement")
+                .icon("mdi:lan-connect"),
+            Entity::new("sensor", "tcp_time_wait")
+                .state_class("measurement")
+                .icon("mdi:lan-pending"),
+        ])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let (established, time_wait) = count("/proc/net/tcp").await?;
+        let (established6, time_wait6) = count("/proc/net/tcp6").await?;
+
+        Ok(vec![
+            Reading::new("tcp_established", (established + established6).to_string()),
+            Reading::new("tcp_time_wait", (time_wait + time_wait6).to_string()),
+        ])
+    }
+}
@@ -0,0 +1,103 @@
+//! The units values can be reported in.
+
+use serde::{Deserialize, Serialize};
+
+/// The units each of the built in sensors report in.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct UnitsConfig {
+    #[serde(default)]
+    pub uptime: TimeUnit,
+
+    #[serde(default)]
+    pub memory: SizeUnit,
+
+    #[serde(default)]
+    pub swap: SizeUnit,
+
+    #[serde(default)]
+    pub drives: SizeUnit,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeUnit {
+    #[default]
+    #[serde(rename = "seconds")]
+    Seconds,
+
+    #[serde(rename = "minutes")]
+    Minutes,
+
+    #[serde(rename = "hours")]
+    Hours,
+
+    #[serde(rename = "days")]
+    Days,
+}
+
+impl TimeUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Seconds => "s",
+            Self::Minutes => "min",
+            Self::Hours => "h",
+            Self::Days => "d",
+        }
+    }
+
+    /// Converts a number of seconds to this unit.
+    pub fn format_seconds(self, seconds: u64) -> String {
+        match self {
+            Self::Seconds => seconds.to_string(),
+            Self::Minutes => (seconds as f64 / 60.0).to_string(),
+            Self::Hours => (seconds as f64 / 60.0 / 60.0).to_string(),
+            Self::Days => (seconds as f64 / 60.0 / 60.0 / 24.0).to_string(),
+        }
+    }
+}
+
+/// How much of something, such as memory or a filesystem, is being used.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeUnit {
+    /// A percentage of the total.
+    #[default]
+    #[serde(rename = "percent")]
+    Percent,
+
+    #[serde(rename = "bytes")]
+    Bytes,
+
+    #[serde(rename = "gibibytes")]
+    Gibibytes,
+}
+
+impl SizeUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Percent => "%",
+            Self::Bytes => "B",
+            Self::Gibibytes => "GiB",
+        }
+    }
+
+    /// The Home Assistant device class of values in this unit.
+    pub fn device_class(self) -> Option<&'static str> {
+        match self {
+            Self::Percent => None,
+            Self::Bytes | Self::Gibibytes => Some("data_size"),
+        }
+    }
+
+    /// Reports `used` bytes out of `total` in this unit.
+    pub fn format_bytes(self, used: u64, total: u64) -> String {
+        match self {
+            // Nothing can be in use of something that has no size, and dividing by it would give NaN.
+            Self::Percent if total == 0 => String::from("0"),
+            Self::Percent => {
+                let percentile = used as f64 / total as f64;
+                (percentile.clamp(0.0, 1.0) * 100.0).to_string()
+            }
+            Self::Bytes => used.to_string(),
+            Self::Gibibytes => (used as f64 / (1024.0 * 1024.0 * 1024.0)).to_string(),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Just enough HTTP to talk to the APIs daemons serve over Unix sockets.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+/// Makes a GET request over a Unix socket and returns the body of the response.
+/// Anything other than `200 OK` is an error.
+pub async fn get(socket: &Path, host: &str, path: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Failed to connect to `{}`.", socket.display()))?;
+
+    // HTTP/1.0 keeps this simple: the response isn't chunked, and ends when the connection is closed.
+    stream
+        .write_all(format!("GET {} HTTP/1.0\r\nHost: {}\r\n\r\n", path, host).as_bytes())
+        .await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .with_context(|| format!("Malformed response from `{}`.", socket.display()))?;
+    let status = head.lines().next().unwrap_or_default();
+    if !status.contains(" 200 ") {
+        bail!("`{}` responded with `{}`.", socket.display(), status);
+    }
+
+    Ok(body.to_string())
+}
@@ -0,0 +1,333 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+use sysinfo::{System, SystemExt};
+use tokio::process::{Child, Command};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OsUpdatesConfig {
+    /// How often to check for updates. Checking can be slow, so this defaults to once an hour.
+    #[serde(default = "OsUpdatesConfig::default_interval")]
+    pub interval: Duration,
+
+    /// Lets Home Assistant install the updates.
+    #[serde(default)]
+    pub allow_install: bool,
+}
+
+impl OsUpdatesConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PackageManager {
+    Apt,
+    Dnf,
+}
+
+impl PackageManager {
+    fn detect() -> Result<Self> {
+        if Path::new("/usr/bin/apt-get").exists() {
+            Ok(Self::Apt)
+        } else if Path::new("/usr/bin/dnf").exists() {
+            Ok(Self::Dnf)
+        } else {
+            bail!("No supported package manager found. Only apt and dnf are supported.")
+        }
+    }
+
+    async fn count_updates(self) -> Result<usize> {
+        let output = match self {
+            // Simulating an upgrade lists what would be installed, without needing root.
+            Self::Apt => Command::new("apt-get")
+                .args(["--simulate", "upgrade"])
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run apt-get.")?,
+            Self::Dnf => Command::new("dnf")
+                .args(["--quiet", "check-update"])
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run dnf.")?,
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let count = match self {
+            Self::Apt => {
+                if !output.status.success() {
+                    bail!("apt-get exited with {}.", output.status);
+                }
+
+                stdout
+                    .lines()
+                    .filter(|line| line.starts_with("Inst "))
+                    .count()
+            }
+            Self::Dnf => match output.status.code() {
+                // dnf exits with 100 when there are updates, and lists one per line.
+                Some(100) => stdout
+                    .lines()
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with(' '))
+                    .count(),
+                Some(0) => 0,
+                _ => bail!("dnf exited with {}.", output.status),
+            },
+        };
+
+        Ok(count)
+    }
+
+    fn install(self) -> Command {
+        let mut command = match self {
+            Self::Apt => {
+                let mut command = Command::new("apt-get");
+                command
+                    .args(["--assume-yes", "upgrade"])
+                    .env("DEBIAN_FRONTEND", "noninteractive");
+                command
+            }
+            Self::Dnf => {
+                let mut command = Command::new("dnf");
+                command.args(["--assumeyes", "upgrade"]);
+                command
+            }
+        };
+        command.stdin(Stdio::null());
+        command
+    }
+}
+
+/// Package managers that sit alongside the operating system's, whose updates are counted
+/// separately.
+#[derive(Clone, Copy)]
+enum AppPackageManager {
+    Flatpak,
+    Snap,
+}
+
+impl AppPackageManager {
+    fn detect() -> Vec<Self> {
+        let mut found = Vec::new();
+        if Path::new("/usr/bin/flatpak").exists() {
+            found.push(Self::Flatpak);
+        }
+        if Path::new("/usr/bin/snap").exists() {
+            found.push(Self::Snap);
+        }
+        found
+    }
+
+    fn entity_name(self) -> &'static str {
+        match self {
+            Self::Flatpak => "flatpak_pending_updates",
+            Self::Snap => "snap_pending_updates",
+        }
+    }
+
+    async fn count_updates(self) -> Result<usize> {
+        let output = match self {
+            // Without a terminal to print to, there's no header, just one application per line.
+            Self::Flatpak => Command::new("flatpak")
+                .args(["remote-ls", "--updates", "--columns=application"])
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run flatpak.")?,
+            Self::Snap => Command::new("snap")
+                .args(["refresh", "--list"])
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run snap.")?,
+        };
+
+        if !output.status.success() {
+            match self {
+                Self::Flatpak => bail!("flatpak exited with {}.", output.status),
+                Self::Snap => bail!("snap exited with {}.", output.status),
+            }
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines = stdout.lines().filter(|line| !line.trim().is_empty());
+        let count = match self {
+            Self::Flatpak => lines.count(),
+            // There's a header above the snaps, and nothing at all when they're up to date.
+            Self::Snap => lines.skip(1).count(),
+        };
+
+        Ok(count)
+    }
+}
+
+/// Home Assistant's update entity, for updates to the operating system's packages.
+pub struct OsUpdates {
+    config: OsUpdatesConfig,
+    package_manager: PackageManager,
+
+    /// Flatpak and Snap, if they're installed. Their updates aren't installed along with the
+    /// operating system's.
+    app_package_managers: Vec<AppPackageManager>,
+
+    /// What the installed version is reported as.
+    os_version: String,
+
+    last_check: Option<Instant>,
+    pending_updates: usize,
+
+    /// The install that's running, if there is one.
+    install: Option<Child>,
+}
+
+impl OsUpdates {
+    pub fn new(config: OsUpdatesConfig) -> Result<Self> {
+        let system = System::new();
+        let os_version = system
+            .long_os_version()
+            .unwrap_or_else(|| String::from("unknown"));
+
+        Ok(Self {
+            config,
+            package_manager: PackageManager::detect()?,
+            app_package_managers: AppPackageManager::detect(),
+            os_version,
+            last_check: None,
+            pending_updates: 0,
+            install: None,
+        })
+    }
+
+    fn state(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct UpdateState<'a> {
+            installed_version: &'a str,
+            latest_version: String,
+            in_progress: bool,
+        }
+
+        // Packages don't have one version, so the latest version is the installed one plus however many updates there are.
+        let latest_version = if self.pending_updates > 0 {
+            format!("{} (+{} updates)", self.os_version, self.pending_updates)
+        } else {
+            self.os_version.clone()
+        };
+
+        serde_json::to_string(&UpdateState {
+            installed_version: &self.os_version,
+            latest_version,
+            in_progress: self.install.is_some(),
+        })
+        .context("Failed to serialize update state.")
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for OsUpdates {
+    fn name(&self) -> &str {
+        "os_updates"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let update = Entity::new("update", "os_update")
+            .state_class("")
+            .icon("mdi:package-up");
+
+        let update = if self.config.allow_install {
+            update.accepts_commands(ActionClass::Updates)
+        } else {
+            update
+        };
+
+        let mut entities = vec![
+            update,
+            Entity::new("sensor", "os_pending_updates")
+                .state_class("measurement")
+                .icon("mdi:package-up"),
+        ];
+
+        for app_package_manager in self.app_package_managers.iter() {
+            entities.push(
+                Entity::new("sensor", app_package_manager.entity_name())
+                    .state_class("measurement")
+                    .icon("mdi:package-up"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        if let Some(install) = &mut self.install {
+            match install
+                .try_wait()
+                .context("Failed to check on update install.")?
+            {
+                Some(status) => {
+                    log::info!("Update install finished with {}.", status);
+                    self.install = None;
+
+                    // Whatever was installed needs to be counted again.
+                    self.last_check = None;
+                }
+                None => return Ok(Vec::new()),
+            }
+        }
+
+        let now = Instant::now();
+        if let Some(last_check) = self.last_check {
+            if now.duration_since(last_check) < self.config.interval {
+                return Ok(Vec::new());
+            }
+        }
+        self.last_check = Some(now);
+
+        self.pending_updates = self.package_manager.count_updates().await?;
+
+        let mut readings = vec![
+            Reading::new("os_update", self.state()?),
+            Reading::new("os_pending_updates", self.pending_updates.to_string()),
+        ];
+
+        for app_package_manager in self.app_package_managers.iter() {
+            readings.push(Reading::new(
+                app_package_manager.entity_name(),
+                app_package_manager.count_updates().await?.to_string(),
+            ));
+        }
+
+        Ok(readings)
+    }
+
+    async fn command(&mut self, _entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        if !self.config.allow_install {
+            bail!("Installing updates is not allowed.");
+        }
+
+        if self.install.is_some() {
+            bail!("Updates are already being installed.");
+        }
+
+        log::info!("Installing updates.");
+
+        // Installing can take a long time, so we don't wait for it here. Collection notices when it's done.
+        self.install = Some(
+            self.package_manager
+                .install()
+                .spawn()
+                .context("Failed to start installing updates.")?,
+        );
+
+        Ok(vec![Reading::new("os_update", self.state()?)])
+    }
+}
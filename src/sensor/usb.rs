@@ -0,0 +1,86 @@
+use super::{Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path};
+use tokio::fs;
+
+const USB_DEVICES: &str = "/sys/bus/usb/devices";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UsbDevice {
+    /// The name the sensor will be reported as.
+    pub name: String,
+
+    /// The vendor and product ID, as shown by `lsusb`, such as `10c4:ea60`.
+    pub id: String,
+}
+
+/// Whether USB devices are plugged in.
+pub struct UsbSensor {
+    devices: Vec<UsbDevice>,
+}
+
+impl UsbSensor {
+    pub fn new(devices: Vec<UsbDevice>) -> Self {
+        Self { devices }
+    }
+}
+
+/// The IDs of every USB device currently attached, in the same form as `lsusb` shows them.
+async fn attached_devices() -> Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+
+    let mut entries = fs::read_dir(USB_DEVICES)
+        .await
+        .context("Failed to list USB devices.")?;
+    while let Some(entry) = entries.next_entry().await? {
+        // Interfaces of devices are listed here too, but they don't have IDs of their own.
+        if let Some(id) = read_id(&entry.path()).await {
+            ids.insert(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+async fn read_id(device: &Path) -> Option<String> {
+    let vendor = fs::read_to_string(device.join("idVendor")).await.ok()?;
+    let product = fs::read_to_string(device.join("idProduct")).await.ok()?;
+
+    Some(format!("{}:{}", vendor.trim(), product.trim()))
+}
+
+#[async_trait(?Send)]
+impl Sensor for UsbSensor {
+    fn name(&self) -> &str {
+        "usb"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .devices
+            .iter()
+            .map(|device| {
+                Entity::new("binary_sensor", &device.name)
+                    .device_class("plug")
+                    .state_class("")
+                    .icon("mdi:usb")
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let attached = attached_devices().await?;
+
+        Ok(self
+            .devices
+            .iter()
+            .map(|device| {
+                let present = attached.contains(&device.id.to_lowercase());
+                Reading::new(device.name.as_str(), if present { "ON" } else { "OFF" })
+            })
+            .collect())
+    }
+}
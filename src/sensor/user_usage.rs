@@ -0,0 +1,205 @@
+use super::{EntityChanges, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio::fs;
+
+/// Where systemd puts the slices of logged in users, like `user-1000.slice`.
+const USER_SLICES: &str = "/sys/fs/cgroup/user.slice";
+
+/// A user we've seen a slice for.
+struct User {
+    /// What their entities are named after.
+    name: String,
+
+    /// The CPU time used by the user's slice in microseconds, and when it was read.
+    last_cpu_usage: Option<(Instant, u64)>,
+}
+
+/// How much CPU and memory each logged in user is using, from their systemd slices. Only
+/// cgroup v2 is supported.
+///
+/// Entities are registered for each user when they log in, and removed again once they've logged
+/// out and systemd has cleaned up after them.
+pub struct UserUsage {
+    /// The users with a slice, by UID.
+    users: BTreeMap<u32, User>,
+
+    changes: EntityChanges,
+}
+
+impl UserUsage {
+    pub fn new() -> Self {
+        Self {
+            users: BTreeMap::new(),
+            changes: EntityChanges::default(),
+        }
+    }
+
+    /// The UIDs of the users with a slice, along with where it is.
+    async fn slices() -> Result<Vec<(u32, PathBuf)>> {
+        let mut entries = fs::read_dir(USER_SLICES)
+            .await
+            .with_context(|| format!("Failed to read `{}`. Is cgroup v2 in use?", USER_SLICES))?;
+
+        let mut slices = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let uid = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("user-")?.strip_suffix(".slice"))
+                .and_then(|uid| uid.parse().ok());
+
+            if let Some(uid) = uid {
+                slices.push((uid, entry.path()));
+            }
+        }
+
+        Ok(slices)
+    }
+
+    fn entity_names(user: &str) -> [String; 2] {
+        [
+            format!("user_{}_cpu", user),
+            format!("user_{}_memory", user),
+        ]
+    }
+}
+
+impl Default for UserUsage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Usernames can have characters entity names can't, like `.` and `-`.
+fn entity_safe(name: &str) -> String {
+    name.chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+async fn read_number(slice: &Path, file: &str, prefix: &str) -> Result<u64> {
+    let path = slice.join(file);
+    let contents = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read `{}`.", path.display()))?;
+
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .and_then(|number| number.trim().parse().ok())
+        .with_context(|| format!("`{}` isn't what we expected.", path.display()))
+}
+
+#[async_trait(?Send)]
+impl Sensor for UserUsage {
+    fn name(&self) -> &str {
+        "user_usage"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        // Users are found as they log in.
+        Ok(Vec::new())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let cpu_count = std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1);
+
+        let mut readings = Vec::new();
+        let mut present = BTreeMap::new();
+        for (uid, slice) in Self::slices().await? {
+            let mut user = match self.users.remove(&uid) {
+                Some(user) => user,
+                None => {
+                    let name = users::get_user_by_uid(uid)
+                        .map(|user| user.name().to_string_lossy().into_owned())
+                        .unwrap_or_else(|| uid.to_string());
+                    let name = entity_safe(&name);
+                    log::info!("User `{}` logged in.", name);
+
+                    let [cpu, memory] = Self::entity_names(&name);
+                    self.changes.added.push(
+                        Entity::new("sensor", &cpu)
+                            .state_class("measurement")
+                            .unit("%")
+                            .icon("mdi:account-cog"),
+                    );
+                    self.changes.added.push(
+                        Entity::new("sensor", &memory)
+                            .device_class("data_size")
+                            .state_class("measurement")
+                            .unit("MiB")
+                            .icon("mdi:account-cog"),
+                    );
+
+                    User {
+                        name,
+                        last_cpu_usage: None,
+                    }
+                }
+            };
+            let [cpu, memory] = Self::entity_names(&user.name);
+
+            // A slice can go away between being listed and being read, when a user logs out.
+            let (cpu_usage, memory_usage) = match (
+                read_number(&slice, "cpu.stat", "usage_usec ").await,
+                read_number(&slice, "memory.current", "").await,
+            ) {
+                (Ok(cpu_usage), Ok(memory_usage)) => (cpu_usage, memory_usage),
+                (Err(error), _) | (_, Err(error)) => {
+                    log::debug!("Failed to read usage of `{}`: {:?}", user.name, error);
+                    present.insert(uid, user);
+                    continue;
+                }
+            };
+
+            // CPU usage is measured between updates, so there's nothing to report the first time.
+            let now = Instant::now();
+            if let Some((last_time, last_usage)) = user.last_cpu_usage {
+                let elapsed = now.duration_since(last_time).as_micros() as f64 * cpu_count as f64;
+                if elapsed > 0.0 {
+                    let used = cpu_usage.saturating_sub(last_usage) as f64 / elapsed;
+                    readings.push(Reading::new(
+                        cpu,
+                        (used.clamp(0.0, 1.0) * 100.0).to_string(),
+                    ));
+                }
+            }
+            user.last_cpu_usage = Some((now, cpu_usage));
+
+            readings.push(Reading::new(
+                memory,
+                (memory_usage as f64 / 1024.0 / 1024.0).to_string(),
+            ));
+
+            present.insert(uid, user);
+        }
+
+        // Whoever is left has logged out.
+        for user in self.users.values() {
+            log::info!("User `{}` logged out.", user.name);
+            self.changes.removed.extend(Self::entity_names(&user.name));
+        }
+        self.users = present;
+
+        Ok(readings)
+    }
+
+    fn entity_changes(&mut self) -> EntityChanges {
+        std::mem::take(&mut self.changes)
+    }
+}
@@ -0,0 +1,126 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use tokio::process::Command;
+
+/// The volume of the default audio output, which can also be set.
+///
+/// This goes through `pactl`, which works with both PulseAudio and PipeWire (through pipewire-pulse).
+pub struct VolumeControl;
+
+/// Runs `pactl` and returns what it printed.
+pub(crate) async fn pactl(arguments: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(arguments)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run pactl.")?;
+
+    if !output.status.success() {
+        bail!(
+            "pactl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl VolumeControl {
+    async fn read_volume() -> Result<Reading> {
+        // Looks like `Volume: front-left: 32768 /  50% / -18.06 dB,   front-right: ...`.
+        // We report the first channel.
+        let output = pactl(&["get-sink-volume", "@DEFAULT_SINK@"]).await?;
+        let volume = output
+            .split_whitespace()
+            .find_map(|word| word.strip_suffix('%'))
+            .context("pactl did not report a volume.")?;
+
+        Ok(Reading::new("volume", volume))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for VolumeControl {
+    fn name(&self) -> &str {
+        "volume"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("number", "volume")
+            .state_class("")
+            .unit("%")
+            .icon("mdi:volume-high")
+            .range(0.0, 100.0, 1.0)
+            .accepts_commands(ActionClass::Media)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![Self::read_volume().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let volume: f64 = payload
+            .trim()
+            .parse()
+            .with_context(|| format!("`{}` is not a volume.", payload))?;
+        let volume = volume.clamp(0.0, 100.0).round();
+
+        pactl(&["set-sink-volume", "@DEFAULT_SINK@", &format!("{}%", volume)]).await?;
+
+        Ok(vec![Self::read_volume().await?])
+    }
+}
+
+/// Whether the default audio input, usually a microphone, is muted, which can also be set.
+pub struct MicrophoneMute;
+
+impl MicrophoneMute {
+    async fn read_mute() -> Result<Reading> {
+        // Looks like `Mute: no`.
+        let output = pactl(&["get-source-mute", "@DEFAULT_SOURCE@"]).await?;
+        let muted = match output.trim().strip_prefix("Mute:").map(str::trim) {
+            Some("yes") => true,
+            Some("no") => false,
+            _ => bail!("pactl did not report whether the microphone is muted."),
+        };
+
+        Ok(Reading::new(
+            "microphone_mute",
+            if muted { "ON" } else { "OFF" },
+        ))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for MicrophoneMute {
+    fn name(&self) -> &str {
+        "microphone_mute"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::new("switch", "microphone_mute")
+            .state_class("")
+            .icon("mdi:microphone-off")
+            .accepts_commands(ActionClass::Media)])
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(vec![Self::read_mute().await?])
+    }
+
+    async fn command(&mut self, _entity_name: &str, payload: &str) -> Result<Vec<Reading>> {
+        let mute = match payload {
+            "ON" => "1",
+            "OFF" => "0",
+            payload => bail!("Unknown microphone mute command `{}`.", payload),
+        };
+
+        pactl(&["set-source-mute", "@DEFAULT_SOURCE@", mute]).await?;
+
+        Ok(vec![Self::read_mute().await?])
+    }
+}
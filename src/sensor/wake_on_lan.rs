@@ -0,0 +1,131 @@
+use super::{Reading, Sensor};
+use crate::sink::{ActionClass, Entity};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+/// The port magic packets are traditionally sent to.
+const WAKE_ON_LAN_PORT: u16 = 9;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WakeOnLanTarget {
+    /// The name the button will be reported as.
+    pub name: String,
+
+    /// The MAC address of the machine to wake, such as `01:23:45:67:89:ab`.
+    pub mac: String,
+
+    /// Where to send the magic packet. Defaults to the broadcast address of the local network.
+    pub broadcast: Option<Ipv4Addr>,
+}
+
+/// Buttons that wake up machines on the local network, for when Home Assistant can't reach them itself.
+pub struct WakeOnLan {
+    /// The targets along with the magic packet that wakes each one.
+    targets: Vec<(WakeOnLanTarget, Vec<u8>)>,
+}
+
+impl WakeOnLan {
+    pub fn new(targets: &[WakeOnLanTarget]) -> Result<Self> {
+        let targets = targets
+            .iter()
+            .map(|target| {
+                let mac = parse_mac(&target.mac).with_context(|| {
+                    format!(
+                        "Invalid MAC address for wake on LAN target `{}`.",
+                        target.name
+                    )
+                })?;
+
+                Ok((target.clone(), magic_packet(&mac)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { targets })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for WakeOnLan {
+    fn name(&self) -> &str {
+        "wake_on_lan"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .targets
+            .iter()
+            .map(|(target, _)| {
+                Entity::new("button", &target.name)
+                    .state_class("")
+                    .icon("mdi:lan-pending")
+                    .accepts_commands(ActionClass::Network)
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        Ok(Vec::new())
+    }
+
+    async fn command(&mut self, entity_name: &str, _payload: &str) -> Result<Vec<Reading>> {
+        let (target, packet) = match self
+            .targets
+            .iter()
+            .find(|(target, _)| target.name == entity_name)
+        {
+            Some(target) => target,
+            None => bail!("`{}` is not a wake on LAN target.", entity_name),
+        };
+
+        let address = SocketAddr::from((
+            target.broadcast.unwrap_or(Ipv4Addr::BROADCAST),
+            WAKE_ON_LAN_PORT,
+        ));
+
+        log::info!("Sending magic packet for `{}` to {}.", target.name, address);
+
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .await
+            .context("Failed to open socket for magic packet.")?;
+        socket
+            .set_broadcast(true)
+            .context("Failed to allow broadcasts.")?;
+        socket
+            .send_to(packet, address)
+            .await
+            .context("Failed to send magic packet.")?;
+
+        Ok(Vec::new())
+    }
+}
+
+fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+    let mut bytes = [0; 6];
+    let mut parts = mac.split(|character| character == ':' || character == '-');
+
+    for byte in bytes.iter_mut() {
+        let part = parts.next().context("MAC address is too short.")?;
+        *byte = u8::from_str_radix(part, 16)
+            .with_context(|| format!("`{}` is not a hexadecimal byte.", part))?;
+    }
+
+    if parts.next().is_some() {
+        bail!("MAC address is too long.");
+    }
+
+    Ok(bytes)
+}
+
+/// Six bytes of `0xFF` followed by the MAC address sixteen times.
+fn magic_packet(mac: &[u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xFF; 6];
+
+    for _ in 0..16 {
+        packet.extend_from_slice(mac);
+    }
+
+    packet
+}
@@ -0,0 +1,245 @@
+//! Sensor plugins compiled to WebAssembly.
+//!
+//! A plugin is a `.wasm` module placed in the plugin directory. It must export:
+//!
+//! * `memory`: its linear memory.
+//! * `alloc(len: i32) -> i32`: allocates `len` bytes and returns a pointer to them. Used to hand data to the plugin.
+//! * `entities() -> i64`: returns a buffer containing a JSON array describing the plugin's entities,
+//!   e.g. `[{"name": "temperature", "unit": "°C", "icon": "mdi:thermometer"}]`.
+//!   `device_class` and `state_class` may also be given.
+//! * `collect() -> i64`: returns a buffer containing a JSON object mapping entity names to their
+//!   current values, e.g. `{"temperature": 42.5}`.
+//!
+//! Buffers are returned as a pointer in the upper 32 bits and a length in the lower 32 bits.
+//!
+//! Plugins may import the following from the `system_mqtt` module:
+//!
+//! * `read_file(path_ptr: i32, path_len: i32) -> i64`: reads a file under `/proc` or `/sys` and returns
+//!   a buffer allocated with the plugin's `alloc`, or -1 if the file could not be read.
+//! * `log(ptr: i32, len: i32)`: writes a message to system-mqtt's log.
+
+use super::{default_state_class, Reading, Sensor};
+use crate::sink::Entity;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use wasmtime::{Caller, Config, Engine, Extern, Linker, Memory, Module, Store, TypedFunc};
+
+/// How many instructions (roughly) a plugin gets to run each time it is called.
+/// Keeps a misbehaving plugin from hanging the whole daemon.
+const FUEL_PER_CALL: u64 = 100_000_000;
+
+/// The only places plugins are permitted to read files from.
+const READABLE_PATHS: [&str; 2] = ["/proc", "/sys"];
+
+#[derive(Deserialize)]
+pub struct PluginEntity {
+    pub name: String,
+    pub unit: Option<String>,
+    pub icon: Option<String>,
+    pub device_class: Option<String>,
+    pub state_class: Option<String>,
+}
+
+pub struct WasmPlugin {
+    name: String,
+    store: Store<()>,
+    memory: Memory,
+    collect: TypedFunc<(), i64>,
+    entities: Vec<PluginEntity>,
+}
+
+impl WasmPlugin {
+    fn load(engine: &Engine, path: &Path) -> Result<Self> {
+        let name = path
+            .file_stem()
+            .context("Plugin has no file name.")?
+            .to_string_lossy()
+            .to_string();
+
+        let module = Module::from_file(engine, path).context("Failed to compile plugin.")?;
+
+        let mut linker = Linker::new(engine);
+        linker.func_wrap(
+            "system_mqtt",
+            "read_file",
+            |mut caller: Caller<'_, ()>, pointer: i32, length: i32| -> i64 {
+                match host_read_file(&mut caller, pointer, length) {
+                    Ok(buffer) => buffer,
+                    Err(error) => {
+                        log::warn!("Plugin failed to read file: {:?}", error);
+                        -1
+                    }
+                }
+            },
+        )?;
+        let plugin_name = name.clone();
+        linker.func_wrap(
+            "system_mqtt",
+            "log",
+            move |mut caller: Caller<'_, ()>, pointer: i32, length: i32| {
+                let message = caller_memory(&mut caller)
+                    .and_then(|memory| read_string(memory.data(&caller), pointer, length));
+
+                match message {
+                    Ok(message) => log::info!("Plugin `{}`: {}", plugin_name, message),
+                    Err(error) => log::warn!("Plugin `{}` failed to log: {:?}", plugin_name, error),
+                }
+            },
+        )?;
+
+        let mut store = Store::new(engine, ());
+        store.add_fuel(FUEL_PER_CALL)?;
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .context("Failed to instantiate plugin.")?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("Plugin does not export its memory.")?;
+        let entities_function = instance.get_typed_func::<(), i64>(&mut store, "entities")?;
+        let collect = instance.get_typed_func::<(), i64>(&mut store, "collect")?;
+
+        store.add_fuel(FUEL_PER_CALL)?;
+        let buffer = entities_function.call(&mut store, ())?;
+        let entities = serde_json::from_str(&read_buffer(memory.data(&store), buffer)?)
+            .context("Plugin returned an invalid entity list.")?;
+
+        Ok(Self {
+            name,
+            store,
+            memory,
+            collect,
+            entities,
+        })
+    }
+
+    /// The name the entity is reported as. Plugin entities are prefixed by the plugin's name
+    /// so that two plugins can't clash with each other.
+    fn topic_name(&self, entity_name: &str) -> String {
+        format!("{}_{}", self.name, entity_name)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        Ok(self
+            .entities
+            .iter()
+            .map(|entity| {
+                Entity::new("sensor", &self.topic_name(&entity.name))
+                    .device_class(entity.device_class.as_deref())
+                    .state_class(
+                        entity
+                            .state_class
+                            .as_deref()
+                            .unwrap_or_else(|| default_state_class(&entity.unit)),
+                    )
+                    .unit(entity.unit.as_deref())
+                    .icon(entity.icon.as_deref().unwrap_or("mdi:puzzle"))
+            })
+            .collect())
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        self.store.add_fuel(FUEL_PER_CALL)?;
+        let buffer = self.collect.call(&mut self.store, ())?;
+        let values: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&read_buffer(self.memory.data(&self.store), buffer)?)
+                .context("Plugin returned invalid values.")?;
+
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    serde_json::Value::Bool(value) => if value { "ON" } else { "OFF" }.to_string(),
+                    value => value.to_string(),
+                };
+
+                Reading::new(self.topic_name(&name), value)
+            })
+            .collect())
+    }
+}
+
+/// Loads every `.wasm` file in the directory as a plugin.
+pub fn load_plugins(directory: &Path) -> Result<Vec<WasmPlugin>> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+
+    let mut paths = std::fs::read_dir(directory)
+        .with_context(|| format!("Failed to read plugin directory `{}`.", directory.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .map_or(false, |extension| extension == "wasm")
+    });
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            log::info!("Loading plugin `{}`.", path.display());
+            WasmPlugin::load(&engine, path)
+                .with_context(|| format!("Failed to load plugin `{}`.", path.display()))
+        })
+        .collect()
+}
+
+fn caller_memory(caller: &mut Caller<'_, ()>) -> Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .context("Plugin does not export its memory.")
+}
+
+fn read_string(memory: &[u8], pointer: i32, length: i32) -> Result<String> {
+    let start = pointer as u32 as usize;
+    let end = start + length as u32 as usize;
+    let bytes = memory
+        .get(start..end)
+        .context("Plugin passed a buffer outside of its memory.")?;
+
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+fn read_buffer(memory: &[u8], buffer: i64) -> Result<String> {
+    read_string(memory, (buffer >> 32) as i32, buffer as i32)
+}
+
+fn host_read_file(caller: &mut Caller<'_, ()>, pointer: i32, length: i32) -> Result<i64> {
+    let memory = caller_memory(caller)?;
+    let path = PathBuf::from(read_string(memory.data(&*caller), pointer, length)?);
+
+    if !READABLE_PATHS
+        .iter()
+        .any(|allowed| path.starts_with(allowed))
+    {
+        bail!(
+            "Plugins may only read files under {:?}, not `{}`.",
+            READABLE_PATHS,
+            path.display()
+        );
+    }
+
+    let contents = std::fs::read(&path)?;
+
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .context("Plugin does not export `alloc`.")?
+        .typed::<i32, i32>(&*caller)?;
+    let output = alloc.call(&mut *caller, contents.len() as i32)?;
+    memory.write(&mut *caller, output as u32 as usize, &contents)?;
+
+    Ok(((output as u32 as i64) << 32) | contents.len() as u32 as i64)
+}
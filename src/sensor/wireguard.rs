@@ -0,0 +1,146 @@
+use super::{Reading, Sensor};
+use crate::{
+    privileged::{Privileged, Request},
+    sink::Entity,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// WireGuard renews its handshake every two minutes while there's traffic, so a peer that
+/// hasn't had one in longer than this is gone.
+const HANDSHAKE_TIMEOUT_SECONDS: u64 = 180;
+
+/// Whether WireGuard interfaces are connected, along with how much data went through them.
+///
+/// This goes through the `wg` command, which needs root.
+pub struct WireGuardSensor {
+    interfaces: Vec<String>,
+    privileged: Privileged,
+}
+
+impl WireGuardSensor {
+    pub fn new(interfaces: Vec<String>, privileged: Privileged) -> Self {
+        Self {
+            interfaces,
+            privileged,
+        }
+    }
+
+    async fn read_interface(&self, interface: &str) -> Result<Vec<Reading>> {
+        let prefix = format!("wireguard_{}", interface);
+
+        // A WireGuard interface that's down doesn't exist at all.
+        if !Path::new("/sys/class/net").join(interface).exists() {
+            return Ok(vec![Reading::new(format!("{}_connected", prefix), "OFF")]);
+        }
+
+        let stdout = self
+            .privileged
+            .run(Request::WireguardDump {
+                interface: interface.to_string(),
+            })
+            .await?;
+
+        // The first line is the interface itself. Every line after it is a peer, with tab separated
+        // public key, preshared key, endpoint, allowed IPs, latest handshake, received, transmitted and keepalive.
+        let mut latest_handshake = 0;
+        let mut received = 0;
+        let mut transmitted = 0;
+        for peer in stdout.lines().skip(1) {
+            let fields: Vec<&str> = peer.split('\t').collect();
+            if fields.len() < 7 {
+                bail!("Unexpected output from wg: `{}`", peer);
+            }
+
+            latest_handshake = latest_handshake.max(fields[4].parse::<u64>()?);
+            received += fields[5].parse::<u64>()?;
+            transmitted += fields[6].parse::<u64>()?;
+        }
+
+        let mut readings = vec![
+            Reading::new(format!("{}_received", prefix), received.to_string()),
+            Reading::new(format!("{}_transmitted", prefix), transmitted.to_string()),
+        ];
+
+        // A handshake of zero means there never was one.
+        let handshake_age = if latest_handshake > 0 {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let handshake_age = now.saturating_sub(latest_handshake);
+            readings.push(Reading::new(
+                format!("{}_handshake_age", prefix),
+                handshake_age.to_string(),
+            ));
+
+            Some(handshake_age)
+        } else {
+            None
+        };
+
+        let connected = matches!(handshake_age, Some(age) if age <= HANDSHAKE_TIMEOUT_SECONDS);
+        readings.push(Reading::new(
+            format!("{}_connected", prefix),
+            if connected { "ON" } else { "OFF" },
+        ));
+
+        Ok(readings)
+    }
+}
+
+#[async_trait(?Send)]
+impl Sensor for WireGuardSensor {
+    fn name(&self) -> &str {
+        "wireguard"
+    }
+
+    async fn register(&mut self) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+
+        for interface in self.interfaces.iter() {
+            let prefix = format!("wireguard_{}", interface);
+
+            entities.push(
+                Entity::new("binary_sensor", &format!("{}_connected", prefix))
+                    .device_class("connectivity")
+                    .state_class("")
+                    .icon("mdi:vpn"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_handshake_age", prefix))
+                    .device_class("duration")
+                    .state_class("measurement")
+                    .unit("s")
+                    .icon("mdi:handshake-outline"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_received", prefix))
+                    .device_class("data_size")
+                    .state_class("total_increasing")
+                    .unit("B")
+                    .icon("mdi:download-network"),
+            );
+            entities.push(
+                Entity::new("sensor", &format!("{}_transmitted", prefix))
+                    .device_class("data_size")
+                    .state_class("total_increasing")
+                    .unit("B")
+                    .icon("mdi:upload-network"),
+            );
+        }
+
+        Ok(entities)
+    }
+
+    async fn collect(&mut self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+
+        for interface in self.interfaces.iter() {
+            readings.extend(self.read_interface(interface).await?);
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,379 @@
+//! Walks through setting up system-mqtt by asking questions on the terminal, for people who would
+//! rather not start by editing the config file by hand.
+
+use crate::{
+    config::{Config, DriveConfig, DriveDiscoveryConfig, MqttServer, PasswordSource},
+    daemon::connect_client,
+    sensor::drives::{entity_name, DiscoveryFilter},
+    sink::ActionClass,
+    KEYRING_SERVICE_NAME,
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+use sysinfo::{DiskExt, NetworksExt, System, SystemExt};
+
+/// Asks for a line of text. An empty answer takes the default, if there is one.
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            bail!("No more answers to read. Setup was cancelled.");
+        }
+
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Asks a yes or no question.
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    loop {
+        let answer = ask(
+            &format!("{} ({})", question, if default { "Y/n" } else { "y/N" }),
+            Some(""),
+        )?;
+
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer yes or no."),
+        }
+    }
+}
+
+/// Sets up the config file at the path, starting from what's in it now.
+pub async fn setup(config_file: &Path, mut config: Config) -> Result<()> {
+    println!("This sets up `{}`.", config_file.display());
+    println!("Press enter to keep what's in brackets.");
+    println!();
+
+    ask_for_server(&mut config).await?;
+    ask_for_sensors(&mut config)?;
+
+    // Everything that was left as it was is written back as it was, but comments are lost.
+    println!();
+    if !confirm(
+        &format!(
+            "Write the config to `{}`? Comments in it will be lost",
+            config_file.display()
+        ),
+        true,
+    )? {
+        println!("Nothing was written.");
+        return Ok(());
+    }
+    if let Some(parent) = config_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(config_file, serde_yaml::to_string(&config)?)
+        .with_context(|| format!("Failed to write `{}`.", config_file.display()))?;
+    println!("Written.");
+
+    offer_service(config_file)
+}
+
+/// Asks where the MQTT server is and how to log into it, and tries connecting.
+async fn ask_for_server(config: &mut Config) -> Result<()> {
+    loop {
+        config.mqtt_server = loop {
+            let server = ask(
+                "MQTT server URL, such as `mqtt://homeassistant.local`, or `auto` to find one",
+                Some(&config.mqtt_server.to_string()),
+            )?;
+
+            match MqttServer::try_from(server) {
+                Ok(server) => break server,
+                Err(error) => println!("That's not a URL: {}", error),
+            }
+        };
+
+        let username = ask(
+            "Username, or `-` if the server doesn't need one",
+            Some(config.username.as_deref().unwrap_or("-")),
+        )?;
+        config.username = (username != "-").then_some(username);
+
+        if let Some(username) = config.username.clone() {
+            ask_for_password(config, username)?;
+        }
+
+        // A client ID of our own, so a daemon that's already running isn't kicked off.
+        let hostname = System::new().host_name().unwrap_or_default();
+        println!("Connecting...");
+        match connect_client(config, format!("system-mqtt-setup-{}", hostname), None).await {
+            Ok(mut client) => {
+                client.disconnect().await?;
+                println!("Connected.");
+                return Ok(());
+            }
+            Err(error) => {
+                println!("Failed to connect: {:#}", error);
+                if !confirm("Try again?", true)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Asks for the password and where to keep it.
+fn ask_for_password(config: &mut Config, username: String) -> Result<()> {
+    let use_keyring = confirm(
+        "Keep the password in the keyring? Otherwise it's kept in a file only you can read",
+        matches!(config.password_source, PasswordSource::Keyring),
+    )?;
+
+    if use_keyring {
+        config.password_source = PasswordSource::Keyring;
+    } else {
+        let current = match &config.password_source {
+            PasswordSource::SecretFile(path) => path.clone(),
+            PasswordSource::Keyring => PathBuf::from("/etc/system-mqtt.secret"),
+        };
+        let path = ask(
+            "Where to keep the password",
+            Some(&current.to_string_lossy()),
+        )?;
+        config.password_source = PasswordSource::SecretFile(PathBuf::from(path));
+    }
+
+    // Leaving it as it was is fine for a password that's already been set.
+    let password = rpassword::prompt_password("Password (leave empty to keep the current one): ")
+        .context("Failed to read password from TTY.")?;
+    if password.is_empty() {
+        return Ok(());
+    }
+
+    match &config.password_source {
+        PasswordSource::Keyring => keyring::Entry::new(KEYRING_SERVICE_NAME, &username)
+            .context("Failed to find password entry in keyring.")?
+            .set_password(&password)
+            .context("Keyring error.")?,
+        PasswordSource::SecretFile(path) => write_secret_file(path, &password)?,
+    }
+
+    Ok(())
+}
+
+/// Writes the password to a file only we can read, which is what's checked when it's read back.
+#[cfg(unix)]
+fn write_secret_file(path: &Path, password: &str) -> Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to write `{}`.", path.display()))?;
+
+    // The mode only applies to files that didn't exist yet.
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(password.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_secret_file(path: &Path, password: &str) -> Result<()> {
+    std::fs::write(path, password)
+        .with_context(|| format!("Failed to write `{}`.", path.display()))?;
+    println!("Make sure only you can read `{}`.", path.display());
+
+    Ok(())
+}
+
+/// Asks which filesystems and network interfaces to report on, and which commands to accept.
+fn ask_for_sensors(config: &mut Config) -> Result<()> {
+    let mut system = System::new();
+    system.refresh_disks_list();
+    system.refresh_networks_list();
+
+    println!();
+    let filter = DiscoveryFilter::new(&DriveDiscoveryConfig::default());
+    let mut drives = Vec::new();
+    for disk in system.disks() {
+        let mount_point = disk.mount_point();
+        let file_system = String::from_utf8_lossy(disk.file_system());
+        if !filter.accepts(&file_system, mount_point) {
+            continue;
+        }
+
+        let configured = config.drives.iter().find(|drive| drive.path == mount_point);
+        if confirm(
+            &format!("Report how full `{}` is?", mount_point.display()),
+            configured.is_some(),
+        )? {
+            drives.push(match configured {
+                Some(drive) => drive.clone(),
+                None => DriveConfig {
+                    path: mount_point.to_path_buf(),
+                    name: entity_name("drive", mount_point),
+                    device: None,
+                    detect_read_only: false,
+                    detect_encryption: false,
+                },
+            });
+        }
+    }
+
+    // Drives that aren't mounted right now are kept as they were.
+    for drive in config.drives.iter() {
+        if !drives.iter().any(|kept| kept.path == drive.path)
+            && !system
+                .disks()
+                .iter()
+                .any(|disk| disk.mount_point() == drive.path)
+        {
+            drives.push(drive.clone());
+        }
+    }
+    config.drives = drives;
+
+    let mut interfaces: Vec<&String> = system
+        .networks()
+        .iter()
+        .map(|(interface, _)| interface)
+        .filter(|interface| interface.as_str() != "lo")
+        .collect();
+    interfaces.sort();
+    let mut network_interfaces = Vec::new();
+    for interface in interfaces {
+        if confirm(
+            &format!("Report how much data goes through `{}`?", interface),
+            config.network_interfaces.contains(interface),
+        )? {
+            network_interfaces.push(interface.clone());
+        }
+    }
+    config.network_interfaces = network_interfaces;
+
+    println!();
+    config.enable_power_commands = confirm(
+        "Add shutdown and reboot buttons to Home Assistant?",
+        config.enable_power_commands,
+    )?;
+    config.enable_suspend_command = confirm(
+        "Add a suspend button to Home Assistant?",
+        config.enable_suspend_command,
+    )?;
+    config.enable_lock_command = confirm(
+        "Add a button to Home Assistant that locks the screen?",
+        config.enable_lock_command,
+    )?;
+
+    // The buttons only do anything once the kinds of command they send are allowed.
+    let mut action_classes = Vec::new();
+    if config.enable_power_commands || config.enable_suspend_command {
+        action_classes.push(ActionClass::Power);
+    }
+    if config.enable_lock_command {
+        action_classes.push(ActionClass::Desktop);
+    }
+    if !action_classes.is_empty() {
+        let commands = config.commands.get_or_insert_with(Default::default);
+        for action_class in action_classes {
+            if !commands.allow.contains(&action_class) {
+                commands.allow.push(action_class);
+            }
+        }
+    }
+    config.enable_sleep_detection = confirm(
+        "Report when the system goes to sleep and wakes up?",
+        config.enable_sleep_detection,
+    )?;
+
+    Ok(())
+}
+
+/// Offers to start system-mqtt with the system.
+#[cfg(target_os = "linux")]
+fn offer_service(config_file: &Path) -> Result<()> {
+    use anyhow::ensure;
+    use std::process::Command;
+
+    const UNIT: &str = include_str!("../systemd/system-mqtt.service");
+    const UNIT_PATH: &str = "/etc/systemd/system/system-mqtt.service";
+
+    // Packages install the unit on their own.
+    let installed = [
+        "/lib/systemd/system",
+        "/usr/lib/systemd/system",
+        "/etc/systemd/system",
+    ]
+    .iter()
+    .any(|directory| Path::new(directory).join("system-mqtt.service").exists());
+
+    println!();
+    if !installed {
+        if !confirm(
+            &format!("Install the systemd unit to `{}`?", UNIT_PATH),
+            true,
+        )? {
+            return Ok(());
+        }
+
+        // Runs this copy of system-mqtt with this config file, wherever they are.
+        let executable = std::env::current_exe().context("Failed to find ourselves.")?;
+        let unit = UNIT.replace(
+            "ExecStart=/usr/bin/system-mqtt run",
+            &format!(
+                "ExecStart={} --config-file {} run",
+                executable.display(),
+                config_file.display()
+            ),
+        );
+
+        std::fs::write(UNIT_PATH, unit)
+            .with_context(|| format!("Failed to write `{}`. Are you root?", UNIT_PATH))?;
+    }
+
+    if !confirm(
+        "Start system-mqtt now, and whenever the system starts?",
+        true,
+    )? {
+        return Ok(());
+    }
+
+    // A daemon that's already running has to be restarted to pick up the new config.
+    for arguments in [
+        &["daemon-reload"][..],
+        &["enable", "system-mqtt"],
+        &["restart", "system-mqtt"],
+    ] {
+        let status = Command::new("systemctl")
+            .args(arguments)
+            .status()
+            .context("Failed to run systemctl.")?;
+        ensure!(
+            status.success(),
+            "`systemctl {}` exited with {}.",
+            arguments.join(" "),
+            status
+        );
+    }
+    println!("system-mqtt is running.");
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn offer_service(_config_file: &Path) -> Result<()> {
+    println!("Use the Task Scheduler, or a service wrapper such as NSSM, to start `system-mqtt run` at boot.");
+    Ok(())
+}
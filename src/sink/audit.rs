@@ -0,0 +1,90 @@
+use super::Command;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// Where every command we're sent is recorded, along with what came of it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditLogConfig {
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// The command was handed to the sensor it was for.
+    Allowed,
+
+    /// The command was refused before it got to a sensor.
+    Denied,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    time: String,
+    topic: &'a str,
+    entity: &'a str,
+    payload: &'a str,
+
+    /// The client that signed the command, if it has a secret of its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    decision: Decision,
+
+    /// What the sensor made of the command, or why it was refused.
+    result: &'a str,
+}
+
+/// Appends a line of JSON to the audit log for every command.
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditLogConfig) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&config.path)
+            .with_context(|| format!("Failed to open audit log `{}`.", config.path.display()))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, command: &Command, decision: Decision, result: &str) {
+        let mut line = match serde_json::to_string(&Entry {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            topic: &command.topic,
+            entity: &command.entity,
+            payload: &command.payload,
+            source: command.source.as_deref(),
+            decision,
+            result,
+        }) {
+            Ok(line) => line,
+            Err(error) => {
+                log::error!("Failed to serialize audit log entry: {:?}", error);
+                return;
+            }
+        };
+        line.push('\n');
+
+        // Commands are rare enough that every entry can go straight to disk.
+        if let Err(error) = self
+            .file
+            .lock()
+            .expect("Audit log lock was poisoned.")
+            .write_all(line.as_bytes())
+        {
+            log::error!("Failed to write to audit log: {:?}", error);
+        }
+    }
+}
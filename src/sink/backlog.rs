@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::VecDeque, time::SystemTime};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BacklogConfig {
+    /// The most values to hold on to. Once full, the oldest are dropped to make room.
+    #[serde(default = "BacklogConfig::default_max_values")]
+    pub max_values: usize,
+}
+
+impl BacklogConfig {
+    fn default_max_values() -> usize {
+        10_000
+    }
+}
+
+/// A value that couldn't be published when it was collected.
+pub struct BackloggedValue {
+    /// The topic the value would have been published on.
+    pub topic: String,
+    pub payload: String,
+    pub time: SystemTime,
+}
+
+impl BackloggedValue {
+    /// The topic the value is replayed on, next to the one it was meant for.
+    pub fn backfill_topic(&self) -> String {
+        format!("{}/backfill", self.topic)
+    }
+
+    /// The value along with when it was collected, so it can be put in its place in history.
+    pub fn backfill_payload(&self) -> String {
+        json!({
+            "time": humantime::format_rfc3339_seconds(self.time).to_string(),
+            "value": self.payload,
+        })
+        .to_string()
+    }
+}
+
+/// Holds on to values that couldn't be published while the MQTT server was unreachable, so they
+/// can be replayed once it's back.
+pub struct Backlog {
+    max_values: usize,
+    values: VecDeque<BackloggedValue>,
+}
+
+impl Backlog {
+    pub fn new(config: BacklogConfig) -> Self {
+        Self {
+            max_values: config.max_values,
+            values: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, topic: String, payload: String) {
+        if self.max_values == 0 {
+            return;
+        }
+
+        if self.values.len() >= self.max_values {
+            self.values.pop_front();
+        }
+
+        self.values.push_back(BackloggedValue {
+            topic,
+            payload,
+            time: SystemTime::now(),
+        });
+    }
+
+    /// Takes the oldest value, to be replayed.
+    pub fn pop(&mut self) -> Option<BackloggedValue> {
+        self.values.pop_front()
+    }
+
+    /// Puts back a value that failed to be replayed, so it's the next one tried.
+    pub fn put_back(&mut self, value: BackloggedValue) {
+        self.values.push_front(value);
+    }
+}
@@ -0,0 +1,171 @@
+use super::Command;
+use crate::config::PasswordSource;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Requires commands to be signed with a secret shared with whoever sends them, so having access
+/// to the command topics isn't enough to control the host.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandAuthConfig {
+    /// Where the shared secret comes from. In the keyring, it's set with
+    /// `system-mqtt set-password --command-secret`.
+    #[serde(default)]
+    pub secret_source: PasswordSource,
+
+    /// How far a command's timestamp may be from our clock before it's refused.
+    #[serde(default = "CommandAuthConfig::default_max_age")]
+    pub max_age: Duration,
+
+    /// Clients that sign commands with secrets of their own, so we can tell which of them sent a
+    /// command.
+    #[serde(default)]
+    pub clients: Vec<CommandClientConfig>,
+}
+
+impl CommandAuthConfig {
+    fn default_max_age() -> Duration {
+        Duration::from_secs(30)
+    }
+}
+
+/// Something that sends us commands, signed with a secret only it and we know.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandClientConfig {
+    pub name: String,
+
+    /// Where the client's secret comes from. In the keyring, it's set with
+    /// `system-mqtt set-password --command-secret --client {name}`.
+    #[serde(default)]
+    pub secret_source: PasswordSource,
+}
+
+/// What a signed command's payload looks like.
+#[derive(Deserialize)]
+struct SignedPayload {
+    /// The payload the command would have had unsigned, such as `ON`.
+    payload: String,
+
+    /// When the command was sent, in seconds since the Unix epoch.
+    timestamp: u64,
+
+    /// Any string, as long as it's never used twice.
+    nonce: String,
+
+    /// The HMAC-SHA256 of `{entity}\n{timestamp}\n{nonce}\n{payload}`, in hex.
+    signature: String,
+
+    /// Which client signed the command, with its own secret. Left out for the shared secret.
+    #[serde(default)]
+    client: Option<String>,
+}
+
+/// Checks the signatures of commands, and refuses ones that have been seen before.
+pub struct CommandAuthenticator {
+    secret: Vec<u8>,
+
+    /// The secrets of clients that have their own, by name.
+    client_secrets: HashMap<String, Vec<u8>>,
+    max_age: Duration,
+
+    /// Nonces of commands that haven't been around long enough to be refused for their age, and
+    /// when they can be forgotten.
+    seen_nonces: HashMap<String, SystemTime>,
+}
+
+impl CommandAuthenticator {
+    pub fn new(
+        secret: &str,
+        client_secrets: HashMap<String, String>,
+        config: &CommandAuthConfig,
+    ) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+            client_secrets: client_secrets
+                .into_iter()
+                .map(|(client, secret)| (client, secret.into_bytes()))
+                .collect(),
+            max_age: config.max_age,
+            seen_nonces: HashMap::new(),
+        }
+    }
+
+    /// Checks a signed command, and hands back the command with the payload that was signed.
+    pub fn verify(&mut self, command: &Command) -> Result<Command> {
+        let signed: SignedPayload =
+            serde_json::from_str(&command.payload).context("Command isn't signed.")?;
+
+        let secret = match &signed.client {
+            Some(client) => self
+                .client_secrets
+                .get(client)
+                .with_context(|| format!("There is no client named `{}`.", client))?,
+            None => &self.secret,
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+            .map_err(|error| anyhow!("Invalid secret: {}", error))?;
+        mac.update(
+            format!(
+                "{}\n{}\n{}\n{}",
+                command.entity, signed.timestamp, signed.nonce, signed.payload
+            )
+            .as_bytes(),
+        );
+        mac.verify_slice(&decode_hex(&signed.signature)?)
+            .map_err(|_| anyhow!("Signature doesn't match."))?;
+
+        let now = SystemTime::now();
+        // The signature has been checked, but the timestamp still comes from outside, so it
+        // mustn't be trusted to fit in our clock.
+        let sent = UNIX_EPOCH
+            .checked_add(Duration::from_secs(signed.timestamp))
+            .context("Command's timestamp is out of range.")?;
+        let age = now
+            .duration_since(sent)
+            .unwrap_or_else(|error| error.duration());
+        ensure!(
+            age <= self.max_age,
+            "Command was sent {:?} away from our clock, more than the {:?} allowed.",
+            age,
+            self.max_age
+        );
+
+        self.seen_nonces.retain(|_, expires| *expires > now);
+        if self.seen_nonces.contains_key(&signed.nonce) {
+            bail!("Command has already been carried out once.");
+        }
+        // Once it's too old, the command is refused for its age instead.
+        let expires = self
+            .max_age
+            .checked_add(Duration::from_secs(1))
+            .and_then(|max_age| sent.checked_add(max_age))
+            .context("Command's timestamp is out of range.")?;
+        self.seen_nonces.insert(signed.nonce, expires);
+
+        Ok(Command {
+            entity: command.entity.clone(),
+            topic: command.topic.clone(),
+            payload: signed.payload,
+            source: signed.client,
+        })
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    ensure!(hex.len() % 2 == 0, "Signature isn't valid hex.");
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .context("Signature isn't valid hex.")
+        })
+        .collect()
+}
@@ -0,0 +1,105 @@
+use super::{ActionClass, Command};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Which kinds of command are carried out, and which of them need to be sent twice. Nothing is
+/// carried out unless its kind is allowed, which is also the case when there's no policy at all.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandPolicyConfig {
+    /// The kinds of command that are carried out. Commands of any other kind are refused.
+    #[serde(default)]
+    pub allow: Vec<ActionClass>,
+
+    /// Kinds of command that are only carried out when the same command comes in again within
+    /// `confirm_within`, so a single stray message can't shut the host down.
+    #[serde(default)]
+    pub confirm: Vec<ActionClass>,
+
+    #[serde(default = "CommandPolicyConfig::default_confirm_within")]
+    pub confirm_within: Duration,
+
+    /// Clients commands may come from, by their names in `command_authentication`. MQTT doesn't
+    /// say who sent a message, so only commands they signed with their own secrets are carried
+    /// out. If empty, commands may come from anyone who can send them.
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+impl CommandPolicyConfig {
+    fn default_confirm_within() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+impl Default for CommandPolicyConfig {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            confirm: Vec::new(),
+            confirm_within: Self::default_confirm_within(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+/// Decides which commands are carried out.
+pub struct CommandPolicy {
+    config: CommandPolicyConfig,
+
+    /// Commands waiting to be confirmed, and when they came in, by entity name.
+    unconfirmed: HashMap<String, (String, Instant)>,
+}
+
+impl CommandPolicy {
+    pub fn new(config: CommandPolicyConfig) -> Self {
+        Self {
+            config,
+            unconfirmed: HashMap::new(),
+        }
+    }
+
+    /// Fails for commands that aren't allowed, and for the first of commands that need confirming.
+    /// The action class is that of the entity the command is for.
+    pub fn check(&mut self, command: &Command, action_class: Option<ActionClass>) -> Result<()> {
+        let action_class = action_class.context("The entity doesn't accept commands.")?;
+        if !self.config.allow.contains(&action_class) {
+            bail!("{:?} commands aren't allowed.", action_class);
+        }
+
+        if !self.config.sources.is_empty() {
+            match &command.source {
+                Some(source) if self.config.sources.contains(source) => {}
+                Some(source) => bail!("Commands from `{}` aren't allowed.", source),
+                None => bail!("Command wasn't signed by any of the clients that are allowed."),
+            }
+        }
+
+        if self.config.confirm.contains(&action_class) {
+            let confirm_within = self.config.confirm_within;
+            let confirmed = self
+                .unconfirmed
+                .remove(&command.entity)
+                .map(|(payload, received)| {
+                    payload == command.payload && received.elapsed() <= confirm_within
+                })
+                .unwrap_or(false);
+
+            if !confirmed {
+                self.unconfirmed.insert(
+                    command.entity.clone(),
+                    (command.payload.clone(), Instant::now()),
+                );
+                bail!(
+                    "Waiting for the command to be sent again within {:?}.",
+                    confirm_within
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChangeFilterConfig {
+    /// Numbers must change by more than this to be published again.
+    /// At zero, any change at all gets published.
+    #[serde(default)]
+    pub deadband: f64,
+
+    /// Overrides the deadband for specific entities.
+    #[serde(default)]
+    pub deadbands: HashMap<String, f64>,
+
+    /// Values are published at least this often, even if they haven't changed.
+    #[serde(default = "ChangeFilterConfig::default_heartbeat")]
+    pub heartbeat: Duration,
+}
+
+impl ChangeFilterConfig {
+    fn default_heartbeat() -> Duration {
+        Duration::from_secs(300)
+    }
+}
+
+/// Suppresses values that haven't changed since they were last published.
+pub struct ChangeFilter {
+    config: ChangeFilterConfig,
+
+    /// The last value published for each entity, and when.
+    last_published: HashMap<String, (String, Instant)>,
+}
+
+impl ChangeFilter {
+    pub fn new(config: ChangeFilterConfig) -> Self {
+        Self {
+            config,
+            last_published: HashMap::new(),
+        }
+    }
+
+    /// Decides if a value should be published. If it should, it is remembered as the last published value.
+    pub fn should_publish(&mut self, entity_name: &str, value: &str) -> bool {
+        let now = Instant::now();
+
+        let changed = match self.last_published.get(entity_name) {
+            Some((_, published_at))
+                if now.duration_since(*published_at) >= self.config.heartbeat =>
+            {
+                true
+            }
+            Some((last_value, _)) => match (last_value.parse::<f64>(), value.parse::<f64>()) {
+                (Ok(last_value), Ok(value)) => {
+                    let deadband = self
+                        .config
+                        .deadbands
+                        .get(entity_name)
+                        .copied()
+                        .unwrap_or(self.config.deadband);
+
+                    if deadband > 0.0 {
+                        (value - last_value).abs() > deadband
+                    } else {
+                        value != last_value
+                    }
+                }
+                _ => last_value != value,
+            },
+            None => true,
+        };
+
+        if changed {
+            self.last_published
+                .insert(entity_name.to_string(), (value.to_string(), now));
+        }
+
+        changed
+    }
+
+    /// Forgets what was last published for an entity, so its next value gets published.
+    pub fn forget(&mut self, entity_name: &str) {
+        self.last_published.remove(entity_name);
+    }
+
+    /// Forgets what was last published, so everything gets published again.
+    pub fn reset(&mut self) {
+        self.last_published.clear();
+    }
+}
@@ -0,0 +1,167 @@
+use super::{Entity, Sink};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How often readings older than the retention period are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryConfig {
+    /// The SQLite database to record readings in. It's created if it doesn't exist.
+    pub path: PathBuf,
+
+    /// How long to keep readings for. Defaults to a week.
+    #[serde(default = "HistoryConfig::default_retention")]
+    pub retention: Duration,
+}
+
+impl HistoryConfig {
+    fn default_retention() -> Duration {
+        Duration::from_secs(7 * 24 * 60 * 60)
+    }
+}
+
+/// A value recorded in the history.
+#[derive(Serialize)]
+pub struct Record {
+    /// When the value was published, in seconds since the Unix epoch.
+    pub time: u64,
+    pub value: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// Reads the values of an entity recorded since the given time, oldest first.
+pub fn query(path: &Path, entity_name: &str, since: SystemTime) -> Result<Vec<Record>> {
+    let connection = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open history database `{}`.", path.display()))?;
+    let since = since
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    let mut statement = connection.prepare(
+        "SELECT time, value FROM readings WHERE entity = ?1 AND time >= ?2 ORDER BY time",
+    )?;
+    let records = statement
+        .query_map(params![entity_name, since as i64], |row| {
+            Ok(Record {
+                time: row.get::<_, i64>(0)? as u64,
+                value: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()
+        .context("Failed to read history.")?;
+
+    Ok(records)
+}
+
+/// Records every value published into a local SQLite database, so there's history to look
+/// back on even while the MQTT server or Home Assistant is down.
+pub struct History {
+    connection: Mutex<Connection>,
+    retention: Duration,
+
+    /// Values published since the last flush, which are written together.
+    pending: Mutex<Vec<(String, String, u64)>>,
+    last_pruned: Mutex<Option<Instant>>,
+}
+
+impl History {
+    pub fn open(config: &HistoryConfig) -> Result<Self> {
+        let connection = Connection::open(&config.path).with_context(|| {
+            format!(
+                "Failed to open history database `{}`.",
+                config.path.display()
+            )
+        })?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS readings (
+                    time INTEGER NOT NULL,
+                    entity TEXT NOT NULL,
+                    value TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS readings_by_entity ON readings (entity, time);",
+            )
+            .context("Failed to set up history database.")?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            retention: config.retention,
+            pending: Mutex::new(Vec::new()),
+            last_pruned: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for History {
+    async fn register(&mut self, _entity: &Entity) -> Result<()> {
+        Ok(())
+    }
+
+    /// Queues a value to be written on the next flush.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        self.pending
+            .lock()
+            .expect("Pending lock was poisoned.")
+            .push((entity_name.to_string(), value.to_string(), now()));
+
+        Ok(())
+    }
+
+    /// Writes all queued values, and deletes old ones every so often.
+    async fn flush(&self) -> Result<()> {
+        let pending =
+            std::mem::take(&mut *self.pending.lock().expect("Pending lock was poisoned."));
+        let mut connection = self
+            .connection
+            .lock()
+            .expect("Connection lock was poisoned.");
+
+        if !pending.is_empty() {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached(
+                    "INSERT INTO readings (time, entity, value) VALUES (?1, ?2, ?3)",
+                )?;
+                for (entity_name, value, time) in pending.iter() {
+                    statement.execute(params![*time as i64, entity_name, value])?;
+                }
+            }
+            transaction
+                .commit()
+                .context("Failed to record readings in history.")?;
+        }
+
+        let mut last_pruned = self
+            .last_pruned
+            .lock()
+            .expect("Prune time lock was poisoned.");
+        if last_pruned.map_or(true, |last_pruned| last_pruned.elapsed() >= PRUNE_INTERVAL) {
+            let cutoff = now().saturating_sub(self.retention.as_secs());
+            connection
+                .execute(
+                    "DELETE FROM readings WHERE time < ?1",
+                    params![cutoff as i64],
+                )
+                .context("Failed to delete old readings from history.")?;
+            *last_pruned = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+}
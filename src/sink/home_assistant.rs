@@ -0,0 +1,774 @@
+use super::{
+    backlog::{Backlog, BacklogConfig},
+    template::PayloadTemplates,
+    Command, Entity, Sink,
+};
+use crate::mqtt::MqttClient;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::pending;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+/// Home Assistant announces itself here when it starts.
+const BIRTH_TOPIC: &str = "homeassistant/status";
+
+/// Which kinds of messages the MQTT server is asked to keep for clients that subscribe later.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RetainConfig {
+    /// The discovery messages Home Assistant finds our entities through.
+    #[serde(default = "RetainConfig::default_true")]
+    pub discovery: bool,
+
+    /// Whether we, and the sensors behind each entity, are available.
+    #[serde(default = "RetainConfig::default_true")]
+    pub availability: bool,
+
+    /// The values of entities.
+    #[serde(default)]
+    pub state: bool,
+
+    /// Overrides `state` for specific entities.
+    #[serde(default)]
+    pub entities: HashMap<String, bool>,
+}
+
+impl RetainConfig {
+    fn default_true() -> bool {
+        true
+    }
+
+    fn state_of(&self, entity_name: &str) -> bool {
+        self.entities
+            .get(entity_name)
+            .copied()
+            .unwrap_or(self.state)
+    }
+}
+
+/// How entities are named in Home Assistant. `{hostname}` is replaced with the name of the host
+/// an entity belongs to, and `{sensor}` with the name of the entity, such as `cpu`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NamingConfig {
+    /// The name entities are shown with.
+    #[serde(default = "NamingConfig::default_name")]
+    pub name: String,
+
+    /// What the ids of entities are made from, such as `{hostname}_{sensor}` for `sensor.laptop_cpu`.
+    /// When unset, Home Assistant makes them from the name.
+    #[serde(default)]
+    pub object_id: Option<String>,
+}
+
+impl NamingConfig {
+    fn default_name() -> String {
+        String::from("{hostname}-{sensor}")
+    }
+
+    fn validate(pattern: &str) -> Result<()> {
+        let mut rest = pattern;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .with_context(|| format!("Unclosed `{{` in naming pattern `{}`.", pattern))?;
+            let placeholder = &rest[start + 1..start + end];
+            if placeholder != "hostname" && placeholder != "sensor" {
+                bail!(
+                    "Unknown placeholder `{{{}}}` in naming pattern `{}`.",
+                    placeholder,
+                    pattern
+                );
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(())
+    }
+
+    fn render(pattern: &str, host: &str, sensor: &str) -> String {
+        pattern
+            .replace("{hostname}", host)
+            .replace("{sensor}", sensor)
+    }
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        Self {
+            name: Self::default_name(),
+            object_id: None,
+        }
+    }
+}
+
+impl Default for RetainConfig {
+    fn default() -> Self {
+        Self {
+            discovery: true,
+            availability: true,
+            state: false,
+            entities: HashMap::new(),
+        }
+    }
+}
+
+/// The values of every entity, waiting to be published together.
+#[derive(Default)]
+struct Aggregate {
+    values: Map<String, Value>,
+
+    /// Set when a value changed since the document was last published.
+    changed: bool,
+}
+
+/// The topics of an entity. They're worked out when it's registered, rather than every time a
+/// value is published.
+struct EntityTopics {
+    state: String,
+    attributes: String,
+    availability: String,
+}
+
+/// Publishes values to an MQTT server along with the discovery messages Home Assistant needs.
+pub struct HomeAssistant {
+    /// The connection to the MQTT server. When this is `None` we're doing a dry run
+    /// and everything is printed to stdout instead.
+    client: Option<MqttClient>,
+    hostname: String,
+
+    /// Maps the topics commands are received on to the entities they are for.
+    command_topics: HashMap<String, String>,
+
+    /// The topics of every registered entity, by entity name.
+    entity_topics: HashMap<String, EntityTopics>,
+
+    /// Our own topics, which are named after our instance.
+    aggregate_topic: String,
+    availability_topic: String,
+
+    /// The kind of Home Assistant entity each registered entity is, which its discovery topic depends on.
+    components: HashMap<String, String>,
+
+    /// When set, values are published together as one JSON document at the end of every update,
+    /// instead of each on its own topic.
+    aggregate: Option<Mutex<Aggregate>>,
+
+    /// The entities whose values go in the aggregate document.
+    aggregated_entities: HashSet<String>,
+
+    /// Reshapes the values of some entities before they are published.
+    payload_templates: PayloadTemplates,
+
+    retain: RetainConfig,
+
+    /// The last message sent on each topic, along with whether it was retained. If Home Assistant
+    /// restarts and the MQTT server has lost the retained messages, these are sent again.
+    last_sent: Mutex<BTreeMap<String, (String, bool)>>,
+
+    /// Set once we're listening for Home Assistant to start.
+    birth_subscribed: bool,
+
+    /// When cleared, no discovery messages are sent and only the values are published.
+    discovery: bool,
+
+    /// The hosts entities that don't belong to us belong to, by the names of the entities.
+    remote_hosts: HashMap<String, String>,
+
+    /// When set, values that couldn't be published are kept and replayed once we can publish again.
+    backlog: Option<Mutex<Backlog>>,
+
+    /// Icons to use instead of the ones the sensors picked, by entity name.
+    icons: HashMap<String, String>,
+
+    naming: NamingConfig,
+
+    /// Set when more than one instance runs on the host, to keep their topics apart.
+    instance_id: Option<String>,
+}
+
+impl HomeAssistant {
+    pub fn new(client: Option<MqttClient>, hostname: String, aggregate_state: bool) -> Self {
+        let mut home_assistant = Self {
+            client,
+            hostname,
+            command_topics: HashMap::new(),
+            entity_topics: HashMap::new(),
+            aggregate_topic: String::new(),
+            availability_topic: String::new(),
+            components: HashMap::new(),
+            aggregate: aggregate_state.then(|| Mutex::new(Aggregate::default())),
+            aggregated_entities: HashSet::new(),
+            payload_templates: PayloadTemplates::default(),
+            retain: RetainConfig::default(),
+            last_sent: Mutex::new(BTreeMap::new()),
+            birth_subscribed: false,
+            discovery: true,
+            remote_hosts: HashMap::new(),
+            backlog: None,
+            icons: HashMap::new(),
+            naming: NamingConfig::default(),
+            instance_id: None,
+        };
+        home_assistant.set_instance_id(None);
+
+        home_assistant
+    }
+
+    /// Keep values that couldn't be published, and replay them once we can publish again.
+    pub fn set_backlog(&mut self, config: BacklogConfig) {
+        self.backlog = Some(Mutex::new(Backlog::new(config)));
+    }
+
+    /// Turns discovery messages on or off. Without them, values are still published on their
+    /// topics, for consumers other than Home Assistant.
+    pub fn set_discovery(&mut self, discovery: bool) {
+        self.discovery = discovery;
+    }
+
+    pub fn set_instance_id(&mut self, instance_id: Option<String>) {
+        self.instance_id = instance_id;
+
+        let node = self.node(&self.hostname);
+        self.aggregate_topic = format!("system-mqtt/{}/state", node);
+        self.availability_topic = format!("system-mqtt/{}/availability", node);
+    }
+
+    pub fn set_retain(&mut self, retain: RetainConfig) {
+        self.retain = retain;
+    }
+
+    pub fn set_payload_templates(&mut self, payload_templates: PayloadTemplates) {
+        self.payload_templates = payload_templates;
+    }
+
+    pub fn set_icons(&mut self, icons: HashMap<String, String>) {
+        self.icons = icons;
+    }
+
+    pub fn set_naming(&mut self, naming: NamingConfig) -> Result<()> {
+        NamingConfig::validate(&naming.name)?;
+        if let Some(object_id) = &naming.object_id {
+            NamingConfig::validate(object_id)?;
+        }
+        self.naming = naming;
+
+        Ok(())
+    }
+
+    /// What our topics are named after: the host, followed by our instance ID if we have one.
+    fn node(&self, host: &str) -> String {
+        match &self.instance_id {
+            Some(instance_id) => format!("{}-{}", host, instance_id),
+            None => host.to_string(),
+        }
+    }
+
+    /// The host an entity belongs to, and its name on that host. Entities of remote hosts are
+    /// named after the host, like `nas_cpu`, which is left out of their topics.
+    fn locate<'a>(&'a self, entity_name: &'a str) -> (&'a str, &'a str) {
+        match self.remote_hosts.get(entity_name) {
+            Some(host) => (
+                host,
+                entity_name
+                    .strip_prefix(host.as_str())
+                    .and_then(|name| name.strip_prefix('_'))
+                    .unwrap_or(entity_name),
+            ),
+            None => (&self.hostname, entity_name),
+        }
+    }
+
+    fn state_topic(&self, entity_name: &str) -> String {
+        let (host, name) = self.locate(entity_name);
+        format!("system-mqtt/{}/{}", self.node(host), name)
+    }
+
+    fn discovery_topic(&self, component: &str, entity_name: &str) -> String {
+        let (host, name) = self.locate(entity_name);
+        format!(
+            "homeassistant/{}/system-mqtt-{}/{}/config",
+            component,
+            self.node(host),
+            name
+        )
+    }
+
+    fn work_out_topics(&self, entity_name: &str) -> EntityTopics {
+        let state = self.state_topic(entity_name);
+
+        EntityTopics {
+            attributes: format!("{}/attributes", state),
+            availability: format!("{}/availability", state),
+            state,
+        }
+    }
+
+    fn topics_of(&self, entity_name: &str) -> Result<&EntityTopics> {
+        self.entity_topics
+            .get(entity_name)
+            .with_context(|| format!("`{}` was never registered.", entity_name))
+    }
+
+    async fn subscribe(&mut self, topic: &str) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            let accepted = client
+                .subscribe(topic)
+                .await
+                .with_context(|| format!("Failed to subscribe to `{}`.", topic))?;
+
+            if !accepted {
+                bail!("MQTT server refused subscription to `{}`.", topic);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, topic: &str, payload: String, retain: bool) -> Result<()> {
+        {
+            let mut last_sent = self.last_sent.lock().expect("Last sent lock was poisoned.");
+            if payload.is_empty() {
+                last_sent.remove(topic);
+            } else if let Some(sent) = last_sent.get_mut(topic) {
+                // The topic only needs copying the first time something is sent on it.
+                *sent = (payload.clone(), retain);
+            } else {
+                last_sent.insert(topic.to_string(), (payload.clone(), retain));
+            }
+        }
+
+        self.publish_raw(topic, payload, retain).await
+    }
+
+    async fn publish_raw(&self, topic: &str, payload: String, retain: bool) -> Result<()> {
+        log::debug!("PUBLISH `{}` TO `{}`", payload, topic);
+
+        if let Some(client) = &self.client {
+            client.publish(topic.to_string(), payload, retain).await?;
+        } else {
+            println!(
+                "{}{}: {}",
+                topic,
+                if retain { " (retained)" } else { "" },
+                payload
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Sends a value, holding on to it if that fails and there's a backlog. Once a value goes out,
+    /// whatever is in the backlog is replayed.
+    async fn send_value(&self, topic: &str, payload: String, retain: bool) -> Result<()> {
+        let backlog = match &self.backlog {
+            Some(backlog) => backlog,
+            None => return self.send(topic, payload, retain).await,
+        };
+
+        // While the connection is down, publishing would only queue the value up to go out late,
+        // or wait for room in a full queue, so it goes straight into the backlog instead.
+        if !self.client.as_ref().map_or(true, MqttClient::is_connected) {
+            backlog
+                .lock()
+                .expect("Backlog lock was poisoned.")
+                .push(topic.to_string(), payload);
+            return Ok(());
+        }
+
+        if let Err(error) = self.send(topic, payload.clone(), retain).await {
+            backlog
+                .lock()
+                .expect("Backlog lock was poisoned.")
+                .push(topic.to_string(), payload);
+            return Err(error);
+        }
+
+        loop {
+            let value = match backlog.lock().expect("Backlog lock was poisoned.").pop() {
+                Some(value) => value,
+                None => break,
+            };
+
+            // Replayed values go on their own topic, with the time they were collected, so the
+            // current state isn't overwritten with old values.
+            let result = self
+                .publish_raw(&value.backfill_topic(), value.backfill_payload(), false)
+                .await;
+            if let Err(error) = result {
+                backlog
+                    .lock()
+                    .expect("Backlog lock was poisoned.")
+                    .put_back(value);
+                return Err(error).context("Failed to replay backlog.");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends everything again: discovery messages first, so Home Assistant knows about the
+    /// entities before their availability and values arrive.
+    async fn announce_again(&self) -> Result<()> {
+        let (discovery, rest): (Vec<_>, Vec<_>) = self
+            .last_sent
+            .lock()
+            .expect("Last sent lock was poisoned.")
+            .iter()
+            .map(|(topic, (payload, retain))| (topic.clone(), payload.clone(), *retain))
+            .partition(|(topic, _, _)| topic.starts_with("homeassistant/"));
+
+        for (topic, payload, retain) in discovery.into_iter().chain(rest) {
+            self.publish_raw(&topic, payload, retain).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for HomeAssistant {
+    async fn set_available(&self, available: bool) -> Result<()> {
+        self.send(
+            &self.availability_topic,
+            if available { "online" } else { "offline" }.into(),
+            self.retain.availability,
+        )
+        .await
+        .context("Failed to publish availability topic.")
+    }
+
+    async fn register(&mut self, entity: &Entity) -> Result<()> {
+        // Without discovery there's nothing to announce again when Home Assistant starts.
+        if self.discovery && !self.birth_subscribed {
+            self.subscribe(BIRTH_TOPIC).await?;
+            self.birth_subscribed = true;
+        }
+
+        if let Some(host) = &entity.remote_host {
+            self.remote_hosts.insert(entity.name.clone(), host.clone());
+        }
+        let topics = self.work_out_topics(&entity.name);
+        self.entity_topics.insert(entity.name.clone(), topics);
+
+        let command_topic = if entity.accepts_commands.is_some() {
+            let topic = format!("{}/set", self.state_topic(&entity.name));
+            self.subscribe(&topic)
+                .await
+                .context("Failed to subscribe to command topic.")?;
+
+            self.command_topics
+                .insert(topic.clone(), entity.name.clone());
+
+            Some(topic)
+        } else {
+            None
+        };
+
+        #[derive(Serialize)]
+        struct Availability {
+            topic: String,
+        }
+
+        #[derive(Serialize)]
+        struct TopicConfig<'a> {
+            name: String,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            object_id: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            device_class: Option<&'a str>,
+            state_class: Option<&'a str>,
+            state_topic: String,
+            unit_of_measurement: Option<&'a str>,
+            icon: Option<&'a str>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            value_template: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            entity_category: Option<&'a str>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            command_topic: Option<String>,
+
+            // Cameras get their images from `topic` rather than `state_topic`.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            topic: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            image_encoding: Option<&'a str>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            min: Option<f64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            max: Option<f64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            step: Option<f64>,
+            // Lights that can be dimmed take and report JSON.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            schema: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            brightness: Option<bool>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            brightness_scale: Option<u64>,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            supported_color_modes: &'a [&'a str],
+
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            options: &'a [String],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            event_types: &'a [String],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            available_tones: &'a [String],
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            json_attributes_topic: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            source_type: Option<&'a str>,
+
+            // An entity is only available while both we and the sensor behind it are.
+            availability: [Availability; 2],
+            availability_mode: &'a str,
+        }
+
+        let is_camera = entity.component == "camera";
+        let is_event = entity.component == "event";
+        let is_siren = entity.component == "siren";
+        let is_dimmable = entity.brightness_scale.is_some();
+
+        // Images are too big to put in the aggregate document, so cameras always get their own topic.
+        // Lights that can be dimmed read their state straight from JSON, without a value template,
+        // so they do too.
+        let aggregated = self.aggregate.is_some() && !is_camera && !is_dimmable;
+        let (state_topic, value_template) = if aggregated {
+            self.aggregated_entities.insert(entity.name.clone());
+
+            (
+                self.aggregate_topic.clone(),
+                Some(format!("{{{{ value_json['{}'] }}}}", entity.name)),
+            )
+        } else {
+            (self.topics_of(&entity.name)?.state.clone(), None)
+        };
+        let topics = self.topics_of(&entity.name)?;
+
+        let (host, name) = self.locate(&entity.name);
+        let message = serde_json::ser::to_string(&TopicConfig {
+            name: NamingConfig::render(&self.naming.name, host, name),
+            object_id: self
+                .naming
+                .object_id
+                .as_ref()
+                .map(|object_id| NamingConfig::render(object_id, host, name)),
+            device_class: entity.device_class.as_deref(),
+            state_class: entity.state_class.as_deref(),
+            topic: is_camera.then(|| state_topic.clone()),
+            // Values are always text, so images are published as base64.
+            image_encoding: is_camera.then_some("b64"),
+            state_topic,
+            value_template,
+            unit_of_measurement: entity.unit.as_deref(),
+            icon: self
+                .icons
+                .get(&entity.name)
+                .or(entity.icon.as_ref())
+                .map(String::as_str),
+            entity_category: entity.entity_category.as_deref(),
+            command_topic,
+            min: entity.min,
+            max: entity.max,
+            step: entity.step,
+            schema: is_dimmable.then_some("json"),
+            brightness: is_dimmable.then_some(true),
+            brightness_scale: entity.brightness_scale,
+            supported_color_modes: if is_dimmable { &["brightness"] } else { &[] },
+            // Events list what they can be as `event_types`, and sirens as `available_tones`,
+            // rather than `options`.
+            options: if is_event || is_siren {
+                &[]
+            } else {
+                &entity.options
+            },
+            event_types: if is_event { &entity.options } else { &[] },
+            available_tones: if is_siren { &entity.options } else { &[] },
+            json_attributes_topic: entity.json_attributes.then(|| topics.attributes.clone()),
+            source_type: entity.source_type.as_deref(),
+            availability: [
+                Availability {
+                    topic: self.availability_topic.clone(),
+                },
+                Availability {
+                    topic: topics.availability.clone(),
+                },
+            ],
+            availability_mode: "all",
+        })
+        .context("Failed to serialize topic information.")?;
+        if self.discovery {
+            self.components
+                .insert(entity.name.clone(), entity.component.clone());
+
+            // An entity registered again, such as when a sensor's entities change, is only
+            // announced again if something about it changed.
+            let discovery_topic = self.discovery_topic(&entity.component, &entity.name);
+            let unchanged = self
+                .last_sent
+                .lock()
+                .expect("Last sent lock was poisoned.")
+                .get(&discovery_topic)
+                .map_or(false, |(sent, _)| sent == &message);
+            if !unchanged {
+                self.send(&discovery_topic, message, self.retain.discovery)
+                    .await
+                    .context("Failed to publish topic to MQTT server.")?;
+            }
+        }
+
+        self.set_entity_available(&entity.name, true).await
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        self.command_topics.retain(|_, name| name != entity_name);
+        self.aggregated_entities.remove(entity_name);
+        if let Some(aggregate) = &self.aggregate {
+            let mut aggregate = aggregate.lock().expect("Aggregate lock was poisoned.");
+            if aggregate.values.remove(entity_name).is_some() {
+                aggregate.changed = true;
+            }
+        }
+
+        // Nothing about the entity needs to be announced again.
+        let state_topic = self.state_topic(entity_name);
+        let prefix = format!("{}/", state_topic);
+        self.last_sent
+            .lock()
+            .expect("Last sent lock was poisoned.")
+            .retain(|topic, _| topic != &state_topic && !topic.starts_with(&prefix));
+
+        // An empty discovery message makes Home Assistant remove the entity.
+        if let Some(component) = self.components.remove(entity_name) {
+            self.send(
+                &self.discovery_topic(&component, entity_name),
+                String::new(),
+                self.retain.discovery,
+            )
+            .await
+            .with_context(|| format!("Failed to remove `{}` from Home Assistant.", entity_name))?;
+        }
+
+        self.remote_hosts.remove(entity_name);
+        self.entity_topics.remove(entity_name);
+
+        Ok(())
+    }
+
+    async fn set_entity_available(&self, entity_name: &str, available: bool) -> Result<()> {
+        self.send(
+            &self.topics_of(entity_name)?.availability,
+            if available { "online" } else { "offline" }.into(),
+            self.retain.availability,
+        )
+        .await
+        .with_context(|| format!("Failed to publish availability of `{}`.", entity_name))
+    }
+
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let value = self.payload_templates.render(entity_name, value);
+
+        if let Some(aggregate) = &self.aggregate {
+            if self.aggregated_entities.contains(entity_name) {
+                let mut aggregate = aggregate.lock().expect("Aggregate lock was poisoned.");
+                aggregate
+                    .values
+                    .insert(entity_name.to_string(), Value::String(value));
+                aggregate.changed = true;
+
+                return Ok(());
+            }
+        }
+
+        self.send_value(
+            &self.topics_of(entity_name)?.state,
+            value,
+            self.retain.state_of(entity_name),
+        )
+        .await
+    }
+
+    async fn publish_attributes(&self, entity_name: &str, attributes: &str) -> Result<()> {
+        self.send(
+            &self.topics_of(entity_name)?.attributes,
+            attributes.to_string(),
+            self.retain.state_of(entity_name),
+        )
+        .await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        if let Some(aggregate) = &self.aggregate {
+            // Every value we know of goes in the document, so entities that weren't updated
+            // this time keep their last value instead of going blank.
+            let document = {
+                let mut aggregate = aggregate.lock().expect("Aggregate lock was poisoned.");
+                if !aggregate.changed {
+                    return Ok(());
+                }
+
+                aggregate.changed = false;
+                serde_json::to_string(&aggregate.values).context("Failed to serialize values.")?
+            };
+
+            self.send_value(&self.aggregate_topic, document, self.retain.state)
+                .await
+                .context("Failed to publish aggregate state.")?;
+        }
+
+        Ok(())
+    }
+
+    async fn next_command(&mut self) -> Result<Command> {
+        loop {
+            let message = match &mut self.client {
+                Some(client) => client
+                    .read_subscriptions()
+                    .await
+                    .context("Failed to read command from MQTT server.")?,
+                // Nothing to receive commands from during a dry run.
+                None => pending().await,
+            };
+
+            let payload = String::from_utf8_lossy(&message.payload).into_owned();
+            log::debug!("RECEIVED `{}` FROM `{}`", payload, message.topic);
+
+            if message.topic == BIRTH_TOPIC {
+                if payload == "online" {
+                    log::info!("Home Assistant has started. Announcing everything again.");
+                    self.announce_again()
+                        .await
+                        .context("Failed to announce to Home Assistant again.")?;
+                }
+            } else if let Some(entity_name) = self.command_topics.get(&message.topic) {
+                return Ok(Command {
+                    entity: entity_name.clone(),
+                    topic: message.topic.clone(),
+                    payload,
+                    source: None,
+                });
+            }
+        }
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            client.disconnect().await?;
+            log::debug!("Disconnected from MQTT server.");
+        }
+
+        Ok(())
+    }
+}
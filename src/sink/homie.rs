@@ -0,0 +1,383 @@
+use super::{Command, Entity, Sink};
+use crate::mqtt::MqttClient;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::pending;
+use rumqttc::{LastWill, QoS};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Every entity is a property of this one node.
+const NODE: &str = "system";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HomieConfig {
+    /// The topic every Homie device lives under.
+    #[serde(default = "HomieConfig::default_base_topic")]
+    pub base_topic: String,
+
+    /// The ID of the device. Defaults to the hostname.
+    pub device_id: Option<String>,
+}
+
+impl HomieConfig {
+    fn default_base_topic() -> String {
+        String::from("homie")
+    }
+}
+
+/// Homie IDs can only have lowercase letters, digits and hyphens.
+fn homie_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    id.trim_matches('-').to_string()
+}
+
+/// How a property is described to Homie controllers.
+struct Property {
+    entity_name: String,
+    datatype: &'static str,
+    format: Option<String>,
+    unit: Option<String>,
+    settable: bool,
+    retained: bool,
+}
+
+impl Property {
+    fn new(entity: &Entity) -> Self {
+        let (datatype, format) = match entity.component.as_str() {
+            "binary_sensor" | "switch" => ("boolean", None),
+            "number" => (
+                "float",
+                entity
+                    .min
+                    .zip(entity.max)
+                    .map(|(min, max)| format!("{}:{}", min, max)),
+            ),
+            "select" => ("enum", Some(entity.options.join(","))),
+            // Sensors that aren't measurements, like text and timestamps, have their state class cleared.
+            "sensor" if entity.state_class.as_deref() != Some("") => ("float", None),
+            _ => ("string", None),
+        };
+
+        Self {
+            entity_name: entity.name.clone(),
+            datatype,
+            format,
+            unit: entity.unit.clone(),
+            settable: entity.accepts_commands.is_some(),
+            // Button presses and events are things that happen, not states to keep.
+            retained: !matches!(entity.component.as_str(), "button" | "event"),
+        }
+    }
+}
+
+/// Publishes values following the Homie 4 convention, for controllers such as openHAB.
+///
+/// The host is a single device, with a single node whose properties are our entities.
+pub struct Homie {
+    /// The connection to the MQTT server. When this is `None` we're doing a dry run
+    /// and everything is printed to stdout instead.
+    client: Option<MqttClient>,
+    hostname: String,
+
+    /// The topic of the device, such as `homie/my-laptop`.
+    device_topic: String,
+
+    /// Properties by their IDs.
+    properties: BTreeMap<String, Property>,
+
+    /// Maps the names of entities to the IDs of their properties.
+    property_ids: HashMap<String, String>,
+
+    /// Maps the topics commands are received on to the entities they are for.
+    command_topics: HashMap<String, String>,
+
+    /// Set once the device itself has been announced.
+    announced: bool,
+}
+
+impl Homie {
+    pub fn new(hostname: String, config: &HomieConfig) -> Self {
+        let device_id = homie_id(config.device_id.as_deref().unwrap_or(&hostname));
+
+        Self {
+            client: None,
+            device_topic: format!("{}/{}", config.base_topic, device_id),
+            hostname,
+            properties: BTreeMap::new(),
+            property_ids: HashMap::new(),
+            command_topics: HashMap::new(),
+            announced: false,
+        }
+    }
+
+    /// Marks the device `lost` if the connection drops without us disconnecting, which the MQTT
+    /// server does for us when it's registered as we connect.
+    pub fn last_will(&self) -> LastWill {
+        LastWill::new(
+            format!("{}/$state", self.device_topic),
+            "lost",
+            QoS::AtLeastOnce,
+            true,
+        )
+    }
+
+    /// Sets the connection to the MQTT server. When this is `None` we're doing a dry run.
+    pub fn set_client(&mut self, client: Option<MqttClient>) {
+        self.client = client;
+    }
+
+    fn property_topic(&self, property_id: &str) -> String {
+        format!("{}/{}/{}", self.device_topic, NODE, property_id)
+    }
+
+    async fn send(&self, topic: String, payload: String, retain: bool) -> Result<()> {
+        log::debug!("PUBLISH `{}` TO `{}`", payload, topic);
+
+        if let Some(client) = &self.client {
+            client.publish(topic, payload, retain).await?;
+        } else {
+            println!(
+                "{}{}: {}",
+                topic,
+                if retain { " (retained)" } else { "" },
+                payload
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn send_device_attribute(&self, attribute: &str, value: &str) -> Result<()> {
+        self.send(
+            format!("{}/{}", self.device_topic, attribute),
+            value.to_string(),
+            true,
+        )
+        .await
+        .with_context(|| format!("Failed to publish Homie attribute `{}`.", attribute))
+    }
+
+    /// Lists the properties of our node, which has to be done again whenever they change.
+    async fn send_properties(&self) -> Result<()> {
+        let properties: Vec<_> = self.properties.keys().map(String::as_str).collect();
+
+        self.send(
+            format!("{}/{}/$properties", self.device_topic, NODE),
+            properties.join(","),
+            true,
+        )
+        .await
+        .context("Failed to publish Homie properties.")
+    }
+
+    async fn subscribe(&mut self, topic: &str) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            let accepted = client
+                .subscribe(topic)
+                .await
+                .with_context(|| format!("Failed to subscribe to `{}`.", topic))?;
+
+            if !accepted {
+                bail!("MQTT server refused subscription to `{}`.", topic);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for Homie {
+    async fn set_available(&self, available: bool) -> Result<()> {
+        self.send_device_attribute("$state", if available { "ready" } else { "disconnected" })
+            .await
+    }
+
+    async fn register(&mut self, entity: &Entity) -> Result<()> {
+        // Images have no place in Homie.
+        if entity.component == "camera" {
+            return Ok(());
+        }
+
+        if !self.announced {
+            self.send_device_attribute("$state", "init").await?;
+            self.send_device_attribute("$homie", "4.0").await?;
+            self.send_device_attribute("$name", &self.hostname).await?;
+            self.send_device_attribute("$nodes", NODE).await?;
+            self.send_device_attribute("$extensions", "").await?;
+            self.send(
+                format!("{}/{}/$name", self.device_topic, NODE),
+                String::from("System"),
+                true,
+            )
+            .await?;
+            self.send(
+                format!("{}/{}/$type", self.device_topic, NODE),
+                String::from("system-mqtt"),
+                true,
+            )
+            .await?;
+
+            self.announced = true;
+        }
+
+        let property_id = homie_id(&entity.name);
+        if let Some(other) = self.properties.get(&property_id) {
+            if other.entity_name != entity.name {
+                bail!(
+                    "`{}` and `{}` would both be the Homie property `{}`.",
+                    other.entity_name,
+                    entity.name,
+                    property_id
+                );
+            }
+        }
+
+        let property = Property::new(entity);
+        let topic = self.property_topic(&property_id);
+
+        self.send(format!("{}/$name", topic), entity.name.clone(), true)
+            .await?;
+        self.send(
+            format!("{}/$datatype", topic),
+            property.datatype.to_string(),
+            true,
+        )
+        .await?;
+        if let Some(format) = &property.format {
+            self.send(format!("{}/$format", topic), format.clone(), true)
+                .await?;
+        }
+        if let Some(unit) = &property.unit {
+            self.send(format!("{}/$unit", topic), unit.clone(), true)
+                .await?;
+        }
+        self.send(
+            format!("{}/$settable", topic),
+            property.settable.to_string(),
+            true,
+        )
+        .await?;
+        self.send(
+            format!("{}/$retained", topic),
+            property.retained.to_string(),
+            true,
+        )
+        .await?;
+
+        if property.settable {
+            let command_topic = format!("{}/set", topic);
+            self.subscribe(&command_topic)
+                .await
+                .context("Failed to subscribe to command topic.")?;
+            self.command_topics
+                .insert(command_topic, entity.name.clone());
+        }
+
+        self.property_ids
+            .insert(entity.name.clone(), property_id.clone());
+        self.properties.insert(property_id, property);
+
+        self.send_properties().await
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        let property_id = match self.property_ids.remove(entity_name) {
+            Some(property_id) => property_id,
+            None => return Ok(()),
+        };
+
+        self.properties.remove(&property_id);
+        self.command_topics.retain(|_, name| name != entity_name);
+        self.send_properties().await?;
+
+        // Clear out what the MQTT server kept for the property.
+        let topic = self.property_topic(&property_id);
+        for attribute in [
+            "",
+            "/$name",
+            "/$datatype",
+            "/$format",
+            "/$unit",
+            "/$settable",
+            "/$retained",
+        ] {
+            self.send(format!("{}{}", topic, attribute), String::new(), true)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let property_id = match self.property_ids.get(entity_name) {
+            Some(property_id) => property_id,
+            None => return Ok(()),
+        };
+        let property = &self.properties[property_id];
+
+        let value = match (property.datatype, value) {
+            ("boolean", "ON") => "true",
+            ("boolean", "OFF") => "false",
+            (_, value) => value,
+        };
+
+        self.send(
+            self.property_topic(property_id),
+            value.to_string(),
+            property.retained,
+        )
+        .await
+    }
+
+    async fn next_command(&mut self) -> Result<Command> {
+        loop {
+            let message = match &mut self.client {
+                Some(client) => client
+                    .read_subscriptions()
+                    .await
+                    .context("Failed to read command from MQTT server.")?,
+                // Nothing to receive commands from during a dry run.
+                None => pending().await,
+            };
+
+            let payload = String::from_utf8_lossy(&message.payload).into_owned();
+            log::debug!("RECEIVED `{}` FROM `{}`", payload, message.topic);
+
+            if let Some(entity_name) = self.command_topics.get(&message.topic) {
+                let payload = match payload.as_str() {
+                    "true" => String::from("ON"),
+                    "false" => String::from("OFF"),
+                    _ => payload,
+                };
+
+                return Ok(Command {
+                    entity: entity_name.clone(),
+                    topic: message.topic.clone(),
+                    payload,
+                    source: None,
+                });
+            }
+        }
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            client.disconnect().await?;
+            log::debug!("Disconnected from MQTT server.");
+        }
+
+        Ok(())
+    }
+}
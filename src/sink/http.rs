@@ -0,0 +1,81 @@
+//! Just enough of an HTTP server to answer GET requests from local tools.
+
+use anyhow::{Context, Result};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// The content type and body to respond with.
+pub type Response = (&'static str, String);
+
+/// Stops serving when dropped.
+pub struct Server {
+    task: JoinHandle<()>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Free up the port so we can bind it again if the application restarts.
+        self.task.abort();
+    }
+}
+
+/// Serves requests by passing the requested path to the handler. If the handler
+/// returns `None`, the client gets a 404.
+pub async fn serve<H>(address: SocketAddr, handler: H) -> Result<Server>
+where
+    H: Fn(&str) -> Option<Response> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to bind HTTP server to `{}`.", address))?;
+    let handler = Arc::new(handler);
+
+    let task = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _address)) => {
+                    let handler = handler.clone();
+
+                    tokio::spawn(async move {
+                        if let Err(error) = respond(stream, handler.as_ref()).await {
+                            log::debug!("Failed to respond to HTTP request: {:?}", error);
+                        }
+                    });
+                }
+                Err(error) => log::warn!("Failed to accept HTTP connection: {:?}", error),
+            }
+        }
+    });
+
+    Ok(Server { task })
+}
+
+async fn respond<H>(mut stream: TcpStream, handler: &H) -> Result<()>
+where
+    H: Fn(&str) -> Option<Response>,
+{
+    // We only care about the request line, which will be in the first read.
+    let mut request = [0u8; 1024];
+    let length = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..length]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let response = match handler(path) {
+        Some((content_type, body)) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        ),
+        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use super::{Entity, Sink};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+#[derive(Serialize, Deserialize)]
+pub struct InfluxConfig {
+    /// The base URL of the InfluxDB server, such as `http://localhost:8086`.
+    pub url: Url,
+
+    /// The organization to write to.
+    pub org: String,
+
+    /// The bucket to write to.
+    pub bucket: String,
+
+    /// The API token to authenticate with, if the server requires one.
+    pub token: Option<String>,
+
+    /// The measurement all values are written under.
+    #[serde(default = "InfluxConfig::default_measurement")]
+    pub measurement: String,
+}
+
+impl InfluxConfig {
+    fn default_measurement() -> String {
+        String::from("system_mqtt")
+    }
+}
+
+/// Writes every value published in an update to InfluxDB as a single line of the line protocol.
+pub struct Writer {
+    client: reqwest::Client,
+    write_url: Url,
+    token: Option<String>,
+    measurement: String,
+    hostname: String,
+    fields: Mutex<Vec<(String, String)>>,
+}
+
+impl Writer {
+    pub fn new(config: &InfluxConfig, hostname: String) -> Result<Self> {
+        let mut write_url = config
+            .url
+            .join("api/v2/write")
+            .context("Invalid InfluxDB URL.")?;
+        write_url
+            .query_pairs_mut()
+            .append_pair("org", &config.org)
+            .append_pair("bucket", &config.bucket)
+            .append_pair("precision", "s");
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            write_url,
+            token: config.token.clone(),
+            measurement: escape_key(&config.measurement),
+            hostname: escape_key(&hostname),
+            fields: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for Writer {
+    async fn register(&mut self, _entity: &Entity) -> Result<()> {
+        Ok(())
+    }
+
+    /// Queues a value to be written on the next flush.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let value = match value {
+            "ON" => String::from("true"),
+            "OFF" => String::from("false"),
+            value => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => number.to_string(),
+                _ => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            },
+        };
+
+        self.fields
+            .lock()
+            .expect("Field lock was poisoned.")
+            .push((escape_key(entity_name), value));
+
+        Ok(())
+    }
+
+    /// Writes all queued values.
+    async fn flush(&self) -> Result<()> {
+        let fields = std::mem::take(&mut *self.fields.lock().expect("Field lock was poisoned."));
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let mut line = format!("{},host={} ", self.measurement, self.hostname);
+        for (index, (key, value)) in fields.iter().enumerate() {
+            if index > 0 {
+                line.push(',');
+            }
+            write!(line, "{}={}", key, value)?;
+        }
+        write!(line, " {}", timestamp)?;
+
+        let mut request = self.client.post(self.write_url.clone()).body(line);
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+
+        request
+            .send()
+            .await
+            .context("Failed to send values to InfluxDB.")?
+            .error_for_status()
+            .context("InfluxDB rejected the values.")?;
+
+        Ok(())
+    }
+}
+
+/// Escapes measurement names, tag values and field keys.
+fn escape_key(key: &str) -> String {
+    key.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
@@ -0,0 +1,524 @@
+//! Places that collected values are sent to.
+
+use crate::sensor::Reading;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::{pending, select_all};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use tokio::time;
+
+pub mod audit;
+pub mod backlog;
+#[cfg(feature = "command-auth")]
+pub mod command_auth;
+pub mod command_policy;
+pub mod filter;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod home_assistant;
+pub mod homie;
+mod http;
+#[cfg(feature = "influxdb")]
+pub mod influx;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod prometheus;
+pub mod rate_limit;
+pub mod sparkplug;
+pub mod status_api;
+pub mod template;
+
+/// The kinds of action commands are, which the command policy allows one kind at a time.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionClass {
+    /// Shutting down, rebooting, suspending and hibernating.
+    Power,
+    /// Starting, stopping and restarting systemd units.
+    Services,
+    /// Installing updates.
+    Updates,
+    /// Running the scripts in `scripts`.
+    Scripts,
+    /// Fan speeds, the CPU governor, the power profile and the battery charge limit.
+    Hardware,
+    /// Turning radios on and off, and waking other hosts.
+    Network,
+    /// Locking the screen, the backlights, turning the displays off, keeping the system awake,
+    /// and notifications.
+    Desktop,
+    /// Media players, the volume, the microphone, the siren and speech.
+    Media,
+    /// Taking screenshots.
+    Screenshots,
+}
+
+/// Describes something values get published for, such as a sensor.
+pub struct Entity {
+    /// The kind of Home Assistant entity this is, such as `sensor`.
+    pub component: String,
+
+    /// The name values are published under.
+    pub name: String,
+
+    pub device_class: Option<String>,
+    pub state_class: Option<String>,
+    pub unit: Option<String>,
+    pub icon: Option<String>,
+
+    /// Set to `diagnostic` or `config` for entities that aren't a primary feature of the host.
+    pub entity_category: Option<String>,
+
+    /// Set for entities that can be controlled, such as buttons and switches, to the kind of
+    /// action their commands are.
+    pub accepts_commands: Option<ActionClass>,
+
+    /// The range of values a `number` entity can be set to.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+
+    /// How bright a `light` entity can be set to, for lights that can be dimmed. They take and
+    /// report JSON, such as `{"state": "ON", "brightness": 2}`.
+    pub brightness_scale: Option<u64>,
+
+    /// What a `select` entity can be set to, the types an `event` entity can fire, or the tones a
+    /// `siren` can play.
+    pub options: Vec<String>,
+
+    /// Set for entities whose readings come with attributes.
+    pub json_attributes: bool,
+
+    /// How a `device_tracker` entity finds what it tracks, such as `bluetooth_le`.
+    pub source_type: Option<String>,
+
+    /// The host the entity belongs to, when it isn't us, such as one we collect from over SSH.
+    /// The entity's name has to start with the host's, like `nas_cpu`.
+    pub remote_host: Option<String>,
+}
+
+impl Entity {
+    pub fn new(component: &str, name: &str) -> Self {
+        Self {
+            component: component.to_string(),
+            name: name.to_string(),
+            device_class: None,
+            state_class: None,
+            unit: None,
+            icon: None,
+            entity_category: None,
+            accepts_commands: None,
+            min: None,
+            max: None,
+            step: None,
+            brightness_scale: None,
+            options: Vec::new(),
+            json_attributes: false,
+            source_type: None,
+            remote_host: None,
+        }
+    }
+
+    pub fn device_class<'a>(mut self, device_class: impl Into<Option<&'a str>>) -> Self {
+        self.device_class = device_class.into().map(str::to_string);
+        self
+    }
+
+    pub fn state_class<'a>(mut self, state_class: impl Into<Option<&'a str>>) -> Self {
+        self.state_class = state_class.into().map(str::to_string);
+        self
+    }
+
+    pub fn unit<'a>(mut self, unit: impl Into<Option<&'a str>>) -> Self {
+        self.unit = unit.into().map(str::to_string);
+        self
+    }
+
+    pub fn icon<'a>(mut self, icon: impl Into<Option<&'a str>>) -> Self {
+        self.icon = icon.into().map(str::to_string);
+        self
+    }
+
+    pub fn entity_category<'a>(mut self, entity_category: impl Into<Option<&'a str>>) -> Self {
+        self.entity_category = entity_category.into().map(str::to_string);
+        self
+    }
+
+    pub fn accepts_commands(mut self, action_class: ActionClass) -> Self {
+        self.accepts_commands = Some(action_class);
+        self
+    }
+
+    pub fn range(mut self, min: f64, max: f64, step: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self.step = Some(step);
+        self
+    }
+
+    pub fn brightness_scale(mut self, scale: u64) -> Self {
+        self.brightness_scale = Some(scale);
+        self
+    }
+
+    pub fn options(mut self, options: Vec<String>) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn json_attributes(mut self) -> Self {
+        self.json_attributes = true;
+        self
+    }
+
+    pub fn source_type<'a>(mut self, source_type: impl Into<Option<&'a str>>) -> Self {
+        self.source_type = source_type.into().map(str::to_string);
+        self
+    }
+
+    pub fn remote_host(mut self, host: &str) -> Self {
+        self.remote_host = Some(host.to_string());
+        self
+    }
+}
+
+/// A request to do something with an entity, such as pressing a button.
+pub struct Command {
+    /// The name of the entity the command is for.
+    pub entity: String,
+
+    /// The topic the command came in on.
+    pub topic: String,
+    pub payload: String,
+
+    /// The client that signed the command with a secret of its own, if one did.
+    pub source: Option<String>,
+}
+
+#[async_trait(?Send)]
+pub trait Sink {
+    /// Makes the sink aware of an entity before any values are published for it.
+    async fn register(&mut self, entity: &Entity) -> Result<()>;
+
+    /// Forgets an entity that has gone away, such as a drive that was unplugged.
+    async fn unregister(&mut self, _entity_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Publishes the latest value of an entity.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()>;
+
+    /// Publishes the attributes that came with the latest value of an entity.
+    /// Sinks with nowhere to put them can ignore them.
+    async fn publish_attributes(&self, _entity_name: &str, _attributes: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reports whether we are up and running.
+    async fn set_available(&self, _available: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reports whether the sensor behind an entity is working.
+    async fn set_entity_available(&self, _entity_name: &str, _available: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Waits for the next command for an entity that accepts them.
+    /// Sinks that can't receive commands never return.
+    async fn next_command(&mut self) -> Result<Command> {
+        pending().await
+    }
+
+    /// Called at the end of every update, for sinks that batch up values.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Every sink we are sending values to.
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+    registered_entities: HashSet<String>,
+    /// The kind of action each entity that accepts commands carries out.
+    action_classes: HashMap<String, ActionClass>,
+    change_filter: Option<Mutex<filter::ChangeFilter>>,
+    rate_limiter: Option<Mutex<rate_limit::RateLimiter>>,
+    #[cfg(feature = "command-auth")]
+    command_authenticator: Option<command_auth::CommandAuthenticator>,
+    command_policy: Option<command_policy::CommandPolicy>,
+    audit_log: Option<audit::AuditLog>,
+}
+
+impl Sinks {
+    pub fn add(&mut self, sink: impl Sink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Only publish values when they change.
+    pub fn set_change_filter(&mut self, change_filter: filter::ChangeFilter) {
+        self.change_filter = Some(Mutex::new(change_filter));
+    }
+
+    /// Forgets what was last published, so every value gets published again.
+    pub fn reset_change_filter(&self) {
+        if let Some(change_filter) = &self.change_filter {
+            change_filter
+                .lock()
+                .expect("Change filter lock was poisoned.")
+                .reset();
+        }
+    }
+
+    /// Limit how often values are published.
+    pub fn set_rate_limiter(&mut self, rate_limiter: rate_limit::RateLimiter) {
+        self.rate_limiter = Some(Mutex::new(rate_limiter));
+    }
+
+    /// Only accept commands that are signed.
+    #[cfg(feature = "command-auth")]
+    pub fn set_command_authenticator(
+        &mut self,
+        command_authenticator: command_auth::CommandAuthenticator,
+    ) {
+        self.command_authenticator = Some(command_authenticator);
+    }
+
+    /// Only carry out the commands the policy allows.
+    pub fn set_command_policy(&mut self, command_policy: command_policy::CommandPolicy) {
+        self.command_policy = Some(command_policy);
+    }
+
+    /// Record every command, and what came of it.
+    pub fn set_audit_log(&mut self, audit_log: audit::AuditLog) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Records what came of a command in the audit log, if there is one.
+    pub fn audit(&self, command: &Command, decision: audit::Decision, result: &str) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(command, decision, result);
+        }
+    }
+
+    pub async fn register(&mut self, entity: Entity) -> Result<()> {
+        log::info!("Registering topic `{}`.", entity.name);
+
+        for sink in self.sinks.iter_mut() {
+            sink.register(&entity).await?;
+        }
+
+        if let Some(action_class) = entity.accepts_commands {
+            self.action_classes
+                .insert(entity.name.clone(), action_class);
+        }
+        self.registered_entities.insert(entity.name);
+
+        Ok(())
+    }
+
+    pub async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        log::info!("Unregistering topic `{}`.", entity_name);
+
+        for sink in self.sinks.iter_mut() {
+            sink.unregister(entity_name).await?;
+        }
+
+        self.registered_entities.remove(entity_name);
+        self.action_classes.remove(entity_name);
+
+        if let Some(change_filter) = &self.change_filter {
+            change_filter
+                .lock()
+                .expect("Change filter lock was poisoned.")
+                .forget(entity_name);
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .lock()
+                .expect("Rate limiter lock was poisoned.")
+                .forget(entity_name);
+        }
+
+        Ok(())
+    }
+
+    pub async fn publish(&self, entity_name: &str, value: String) {
+        if self.registered_entities.contains(entity_name) {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                if !rate_limiter
+                    .lock()
+                    .expect("Rate limiter lock was poisoned.")
+                    .may_publish(entity_name)
+                {
+                    log::debug!("Value of `{}` was published too recently.", entity_name);
+                    return;
+                }
+            }
+
+            if let Some(change_filter) = &self.change_filter {
+                if !change_filter
+                    .lock()
+                    .expect("Change filter lock was poisoned.")
+                    .should_publish(entity_name, &value)
+                {
+                    log::debug!("Value of `{}` has not changed.", entity_name);
+                    return;
+                }
+            }
+
+            if let Some(rate_limiter) = &self.rate_limiter {
+                let slot = {
+                    let mut rate_limiter = rate_limiter
+                        .lock()
+                        .expect("Rate limiter lock was poisoned.");
+                    rate_limiter.published(entity_name);
+                    rate_limiter.reserve()
+                };
+                time::sleep_until(slot.into()).await;
+            }
+
+            for sink in self.sinks.iter() {
+                if let Err(error) = sink.publish(entity_name, &value).await {
+                    log::error!("Failed to publish topic `{}`: {:?}", entity_name, error);
+                }
+            }
+        } else {
+            log::error!(
+                "Attempt to publish topic `{}`, which was never registered.",
+                entity_name
+            );
+        }
+    }
+
+    /// Publishes the value of a reading, along with its attributes if it has any.
+    pub async fn publish_reading(&self, reading: Reading) {
+        if let Some(attributes) = &reading.attributes {
+            if self.registered_entities.contains(&reading.entity) {
+                for sink in self.sinks.iter() {
+                    if let Err(error) = sink.publish_attributes(&reading.entity, attributes).await {
+                        log::error!(
+                            "Failed to publish attributes of `{}`: {:?}",
+                            reading.entity,
+                            error
+                        );
+                    }
+                }
+            }
+        }
+
+        self.publish(&reading.entity, reading.value).await;
+    }
+
+    pub async fn set_available(&self, available: bool) -> Result<()> {
+        for sink in self.sinks.iter() {
+            sink.set_available(available).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks a single entity as available or not, without affecting the others.
+    pub async fn set_entity_available(&self, entity_name: &str, available: bool) {
+        if let Some(change_filter) = &self.change_filter {
+            // Whatever comes in once the entity is back has to be published, even if it matches the old value.
+            change_filter
+                .lock()
+                .expect("Change filter lock was poisoned.")
+                .forget(entity_name);
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .lock()
+                .expect("Rate limiter lock was poisoned.")
+                .forget(entity_name);
+        }
+
+        for sink in self.sinks.iter() {
+            if let Err(error) = sink.set_entity_available(entity_name, available).await {
+                log::error!(
+                    "Failed to set availability of `{}`: {:?}",
+                    entity_name,
+                    error
+                );
+            }
+        }
+    }
+
+    /// Waits for the next command from any sink.
+    pub async fn next_command(&mut self) -> Result<Command> {
+        if self.sinks.is_empty() {
+            return pending().await;
+        }
+
+        loop {
+            let (command, _, _) =
+                select_all(self.sinks.iter_mut().map(|sink| sink.next_command())).await;
+            let command = command?;
+
+            if !self.registered_entities.contains(&command.entity) {
+                log::error!(
+                    "Received a command for `{}`, which was never registered.",
+                    command.entity
+                );
+                self.audit(&command, audit::Decision::Denied, "unknown entity");
+                continue;
+            }
+
+            #[cfg(feature = "command-auth")]
+            let command = match &mut self.command_authenticator {
+                Some(command_authenticator) => match command_authenticator.verify(&command) {
+                    Ok(command) => command,
+                    Err(error) => {
+                        log::warn!("Refused a command for `{}`: {:#}", command.entity, error);
+                        self.audit(&command, audit::Decision::Denied, &format!("{:#}", error));
+                        continue;
+                    }
+                },
+                None => command,
+            };
+
+            if let Some(command_policy) = &mut self.command_policy {
+                let action_class = self.action_classes.get(&command.entity).copied();
+                if let Err(error) = command_policy.check(&command, action_class) {
+                    log::warn!("Refused a command for `{}`: {:#}", command.entity, error);
+                    self.audit(&command, audit::Decision::Denied, &format!("{:#}", error));
+                    continue;
+                }
+            }
+
+            return Ok(command);
+        }
+    }
+
+    pub async fn flush(&self) {
+        for sink in self.sinks.iter() {
+            if let Err(error) = sink.flush().await {
+                log::error!("Failed to flush values: {:?}", error);
+            }
+        }
+    }
+
+    pub async fn disconnect(mut self) -> Result<()> {
+        self.set_available(false).await?;
+
+        for sink in self.sinks.iter_mut() {
+            sink.disconnect()
+                .await
+                .context("Failed to disconnect cleanly.")?;
+        }
+
+        Ok(())
+    }
+}
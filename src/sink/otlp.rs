@@ -0,0 +1,204 @@
+use super::{Entity, Sink};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+#[derive(Serialize, Deserialize)]
+pub struct OtlpConfig {
+    /// The base URL of the collector's OTLP/HTTP receiver, such as `http://localhost:4318`.
+    pub endpoint: Url,
+
+    /// Extra headers to send with every export, such as ones for authentication.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// How often to export. Defaults to a minute.
+    #[serde(default = "OtlpConfig::default_interval")]
+    pub interval: Duration,
+}
+
+impl OtlpConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+/// A metric waiting to be exported.
+struct Metric {
+    value: f64,
+    time: SystemTime,
+}
+
+/// Pushes the latest value of every numeric sensor to an OpenTelemetry collector, as gauges,
+/// using OTLP over HTTP with JSON encoding.
+pub struct Exporter {
+    client: reqwest::Client,
+    metrics_url: Url,
+    headers: BTreeMap<String, String>,
+    interval: Duration,
+    hostname: String,
+
+    /// The units of registered entities, by name.
+    units: HashMap<String, String>,
+    metrics: Mutex<BTreeMap<String, Metric>>,
+    last_export: Mutex<Option<Instant>>,
+}
+
+impl Exporter {
+    pub fn new(config: &OtlpConfig, hostname: String) -> Result<Self> {
+        let metrics_url = config
+            .endpoint
+            .join("v1/metrics")
+            .context("Invalid OTLP endpoint.")?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            metrics_url,
+            headers: config.headers.clone(),
+            interval: config.interval,
+            hostname,
+            units: HashMap::new(),
+            metrics: Mutex::new(BTreeMap::new()),
+            last_export: Mutex::new(None),
+        })
+    }
+
+    fn render(&self, metrics: &BTreeMap<String, Metric>) -> Result<Value> {
+        let mut rendered = Vec::new();
+        for (name, metric) in metrics.iter() {
+            let time = metric.time.duration_since(UNIX_EPOCH)?.as_nanos();
+
+            rendered.push(json!({
+                "name": metric_name(name),
+                "unit": self.units.get(name).map(String::as_str).unwrap_or_default(),
+                "gauge": {
+                    "dataPoints": [{
+                        "asDouble": metric.value,
+                        // 64 bit integers are sent as strings in the JSON encoding.
+                        "timeUnixNano": time.to_string(),
+                    }],
+                },
+            }));
+        }
+
+        Ok(json!({
+            "resourceMetrics": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": "system-mqtt" } },
+                        { "key": "host.name", "value": { "stringValue": self.hostname } },
+                    ],
+                },
+                "scopeMetrics": [{
+                    "scope": {
+                        "name": "system-mqtt",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                    "metrics": rendered,
+                }],
+            }],
+        }))
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for Exporter {
+    async fn register(&mut self, entity: &Entity) -> Result<()> {
+        if let Some(unit) = &entity.unit {
+            self.units.insert(entity.name.clone(), unit.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        self.units.remove(entity_name);
+        self.metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .remove(entity_name);
+
+        Ok(())
+    }
+
+    /// Records the latest value of a sensor. Values that aren't numbers can't be represented, so are ignored.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let value = match value {
+            "ON" => 1.0,
+            "OFF" => 0.0,
+            value => match value.parse() {
+                Ok(value) => value,
+                Err(_) => return Ok(()),
+            },
+        };
+
+        self.metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .insert(
+                entity_name.to_string(),
+                Metric {
+                    value,
+                    time: SystemTime::now(),
+                },
+            );
+
+        Ok(())
+    }
+
+    /// Exports the latest values, once the interval has passed since the last export.
+    async fn flush(&self) -> Result<()> {
+        let now = Instant::now();
+        {
+            let mut last_export = self
+                .last_export
+                .lock()
+                .expect("Export time lock was poisoned.");
+            match *last_export {
+                Some(last_export) if now.duration_since(last_export) < self.interval => {
+                    return Ok(())
+                }
+                _ => *last_export = Some(now),
+            }
+        }
+
+        let body = {
+            let metrics = self.metrics.lock().expect("Metrics lock was poisoned.");
+            if metrics.is_empty() {
+                return Ok(());
+            }
+
+            self.render(&metrics)?
+        };
+
+        let mut request = self
+            .client
+            .post(self.metrics_url.clone())
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        for (name, value) in self.headers.iter() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        request
+            .send()
+            .await
+            .context("Failed to send metrics to the OpenTelemetry collector.")?
+            .error_for_status()
+            .context("The OpenTelemetry collector rejected the metrics.")?;
+
+        Ok(())
+    }
+}
+
+/// OpenTelemetry names metrics with dots between namespaces, like `system_mqtt.cpu_usage`.
+fn metric_name(entity_name: &str) -> String {
+    format!("system_mqtt.{}", entity_name)
+}
@@ -0,0 +1,108 @@
+use super::{http, Entity, Sink};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+type Metrics = Arc<Mutex<BTreeMap<String, f64>>>;
+
+/// Serves the latest value of every numeric sensor on `/metrics` in the Prometheus text format.
+pub struct Exporter {
+    metrics: Metrics,
+    _server: http::Server,
+}
+
+impl Exporter {
+    pub async fn start(address: SocketAddr, hostname: String) -> Result<Self> {
+        let metrics = Metrics::default();
+
+        let server = {
+            let metrics = metrics.clone();
+            http::serve(address, move |path| {
+                if path == "/metrics" {
+                    Some((
+                        "text/plain; version=0.0.4",
+                        render(
+                            &hostname,
+                            &metrics.lock().expect("Metrics lock was poisoned."),
+                        ),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .await?
+        };
+        log::info!("Serving Prometheus metrics on `{}`.", address);
+
+        Ok(Self {
+            metrics,
+            _server: server,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for Exporter {
+    async fn register(&mut self, _entity: &Entity) -> Result<()> {
+        Ok(())
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        self.metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .remove(&metric_name(entity_name));
+
+        Ok(())
+    }
+
+    /// Records the latest value of a sensor. Values that aren't numbers can't be represented, so are ignored.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let value = match value {
+            "ON" => 1.0,
+            "OFF" => 0.0,
+            value => match value.parse() {
+                Ok(value) => value,
+                Err(_) => return Ok(()),
+            },
+        };
+
+        self.metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .insert(metric_name(entity_name), value);
+
+        Ok(())
+    }
+}
+
+fn metric_name(topic_name: &str) -> String {
+    let sanitized: String = topic_name
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("system_mqtt_{}", sanitized)
+}
+
+fn render(hostname: &str, metrics: &BTreeMap<String, f64>) -> String {
+    let mut body = String::new();
+    for (name, value) in metrics.iter() {
+        // Writing to a string can't fail.
+        let _ = writeln!(body, "# TYPE {} gauge", name);
+        let _ = writeln!(body, "{}{{host=\"{}\"}} {}", name, hostname, value);
+    }
+
+    body
+}
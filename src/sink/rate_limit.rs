@@ -0,0 +1,98 @@
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// The most values to publish per second, across every entity.
+    /// Values beyond that wait their turn rather than being dropped.
+    pub max_messages_per_second: Option<f64>,
+
+    /// A single entity is published at most this often. Values that come in sooner are dropped.
+    #[serde(default)]
+    pub min_interval: Duration,
+
+    /// Overrides the minimum interval for specific entities.
+    #[serde(default)]
+    pub min_intervals: HashMap<String, Duration>,
+}
+
+/// Keeps us from publishing faster than the MQTT server is willing to take.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+
+    /// The time between two values, when there is a global limit.
+    spacing: Option<Duration>,
+
+    /// The earliest the next value may be published.
+    next_slot: Instant,
+
+    /// When each entity was last published.
+    last_published: HashMap<String, Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Result<Self> {
+        let spacing = match config.max_messages_per_second {
+            Some(rate) => {
+                ensure!(
+                    rate > 0.0,
+                    "The maximum number of messages per second must be above zero."
+                );
+                Some(Duration::from_secs_f64(1.0 / rate))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            config,
+            spacing,
+            next_slot: Instant::now(),
+            last_published: HashMap::new(),
+        })
+    }
+
+    /// Decides if a value for an entity may be published, given how recently the last one was.
+    pub fn may_publish(&self, entity_name: &str) -> bool {
+        let min_interval = self
+            .config
+            .min_intervals
+            .get(entity_name)
+            .copied()
+            .unwrap_or(self.config.min_interval);
+
+        match self.last_published.get(entity_name) {
+            Some(published_at) => published_at.elapsed() >= min_interval,
+            None => true,
+        }
+    }
+
+    /// Remembers that a value for an entity is being published. Only values that are actually
+    /// sent count, so one that's dropped for some other reason doesn't hold up the next.
+    pub fn published(&mut self, entity_name: &str) {
+        self.last_published
+            .insert(entity_name.to_string(), Instant::now());
+    }
+
+    /// Reserves a slot for one value under the global limit, and returns when it begins.
+    pub fn reserve(&mut self) -> Instant {
+        let now = Instant::now();
+
+        match self.spacing {
+            Some(spacing) => {
+                let slot = self.next_slot.max(now);
+                self.next_slot = slot + spacing;
+                slot
+            }
+            None => now,
+        }
+    }
+
+    /// Forgets when an entity was last published, so its next value gets published.
+    pub fn forget(&mut self, entity_name: &str) {
+        self.last_published.remove(entity_name);
+    }
+}
@@ -0,0 +1,607 @@
+use super::{Command, Entity, Sink};
+use crate::mqtt::MqttClient;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::pending;
+use rumqttc::{LastWill, QoS};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The metric a host application sets to ask us to send our birth certificate again.
+const REBIRTH_METRIC: &str = "Node Control/Rebirth";
+
+/// The metric that ties a birth certificate to the death certificate that ends it.
+const BIRTH_DEATH_SEQUENCE_METRIC: &str = "bdSeq";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SparkplugConfig {
+    /// The group our edge node belongs to.
+    #[serde(default = "SparkplugConfig::default_group_id")]
+    pub group_id: String,
+
+    /// The ID of our edge node. Defaults to the hostname.
+    pub edge_node_id: Option<String>,
+}
+
+impl SparkplugConfig {
+    fn default_group_id() -> String {
+        String::from("system-mqtt")
+    }
+}
+
+/// Sparkplug B data types, as they're numbered in the payload.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DataType {
+    UInt64 = 8,
+    Double = 10,
+    Boolean = 11,
+    String = 12,
+}
+
+impl DataType {
+    fn of(entity: &Entity) -> Self {
+        match entity.component.as_str() {
+            "binary_sensor" | "switch" => DataType::Boolean,
+            "number" => DataType::Double,
+            // Sensors that aren't measurements, like text and timestamps, have their state class cleared.
+            "sensor" if entity.state_class.as_deref() != Some("") => DataType::Double,
+            _ => DataType::String,
+        }
+    }
+}
+
+/// The value of a metric, as it's sent.
+#[derive(Clone)]
+enum MetricValue {
+    Null,
+    UInt64(u64),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+}
+
+impl MetricValue {
+    fn parse(data_type: DataType, value: &str) -> Self {
+        match data_type {
+            DataType::Boolean => match value {
+                "ON" => MetricValue::Boolean(true),
+                "OFF" => MetricValue::Boolean(false),
+                _ => MetricValue::Null,
+            },
+            DataType::Double => match value.parse() {
+                Ok(value) => MetricValue::Double(value),
+                Err(_) => MetricValue::Null,
+            },
+            DataType::UInt64 => match value.parse() {
+                Ok(value) => MetricValue::UInt64(value),
+                Err(_) => MetricValue::Null,
+            },
+            DataType::String => MetricValue::String(value.to_string()),
+        }
+    }
+}
+
+struct Metric {
+    data_type: DataType,
+    unit: Option<String>,
+    value: MetricValue,
+}
+
+/// Just enough of the protobuf wire format to write and read Sparkplug B payloads.
+mod protobuf {
+    use std::convert::TryInto;
+
+    const VARINT: u64 = 0;
+    const FIXED64: u64 = 1;
+    const LENGTH_DELIMITED: u64 = 2;
+    const FIXED32: u64 = 5;
+
+    pub fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buffer.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+
+    fn write_key(buffer: &mut Vec<u8>, field: u64, wire_type: u64) {
+        write_varint(buffer, field << 3 | wire_type);
+    }
+
+    pub fn write_uint(buffer: &mut Vec<u8>, field: u64, value: u64) {
+        write_key(buffer, field, VARINT);
+        write_varint(buffer, value);
+    }
+
+    pub fn write_bool(buffer: &mut Vec<u8>, field: u64, value: bool) {
+        write_uint(buffer, field, value as u64);
+    }
+
+    pub fn write_double(buffer: &mut Vec<u8>, field: u64, value: f64) {
+        write_key(buffer, field, FIXED64);
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+        write_key(buffer, field, LENGTH_DELIMITED);
+        write_varint(buffer, value.len() as u64);
+        buffer.extend_from_slice(value);
+    }
+
+    /// A field read from a message.
+    pub enum Field<'a> {
+        Varint(u64),
+        Fixed64([u8; 8]),
+        Fixed32([u8; 4]),
+        Bytes(&'a [u8]),
+    }
+
+    fn read_varint(buffer: &mut &[u8]) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = buffer.split_first()?;
+            *buffer = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    fn read_array<const N: usize>(buffer: &mut &[u8]) -> Option<[u8; N]> {
+        if buffer.len() < N {
+            return None;
+        }
+
+        let (bytes, rest) = buffer.split_at(N);
+        *buffer = rest;
+        bytes.try_into().ok()
+    }
+
+    /// Reads the fields of a message, with their numbers. Returns `None` if it's malformed.
+    pub fn read_fields(mut buffer: &[u8]) -> Option<Vec<(u64, Field)>> {
+        let mut fields = Vec::new();
+
+        while !buffer.is_empty() {
+            let key = read_varint(&mut buffer)?;
+            let field = match key & 0x7 {
+                VARINT => Field::Varint(read_varint(&mut buffer)?),
+                FIXED64 => Field::Fixed64(read_array(&mut buffer)?),
+                FIXED32 => Field::Fixed32(read_array(&mut buffer)?),
+                LENGTH_DELIMITED => {
+                    let length = read_varint(&mut buffer)? as usize;
+                    if buffer.len() < length {
+                        return None;
+                    }
+
+                    let (bytes, rest) = buffer.split_at(length);
+                    buffer = rest;
+                    Field::Bytes(bytes)
+                }
+                _ => return None,
+            };
+
+            fields.push((key >> 3, field));
+        }
+
+        Some(fields)
+    }
+}
+
+/// Encodes a metric as the `Metric` message of the Sparkplug B payload.
+fn encode_metric(name: &str, timestamp: u64, metric: &Metric, with_properties: bool) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    protobuf::write_bytes(&mut buffer, 1, name.as_bytes());
+    protobuf::write_uint(&mut buffer, 3, timestamp);
+    protobuf::write_uint(&mut buffer, 4, metric.data_type as u64);
+
+    // Units only need to be described once, in the birth certificate.
+    if let (true, Some(unit)) = (with_properties, &metric.unit) {
+        let mut value = Vec::new();
+        protobuf::write_uint(&mut value, 1, DataType::String as u64);
+        protobuf::write_bytes(&mut value, 8, unit.as_bytes());
+
+        let mut properties = Vec::new();
+        protobuf::write_bytes(&mut properties, 1, b"engUnit");
+        protobuf::write_bytes(&mut properties, 2, &value);
+
+        protobuf::write_bytes(&mut buffer, 9, &properties);
+    }
+
+    match &metric.value {
+        MetricValue::Null => protobuf::write_bool(&mut buffer, 7, true),
+        MetricValue::UInt64(value) => protobuf::write_uint(&mut buffer, 11, *value),
+        MetricValue::Double(value) => protobuf::write_double(&mut buffer, 13, *value),
+        MetricValue::Boolean(value) => protobuf::write_bool(&mut buffer, 14, *value),
+        MetricValue::String(value) => protobuf::write_bytes(&mut buffer, 15, value.as_bytes()),
+    }
+
+    buffer
+}
+
+/// Reads the names and values of the metrics in a command.
+fn decode_command(payload: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut metrics = Vec::new();
+
+    for (number, field) in protobuf::read_fields(payload)? {
+        let metric = match (number, field) {
+            (2, protobuf::Field::Bytes(metric)) => metric,
+            _ => continue,
+        };
+
+        let mut name = None;
+        let mut value = None;
+        for (number, field) in protobuf::read_fields(metric)? {
+            match (number, field) {
+                (1, protobuf::Field::Bytes(bytes)) => {
+                    name = Some(String::from_utf8_lossy(bytes).into_owned())
+                }
+                (10 | 11, protobuf::Field::Varint(number)) => value = Some(number.to_string()),
+                (12, protobuf::Field::Fixed32(bytes)) => {
+                    value = Some(f32::from_le_bytes(bytes).to_string())
+                }
+                (13, protobuf::Field::Fixed64(bytes)) => {
+                    value = Some(f64::from_le_bytes(bytes).to_string())
+                }
+                (14, protobuf::Field::Varint(boolean)) => {
+                    value = Some(String::from(if boolean != 0 { "ON" } else { "OFF" }))
+                }
+                (15, protobuf::Field::Bytes(bytes)) => {
+                    value = Some(String::from_utf8_lossy(bytes).into_owned())
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(name), Some(value)) = (name, value) {
+            metrics.push((name, value));
+        }
+    }
+
+    Some(metrics)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// What has to go out with the next flush.
+#[derive(Default)]
+struct Pending {
+    /// Set when the birth certificate has to be sent, such as when the metrics changed.
+    birth: bool,
+
+    /// Metrics whose values changed since the last flush.
+    changed: BTreeSet<String>,
+
+    /// Counts up with every message, and wraps around after 255.
+    sequence: u8,
+}
+
+/// Publishes values as a Sparkplug B edge node, for SCADA and IIoT systems.
+///
+/// Every entity is a metric of the node. The node's birth certificate (`NBIRTH`) carries every
+/// metric and is sent before any data (`NDATA`), and again whenever a metric is added or removed.
+/// Commands (`NCMD`) set entities that accept them, or ask for the birth certificate again.
+pub struct Sparkplug {
+    /// The connection to the MQTT server. When this is `None` we're doing a dry run
+    /// and everything is printed to stdout instead.
+    client: Option<MqttClient>,
+
+    group_id: String,
+    edge_node_id: String,
+
+    /// Ties our birth certificates to our death certificate. It only has to differ from the last
+    /// run's, so it comes from the time we started rather than being kept across restarts.
+    birth_death_sequence: u64,
+
+    metrics: Mutex<BTreeMap<String, Metric>>,
+    pending: Mutex<Pending>,
+    subscribed: bool,
+}
+
+impl Sparkplug {
+    pub fn new(hostname: String, config: &SparkplugConfig) -> Self {
+        Self {
+            client: None,
+            group_id: config.group_id.clone(),
+            edge_node_id: config.edge_node_id.clone().unwrap_or(hostname),
+            birth_death_sequence: now() / 1000 % 256,
+            metrics: Mutex::new(BTreeMap::new()),
+            pending: Mutex::new(Pending::default()),
+            subscribed: false,
+        }
+    }
+
+    /// Our death certificate, which the MQTT server sends for us if the connection drops without
+    /// us disconnecting. It has to be registered as we connect, and carries the same sequence
+    /// number as our birth certificates.
+    pub fn last_will(&self) -> LastWill {
+        let payload = self.payload(vec![self.birth_death_sequence_metric(now())], None);
+        LastWill::new(self.topic("NDEATH"), payload, QoS::AtLeastOnce, false)
+    }
+
+    /// Sets the connection to the MQTT server. When this is `None` we're doing a dry run.
+    pub fn set_client(&mut self, client: Option<MqttClient>) {
+        self.client = client;
+    }
+
+    fn topic(&self, message_type: &str) -> String {
+        format!(
+            "spBv1.0/{}/{}/{}",
+            self.group_id, message_type, self.edge_node_id
+        )
+    }
+
+    async fn send(&self, message_type: &str, payload: Vec<u8>) -> Result<()> {
+        let topic = self.topic(message_type);
+        log::debug!("PUBLISH {} bytes TO `{}`", payload.len(), topic);
+
+        if let Some(client) = &self.client {
+            client.publish(topic, payload, false).await?;
+        } else {
+            println!("{}: {} bytes", topic, payload.len());
+        }
+
+        Ok(())
+    }
+
+    /// Builds a payload out of encoded metrics, taking the next sequence number.
+    fn payload(&self, metrics: Vec<Vec<u8>>, sequence: Option<u64>) -> Vec<u8> {
+        let mut payload = Vec::new();
+        protobuf::write_uint(&mut payload, 1, now());
+        for metric in metrics {
+            protobuf::write_bytes(&mut payload, 2, &metric);
+        }
+        if let Some(sequence) = sequence {
+            protobuf::write_uint(&mut payload, 3, sequence);
+        }
+
+        payload
+    }
+
+    fn birth_death_sequence_metric(&self, timestamp: u64) -> Vec<u8> {
+        encode_metric(
+            BIRTH_DEATH_SEQUENCE_METRIC,
+            timestamp,
+            &Metric {
+                data_type: DataType::UInt64,
+                unit: None,
+                value: MetricValue::UInt64(self.birth_death_sequence),
+            },
+            false,
+        )
+    }
+
+    async fn send_birth(&self) -> Result<()> {
+        let timestamp = now();
+        let payload = {
+            let metrics = self.metrics.lock().expect("Metrics lock was poisoned.");
+            let mut pending = self.pending.lock().expect("Pending lock was poisoned.");
+
+            let mut encoded = vec![
+                self.birth_death_sequence_metric(timestamp),
+                encode_metric(
+                    REBIRTH_METRIC,
+                    timestamp,
+                    &Metric {
+                        data_type: DataType::Boolean,
+                        unit: None,
+                        value: MetricValue::Boolean(false),
+                    },
+                    false,
+                ),
+            ];
+            for (name, metric) in metrics.iter() {
+                encoded.push(encode_metric(name, timestamp, metric, true));
+            }
+
+            // Birth certificates always start the sequence over.
+            pending.birth = false;
+            pending.changed.clear();
+            pending.sequence = 1;
+
+            self.payload(encoded, Some(0))
+        };
+
+        self.send("NBIRTH", payload)
+            .await
+            .context("Failed to publish Sparkplug birth certificate.")
+    }
+
+    async fn subscribe(&mut self, topic: &str) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            let accepted = client
+                .subscribe(topic)
+                .await
+                .with_context(|| format!("Failed to subscribe to `{}`.", topic))?;
+
+            if !accepted {
+                bail!("MQTT server refused subscription to `{}`.", topic);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for Sparkplug {
+    async fn set_available(&self, available: bool) -> Result<()> {
+        if available {
+            self.pending
+                .lock()
+                .expect("Pending lock was poisoned.")
+                .birth = true;
+            Ok(())
+        } else {
+            // Death certificates only carry the sequence number of the session they end.
+            let payload = self.payload(vec![self.birth_death_sequence_metric(now())], None);
+            self.send("NDEATH", payload)
+                .await
+                .context("Failed to publish Sparkplug death certificate.")
+        }
+    }
+
+    async fn register(&mut self, entity: &Entity) -> Result<()> {
+        // Images have no place in a metric.
+        if entity.component == "camera" {
+            return Ok(());
+        }
+
+        if !self.subscribed {
+            let command_topic = self.topic("NCMD");
+            self.subscribe(&command_topic).await?;
+            self.subscribed = true;
+        }
+
+        self.metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .insert(
+                entity.name.clone(),
+                Metric {
+                    data_type: DataType::of(entity),
+                    unit: entity.unit.clone(),
+                    value: MetricValue::Null,
+                },
+            );
+        self.pending
+            .lock()
+            .expect("Pending lock was poisoned.")
+            .birth = true;
+
+        Ok(())
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        if self
+            .metrics
+            .lock()
+            .expect("Metrics lock was poisoned.")
+            .remove(entity_name)
+            .is_some()
+        {
+            self.pending
+                .lock()
+                .expect("Pending lock was poisoned.")
+                .birth = true;
+        }
+
+        Ok(())
+    }
+
+    /// Records the latest value of a metric, to be sent on the next flush.
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        let mut metrics = self.metrics.lock().expect("Metrics lock was poisoned.");
+        if let Some(metric) = metrics.get_mut(entity_name) {
+            metric.value = MetricValue::parse(metric.data_type, value);
+            self.pending
+                .lock()
+                .expect("Pending lock was poisoned.")
+                .changed
+                .insert(entity_name.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        if self
+            .pending
+            .lock()
+            .expect("Pending lock was poisoned.")
+            .birth
+        {
+            return self.send_birth().await;
+        }
+
+        let timestamp = now();
+        let payload = {
+            let metrics = self.metrics.lock().expect("Metrics lock was poisoned.");
+            let mut pending = self.pending.lock().expect("Pending lock was poisoned.");
+            if pending.changed.is_empty() {
+                return Ok(());
+            }
+
+            let encoded = std::mem::take(&mut pending.changed)
+                .iter()
+                .filter_map(|name| Some(encode_metric(name, timestamp, metrics.get(name)?, false)))
+                .collect();
+
+            let sequence = pending.sequence;
+            pending.sequence = sequence.wrapping_add(1);
+
+            self.payload(encoded, Some(sequence as u64))
+        };
+
+        self.send("NDATA", payload)
+            .await
+            .context("Failed to publish Sparkplug data.")
+    }
+
+    async fn next_command(&mut self) -> Result<Command> {
+        loop {
+            let message = match &mut self.client {
+                Some(client) => client
+                    .read_subscriptions()
+                    .await
+                    .context("Failed to read command from MQTT server.")?,
+                // Nothing to receive commands from during a dry run.
+                None => pending().await,
+            };
+
+            let metrics = match decode_command(&message.payload) {
+                Some(metrics) => metrics,
+                None => {
+                    log::warn!("Received a malformed Sparkplug command.");
+                    continue;
+                }
+            };
+
+            for (name, value) in metrics {
+                log::debug!("RECEIVED `{}` FOR `{}`", value, name);
+
+                if name == REBIRTH_METRIC {
+                    if value == "ON" {
+                        log::info!("Sparkplug host asked for a rebirth.");
+                        self.send_birth().await?;
+                    }
+                } else if self
+                    .metrics
+                    .lock()
+                    .expect("Metrics lock was poisoned.")
+                    .contains_key(&name)
+                {
+                    // Only the first metric of a command can be acted on. Host applications
+                    // send one per command in practice.
+                    return Ok(Command {
+                        entity: name,
+                        topic: message.topic.clone(),
+                        payload: value,
+                        source: None,
+                    });
+                }
+            }
+        }
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(client) = &mut self.client {
+            client.disconnect().await?;
+            log::debug!("Disconnected from MQTT server.");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use super::{http, Entity, Sink};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize)]
+struct Status {
+    hostname: String,
+    version: &'static str,
+    available: bool,
+
+    /// When we started up, in seconds since the Unix epoch.
+    started: u64,
+
+    /// When the last update finished, in seconds since the Unix epoch.
+    last_update: Option<u64>,
+
+    readings: BTreeMap<String, String>,
+}
+
+/// Serves the latest readings and our own health as JSON, for scripts running on the same machine.
+///
+/// If there's a history database, the history of an entity is served on `/history/<entity>`,
+/// going back `since` seconds (an hour by default), like `/history/cpu?since=600`.
+pub struct StatusApi {
+    status: Arc<Mutex<Status>>,
+    _server: http::Server,
+}
+
+impl StatusApi {
+    pub async fn start(
+        address: SocketAddr,
+        hostname: String,
+        history_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        let status = Arc::new(Mutex::new(Status {
+            hostname,
+            version: env!("CARGO_PKG_VERSION"),
+            available: false,
+            started: now(),
+            last_update: None,
+            readings: BTreeMap::new(),
+        }));
+
+        // Without the history feature, there's no history to serve.
+        #[cfg(not(feature = "history"))]
+        let _ = history_path;
+
+        let server = {
+            let status = status.clone();
+            http::serve(address, move |path| {
+                #[cfg(feature = "history")]
+                if let Some(request) = path.strip_prefix("/history/") {
+                    return serve_history(history_path.as_ref()?, request);
+                }
+
+                let status = status.lock().expect("Status lock was poisoned.");
+                let body = match path {
+                    "/" | "/status" => serde_json::to_string(&*status),
+                    "/readings" => serde_json::to_string(&status.readings),
+                    _ => return None,
+                };
+
+                body.ok().map(|body| ("application/json", body))
+            })
+            .await?
+        };
+        log::info!("Serving status API on `{}`.", address);
+
+        Ok(Self {
+            status,
+            _server: server,
+        })
+    }
+
+    fn status(&self) -> std::sync::MutexGuard<Status> {
+        self.status.lock().expect("Status lock was poisoned.")
+    }
+}
+
+#[async_trait(?Send)]
+impl Sink for StatusApi {
+    async fn register(&mut self, _entity: &Entity) -> Result<()> {
+        Ok(())
+    }
+
+    async fn unregister(&mut self, entity_name: &str) -> Result<()> {
+        self.status().readings.remove(entity_name);
+
+        Ok(())
+    }
+
+    async fn publish(&self, entity_name: &str, value: &str) -> Result<()> {
+        self.status()
+            .readings
+            .insert(entity_name.to_string(), value.to_string());
+
+        Ok(())
+    }
+
+    async fn set_available(&self, available: bool) -> Result<()> {
+        self.status().available = available;
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.status().last_update = Some(now());
+
+        Ok(())
+    }
+}
+
+/// Answers a request like `cpu?since=600` with the history of the entity.
+#[cfg(feature = "history")]
+fn serve_history(history_path: &std::path::Path, request: &str) -> Option<http::Response> {
+    use super::history;
+    use std::time::Duration;
+
+    let (entity_name, query) = request.split_once('?').unwrap_or((request, ""));
+    let since = query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("since="))
+        .and_then(|since| since.parse().ok())
+        .unwrap_or(60 * 60);
+
+    let records = match history::query(
+        history_path,
+        entity_name,
+        SystemTime::now() - Duration::from_secs(since),
+    ) {
+        Ok(records) => records,
+        Err(error) => {
+            log::warn!("Failed to read history of `{}`: {:?}", entity_name, error);
+            return None;
+        }
+    };
+
+    serde_json::to_string(&records)
+        .ok()
+        .map(|body| ("application/json", body))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+use std::{collections::HashMap, fmt::Write};
+
+enum Part {
+    Literal(String),
+
+    /// The value itself, rounded to this many decimal places if it's a number and that's set.
+    Value(Option<usize>),
+
+    /// The name of the entity.
+    Entity,
+}
+
+/// Shapes the payload a value is published as, such as `{{"temperature": {value:.1}}}`.
+///
+/// `{value}` is replaced with the value and `{entity}` with the name of the entity.
+/// `{value:.N}` rounds numbers to N decimal places. Use `{{` and `}}` for literal braces.
+pub struct PayloadTemplate {
+    parts: Vec<Part>,
+}
+
+impl PayloadTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut characters = template.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            match character {
+                '{' if characters.peek() == Some(&'{') => {
+                    characters.next();
+                    literal.push('{');
+                }
+                '}' if characters.peek() == Some(&'}') => {
+                    characters.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match characters.next() {
+                            Some('}') => break,
+                            Some(character) => placeholder.push(character),
+                            None => bail!(
+                                "Unclosed `{{` in payload template. Use `{{{{` for a literal one."
+                            ),
+                        }
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    parts.push(match placeholder.split_once(':') {
+                        None if placeholder == "value" => Part::Value(None),
+                        None if placeholder == "entity" => Part::Entity,
+                        Some(("value", precision)) => {
+                            let precision = precision
+                                .strip_prefix('.')
+                                .and_then(|precision| precision.parse().ok())
+                                .with_context(|| {
+                                    format!(
+                                        "Invalid precision `{}` in payload template.",
+                                        precision
+                                    )
+                                })?;
+
+                            Part::Value(Some(precision))
+                        }
+                        _ => bail!(
+                            "Unknown placeholder `{{{}}}` in payload template.",
+                            placeholder
+                        ),
+                    });
+                }
+                '}' => bail!("Unmatched `}}` in payload template. Use `}}}}` for a literal one."),
+                character => literal.push(character),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, entity_name: &str, value: &str) -> String {
+        let mut payload = String::new();
+
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(literal) => payload.push_str(literal),
+                Part::Value(Some(precision)) => match value.parse::<f64>() {
+                    Ok(number) => {
+                        // Writing to a string can't fail.
+                        let _ = write!(payload, "{:.*}", precision, number);
+                    }
+                    // Only numbers can be rounded.
+                    Err(_) => payload.push_str(value),
+                },
+                Part::Value(None) => payload.push_str(value),
+                Part::Entity => payload.push_str(entity_name),
+            }
+        }
+
+        payload
+    }
+}
+
+/// Payload templates for specific entities. Entities without one are published as they are.
+#[derive(Default)]
+pub struct PayloadTemplates {
+    templates: HashMap<String, PayloadTemplate>,
+}
+
+impl PayloadTemplates {
+    pub fn new<'a>(templates: impl IntoIterator<Item = (&'a String, &'a String)>) -> Result<Self> {
+        let templates = templates
+            .into_iter()
+            .map(|(entity_name, template)| {
+                let template = PayloadTemplate::parse(template)
+                    .with_context(|| format!("Invalid payload template for `{}`.", entity_name))?;
+
+                Ok((entity_name.clone(), template))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { templates })
+    }
+
+    pub fn render(&self, entity_name: &str, value: &str) -> String {
+        match self.templates.get(entity_name) {
+            Some(template) => template.render(entity_name, value),
+            None => value.to_string(),
+        }
+    }
+}
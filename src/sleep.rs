@@ -0,0 +1,89 @@
+//! Finds out when the system is about to go to sleep, and when it wakes up again, from logind.
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use zbus::{dbus_proxy, zvariant::OwnedFd, Connection};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+pub struct SleepWatcher {
+    proxy: ManagerProxy<'static>,
+    signals: PrepareForSleepStream<'static>,
+
+    /// While we hold this, logind waits for us before putting the system to sleep (up to its
+    /// `InhibitDelayMaxSec`), which gives us time to say we're going offline.
+    inhibitor: Option<OwnedFd>,
+}
+
+impl SleepWatcher {
+    pub async fn new() -> Result<Self> {
+        let connection = Connection::system()
+            .await
+            .context("Failed to connect to the D-Bus system bus.")?;
+        let proxy = ManagerProxy::new(&connection).await?;
+        let signals = proxy
+            .receive_prepare_for_sleep()
+            .await
+            .context("Failed to listen for the system going to sleep.")?;
+
+        let mut watcher = Self {
+            proxy,
+            signals,
+            inhibitor: None,
+        };
+        watcher.inhibit().await;
+
+        Ok(watcher)
+    }
+
+    /// Asks logind to wait for us the next time the system goes to sleep.
+    /// If it won't, we carry on without it, and may be cut off before we say we're going offline.
+    pub async fn inhibit(&mut self) {
+        if self.inhibitor.is_some() {
+            return;
+        }
+
+        match self
+            .proxy
+            .inhibit(
+                "sleep",
+                "system-mqtt",
+                "Reporting that the system is going to sleep",
+                "delay",
+            )
+            .await
+        {
+            Ok(inhibitor) => self.inhibitor = Some(inhibitor),
+            Err(error) => log::warn!("Failed to take sleep inhibitor lock: {:?}", error),
+        }
+    }
+
+    /// Lets the system go to sleep.
+    pub fn release(&mut self) {
+        self.inhibitor = None;
+    }
+
+    /// Waits until the system is about to go to sleep, which returns `true`, or has just woken up, which returns `false`.
+    pub async fn next(&mut self) -> Result<bool> {
+        let signal = self
+            .signals
+            .next()
+            .await
+            .context("Lost connection to logind.")?;
+
+        Ok(signal
+            .args()
+            .context("Failed to read sleep signal from logind.")?
+            .start)
+    }
+}
@@ -0,0 +1,166 @@
+//! Values that need to survive a restart, such as the running totals of counters.
+//!
+//! Everything is kept in a single JSON file in the state directory. If there is no state
+//! directory, or it can't be written to, the state lives only as long as the process does.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+use tokio::fs;
+
+const STATE_FILE_NAME: &str = "state.json";
+
+/// A total built up from a counter the kernel resets every boot.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct Counter {
+    /// When the system was booted the last time the counter was read, in seconds since the epoch.
+    boot_time: u64,
+
+    /// What the counter read last time.
+    last_raw: u64,
+
+    /// Everything counted so far, across reboots.
+    total: u64,
+}
+
+/// Where a total stood when a period, such as a billing month, began.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct Period {
+    /// Identifies the period. Anything that changes when a new period starts will do.
+    start: u64,
+
+    /// The total at the start of the period.
+    baseline: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StateFile {
+    #[serde(default)]
+    counters: BTreeMap<String, Counter>,
+
+    #[serde(default)]
+    periods: BTreeMap<String, Period>,
+}
+
+pub struct StateStore {
+    /// Where the state is saved. `None` means it isn't.
+    path: Option<PathBuf>,
+    state: Mutex<StateFile>,
+
+    /// Set when something changed since the last save.
+    dirty: AtomicBool,
+}
+
+impl StateStore {
+    /// A store that is never saved.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            state: Mutex::new(StateFile::default()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Loads the state from the state directory, creating the directory if it doesn't exist yet.
+    pub async fn load(state_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(state_dir).await.with_context(|| {
+            format!(
+                "Failed to create state directory `{}`.",
+                state_dir.display()
+            )
+        })?;
+
+        let path = state_dir.join(STATE_FILE_NAME);
+        let state = if path.is_file() {
+            serde_json::from_str(&fs::read_to_string(&path).await?)
+                .context("Failed to deserialize state file.")?
+        } else {
+            StateFile::default()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            state: Mutex::new(state),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Adds the latest reading of a counter to its total and returns the total.
+    ///
+    /// The counter is expected to only go up until the system is rebooted, at which point it
+    /// starts again from zero.
+    pub fn accumulate(&self, name: &str, raw: u64, boot_time: u64) -> u64 {
+        let mut state = self.state.lock().expect("State lock was poisoned.");
+
+        let counter = state.counters.entry(name.to_string()).or_insert(Counter {
+            boot_time,
+            last_raw: raw,
+            total: 0,
+        });
+
+        let increase = if counter.boot_time == boot_time && raw >= counter.last_raw {
+            raw - counter.last_raw
+        } else {
+            // The system was rebooted (or the counter wrapped), so everything it reads was counted since then.
+            raw
+        };
+
+        counter.boot_time = boot_time;
+        counter.last_raw = raw;
+        counter.total += increase;
+
+        self.dirty.store(true, Ordering::Relaxed);
+
+        counter.total
+    }
+
+    /// How much a total has gone up since the start of the current period.
+    /// When `period_start` changes, a new period begins and this goes back to zero.
+    pub fn since_period_start(&self, name: &str, total: u64, period_start: u64) -> u64 {
+        let mut state = self.state.lock().expect("State lock was poisoned.");
+
+        let period = state.periods.entry(name.to_string()).or_insert(Period {
+            start: period_start,
+            baseline: total,
+        });
+
+        if period.start != period_start {
+            period.start = period_start;
+            period.baseline = total;
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+
+        total.saturating_sub(period.baseline)
+    }
+
+    /// Writes the state to disk if anything changed.
+    pub async fn save(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            if self.dirty.swap(false, Ordering::Relaxed) {
+                let content = {
+                    let state = self.state.lock().expect("State lock was poisoned.");
+                    serde_json::to_string(&*state).context("Failed to serialize state.")?
+                };
+
+                // Write to a temporary file first so a crash can't leave us with half a state file.
+                let temporary_path = path.with_extension("json.tmp");
+                fs::write(&temporary_path, content)
+                    .await
+                    .context("Failed to write state file.")?;
+                fs::rename(&temporary_path, path)
+                    .await
+                    .context("Failed to replace state file.")?;
+            }
+        }
+
+        Ok(())
+    }
+}